@@ -0,0 +1,3 @@
+-- Uploads are looked up by the digest of their content to deduplicate them. Every upload
+-- keeps its own record, so a content uploaded under several names has several records.
+CREATE INDEX IF NOT EXISTS uploads_sha256_idx ON uploads (sha256);
@@ -0,0 +1,11 @@
+-- API keys accepted on the mutating file routes, alongside those set in `API_KEYS`.
+-- Only the SHA-256 digest of each key is stored, and revoked keys are kept for auditing.
+CREATE TABLE IF NOT EXISTS api_keys (
+    identity TEXT PRIMARY KEY,
+    key_sha256 TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    revoked_at TIMESTAMPTZ
+);
+
+-- The identity of the API key each file was uploaded with, unknown for older uploads.
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS uploaded_by TEXT;
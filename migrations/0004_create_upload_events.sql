@@ -0,0 +1,13 @@
+-- Records every upload of a competition archive, so the previous versions stay traceable
+-- after the archive is re-uploaded. Versions are numbered from 1 per competition.
+CREATE TABLE IF NOT EXISTS upload_events (
+    id BIGSERIAL PRIMARY KEY,
+    competition TEXT NOT NULL,
+    s3_key TEXT NOT NULL,
+    version INTEGER NOT NULL,
+    size_bytes BIGINT NOT NULL,
+    sha256 TEXT NOT NULL,
+    uploaded_by TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (competition, version)
+);
@@ -0,0 +1,5 @@
+-- Soft-deleted uploads keep their record until the retention window passes, their object
+-- being moved under the `deleted/` prefix meanwhile.
+ALTER TABLE uploads ADD COLUMN IF NOT EXISTS deleted_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS uploads_deleted_at_idx ON uploads (deleted_at) WHERE deleted_at IS NOT NULL;
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use log::info;
+use metrics_exporter_prometheus::PrometheusHandle;
+use crate::clients::clients::Clients;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::services::file_service::FileService;
+use crate::utils::file_utils::FileValidator;
+
+/// The state shared by every handler, middleware and background task.
+///
+/// # Fields
+/// - `config`: The application configuration.
+/// - `validator`: The upload file validator, built once from the configuration.
+/// - `clients`: The clients of the external services.
+/// - `file_service`: The file service, built once and shared by every request.
+/// - `metrics`: The handle rendering the Prometheus metrics, unset when metrics are disabled.
+///
+pub struct AppState {
+    config: Arc<AppConfig>,
+    validator: Arc<FileValidator>,
+    clients: Arc<Clients>,
+    file_service: Arc<FileService>,
+    metrics: Option<PrometheusHandle>,
+}
+
+impl AppState {
+    /// Creates the application state from the configuration, the clients and the metrics
+    /// handle, if any.
+    ///
+    /// The file types defined in `FILE_TYPES_CONFIG` are loaded here, so an invalid
+    /// definition fails at startup rather than at upload time.
+    pub fn new(config: AppConfig, clients: Arc<Clients>, metrics: Option<PrometheusHandle>) -> Result<Self, AppError> {
+        let validator = match &config.file_types_config {
+            Some(path) => {
+                let validator = FileValidator::from_config(&config, path)?;
+                info!("Loaded file type definitions from '{}'", path);
+                validator
+            }
+            None => FileValidator::new(&config),
+        };
+
+        let config = Arc::new(config);
+        let validator = Arc::new(validator);
+        let file_service = Arc::new(FileService::new(clients.clone(), config.clone(), validator.clone()));
+        Ok(Self { config, validator, clients, file_service, metrics })
+    }
+
+    /// Returns a reference to the application configuration.
+    pub fn get_config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Returns the shared application configuration, for the services holding onto it.
+    pub fn get_shared_config(&self) -> Arc<AppConfig> {
+        self.config.clone()
+    }
+
+    /// Returns the shared upload file validator.
+    pub fn get_validator(&self) -> Arc<FileValidator> {
+        self.validator.clone()
+    }
+
+    /// Returns the clients of the external services.
+    pub fn get_clients(&self) -> &Arc<Clients> {
+        &self.clients
+    }
+
+    /// Returns the shared file service.
+    pub fn get_file_service(&self) -> &FileService {
+        &self.file_service
+    }
+
+    /// Returns the shared file service, for the services holding onto it.
+    pub fn get_shared_file_service(&self) -> Arc<FileService> {
+        self.file_service.clone()
+    }
+
+    /// Returns the handle rendering the Prometheus metrics, if metrics are enabled.
+    pub fn get_metrics(&self) -> Option<&PrometheusHandle> {
+        self.metrics.as_ref()
+    }
+}
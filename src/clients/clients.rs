@@ -1,8 +1,13 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use log::{error, info};
-use crate::config::AppConfig;
+use crate::config::{AppConfig, StorageBackend};
 use crate::error::AppError;
+use crate::middleware::upload_tracker::ActiveUploads;
 use crate::clients::{
+    local_storage::LocalFsStorage,
     s3_client::S3Client,
+    storage::Storage,
     postgres_client::PostgresClient,
     redis_client::RedisClient
 };
@@ -11,14 +16,20 @@ use crate::clients::{
 ///
 /// # Fields
 ///
-/// * `s3_client` - An instance of the S3 client.
+/// * `storage` - The storage backend selected by `STORAGE_BACKEND`.
 /// * `postgres_client` - An instance of the PostgreSQL client.
 /// * `redis_client` - An instance of the Redis client.
+/// * `active_uploads` - The upload requests in progress, waited for on shutdown.
+/// * `ready` - Whether the startup connection tests and migrations completed.
+/// * `connected` - Whether the required services answered the last readiness check.
 ///
 pub struct Clients {
-    s3_client: S3Client,
+    storage: Arc<dyn Storage>,
     postgres_client: PostgresClient,
     redis_client: RedisClient,
+    active_uploads: ActiveUploads,
+    ready: AtomicBool,
+    connected: AtomicBool,
 }
 
 /// Implementation block for `Clients`.
@@ -32,48 +43,114 @@ pub struct Clients {
 impl Clients {
     /// Creates a new instance of `Clients`.
     pub async fn new(config: &AppConfig) -> Result<Self, AppError> {
+        let storage: Arc<dyn Storage> = match config.storage_backend {
+            StorageBackend::S3 => Arc::new(S3Client::new(config).await),
+            StorageBackend::Local => {
+                info!("Storing files locally under '{}'", config.local_storage_dir);
+                Arc::new(LocalFsStorage::new(&config.local_storage_dir))
+            }
+        };
+
         Ok(Self {
-            s3_client: S3Client::new(config),
+            storage,
             postgres_client: PostgresClient::new(config).await?,
             redis_client: RedisClient::new(config)?,
+            active_uploads: ActiveUploads::new(),
+            ready: AtomicBool::new(false),
+            connected: AtomicBool::new(true),
         })
     }
 
     /// Tests the connections to all the clients.
+    ///
+    /// Only PostgreSQL connectivity is checked here, since the schema is created by the
+    /// migrations that run after this test.
     pub async fn test_connections(&self) -> Result<(), AppError> {
-        if let Err(e) = self.s3_client.test_connection().await {
-            error!("Failed to connect to S3: {}", e);
-            return Err(e.into());
+        if let Err(e) = self.storage.test_connection().await {
+            error!("Failed to connect to storage: {}", e);
+            return Err(e);
         }
-        info!("S3 connection established successfully!");
+        info!("Storage connection established successfully!");
 
-        if let Err(e) = self.postgres_client.test_connection().await {
+        if let Err(e) = self.postgres_client.check_connection().await {
             error!("Failed to connect to PostgreSQL: {}", e);
-            return Err(e.into());
+            return Err(e);
         }
         info!("PostgreSQL connection established successfully!");
 
         if let Err(e) = self.redis_client.test_connection().await {
             error!("Failed to connect to Redis: {}", e);
-            return Err(e.into());
+            return Err(e);
         }
         info!("Redis connection established successfully!");
 
         Ok(())
     }
 
-    /// Returns a reference to the S3 client.
-    pub fn get_s3_client(&self) -> S3Client {
-        self.s3_client.clone()
+    /// Returns the storage backend.
+    pub fn get_storage(&self) -> Arc<dyn Storage> {
+        self.storage.clone()
+    }
+
+    /// Replaces the storage backend selected by `STORAGE_BACKEND`, e.g. to wrap it.
+    pub fn with_storage(mut self, storage: Arc<dyn Storage>) -> Self {
+        self.storage = storage;
+        self
     }
 
     /// Returns a reference to the PostgreSQL client.
-    pub fn get_postgres_client(&self) -> PostgresClient {
-        self.postgres_client.clone()
+    pub fn get_postgres_client(&self) -> &PostgresClient {
+        &self.postgres_client
     }
 
     /// Returns a reference to the Redis client.
-    pub fn get_redis_client(&self) -> RedisClient {
-        self.redis_client.clone()
+    pub fn get_redis_client(&self) -> &RedisClient {
+        &self.redis_client
+    }
+
+    /// Marks the application as ready, once the startup checks completed.
+    pub fn mark_ready(&self) {
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// Returns whether the startup checks completed.
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+
+    /// Records whether the required services answered the last readiness check.
+    ///
+    /// # Returns
+    /// Whether the connection state changed.
+    pub fn set_connected(&self, connected: bool) -> bool {
+        self.connected.swap(connected, Ordering::AcqRel) != connected
+    }
+
+    /// Returns whether the application can serve traffic: the startup checks completed
+    /// and the required services answered the last readiness check.
+    pub fn is_serving(&self) -> bool {
+        self.is_ready() && self.connected.load(Ordering::Acquire)
+    }
+
+    /// Returns the tracker of the upload requests in progress.
+    pub fn get_active_uploads(&self) -> ActiveUploads {
+        self.active_uploads.clone()
+    }
+}
+
+#[cfg(test)]
+impl Clients {
+    /// Returns the clients of the unit tests: files are stored locally under the configured
+    /// directory, and PostgreSQL and Redis are only connected to when first used. As with
+    /// `new`, the clients aren't marked ready.
+    pub fn for_tests(config: &AppConfig) -> Self {
+        Self {
+            storage: Arc::new(LocalFsStorage::new(&config.local_storage_dir)),
+            postgres_client: PostgresClient::connect_lazy(config),
+            redis_client: RedisClient::new(config).expect("Invalid Redis URL"),
+            active_uploads: ActiveUploads::new(),
+            ready: AtomicBool::new(false),
+            connected: AtomicBool::new(true),
+        }
     }
 }
@@ -0,0 +1,510 @@
+use std::io::SeekFrom;
+use std::path::{Component, Path, PathBuf};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use uuid::Uuid;
+use crate::clients::storage::{write_verified, ByteRange, ObjectMetadata, Storage, StoredObject};
+use crate::error::AppError;
+use crate::utils::file_utils::compute_sha256;
+
+/// The size of the buffer used when streaming files to hash them.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Storage backend keeping objects on the local filesystem, for development without AWS.
+///
+/// The root directory holds:
+/// - `objects/`: The objects, at their key relative to this directory.
+/// - `meta/`: The SHA-256 digest of each object, at its key followed by `.sha256`, and its
+///   declared content type, at its key followed by `.content-type`.
+/// - `multipart/`: One directory per in-progress multipart upload, holding its parts.
+/// - `tmp/`: Files being written, renamed into place once complete.
+#[derive(Clone)]
+pub struct LocalFsStorage {
+    root: PathBuf,
+}
+
+impl LocalFsStorage {
+    /// Creates a new local storage rooted at the given directory.
+    /// The directory is created on first write.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the path of an object, rejecting keys that would escape the objects directory.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    fn object_path(&self, key: &str) -> Result<PathBuf, AppError> {
+        Ok(self.root.join("objects").join(validate_key(key)?))
+    }
+
+    /// Returns the path of the file holding the digest of an object.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    fn digest_path(&self, key: &str) -> Result<PathBuf, AppError> {
+        Ok(self.root.join("meta").join(format!("{}.sha256", validate_key(key)?.display())))
+    }
+
+    /// Returns the path of the file holding the declared content type of an object.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    fn content_type_path(&self, key: &str) -> Result<PathBuf, AppError> {
+        Ok(self.root.join("meta").join(format!("{}.content-type", validate_key(key)?.display())))
+    }
+
+    /// Returns the directory of a multipart upload, rejecting ids not issued by this backend.
+    ///
+    /// # Parameters
+    /// - `upload_id` - The id of the multipart upload.
+    fn upload_dir(&self, upload_id: &str) -> Result<PathBuf, AppError> {
+        let upload_id = Uuid::parse_str(upload_id)
+            .map_err(|_| AppError::ValidationError(format!("Invalid upload id: '{}'", upload_id)))?;
+        Ok(self.root.join("multipart").join(upload_id.to_string()))
+    }
+
+    /// Returns a new unique path in the temporary directory, creating the directory.
+    async fn temp_path(&self) -> Result<PathBuf, AppError> {
+        let tmp_dir = self.root.join("tmp");
+        fs::create_dir_all(&tmp_dir).await?;
+        Ok(tmp_dir.join(Uuid::new_v4().to_string()))
+    }
+
+    /// Moves a completely written temporary file into place, creating the parent directories.
+    ///
+    /// # Parameters
+    /// - `temp_path` - The path of the temporary file.
+    /// - `path` - The final path of the file.
+    async fn persist(&self, temp_path: &Path, path: &Path) -> Result<(), AppError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::rename(temp_path, path).await?;
+        Ok(())
+    }
+
+    /// Writes a file atomically, so readers never see it partially written.
+    ///
+    /// # Parameters
+    /// - `path` - The path of the file.
+    /// - `data` - The content of the file.
+    async fn write_atomic(&self, path: &Path, data: &[u8]) -> Result<(), AppError> {
+        let temp_path = self.temp_path().await?;
+        fs::write(&temp_path, data).await?;
+        self.persist(&temp_path, path).await
+    }
+
+    /// Reads the stored digest of an object, if one was recorded.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    async fn stored_digest(&self, key: &str) -> Option<String> {
+        let digest = fs::read_to_string(self.digest_path(key).ok()?).await.ok()?;
+        Some(digest.trim().to_string())
+    }
+
+    /// Reads the declared content type of an object, if one was recorded.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    async fn stored_content_type(&self, key: &str) -> Option<String> {
+        fs::read_to_string(self.content_type_path(key).ok()?).await.ok()
+    }
+
+    /// Records the declared content type of an object, or removes it when there is none.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    /// - `content_type` - The declared content type, if any.
+    async fn write_content_type(&self, key: &str, content_type: Option<&str>) -> Result<(), AppError> {
+        let path = self.content_type_path(key)?;
+        match content_type {
+            Some(content_type) => self.write_atomic(&path, content_type.as_bytes()).await,
+            None => match fs::remove_file(&path).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::FileIoError(e)),
+                _ => Ok(()),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for LocalFsStorage {
+    /// Tests that the root directory can be created and written to.
+    async fn test_connection(&self) -> Result<(), AppError> {
+        let temp_path = self.temp_path().await?;
+        fs::write(&temp_path, b"").await?;
+        fs::remove_file(&temp_path).await?;
+        Ok(())
+    }
+
+    /// Writes an object, its digest and its content type. Local storage has no storage
+    /// classes, and keeps no metadata besides the digest and content type.
+    async fn upload(
+        &self,
+        key: &str,
+        data: &[u8],
+        sha256: &str,
+        _file_name: Option<&str>,
+        content_type: Option<&str>,
+        _storage_class: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.write_atomic(&self.object_path(key)?, data).await?;
+        self.write_atomic(&self.digest_path(key)?, sha256.as_bytes()).await?;
+        self.write_content_type(key, content_type).await
+    }
+
+    /// Copies a local file into place as an object, and writes its digest and content type.
+    async fn upload_from_path(
+        &self,
+        key: &str,
+        path: &Path,
+        sha256: &str,
+        _file_name: Option<&str>,
+        content_type: Option<&str>,
+        _storage_class: Option<&str>,
+    ) -> Result<(), AppError> {
+        let temp_path = self.temp_path().await?;
+        fs::copy(path, &temp_path).await?;
+        self.persist(&temp_path, &self.object_path(key)?).await?;
+        self.write_atomic(&self.digest_path(key)?, sha256.as_bytes()).await?;
+        self.write_content_type(key, content_type).await
+    }
+
+    /// Copies an object into a file, verifying it on the way.
+    async fn download_to_file(&self, key: &str, path: &Path) -> Result<u64, AppError> {
+        let file = fs::File::open(self.object_path(key)?).await.map_err(|e| not_found_or(key, e))?;
+        let expected = self.stored_digest(key).await;
+
+        write_verified(key, file, expected, path).await
+    }
+
+    /// Opens an object, seeking to the start of the requested range.
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<StoredObject, AppError> {
+        let mut file = fs::File::open(self.object_path(key)?).await.map_err(|e| not_found_or(key, e))?;
+        let size = file.metadata().await?.len();
+        let etag = self.etag(key).await;
+        let content_type = self.stored_content_type(key).await;
+
+        let Some((first, last)) = range.map(|range| range.resolve(size)).transpose()? else {
+            return Ok(StoredObject {
+                content_type,
+                content_length: Some(size as i64),
+                content_range: None,
+                etag,
+                body: Box::pin(file),
+            });
+        };
+
+        file.seek(SeekFrom::Start(first)).await?;
+        let length = last - first + 1;
+
+        Ok(StoredObject {
+            content_type,
+            content_length: Some(length as i64),
+            content_range: Some(format!("bytes {}-{}/{}", first, last, size)),
+            etag,
+            body: Box::pin(file.take(length)),
+        })
+    }
+
+    /// Fetches the size and modification time of an object file.
+    async fn head_file(&self, key: &str) -> Result<Option<ObjectMetadata>, AppError> {
+        let metadata = match fs::metadata(self.object_path(key)?).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            Ok(_) => return Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(AppError::FileIoError(e)),
+        };
+
+        Ok(Some(ObjectMetadata {
+            size: metadata.len(),
+            etag: self.etag(key).await,
+            last_modified: metadata.modified().ok().map(DateTime::<Utc>::from),
+            content_type: self.stored_content_type(key).await,
+        }))
+    }
+
+    /// Returns the stored digest of an object as its ETag, falling back to its size and
+    /// modification time for files added to the directory by hand.
+    async fn etag(&self, key: &str) -> Option<String> {
+        let metadata = fs::metadata(self.object_path(key).ok()?).await.ok()?;
+        if !metadata.is_file() {
+            return None;
+        }
+
+        if let Some(digest) = self.stored_digest(key).await {
+            return Some(format!("\"{}\"", digest));
+        }
+
+        let modified = metadata
+            .modified()
+            .ok()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_nanos();
+        Some(format!("\"{}-{}\"", metadata.len(), modified))
+    }
+
+    /// Reads the stored digest of an object.
+    async fn checksum(&self, key: &str) -> Result<Option<String>, AppError> {
+        if !self.exists(key).await? {
+            return Err(AppError::ObjectNotFound(key.to_string()));
+        }
+
+        Ok(self.stored_digest(key).await)
+    }
+
+    /// Computes the digest of an object by reading it in chunks.
+    async fn digest(&self, key: &str) -> Result<(String, u64), AppError> {
+        let mut file = fs::File::open(self.object_path(key)?).await.map_err(|e| not_found_or(key, e))?;
+
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0; READ_BUFFER_SIZE];
+        let mut size = 0;
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            size += read as u64;
+            hasher.update(&buffer[..read]);
+        }
+
+        Ok((format!("{:x}", hasher.finalize()), size))
+    }
+
+    /// Deletes an object, its digest and its content type.
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        for path in [self.object_path(key)?, self.digest_path(key)?, self.content_type_path(key)?] {
+            match fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies an object file, its digest and its content type.
+    async fn copy_file(&self, src_key: &str, dst_key: &str) -> Result<(), AppError> {
+        let temp_path = self.temp_path().await?;
+        fs::copy(self.object_path(src_key)?, &temp_path).await.map_err(|e| not_found_or(src_key, e))?;
+        self.persist(&temp_path, &self.object_path(dst_key)?).await?;
+        self.write_content_type(dst_key, self.stored_content_type(src_key).await.as_deref()).await?;
+
+        match self.stored_digest(src_key).await {
+            Some(digest) => self.write_atomic(&self.digest_path(dst_key)?, digest.as_bytes()).await,
+            None => match fs::remove_file(self.digest_path(dst_key)?).await {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(AppError::FileIoError(e)),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    /// Lists the objects by walking the objects directory.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, AppError> {
+        let objects_dir = self.root.join("objects");
+        let mut keys = Vec::new();
+        let mut pending = vec![objects_dir.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    pending.push(path);
+                    continue;
+                }
+
+                let Ok(relative) = path.strip_prefix(&objects_dir) else {
+                    continue;
+                };
+                let key = relative
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                if key.starts_with(prefix) {
+                    keys.push(key);
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Creates the directory of a new multipart upload.
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, AppError> {
+        validate_key(key)?;
+
+        let upload_id = Uuid::new_v4().to_string();
+        fs::create_dir_all(self.upload_dir(&upload_id)?).await?;
+        Ok(upload_id)
+    }
+
+    /// Writes a part into the directory of its upload, using its digest as its ETag.
+    async fn upload_part(
+        &self,
+        _key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, AppError> {
+        let upload_dir = self.upload_dir(upload_id)?;
+        if fs::metadata(&upload_dir).await.is_err() {
+            return Err(AppError::ObjectNotFound(format!("multipart upload {}", upload_id)));
+        }
+
+        self.write_atomic(&upload_dir.join(part_number.to_string()), &data).await?;
+        Ok(format!("\"{}\"", compute_sha256(&data)))
+    }
+
+    /// Concatenates the parts into the object, checking each part against its ETag.
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> Result<(), AppError> {
+        let upload_dir = self.upload_dir(upload_id)?;
+        let temp_path = self.temp_path().await?;
+        let mut output = fs::File::create(&temp_path).await?;
+        let mut hasher = Sha256::new();
+
+        for (part_number, etag) in parts {
+            let data = fs::read(upload_dir.join(part_number.to_string()))
+                .await
+                .map_err(|e| not_found_or(&format!("part {} of upload {}", part_number, upload_id), e))?;
+
+            if format!("\"{}\"", compute_sha256(&data)) != etag {
+                fs::remove_file(&temp_path).await.ok();
+                return Err(AppError::ValidationError(format!(
+                    "ETag mismatch for part {} of upload {}",
+                    part_number, upload_id
+                )));
+            }
+
+            hasher.update(&data);
+            output.write_all(&data).await?;
+        }
+
+        output.flush().await?;
+        drop(output);
+
+        self.persist(&temp_path, &self.object_path(key)?).await?;
+        self.write_atomic(&self.digest_path(key)?, format!("{:x}", hasher.finalize()).as_bytes()).await?;
+        self.write_content_type(key, None).await?;
+        fs::remove_dir_all(upload_dir).await?;
+        Ok(())
+    }
+
+    /// Removes the directory of a multipart upload.
+    async fn abort_multipart_upload(&self, _key: &str, upload_id: &str) -> Result<(), AppError> {
+        match fs::remove_dir_all(self.upload_dir(upload_id)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Checks that a key only contains normal path segments, so it can't escape the root.
+///
+/// # Parameters
+/// - `key` - The key of the object.
+///
+/// # Returns
+/// The key as a relative path.
+fn validate_key(key: &str) -> Result<&Path, AppError> {
+    let path = Path::new(key);
+    let is_valid = !key.is_empty()
+        && path.components().all(|component| matches!(component, Component::Normal(_)));
+
+    if is_valid {
+        Ok(path)
+    } else {
+        Err(AppError::ValidationError(format!("Invalid storage key: '{}'", key)))
+    }
+}
+
+/// Maps a missing file to `AppError::ObjectNotFound`, and other IO errors to `AppError::FileIoError`.
+///
+/// # Parameters
+/// - `key` - The key of the object being read.
+/// - `error` - The IO error.
+fn not_found_or(key: &str, error: std::io::Error) -> AppError {
+    if error.kind() == std::io::ErrorKind::NotFound {
+        AppError::ObjectNotFound(key.to_string())
+    } else {
+        AppError::FileIoError(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const CONTENT: &[u8] = b"fn main() {}";
+
+    async fn storage_with_object(key: &str) -> (TempDir, LocalFsStorage) {
+        let dir = TempDir::new().unwrap();
+        let storage = LocalFsStorage::new(dir.path());
+        storage.upload(key, CONTENT, &compute_sha256(CONTENT), None, None, None).await.unwrap();
+        (dir, storage)
+    }
+
+    #[tokio::test]
+    async fn download_of_an_intact_object_is_verified() {
+        let (dir, storage) = storage_with_object("archive.zip").await;
+        let path = dir.path().join("download");
+
+        assert_eq!(storage.download_to_file("archive.zip", &path).await.unwrap(), CONTENT.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), CONTENT);
+    }
+
+    #[tokio::test]
+    async fn download_of_a_corrupted_object_is_rejected_and_removed() {
+        let (dir, storage) = storage_with_object("archive.zip").await;
+        std::fs::write(dir.path().join("objects/archive.zip"), b"fn main() { corrupted }").unwrap();
+        let path = dir.path().join("download");
+
+        match storage.download_to_file("archive.zip", &path).await {
+            Err(AppError::ValidationError(message)) => assert!(message.starts_with("Checksum mismatch for 'archive.zip'")),
+            result => panic!("the corrupted object was not rejected: {:?}", result),
+        }
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn declared_content_type_is_kept_with_the_object() {
+        let (_dir, storage) = storage_with_object("archive.zip").await;
+        assert_eq!(storage.open("archive.zip", None).await.unwrap().content_type, None);
+
+        storage.upload("archive.tar.gz", CONTENT, &compute_sha256(CONTENT), None, Some("application/gzip"), None).await.unwrap();
+        assert_eq!(storage.open("archive.tar.gz", None).await.unwrap().content_type.as_deref(), Some("application/gzip"));
+        let metadata = storage.head_file("archive.tar.gz").await.unwrap().unwrap();
+        assert_eq!(metadata.content_type.as_deref(), Some("application/gzip"));
+
+        storage.copy_file("archive.tar.gz", "copy.tar.gz").await.unwrap();
+        assert_eq!(storage.open("copy.tar.gz", None).await.unwrap().content_type.as_deref(), Some("application/gzip"));
+
+        // Replacing or deleting the object drops its content type
+        storage.upload("archive.tar.gz", CONTENT, &compute_sha256(CONTENT), None, None, None).await.unwrap();
+        assert_eq!(storage.open("archive.tar.gz", None).await.unwrap().content_type, None);
+        storage.delete("copy.tar.gz").await.unwrap();
+        storage.upload("copy.tar.gz", CONTENT, &compute_sha256(CONTENT), None, None, None).await.unwrap();
+        assert_eq!(storage.open("copy.tar.gz", None).await.unwrap().content_type, None);
+    }
+}
@@ -1,4 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod clients;
+pub mod local_storage;
 pub mod postgres_client;
 pub mod redis_client;
-pub mod s3_client;
\ No newline at end of file
+pub mod s3_client;
+pub mod storage;
\ No newline at end of file
@@ -1,14 +1,73 @@
-use sqlx::PgPool;
-use crate::config::AppConfig;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{Executor, FromRow, PgPool};
+use uuid::Uuid;
+use crate::config::{AppConfig, PostgresProbe};
 use crate::error::AppError;
 
+/// Metadata describing a file that was uploaded to S3.
+///
+/// # Fields
+/// - `file_name`: The original name of the uploaded file.
+/// - `s3_key`: The key the file was stored under in S3.
+/// - `size_bytes`: The size of the file in bytes.
+/// - `content_type`: The content type declared by the client.
+/// - `sha256`: The hex-encoded SHA-256 digest of the file content.
+/// - `uploaded_by`: The identity of the API key the file was uploaded with.
+///
+pub struct UploadMeta {
+    pub file_name: String,
+    pub s3_key: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+    pub sha256: String,
+    pub uploaded_by: String,
+}
+
+/// The condition of the `uploads` records whose object wasn't replaced since, by a later
+/// live upload of another content under the same key.
+const NOT_REPLACED: &str = "NOT EXISTS (SELECT 1 FROM uploads later \
+    WHERE later.s3_key = uploads.s3_key AND later.deleted_at IS NULL \
+    AND later.created_at > uploads.created_at AND later.sha256 <> uploads.sha256)";
+
 /// A client for interacting with a PostgreSQL database.
 ///
 /// This struct encapsulates a connection pool to a PostgreSQL database and provides
 /// methods for testing the connection and performing database operations.
-#[derive(Clone)]
 pub struct PostgresClient {
     pool: PgPool,
+    probe: PostgresProbe,
+}
+
+/// A row of the `uploads` table.
+///
+/// `created_at` and `deleted_at` serialize as RFC3339 timestamps. `deleted_at` is set while
+/// the file is soft-deleted.
+#[derive(Debug, Serialize, FromRow)]
+pub struct UploadRecord {
+    pub id: Uuid,
+    pub file_name: String,
+    pub s3_key: String,
+    pub size_bytes: i64,
+    pub content_type: String,
+    pub sha256: String,
+    pub uploaded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+/// A row of the `upload_events` table, one per upload of a competition archive.
+///
+/// `created_at` serializes as an RFC3339 timestamp.
+#[derive(Debug, Serialize, FromRow)]
+pub struct UploadEvent {
+    pub competition: String,
+    pub s3_key: String,
+    pub version: i32,
+    pub size_bytes: i64,
+    pub sha256: String,
+    pub uploaded_by: Option<String>,
+    pub created_at: DateTime<Utc>,
 }
 
 impl PostgresClient {
@@ -22,26 +81,369 @@ impl PostgresClient {
     /// - `Err(AppError)`: An error if the connection to the database fails.
     pub async fn new(config: &AppConfig) -> Result<Self, AppError> {
         let pool = PgPool::connect(&config.database_url).await?;
-        Ok(Self { pool })
+        Ok(Self { pool, probe: config.postgres_probe })
+    }
+
+    /// Creates a `PostgresClient` instance which only connects when first queried, for the
+    /// unit tests not needing a database.
+    #[cfg(test)]
+    pub(crate) fn connect_lazy(config: &AppConfig) -> Self {
+        let pool = PgPool::connect_lazy(&config.database_url).expect("Invalid database URL");
+        Self { pool, probe: config.postgres_probe }
     }
 
     /// Tests the connection to the PostgreSQL database.
     ///
-    /// This method performs a simple query to verify that the database is reachable
-    /// and responsive.
+    /// This method runs the probe selected by `POSTGRES_HEALTH_PROBE`: the lightweight
+    /// probe only checks connectivity, while the full probe also checks the schema.
     ///
     /// # Returns
     /// - `Ok(())`: If the connection test is successful.
     /// - `Err(AppError)`: If the connection test fails.
     pub async fn test_connection(&self) -> Result<(), AppError> {
-        let _: (i64,) = sqlx::query_as("SELECT $1")
-            .bind(1_i64)
+        self.check_connection().await?;
+
+        if self.probe == PostgresProbe::Full {
+            self.check_schema().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the database is reachable by running `SELECT 1`.
+    ///
+    /// The query is sent unprepared, so it also works against read-replicas and poolers
+    /// that don't support prepared statements.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the query succeeds.
+    /// - `Err(AppError)`: If the database cannot be reached.
+    pub async fn check_connection(&self) -> Result<(), AppError> {
+        self.pool.execute("SELECT 1").await?;
+        Ok(())
+    }
+
+    /// Checks that the tables the application relies on exist.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the schema is present.
+    /// - `Err(AppError)`: If the query fails or a table is missing.
+    pub async fn check_schema(&self) -> Result<(), AppError> {
+        let (exists,): (bool,) = sqlx::query_as("SELECT to_regclass('uploads') IS NOT NULL")
             .fetch_one(&self.pool)
             .await?;
+
+        if !exists {
+            return Err(AppError::DatabaseSchemaError("table 'uploads' does not exist".to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// Applies the pending database migrations from the `migrations` directory.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If all migrations were applied.
+    /// - `Err(AppError)`: If a migration fails.
+    pub async fn run_migrations(&self) -> Result<(), AppError> {
+        sqlx::migrate!("./migrations").run(&self.pool).await?;
         Ok(())
     }
 
+    /// Records an uploaded file in the `uploads` table.
+    ///
+    /// Every upload gets its own record, even when its content is already recorded under
+    /// another name, so the records of the earlier uploads are kept.
+    ///
+    /// # Arguments
+    /// - `meta`: The metadata of the uploaded file.
+    ///
+    /// # Returns
+    /// - `Ok(Uuid)`: The id of the record.
+    /// - `Err(AppError)`: If the insert fails.
+    pub async fn record_upload(&self, meta: &UploadMeta) -> Result<Uuid, AppError> {
+        let (id,): (Uuid,) = sqlx::query_as(
+            "INSERT INTO uploads (id, file_name, s3_key, size_bytes, content_type, sha256, uploaded_by) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7) \
+             RETURNING id",
+        )
+            .bind(Uuid::new_v4())
+            .bind(&meta.file_name)
+            .bind(&meta.s3_key)
+            .bind(meta.size_bytes)
+            .bind(&meta.content_type)
+            .bind(&meta.sha256)
+            .bind(&meta.uploaded_by)
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(id)
+    }
+
+    /// Records an upload of a competition archive in the `upload_events` table, numbering it
+    /// after the previous versions of the competition.
+    ///
+    /// The competition is locked for the duration of the transaction, so concurrent uploads
+    /// get distinct version numbers.
+    ///
+    /// # Arguments
+    /// - `competition`: The name of the competition.
+    /// - `meta`: The metadata of the uploaded archive.
+    ///
+    /// # Returns
+    /// - `Ok(i32)`: The version number of the upload.
+    /// - `Err(AppError)`: If the queries fail.
+    pub async fn record_upload_event(&self, competition: &str, meta: &UploadMeta) -> Result<i32, AppError> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext($1))")
+            .bind(competition)
+            .execute(&mut *transaction)
+            .await?;
+
+        let (version,): (i32,) = sqlx::query_as(
+            "INSERT INTO upload_events (competition, s3_key, version, size_bytes, sha256, uploaded_by) \
+             SELECT $1, $2, COALESCE(MAX(version), 0) + 1, $3, $4, $5 \
+             FROM upload_events WHERE competition = $1 \
+             RETURNING version",
+        )
+            .bind(competition)
+            .bind(&meta.s3_key)
+            .bind(meta.size_bytes)
+            .bind(&meta.sha256)
+            .bind(&meta.uploaded_by)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(version)
+    }
+
+    /// Fetches a page of the upload history of a competition, the latest version first.
+    ///
+    /// # Arguments
+    /// - `competition`: The name of the competition.
+    /// - `limit`: The maximum number of versions to return.
+    /// - `offset`: The number of most recent versions to skip.
+    ///
+    /// # Returns
+    /// - `Ok((Vec<UploadEvent>, i64))`: The versions of the page, and the total number of versions.
+    /// - `Err(AppError)`: If the queries fail.
+    pub async fn list_upload_events(
+        &self,
+        competition: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<(Vec<UploadEvent>, i64), AppError> {
+        let events = sqlx::query_as::<_, UploadEvent>(
+            "SELECT competition, s3_key, version, size_bytes, sha256, uploaded_by, created_at \
+             FROM upload_events WHERE competition = $1 \
+             ORDER BY version DESC LIMIT $2 OFFSET $3",
+        )
+            .bind(competition)
+            .bind(limit)
+            .bind(offset)
+            .fetch_all(&self.pool)
+            .await?;
+
+        let (total,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM upload_events WHERE competition = $1")
+            .bind(competition)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok((events, total))
+    }
+
+    /// Fetches a recorded upload by its id.
+    ///
+    /// # Arguments
+    /// - `id`: The id of the upload.
+    /// - `include_deleted`: Whether to also return the upload if it is soft-deleted.
+    ///
+    /// # Returns
+    /// - `Ok(Some(UploadRecord))`: The stored metadata if the upload exists.
+    /// - `Ok(None)`: If no upload has this id.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn get_upload(&self, id: Uuid, include_deleted: bool) -> Result<Option<UploadRecord>, AppError> {
+        let record = sqlx::query_as::<_, UploadRecord>(
+            "SELECT id, file_name, s3_key, size_bytes, content_type, sha256, uploaded_by, created_at, deleted_at \
+             FROM uploads WHERE id = $1 AND ($2 OR deleted_at IS NULL)",
+        )
+            .bind(id)
+            .bind(include_deleted)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(record)
+    }
+
+    /// Fetches the first recorded upload whose content is still stored, by its SHA-256 digest.
+    ///
+    /// # Arguments
+    /// - `sha256`: The hex-encoded SHA-256 digest of the content.
+    ///
+    /// # Returns
+    /// - `Ok(Some(UploadRecord))`: The stored metadata if the content was already uploaded.
+    /// - `Ok(None)`: If no upload holds this content, or only soft-deleted or replaced ones.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn find_upload_by_sha256(&self, sha256: &str) -> Result<Option<UploadRecord>, AppError> {
+        let record = sqlx::query_as::<_, UploadRecord>(&format!(
+            "SELECT id, file_name, s3_key, size_bytes, content_type, sha256, uploaded_by, created_at, deleted_at \
+             FROM uploads WHERE sha256 = $1 AND deleted_at IS NULL AND {} ORDER BY created_at LIMIT 1",
+            NOT_REPLACED,
+        ))
+            .bind(sha256)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(record)
+    }
+
+    /// Finds the latest live upload recorded under a file name, whose content is still stored.
+    ///
+    /// # Arguments
+    /// - `file_name`: The name the file was uploaded under.
+    ///
+    /// # Returns
+    /// - `Ok(Some(UploadRecord))`: The stored metadata of the latest upload with this name.
+    /// - `Ok(None)`: If no live upload has this name, or its object was replaced since.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn find_upload_by_file_name(&self, file_name: &str) -> Result<Option<UploadRecord>, AppError> {
+        let record = sqlx::query_as::<_, UploadRecord>(&format!(
+            "SELECT id, file_name, s3_key, size_bytes, content_type, sha256, uploaded_by, created_at, deleted_at \
+             FROM uploads WHERE file_name = $1 AND deleted_at IS NULL AND {} ORDER BY created_at DESC LIMIT 1",
+            NOT_REPLACED,
+        ))
+            .bind(file_name)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(record)
+    }
+
+    /// Points the recorded uploads of an object at its new key after the object was renamed.
+    /// The records of any object replaced by the rename are deleted, while soft-deleted
+    /// records are left alone.
+    ///
+    /// # Arguments
+    /// - `old_key`: The key the object was stored under.
+    /// - `new_key`: The key the object is now stored under.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of records pointed at the new key.
+    /// - `Err(AppError)`: If the queries fail.
+    pub async fn rename_upload_key(&self, old_key: &str, new_key: &str) -> Result<u64, AppError> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM uploads WHERE s3_key = $1 AND deleted_at IS NULL")
+            .bind(new_key)
+            .execute(&mut *transaction)
+            .await?;
+
+        let result = sqlx::query("UPDATE uploads SET s3_key = $2 WHERE s3_key = $1 AND deleted_at IS NULL")
+            .bind(old_key)
+            .bind(new_key)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Marks the recorded uploads of an object as soft-deleted. The records of a previously
+    /// soft-deleted object with the same key are deleted, since its object is replaced.
+    ///
+    /// # Arguments
+    /// - `key`: The key the object was stored under.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of records marked as deleted.
+    /// - `Err(AppError)`: If the queries fail.
+    pub async fn mark_upload_deleted(&self, key: &str) -> Result<u64, AppError> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM uploads WHERE s3_key = $1 AND deleted_at IS NOT NULL")
+            .bind(key)
+            .execute(&mut *transaction)
+            .await?;
+
+        let result = sqlx::query("UPDATE uploads SET deleted_at = NOW() WHERE s3_key = $1 AND deleted_at IS NULL")
+            .bind(key)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Clears the soft-deletion of the recorded uploads of a restored object.
+    ///
+    /// # Arguments
+    /// - `key`: The key the object was restored to.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of restored records.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn restore_upload(&self, key: &str) -> Result<u64, AppError> {
+        let result = sqlx::query("UPDATE uploads SET deleted_at = NULL WHERE s3_key = $1 AND deleted_at IS NOT NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Lists the keys of the objects soft-deleted for longer than the retention window.
+    ///
+    /// # Arguments
+    /// - `retention_secs`: The retention window of soft-deleted objects, in seconds.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<String>)`: The keys the expired objects were stored under before their deletion.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn list_expired_deletions(&self, retention_secs: u64) -> Result<Vec<String>, AppError> {
+        let keys = sqlx::query_scalar(
+            "SELECT DISTINCT s3_key FROM uploads \
+             WHERE deleted_at < NOW() - make_interval(secs => $1)",
+        )
+            .bind(retention_secs as f64)
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(keys)
+    }
+
+    /// Deletes the soft-deleted records of an object once it was purged.
+    ///
+    /// # Arguments
+    /// - `key`: The key the object was stored under before its deletion.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of deleted records.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn purge_deleted_uploads(&self, key: &str) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM uploads WHERE s3_key = $1 AND deleted_at IS NOT NULL")
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Fetches the identity of an API key stored in the `api_keys` table.
+    ///
+    /// # Arguments
+    /// - `key_sha256`: The hex-encoded SHA-256 digest of the API key.
+    ///
+    /// # Returns
+    /// - `Ok(Some(String))`: The identity of the key if it exists and isn't revoked.
+    /// - `Ok(None)`: If no active key has this digest.
+    /// - `Err(AppError)`: If the query fails.
+    pub async fn find_api_key_identity(&self, key_sha256: &str) -> Result<Option<String>, AppError> {
+        let identity = sqlx::query_scalar(
+            "SELECT identity FROM api_keys WHERE key_sha256 = $1 AND revoked_at IS NULL",
+        )
+            .bind(key_sha256)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(identity)
+    }
+
     /// Returns a reference to the connection pool.
+    #[allow(dead_code)]
     pub fn get_pool(&self) -> &PgPool {
         &self.pool
     }
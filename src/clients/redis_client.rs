@@ -1,14 +1,49 @@
-use redis::{AsyncCommands, Client};
+use std::time::Duration;
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, Client, Script};
+use tokio::sync::OnceCell;
+use uuid::Uuid;
 use crate::config::AppConfig;
 use crate::error::AppError;
 
+/// Maximum number of keys removed by a single `DEL` command.
+const DELETE_BATCH_SIZE: usize = 500;
+
+/// How often a held lock is tried again while waiting for it.
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// How long each attempt to open the shared connection may take.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How many times opening the shared connection is retried before the command fails.
+const CONNECTION_RETRIES: usize = 2;
+
+/// The longest delay between two attempts to open the shared connection, in milliseconds.
+const CONNECTION_RETRY_MAX_DELAY_MS: u64 = 500;
+
+/// Deletes a lock only if it still holds the caller's token, so an expired holder can't
+/// release a lock taken over by someone else.
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call("GET", KEYS[1]) == ARGV[1] then
+    return redis.call("DEL", KEYS[1])
+end
+return 0
+"#;
+
 /// A client for interacting with a Redis server.
 ///
 /// This struct encapsulates the connection to a Redis server and provides methods
 /// for testing the connection and performing Redis operations.
-#[derive(Clone)]
+///
+/// Every key owned by the application is namespaced with the configured key prefix,
+/// so the application's keys can be flushed without touching co-tenant data.
+///
+/// Commands share a single connection, opened on first use and re-established
+/// automatically when it drops.
 pub struct RedisClient {
     client: Client,
+    key_prefix: String,
+    connection: OnceCell<ConnectionManager>,
 }
 
 impl RedisClient {
@@ -22,30 +57,250 @@ impl RedisClient {
     /// - `Err(AppError)`: An error if the connection to Redis fails.
     pub fn new(config: &AppConfig) -> Result<Self, AppError> {
         let client = Client::open(config.redis_url.clone())?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            key_prefix: config.redis_key_prefix.clone(),
+            connection: OnceCell::new(),
+        })
+    }
+
+    /// Namespaces a key with the configured key prefix.
+    ///
+    /// # Arguments
+    /// - `key`: The key without the prefix.
+    ///
+    /// # Returns
+    /// - The prefixed key.
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{}", self.key_prefix, key)
     }
 
     /// Tests the connection to the Redis server.
     ///
-    /// This method performs a simple set/get operation to verify that the Redis server
-    /// is reachable and responsive.
+    /// This method performs a simple set/get operation over the shared connection to verify
+    /// that the Redis server is reachable and responsive.
     ///
     /// # Returns
     /// - `Ok(())`: If the connection test is successful.
     /// - `Err(AppError)`: If the connection test fails.
     pub async fn test_connection(&self) -> Result<(), AppError> {
-        let mut con = self.client.get_multiplexed_async_connection().await?;
-        let _: () = con.set("test_key", "test_value").await?;
-        let _: String = con.get("test_key").await?;
+        let mut con = self.get_connection().await?;
+        let _: () = con.set(self.key("test_key"), "test_value").await?;
+        let _: String = con.get(self.key("test_key")).await?;
         Ok(())
     }
 
-    /// Returns a reference to the Redis client.
+    /// Increments a counter that expires at the end of a fixed window.
+    ///
+    /// The window starts with the first increment, which sets the expiration of the key.
+    ///
+    /// # Arguments
+    /// - `key`: The key of the counter.
+    /// - `window_secs`: The length of the window in seconds.
+    ///
+    /// # Returns
+    /// - `Ok((u64, u64))`: The counter value after the increment and the seconds left in the window.
+    /// - `Err(AppError)`: If the Redis commands fail.
+    pub async fn increment_in_window(&self, key: &str, window_secs: u64) -> Result<(u64, u64), AppError> {
+        let mut con = self.get_connection().await?;
+
+        let count: u64 = con.incr(key, 1).await?;
+        if count == 1 {
+            let _: () = con.expire(key, window_secs as i64).await?;
+        }
+
+        let ttl: i64 = con.ttl(key).await?;
+        Ok((count, ttl.max(0) as u64))
+    }
+
+    /// Acquires a lock, held until it is released or its TTL elapses.
+    ///
+    /// # Arguments
+    /// - `key`: The prefixed key of the lock.
+    /// - `ttl`: How long the lock is held if it is never released, so a crashed holder
+    ///   doesn't keep it forever.
+    ///
+    /// # Returns
+    /// - `Ok(Some(String))`: The token of this holder, required to release the lock.
+    /// - `Ok(None)`: If the lock is already held.
+    /// - `Err(AppError)`: If the Redis command fails.
+    pub async fn acquire_lock(&self, key: &str, ttl: Duration) -> Result<Option<String>, AppError> {
+        let mut con = self.get_connection().await?;
+        let token = Uuid::new_v4().to_string();
+
+        let acquired: Option<String> = redis::cmd("SET")
+            .arg(key)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut con)
+            .await?;
+
+        Ok(acquired.map(|_| token))
+    }
+
+    /// Acquires a lock, waiting for its holder to release it for at most `timeout`.
+    ///
+    /// # Arguments
+    /// - `key`: The prefixed key of the lock.
+    /// - `ttl`: How long the lock is held if it is never released.
+    /// - `timeout`: How long to wait for the lock.
+    ///
+    /// # Returns
+    /// - `Ok(Some(String))`: The token of this holder, required to release the lock.
+    /// - `Ok(None)`: If the lock is still held once `timeout` elapsed.
+    /// - `Err(AppError)`: If the Redis command fails.
+    pub async fn wait_for_lock(&self, key: &str, ttl: Duration, timeout: Duration) -> Result<Option<String>, AppError> {
+        let deadline = tokio::time::Instant::now() + timeout;
+
+        loop {
+            if let Some(token) = self.acquire_lock(key, ttl).await? {
+                return Ok(Some(token));
+            }
+
+            if tokio::time::Instant::now() + LOCK_POLL_INTERVAL > deadline {
+                return Ok(None);
+            }
+            tokio::time::sleep(LOCK_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Releases a lock, provided it is still held with the given token.
+    ///
+    /// # Arguments
+    /// - `key`: The prefixed key of the lock.
+    /// - `token`: The token returned by `acquire_lock`.
     ///
     /// # Returns
-    /// - A reference to the Redis client.
+    /// - `Ok(bool)`: Whether the lock was released, `false` if it had expired meanwhile.
+    /// - `Err(AppError)`: If the Redis command fails.
+    pub async fn release_lock(&self, key: &str, token: &str) -> Result<bool, AppError> {
+        let mut con = self.get_connection().await?;
+        let released: i64 = Script::new(RELEASE_LOCK_SCRIPT)
+            .key(key)
+            .arg(token)
+            .invoke_async(&mut con)
+            .await?;
+
+        Ok(released == 1)
+    }
+
+    /// Deletes every key matching a glob pattern, using `SCAN` rather than `KEYS`
+    /// so the server is not blocked on large keyspaces.
     ///
-    pub fn get_client(&self) -> &Client {
-        &self.client
+    /// # Arguments
+    /// - `pattern`: The prefixed glob pattern to match keys against.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of keys deleted.
+    /// - `Err(AppError)`: If the Redis commands fail.
+    pub async fn delete_matching(&self, pattern: &str) -> Result<usize, AppError> {
+        let mut con = self.get_connection().await?;
+
+        let keys: Vec<String> = {
+            let mut iter = con.scan_match::<_, String>(pattern).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+
+        let mut deleted = 0;
+        for batch in keys.chunks(DELETE_BATCH_SIZE) {
+            let count: usize = con.del(batch).await?;
+            deleted += count;
+        }
+
+        Ok(deleted)
     }
-}
\ No newline at end of file
+
+    /// Renames the keys matching a glob pattern, replacing their prefix. Keys are found with
+    /// `SCAN` rather than `KEYS`, so the server isn't blocked on large keyspaces.
+    ///
+    /// # Parameters
+    /// - `pattern`: The glob pattern of the keys to rename, every match starting with `from_prefix`.
+    /// - `from_prefix`: The prefix to replace.
+    /// - `to_prefix`: The prefix replacing it.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of keys renamed.
+    /// - `Err(AppError)`: If the Redis commands fail.
+    pub async fn rename_matching(&self, pattern: &str, from_prefix: &str, to_prefix: &str) -> Result<usize, AppError> {
+        let mut con = self.get_connection().await?;
+
+        let keys: Vec<String> = {
+            let mut iter = con.scan_match::<_, String>(pattern).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+
+        let mut renamed = 0;
+        for key in keys {
+            let Some(suffix) = key.strip_prefix(from_prefix) else {
+                continue;
+            };
+            let _: () = con.rename(&key, format!("{}{}", to_prefix, suffix)).await?;
+            renamed += 1;
+        }
+
+        Ok(renamed)
+    }
+
+    /// Deletes every key owned by the application, i.e. every key carrying the configured prefix.
+    /// `FLUSHALL` is never used, so keys belonging to other tenants of the server are left intact.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of keys deleted.
+    /// - `Err(AppError)`: If the Redis commands fail.
+    pub async fn flush_prefixed_keys(&self) -> Result<usize, AppError> {
+        self.delete_matching(&format!("{}*", escape_glob(&self.key_prefix))).await
+    }
+
+    /// Returns the shared connection to the Redis server, opening it on first use.
+    ///
+    /// The returned handle is cheap to clone, and every clone multiplexes its commands
+    /// over the same connection.
+    ///
+    /// Opening the connection is retried `CONNECTION_RETRIES` times at most, each attempt
+    /// bounded by `CONNECTION_TIMEOUT`, so commands fail within seconds while Redis is down
+    /// rather than after the minutes of the default retry policy.
+    ///
+    /// # Returns
+    /// - `Ok(ConnectionManager)`: A handle to the shared connection.
+    /// - `Err(AppError)`: If the connection can't be opened.
+    pub async fn get_connection(&self) -> Result<ConnectionManager, AppError> {
+        let connection = self.connection
+            .get_or_try_init(|| {
+                let config = ConnectionManagerConfig::new()
+                    .set_connection_timeout(CONNECTION_TIMEOUT)
+                    .set_number_of_retries(CONNECTION_RETRIES)
+                    .set_max_delay(CONNECTION_RETRY_MAX_DELAY_MS);
+                self.client.get_connection_manager_with_config(config)
+            })
+            .await?;
+        Ok(connection.clone())
+    }
+}
+
+/// Escapes the glob metacharacters of a literal so it can be used in a `SCAN MATCH` pattern.
+///
+/// # Arguments
+/// - `literal`: The literal to escape.
+///
+/// # Returns
+/// - The escaped literal.
+pub fn escape_glob(literal: &str) -> String {
+    let mut escaped = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
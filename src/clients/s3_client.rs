@@ -1,52 +1,129 @@
-use std::error::Error;
-use aws_sdk_s3::{Client, config::{Credentials, Region}};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use axum::body::Bytes;
+use aws_sdk_s3::{Client, config::{BehaviorVersion, Credentials, Region}};
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::operation::get_object::{GetObjectError, GetObjectOutput};
 use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart, StorageClass};
+use log::{debug, info, warn};
+use sha2::{Digest, Sha256};
+use crate::clients::storage::{write_verified, ByteRange, ObjectMetadata, Storage, StoredObject};
 use crate::config::AppConfig;
 use crate::error::AppError;
+use crate::utils::metrics::{S3_ERRORS_TOTAL, S3_RETRIES_TOTAL};
+
+/// The object metadata key holding the SHA-256 digest of the uploaded content.
+const SHA256_METADATA_KEY: &str = "sha256";
+
+/// The object metadata key holding the name the object was uploaded under, URL-encoded since
+/// S3 metadata values must be ASCII.
+const FILE_NAME_METADATA_KEY: &str = "file-name";
+
+/// The largest object `CopyObject` can copy, larger objects being copied part by part.
+const MAX_COPY_OBJECT_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// The size of the parts of a multipart copy, small enough to copy a 5 TB object in 10000 parts.
+const COPY_PART_BYTES: u64 = 512 * 1024 * 1024;
+
+/// The longest delay between two attempts of a request.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// How failed S3 requests are retried.
+///
+/// # Fields
+/// - `max_attempts`: The maximum number of attempts of a request, including the first one.
+/// - `base_delay`: The delay ceiling after the first attempt, doubled after each further attempt.
+///
+#[derive(Clone, Copy, Debug)]
+struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Returns the delay before the next attempt, picked at random up to an exponentially
+    /// growing ceiling so that clients failing together don't retry together.
+    ///
+    /// # Parameters
+    /// - `attempt` - The number of the attempt that just failed, starting at 1.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.base_delay
+            .saturating_mul(1 << (attempt - 1).min(16))
+            .min(MAX_RETRY_DELAY);
+        ceiling.mul_f64(rand::random::<f64>())
+    }
+}
 
 /// Client for interacting with AWS S3.
 #[derive(Clone)]
 pub struct S3Client {
     client: Client,
     bucket_name: String,
+    retry: RetryPolicy,
 }
 
 impl S3Client {
     /// Creates a new S3 client using the provided configuration.
-    pub fn new(config: &AppConfig) -> Self {
-        let credentials = Credentials::new(
-            config.aws_access_key_id.clone(),
-            config.aws_secret_access_key.clone(),
-            None,
-            None,
-            "loaded-from-env",
-        );
-
-        let s3_config = aws_sdk_s3::Config::builder()
-            .region(Region::new(config.aws_region.clone()))
-            .credentials_provider(credentials)
-            .build();
+    ///
+    /// When `S3_ENDPOINT_URL` is set, requests are sent to that endpoint instead of AWS,
+    /// which allows running against S3-compatible services like MinIO or LocalStack.
+    ///
+    /// Requests are signed with `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` when set, or
+    /// otherwise with the credentials of the default AWS provider chain, such as an instance
+    /// or task role, or a web identity token.
+    ///
+    /// The SDK's own retries are disabled, since requests are retried by `with_retries`
+    /// according to `S3_RETRY_MAX_ATTEMPTS` and `S3_RETRY_BASE_DELAY_MS`.
+    pub async fn new(config: &AppConfig) -> Self {
+        let region = Region::new(config.aws_region.clone());
+
+        let mut s3_config = match (&config.aws_access_key_id, &config.aws_secret_access_key) {
+            (Some(access_key_id), Some(secret_access_key)) => {
+                let credentials = Credentials::new(
+                    access_key_id.clone(),
+                    secret_access_key.clone(),
+                    None,
+                    None,
+                    "loaded-from-env",
+                );
+                aws_sdk_s3::Config::builder().credentials_provider(credentials)
+            }
+            _ => {
+                info!("No static AWS credentials configured, using the default provider chain");
+                let sdk_config = aws_config::defaults(BehaviorVersion::latest())
+                    .region(region.clone())
+                    .load()
+                    .await;
+                aws_sdk_s3::config::Builder::from(&sdk_config)
+            }
+        };
+
+        s3_config = s3_config
+            .region(region)
+            .force_path_style(config.s3_force_path_style)
+            .retry_config(RetryConfig::disabled());
+
+        if let Some(endpoint_url) = &config.s3_endpoint_url {
+            s3_config = s3_config.endpoint_url(endpoint_url);
+        }
 
         Self {
-            client: Client::from_conf(s3_config),
+            client: Client::from_conf(s3_config.build()),
             bucket_name: config.s3_bucket_name.clone(),
+            retry: RetryPolicy {
+                max_attempts: config.s3_retry_max_attempts.max(1),
+                base_delay: Duration::from_millis(config.s3_retry_base_delay_ms),
+            },
         }
     }
 
-    /// Tests the connection to the S3 bucket by listing objects.
-    ///
-    /// # Returns
-    /// - `Ok(())` if the connection is successful.
-    /// - `Err(AppError)` if the connection fails.
-    pub async fn test_connection(&self) -> Result<(), AppError> {
-        self.client
-            .list_objects_v2()
-            .bucket(&self.bucket_name)
-            .send()
-            .await?;
-        Ok(())
-    }
-
     /// Returns a reference to the S3 client.
     ///
     /// # Returns
@@ -65,50 +142,746 @@ impl S3Client {
         self.bucket_name.clone()
     }
 
-    /// Uploads a file to the S3 bucket.
+    /// Opens a byte range of a file in the S3 bucket for streaming.
     ///
     /// # Parameters
-    /// - `file_name` - The name of the file to upload.
-    /// - `data` - The file content as a byte array.
+    /// - `key` - The key of the file.
+    /// - `start` - The first byte of the range.
+    /// - `end` - The last byte of the range, both inclusive, or `None` to read to the end of the file.
     ///
-    pub async fn upload_file(&self, file_name: &str, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        let byte_stream = ByteStream::from(data.to_vec());
-        self.get_client()
-            .put_object()
-            .bucket(self.get_bucket_name())
-            .key(file_name)
-            .body(byte_stream)
-            .send()
-            .await?;
-        Ok(())
+    /// # Returns
+    /// - `Ok(StoredObject)`: The requested range of the file, with its `Content-Range`.
+    /// - `Err(AppError::RangeNotSatisfiable)`: If the range starts past the end of the file.
+    pub async fn download_range(&self, key: &str, start: u64, end: Option<u64>) -> Result<StoredObject, AppError> {
+        let response = self.get_object(key, Some(ByteRange::From(start, end))).await?;
+        Ok(stored_object(response))
     }
 
-    /// Downloads a file from the S3 bucket.
+    /// Fetches a file from the S3 bucket, optionally restricted to a byte range.
     ///
     /// # Parameters
-    /// - `key` - The key of the file to download.
+    /// - `key` - The key of the file.
+    /// - `range` - The byte range to fetch, sent to S3 as a `Range` header.
     ///
-    pub async fn download_file(&self, key: &str) -> Result<Vec<u8>, AppError> {
-        let response = self.client
-            .get_object()
-            .bucket(&self.bucket_name)
-            .key(key)
-            .send()
+    /// # Returns
+    /// - `Ok(GetObjectOutput)`: The object metadata and its body, not read yet.
+    /// - `Err(AppError)`: If the file can't be fetched.
+    async fn get_object(&self, key: &str, range: Option<ByteRange>) -> Result<GetObjectOutput, AppError> {
+        self.with_retries("download", key, || {
+            self.client
+                .get_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .set_range(range.map(|range| range.to_string()))
+                .send()
+        })
+        .await
+        .map_err(|e| get_object_error(key, e))
+    }
+
+    /// Copies an object into a multipart upload, `COPY_PART_BYTES` at a time.
+    ///
+    /// # Parameters
+    /// - `copy_source` - The bucket and URL-encoded key of the object to copy.
+    /// - `key` - The key of the object being uploaded.
+    /// - `upload_id` - The id of the multipart upload.
+    /// - `size` - The size of the object to copy in bytes.
+    ///
+    /// # Returns
+    /// The number and ETag of each copied part, needed to complete the upload.
+    async fn copy_parts(
+        &self,
+        copy_source: &str,
+        key: &str,
+        upload_id: &str,
+        size: u64,
+    ) -> Result<Vec<(i32, String)>, AppError> {
+        let mut parts = Vec::new();
+
+        for (index, first) in (0..size).step_by(COPY_PART_BYTES as usize).enumerate() {
+            let part_number = index as i32 + 1;
+            let last = (first + COPY_PART_BYTES).min(size) - 1;
+            let response = self.with_retries("part copy", key, || {
+                self.client
+                    .upload_part_copy()
+                    .bucket(&self.bucket_name)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .part_number(part_number)
+                    .copy_source(copy_source)
+                    .copy_source_range(format!("bytes={}-{}", first, last))
+                    .send()
+            })
             .await?;
 
-        let data = response.body.collect().await?;
-        Ok(data.into_bytes().to_vec())
+            let etag = response
+                .copy_part_result
+                .and_then(|result| result.e_tag)
+                .ok_or_else(|| AppError::ValidationError(format!("S3 returned no ETag for part {} of '{}'", part_number, key)))?;
+            parts.push((part_number, etag));
+        }
+
+        Ok(parts)
     }
 
-    /// Checks if a file exists in the S3 bucket.
+    /// Sends an S3 request, retrying transient failures with exponential backoff and jitter.
+    ///
+    /// Throttling, server errors, timeouts and connection failures are retried up to the
+    /// configured number of attempts. Other errors, like a denied access or a missing
+    /// object, are returned at once.
     ///
     /// # Parameters
-    /// - `key` - The key of the file to check.
+    /// - `operation` - The name of the operation, used in logs.
+    /// - `key` - The key of the file the request is about, used in logs.
+    /// - `request` - Builds and sends the request, called once per attempt.
     ///
-    pub async fn file_exists(&self, key: &str) -> bool {
-        match self.get_client().head_object().bucket(&self.get_bucket_name()).key(key).send().await {
-            Ok(_) => true,
-            Err(_) => false,
+    /// # Returns
+    /// The response of the first successful attempt, or the error of the last attempt.
+    async fn with_retries<T, E, F, Fut>(
+        &self,
+        operation: &str,
+        key: &str,
+        mut request: F,
+    ) -> Result<T, SdkError<E, HttpResponse>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+    {
+        let mut attempt = 1;
+        loop {
+            debug!("S3 {} of '{}', attempt {}/{}", operation, key, attempt, self.retry.max_attempts);
+            match request().await {
+                Ok(output) => {
+                    if attempt > 1 {
+                        debug!("S3 {} of '{}' succeeded after {} attempts", operation, key, attempt);
+                    }
+                    return Ok(output);
+                }
+                Err(e) if attempt < self.retry.max_attempts && is_retryable(&e) => {
+                    let delay = self.retry.backoff(attempt);
+                    metrics::counter!(S3_RETRIES_TOTAL, "operation" => operation.to_string()).increment(1);
+                    warn!(
+                        "S3 {} of '{}' failed on attempt {}/{}, retrying in {:?}: {}",
+                        operation, key, attempt, self.retry.max_attempts, delay, DisplayErrorContext(&e)
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+                Err(e) => {
+                    // A missing object is an expected outcome rather than a failure of S3
+                    if e.raw_response().is_none_or(|response| response.status().as_u16() != 404) {
+                        metrics::counter!(S3_ERRORS_TOTAL, "operation" => operation.to_string()).increment(1);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Storage for S3Client {
+    /// Tests the connection to the S3 bucket by listing objects.
+    /// The request isn't retried, so health checks report failures promptly.
+    async fn test_connection(&self) -> Result<(), AppError> {
+        self.get_client()
+            .list_objects_v2()
+            .bucket(self.get_bucket_name())
+            .send()
+            .await?;
+        Ok(())
+    }
+
+    /// Uploads a file to the S3 bucket, storing its digest and file name as object metadata.
+    async fn upload(
+        &self,
+        key: &str,
+        data: &[u8],
+        sha256: &str,
+        file_name: Option<&str>,
+        content_type: Option<&str>,
+        storage_class: Option<&str>,
+    ) -> Result<(), AppError> {
+        let data = Bytes::copy_from_slice(data);
+        self.with_retries("upload", key, || {
+            self.client
+                .put_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .set_metadata(Some(object_metadata(sha256, file_name)))
+                .set_content_type(content_type.map(str::to_string))
+                .set_storage_class(storage_class.map(StorageClass::from))
+                .body(ByteStream::from(data.clone()))
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Uploads a local file to the S3 bucket, streaming it from disk.
+    async fn upload_from_path(
+        &self,
+        key: &str,
+        path: &Path,
+        sha256: &str,
+        file_name: Option<&str>,
+        content_type: Option<&str>,
+        storage_class: Option<&str>,
+    ) -> Result<(), AppError> {
+        self.with_retries("upload", key, || async {
+            let body = ByteStream::from_path(path).await.map_err(SdkError::construction_failure)?;
+            self.client
+                .put_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .set_metadata(Some(object_metadata(sha256, file_name)))
+                .set_content_type(content_type.map(str::to_string))
+                .set_storage_class(storage_class.map(StorageClass::from))
+                .body(body)
+                .send()
+                .await
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Streams a file from the S3 bucket into a local file, verifying it on the way.
+    async fn download_to_file(&self, key: &str, path: &Path) -> Result<u64, AppError> {
+        let response = self.get_object(key, None).await?;
+
+        let expected = response
+            .metadata()
+            .and_then(|metadata| metadata.get(SHA256_METADATA_KEY))
+            .cloned();
+
+        write_verified(key, response.body.into_async_read(), expected, path).await
+    }
+
+    /// Opens a file in the S3 bucket for streaming, passing the range through to S3.
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<StoredObject, AppError> {
+        match range {
+            Some(ByteRange::From(start, end)) => self.download_range(key, start, end).await,
+            range => Ok(stored_object(self.get_object(key, range).await?)),
+        }
+    }
+
+    /// Fetches the metadata of a file in the S3 bucket, only a 404 meaning it doesn't exist.
+    async fn head_file(&self, key: &str) -> Result<Option<ObjectMetadata>, AppError> {
+        let response = match self
+            .with_retries("head", key, || self.client.head_object().bucket(&self.bucket_name).key(key).send())
+            .await
+        {
+            Ok(response) => response,
+            Err(e) if e.raw_response().is_some_and(|response| response.status().as_u16() == 404) => return Ok(None),
+            Err(e) => return Err(AppError::SdkHeadObjectError(Box::new(e))),
+        };
+
+        Ok(Some(ObjectMetadata {
+            size: response.content_length.unwrap_or_default().max(0) as u64,
+            last_modified: response
+                .last_modified
+                .and_then(|time| DateTime::<Utc>::from_timestamp(time.secs(), time.subsec_nanos())),
+            etag: response.e_tag,
+            content_type: response.content_type,
+        }))
+    }
+
+    /// Fetches the ETag of a file in the S3 bucket.
+    async fn etag(&self, key: &str) -> Option<String> {
+        self.with_retries("head", key, || self.client.head_object().bucket(&self.bucket_name).key(key).send())
+            .await
+            .ok()?
+            .e_tag
+    }
+
+    /// Fetches the SHA-256 digest from the metadata of a file in the S3 bucket.
+    async fn checksum(&self, key: &str) -> Result<Option<String>, AppError> {
+        let response = self
+            .with_retries("head", key, || self.client.head_object().bucket(&self.bucket_name).key(key).send())
+            .await
+            .map_err(|e| match e.raw_response().map(|response| response.status().as_u16()) {
+                Some(404) => AppError::ObjectNotFound(key.to_string()),
+                _ => AppError::SdkHeadObjectError(Box::new(e)),
+            })?;
+
+        Ok(response
+            .metadata()
+            .and_then(|metadata| metadata.get(SHA256_METADATA_KEY))
+            .cloned())
+    }
+
+    /// Computes the SHA-256 digest and size of a file by streaming it from S3.
+    async fn digest(&self, key: &str) -> Result<(String, u64), AppError> {
+        let mut response = self.get_object(key, None).await?;
+
+        let mut hasher = Sha256::new();
+        let mut size = 0;
+        while let Some(chunk) = response.body.try_next().await? {
+            size += chunk.len() as u64;
+            hasher.update(&chunk);
+        }
+
+        Ok((format!("{:x}", hasher.finalize()), size))
+    }
+
+    /// Deletes a file from the S3 bucket.
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        self.with_retries("delete", key, || {
+            self.client
+                .delete_object()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Copies a file within the S3 bucket, keeping its metadata. Files over 5 GB are copied
+    /// with a multipart upload, one `UploadPartCopy` per part.
+    async fn copy_file(&self, src_key: &str, dst_key: &str) -> Result<(), AppError> {
+        let source = self
+            .with_retries("head", src_key, || self.client.head_object().bucket(&self.bucket_name).key(src_key).send())
+            .await
+            .map_err(|e| match e.raw_response().map(|response| response.status().as_u16()) {
+                Some(404) => AppError::ObjectNotFound(src_key.to_string()),
+                _ => AppError::SdkHeadObjectError(Box::new(e)),
+            })?;
+
+        let copy_source = format!("{}/{}", self.bucket_name, encode_key(src_key));
+        let size = source.content_length.unwrap_or_default().max(0) as u64;
+
+        if size <= MAX_COPY_OBJECT_BYTES {
+            self.with_retries("copy", src_key, || {
+                self.client
+                    .copy_object()
+                    .bucket(&self.bucket_name)
+                    .key(dst_key)
+                    .copy_source(&copy_source)
+                    .send()
+            })
+            .await?;
+            return Ok(());
+        }
+
+        let upload_id = self.with_retries("multipart upload start", dst_key, || {
+            self.client
+                .create_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(dst_key)
+                .set_content_type(source.content_type.clone())
+                .set_metadata(source.metadata.clone())
+                .send()
+        })
+        .await?
+        .upload_id
+        .ok_or_else(|| AppError::ValidationError(format!("S3 returned no upload id for '{}'", dst_key)))?;
+
+        match self.copy_parts(&copy_source, dst_key, &upload_id, size).await {
+            Ok(parts) => self.complete_multipart_upload(dst_key, &upload_id, parts).await,
+            Err(e) => {
+                if let Err(abort_error) = self.abort_multipart_upload(dst_key, &upload_id).await {
+                    warn!("Failed to abort the multipart copy of '{}': {}", dst_key, abort_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Lists the keys of the files in the S3 bucket starting with a prefix, following pagination.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, AppError> {
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+
+        loop {
+            let page = self.with_retries("list", prefix, || {
+                self.client
+                    .list_objects_v2()
+                    .bucket(&self.bucket_name)
+                    .prefix(prefix)
+                    .set_continuation_token(continuation_token.clone())
+                    .send()
+            })
+            .await?;
+
+            keys.extend(page.contents().iter().filter_map(|object| object.key().map(str::to_string)));
+
+            match page.next_continuation_token {
+                Some(token) if page.is_truncated == Some(true) => continuation_token = Some(token),
+                _ => return Ok(keys),
+            }
+        }
+    }
+
+    /// Starts an S3 multipart upload.
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, AppError> {
+        let response = self.with_retries("multipart upload start", key, || {
+            self.client
+                .create_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .send()
+        })
+        .await?;
+
+        response
+            .upload_id
+            .ok_or_else(|| AppError::ValidationError(format!("S3 returned no upload id for '{}'", key)))
+    }
+
+    /// Uploads a part of an S3 multipart upload.
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, AppError> {
+        let data = Bytes::from(data);
+        let response = self.with_retries("part upload", key, || {
+            self.client
+                .upload_part()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(data.clone()))
+                .send()
+        })
+        .await?;
+
+        response
+            .e_tag
+            .ok_or_else(|| AppError::ValidationError(format!("S3 returned no ETag for part {} of '{}'", part_number, key)))
+    }
+
+    /// Completes an S3 multipart upload.
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> Result<(), AppError> {
+        let parts: Vec<CompletedPart> = parts
+            .into_iter()
+            .map(|(part_number, etag)| CompletedPart::builder().part_number(part_number).e_tag(etag).build())
+            .collect();
+
+        self.with_retries("multipart upload completion", key, || {
+            self.client
+                .complete_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .upload_id(upload_id)
+                .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts.clone())).build())
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Aborts an S3 multipart upload.
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), AppError> {
+        self.with_retries("multipart upload abort", key, || {
+            self.client
+                .abort_multipart_upload()
+                .bucket(&self.bucket_name)
+                .key(key)
+                .upload_id(upload_id)
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+}
+
+/// URL-encodes a key for the `x-amz-copy-source` header, keeping its `/` separators.
+///
+/// # Parameters
+/// - `key` - The key to encode.
+fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// Returns whether a failed S3 request may succeed if sent again.
+///
+/// # Parameters
+/// - `error` - The SDK error.
+fn is_retryable<E: ProvideErrorMetadata>(error: &SdkError<E, HttpResponse>) -> bool {
+    match error {
+        SdkError::TimeoutError(_) | SdkError::ResponseError(_) => true,
+        SdkError::DispatchFailure(failure) => !failure.is_user(),
+        SdkError::ServiceError(service_error) => {
+            let status = service_error.raw().status().as_u16();
+            status == 429
+                || status >= 500
+                || matches!(error.code(), Some("SlowDown" | "Throttling" | "ThrottlingException" | "RequestTimeout"))
+        }
+        _ => false,
+    }
+}
+
+/// Wraps the response of a `GetObject` request for streaming.
+fn stored_object(response: GetObjectOutput) -> StoredObject {
+    StoredObject {
+        content_type: response.content_type().map(str::to_string),
+        content_length: response.content_length(),
+        content_range: response.content_range().map(str::to_string),
+        etag: response.e_tag().map(str::to_string),
+        body: Box::pin(response.body.into_async_read()),
+    }
+}
+
+/// Maps a `GetObject` failure to `AppError`, reporting a missing object as
+/// `AppError::ObjectNotFound` and an invalid range as `AppError::RangeNotSatisfiable`.
+///
+/// # Parameters
+/// - `key` - The key of the requested file.
+/// - `error` - The SDK error.
+fn get_object_error(key: &str, error: SdkError<GetObjectError, HttpResponse>) -> AppError {
+    if error.as_service_error().is_some_and(|e| e.is_no_such_key()) {
+        return AppError::ObjectNotFound(key.to_string());
+    }
+
+    match error.raw_response().map(|response| response.status().as_u16()) {
+        Some(404) => AppError::ObjectNotFound(key.to_string()),
+        Some(416) => AppError::RangeNotSatisfiable(key.to_string()),
+        _ => AppError::SdkDownloadObjectError(Box::new(error)),
+    }
+}
+
+/// Builds the metadata stored with an uploaded object.
+///
+/// # Parameters
+/// - `sha256` - The hex-encoded SHA-256 digest of the content.
+/// - `file_name` - The name the object was uploaded under, if it should be kept.
+fn object_metadata(sha256: &str, file_name: Option<&str>) -> HashMap<String, String> {
+    let mut metadata = HashMap::from([(SHA256_METADATA_KEY.to_string(), sha256.to_string())]);
+    if let Some(file_name) = file_name {
+        let encoded = url::form_urlencoded::byte_serialize(file_name.as_bytes()).collect();
+        metadata.insert(FILE_NAME_METADATA_KEY.to_string(), encoded);
+    }
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use axum::extract::Path;
+    use axum::http::{header, HeaderMap, StatusCode};
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use aws_sdk_s3::error::ErrorMetadata;
+    use aws_sdk_s3::primitives::SdkBody;
+
+    fn client(max_attempts: u32) -> S3Client {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .build();
+        S3Client {
+            client: Client::from_conf(config),
+            bucket_name: "test".to_string(),
+            retry: RetryPolicy { max_attempts, base_delay: Duration::from_millis(1) },
         }
     }
-}
\ No newline at end of file
+
+    /// Returns a service error of the given status and error code.
+    fn service_error(status: u16, code: &str) -> SdkError<GetObjectError, HttpResponse> {
+        let error = GetObjectError::generic(ErrorMetadata::builder().code(code).build());
+        SdkError::service_error(error, HttpResponse::new(status.try_into().unwrap(), SdkBody::empty()))
+    }
+
+    /// Sends a request failing with `error` on its first `failures` attempts, returning the
+    /// result and the number of attempts.
+    async fn send(
+        client: &S3Client,
+        failures: u32,
+        error: fn() -> SdkError<GetObjectError, HttpResponse>,
+    ) -> (Result<(), SdkError<GetObjectError, HttpResponse>>, u32) {
+        let attempts = AtomicU32::new(0);
+        let result = client.with_retries("download", "key", || async {
+            match attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                true => Err(error()),
+                false => Ok(()),
+            }
+        }).await;
+        (result, attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_until_success() {
+        let (result, attempts) = send(&client(3), 2, || service_error(503, "SlowDown")).await;
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_the_maximum_attempts() {
+        let (result, attempts) = send(&client(3), u32::MAX, || service_error(500, "InternalError")).await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_failures_fail_fast() {
+        for error in [|| service_error(403, "AccessDenied"), || service_error(404, "NoSuchKey")] {
+            let (result, attempts) = send(&client(3), 1, error).await;
+            assert!(result.is_err());
+            assert_eq!(attempts, 1);
+        }
+    }
+
+    #[test]
+    fn throttling_and_server_errors_are_retryable() {
+        assert!(is_retryable(&service_error(429, "TooManyRequests")));
+        assert!(is_retryable(&service_error(400, "RequestTimeout")));
+        assert!(is_retryable(&service_error(502, "BadGateway")));
+        assert!(!is_retryable(&service_error(400, "InvalidArgument")));
+    }
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_maximum_delay() {
+        let retry = RetryPolicy { max_attempts: 10, base_delay: Duration::from_millis(100) };
+        for _ in 0..100 {
+            assert!(retry.backoff(1) <= Duration::from_millis(100));
+            assert!(retry.backoff(3) <= Duration::from_millis(400));
+            assert!(retry.backoff(30) <= MAX_RETRY_DELAY);
+        }
+    }
+
+    /// Serves an object from a fake S3 endpoint, a request without range being answered with
+    /// the whole object, and returns a client of that endpoint and the headers it received.
+    /// Uploaded objects are kept with their metadata and served instead of `content`. The keys
+    /// `missing` and `denied` are answered with a 404 and a 403.
+    async fn fake_s3(content: &'static [u8]) -> (S3Client, Arc<Mutex<Vec<HeaderMap>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let objects = Arc::new(Mutex::new(HashMap::<String, (HeaderMap, Bytes)>::new()));
+        let (received, stored) = (requests.clone(), requests.clone());
+        let (served, uploaded) = (objects.clone(), objects);
+        let app = axum::Router::new().route("/test/{*key}", get(move |Path(key): Path<String>, headers: HeaderMap| async move {
+            match key.as_str() {
+                "missing" => return StatusCode::NOT_FOUND.into_response(),
+                "denied" => return StatusCode::FORBIDDEN.into_response(),
+                _ => {}
+            }
+            let range = headers.get(header::RANGE).map(|range| range.to_str().unwrap().to_string());
+            received.lock().unwrap().push(headers);
+            let (metadata, content) = served.lock().unwrap().get(&key).cloned().unwrap_or_else(|| (HeaderMap::new(), Bytes::from_static(content)));
+            let Some(range) = range else {
+                return (metadata, content).into_response();
+            };
+            let Some(Ok((first, last))) = ByteRange::parse(&range).unwrap().map(|range| range.resolve(content.len() as u64)) else {
+                return StatusCode::RANGE_NOT_SATISFIABLE.into_response();
+            };
+            let content_range = format!("bytes {}-{}/{}", first, last, content.len());
+            (StatusCode::PARTIAL_CONTENT, metadata, [(header::CONTENT_RANGE, content_range)], content.slice(first as usize..=last as usize)).into_response()
+        }).put(move |Path(key): Path<String>, headers: HeaderMap, body: Bytes| async move {
+            let metadata = headers.iter()
+                .filter(|(name, _)| name.as_str().starts_with("x-amz-meta-") || *name == header::CONTENT_TYPE)
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            uploaded.lock().unwrap().insert(key, (metadata, body));
+            stored.lock().unwrap().push(headers);
+            StatusCode::OK
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .endpoint_url(endpoint)
+            .force_path_style(true)
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        let client = S3Client {
+            client: Client::from_conf(config),
+            bucket_name: "test".to_string(),
+            retry: RetryPolicy { max_attempts: 1, base_delay: Duration::from_millis(1) },
+        };
+        (client, requests)
+    }
+
+    #[tokio::test]
+    async fn mid_file_range_is_requested_and_returned() {
+        const CONTENT: &[u8] = b"0123456789abcdefghij";
+        let (client, requests) = fake_s3(CONTENT).await;
+
+        let mut object = client.download_range("file.bin", 4, Some(11)).await.unwrap();
+        let mut body = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut object.body, &mut body).await.unwrap();
+        assert_eq!(body, b"456789ab");
+        assert_eq!(object.content_range.as_deref(), Some("bytes 4-11/20"));
+        assert_eq!(object.content_length, Some(8));
+
+        let result = client.download_range("file.bin", 20, None).await;
+        assert!(matches!(result, Err(AppError::RangeNotSatisfiable(_))));
+        let ranges: Vec<_> = requests.lock().unwrap().iter().map(|headers| headers[header::RANGE].clone()).collect();
+        assert_eq!(ranges, ["bytes=4-11", "bytes=20-"]);
+    }
+
+    #[tokio::test]
+    async fn upload_is_stored_in_the_requested_storage_class() {
+        let (client, requests) = fake_s3(b"").await;
+
+        client.upload("cold.pdf", b"%PDF-1.4", "digest", None, None, Some("GLACIER_IR")).await.unwrap();
+        client.upload("default.pdf", b"%PDF-1.4", "digest", None, None, None).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0]["x-amz-storage-class"], "GLACIER_IR");
+        assert!(!requests[1].contains_key("x-amz-storage-class"));
+    }
+
+    #[tokio::test]
+    async fn denied_access_is_not_mistaken_for_a_missing_file() {
+        let (client, _) = fake_s3(b"%PDF-1.4").await;
+
+        let metadata = client.head_file("report.pdf").await.unwrap().unwrap();
+        assert_eq!(metadata.size, 8);
+        assert!(client.exists("report.pdf").await.unwrap());
+
+        assert!(client.head_file("missing").await.unwrap().is_none());
+        assert!(!client.exists("missing").await.unwrap());
+
+        assert!(matches!(client.head_file("denied").await, Err(AppError::SdkHeadObjectError(_))));
+        assert!(client.exists("denied").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn downloaded_file_matches_the_uploaded_bytes() {
+        let (client, _) = fake_s3(b"").await;
+        let content: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let sha256 = format!("{:x}", Sha256::digest(&content));
+        client.upload("archive.zip", &content, &sha256, Some("archive.zip"), None, None).await.unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("archive.zip");
+        assert_eq!(client.download_to_file("archive.zip", &path).await.unwrap(), content.len() as u64);
+        assert_eq!(std::fs::read(&path).unwrap(), content);
+
+        // An object whose content doesn't match its stored digest isn't kept
+        client.upload("corrupt.zip", &content, &"0".repeat(64), None, None, None).await.unwrap();
+        let path = dir.path().join("corrupt.zip");
+        assert!(matches!(client.download_to_file("corrupt.zip", &path).await, Err(AppError::ValidationError(_))));
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn declared_content_type_is_stored_with_the_object() {
+        let (client, _) = fake_s3(b"").await;
+        client.upload("archive.tar.gz", b"\x1f\x8b", "digest", None, Some("application/gzip"), None).await.unwrap();
+
+        let object = client.open("archive.tar.gz", None).await.unwrap();
+        assert_eq!(object.content_type.as_deref(), Some("application/gzip"));
+    }
+}
@@ -0,0 +1,465 @@
+use std::fmt;
+use std::path::Path;
+use std::pin::Pin;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::warn;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use crate::error::AppError;
+
+/// The size of the buffer used when streaming objects to files.
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A stored object opened for streaming.
+///
+/// # Fields
+/// - `content_type`: The content type recorded by the backend, if any.
+/// - `content_length`: The number of bytes in `body`.
+/// - `content_range`: The `Content-Range` of the returned bytes, set when a range was requested.
+/// - `etag`: The ETag of the object, if any.
+/// - `body`: The content of the object, not read yet.
+///
+pub struct StoredObject {
+    pub content_type: Option<String>,
+    pub content_length: Option<i64>,
+    pub content_range: Option<String>,
+    pub etag: Option<String>,
+    pub body: Pin<Box<dyn AsyncRead + Send>>,
+}
+
+/// The metadata of a stored object, fetched without reading its content.
+///
+/// # Fields
+/// - `size`: The size of the object in bytes.
+/// - `etag`: The ETag of the object, if any.
+/// - `last_modified`: When the object was last written, if known.
+/// - `content_type`: The content type recorded by the backend, if any.
+///
+#[derive(Clone, Debug)]
+pub struct ObjectMetadata {
+    pub size: u64,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub content_type: Option<String>,
+}
+
+/// The operations the application needs from an object storage backend.
+///
+/// Objects are addressed by `/`-separated keys. Missing objects are reported as
+/// `AppError::ObjectNotFound` by the operations that read them.
+#[async_trait]
+pub trait Storage: Send + Sync {
+    /// Tests that the backend can be reached.
+    async fn test_connection(&self) -> Result<(), AppError>;
+
+    /// Stores an object, replacing any object with the same key.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    /// - `data` - The content of the object.
+    /// - `file_name` - The name the object was uploaded under, kept in its metadata when set.
+    ///   Backends without object metadata ignore it.
+    /// - `sha256` - The hex-encoded SHA-256 digest of the content, kept to verify downloads.
+    /// - `content_type` - The declared content type of the object, returned when it is opened.
+    /// - `storage_class` - The storage class to store the object in, or `None` for the default.
+    ///   Backends without storage classes ignore it.
+    async fn upload(
+        &self,
+        key: &str,
+        data: &[u8],
+        sha256: &str,
+        file_name: Option<&str>,
+        content_type: Option<&str>,
+        storage_class: Option<&str>,
+    ) -> Result<(), AppError>;
+
+    /// Stores an object from the content of a local file, without loading it in memory.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    /// - `path` - The path of the file holding the content.
+    /// - `sha256` - The hex-encoded SHA-256 digest of the content, kept to verify downloads.
+    /// - `file_name` - The name the object was uploaded under, kept in its metadata when set.
+    ///   Backends without object metadata ignore it.
+    /// - `content_type` - The declared content type of the object, returned when it is opened.
+    /// - `storage_class` - The storage class to store the object in, or `None` for the default.
+    ///   Backends without storage classes ignore it.
+    async fn upload_from_path(
+        &self,
+        key: &str,
+        path: &Path,
+        sha256: &str,
+        file_name: Option<&str>,
+        content_type: Option<&str>,
+        storage_class: Option<&str>,
+    ) -> Result<(), AppError>;
+
+    /// Downloads an object into a file, streaming it without loading it in memory, and
+    /// verifying it against its stored SHA-256 digest. The file is removed if the download fails.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    /// - `path` - The path of the file to write, replaced if it exists.
+    ///
+    /// # Returns
+    /// - `Ok(u64)`: The number of bytes written.
+    /// - `Err(AppError::ValidationError)`: If the content doesn't match the stored digest.
+    async fn download_to_file(&self, key: &str, path: &Path) -> Result<u64, AppError>;
+
+    /// Opens an object for streaming, optionally restricted to a byte range.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    /// - `range` - The byte range to return, or `None` for the whole object.
+    ///
+    /// # Returns
+    /// - `Ok(StoredObject)`: The object, or the requested range of it.
+    /// - `Err(AppError::RangeNotSatisfiable)`: If the range lies outside the object.
+    async fn open(&self, key: &str, range: Option<ByteRange>) -> Result<StoredObject, AppError>;
+
+    /// Fetches the metadata of an object.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    ///
+    /// # Returns
+    /// - `Ok(Some(ObjectMetadata))`: The metadata of the object.
+    /// - `Ok(None)`: If the object doesn't exist.
+    /// - `Err(AppError)`: If the backend can't be reached or denies the request.
+    async fn head_file(&self, key: &str) -> Result<Option<ObjectMetadata>, AppError>;
+
+    /// Checks if an object exists, telling a missing object apart from a failing backend.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    async fn exists(&self, key: &str) -> Result<bool, AppError> {
+        Ok(self.head_file(key).await?.is_some())
+    }
+
+    /// Fetches the ETag of an object, which changes whenever its content does.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    ///
+    /// # Returns
+    /// The ETag, or `None` if the object doesn't exist or the backend can't be reached.
+    async fn etag(&self, key: &str) -> Option<String>;
+
+    /// Fetches the SHA-256 digest stored with an object when it was uploaded.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    ///
+    /// # Returns
+    /// The hex-encoded digest, or `None` if the object was stored without one.
+    async fn checksum(&self, key: &str) -> Result<Option<String>, AppError>;
+
+    /// Computes the SHA-256 digest and size of an object by streaming it.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    ///
+    /// # Returns
+    /// The hex-encoded digest and the size of the object in bytes.
+    async fn digest(&self, key: &str) -> Result<(String, u64), AppError>;
+
+    /// Deletes an object. Deleting a missing object succeeds.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object.
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+
+    /// Copies an object to another key, along with its stored digest, replacing any object
+    /// with the same key.
+    ///
+    /// # Parameters
+    /// - `src_key` - The key of the object to copy.
+    /// - `dst_key` - The key of the copy.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If the object was copied.
+    /// - `Err(AppError::ObjectNotFound)`: If no object has the source key.
+    async fn copy_file(&self, src_key: &str, dst_key: &str) -> Result<(), AppError>;
+
+    /// Lists the keys of the objects starting with a prefix, in ascending order.
+    ///
+    /// # Parameters
+    /// - `prefix` - The prefix of the keys, or an empty string for every object.
+    #[allow(dead_code)]
+    async fn list(&self, prefix: &str) -> Result<Vec<String>, AppError>;
+
+    /// Starts a multipart upload of an object.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object to upload.
+    ///
+    /// # Returns
+    /// The id of the multipart upload.
+    async fn create_multipart_upload(&self, key: &str) -> Result<String, AppError>;
+
+    /// Uploads a part of a multipart upload, replacing any part with the same number.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object being uploaded.
+    /// - `upload_id` - The id of the multipart upload.
+    /// - `part_number` - The number of the part, from 1 to 10000.
+    /// - `data` - The content of the part.
+    ///
+    /// # Returns
+    /// The ETag of the uploaded part, needed to complete the upload.
+    async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Vec<u8>,
+    ) -> Result<String, AppError>;
+
+    /// Completes a multipart upload, assembling its parts into the final object.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object being uploaded.
+    /// - `upload_id` - The id of the multipart upload.
+    /// - `parts` - The number and ETag of every part, in ascending part order.
+    async fn complete_multipart_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: Vec<(i32, String)>,
+    ) -> Result<(), AppError>;
+
+    /// Aborts a multipart upload, discarding its uploaded parts.
+    ///
+    /// # Parameters
+    /// - `key` - The key of the object being uploaded.
+    /// - `upload_id` - The id of the multipart upload.
+    async fn abort_multipart_upload(&self, key: &str, upload_id: &str) -> Result<(), AppError>;
+}
+
+/// A single byte range requested by an HTTP `Range` header.
+///
+/// - `From(first, last)`: The bytes from `first` to `last`, both inclusive, or to the end of the object.
+/// - `Suffix(length)`: The last `length` bytes of the object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteRange {
+    From(u64, Option<u64>),
+    Suffix(u64),
+}
+
+impl ByteRange {
+    /// Parses a `Range` header holding a single byte range.
+    ///
+    /// # Parameters
+    /// - `range` - The `Range` header value, such as `bytes=0-1023`, `bytes=1024-` or `bytes=-512`.
+    ///
+    /// # Returns
+    /// - `Ok(Some(ByteRange))`: The requested range.
+    /// - `Ok(None)`: If the header uses another unit or holds several ranges, in which case it should be ignored.
+    /// - `Err(AppError::RangeNotSatisfiable)`: If the byte range is malformed.
+    pub fn parse(range: &str) -> Result<Option<Self>, AppError> {
+        let Some(spec) = range.strip_prefix("bytes=") else {
+            return Ok(None);
+        };
+        if spec.contains(',') {
+            return Ok(None);
+        }
+
+        let malformed = || AppError::RangeNotSatisfiable(format!("malformed range {}", range));
+        let (start, end) = spec.trim().split_once('-').ok_or_else(malformed)?;
+
+        match (start.trim(), end.trim()) {
+            ("", suffix) => match suffix.parse::<u64>() {
+                Ok(length) => Ok(Some(ByteRange::Suffix(length))),
+                Err(_) => Err(malformed()),
+            },
+            (start, "") => start.parse().map(|first| Some(ByteRange::From(first, None))).map_err(|_| malformed()),
+            (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+                (Ok(first), Ok(last)) if last >= first => Ok(Some(ByteRange::From(first, Some(last)))),
+                _ => Err(malformed()),
+            },
+        }
+    }
+
+    /// Resolves the range against the size of an object.
+    ///
+    /// # Parameters
+    /// - `size` - The size of the object in bytes.
+    ///
+    /// # Returns
+    /// - `Ok((u64, u64))`: The first and last byte of the range, both inclusive.
+    /// - `Err(AppError::RangeNotSatisfiable)`: If the range lies outside the object.
+    pub fn resolve(&self, size: u64) -> Result<(u64, u64), AppError> {
+        let not_satisfiable = || AppError::RangeNotSatisfiable(format!("{} of {} bytes", self, size));
+
+        let (first, last) = match *self {
+            ByteRange::Suffix(0) => return Err(not_satisfiable()),
+            ByteRange::Suffix(length) => (size.saturating_sub(length), size.saturating_sub(1)),
+            ByteRange::From(first, last) => (first, last.unwrap_or(u64::MAX).min(size.saturating_sub(1))),
+        };
+
+        if size == 0 || first >= size {
+            return Err(not_satisfiable());
+        }
+
+        Ok((first, last))
+    }
+}
+
+impl fmt::Display for ByteRange {
+    /// Formats the range as a `Range` header value.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ByteRange::From(first, Some(last)) => write!(f, "bytes={}-{}", first, last),
+            ByteRange::From(first, None) => write!(f, "bytes={}-", first),
+            ByteRange::Suffix(length) => write!(f, "bytes=-{}", length),
+        }
+    }
+}
+
+/// Streams the content of an object into a file, checking it against the object's stored digest.
+/// The file is removed if the content can't be written or doesn't match the digest.
+///
+/// # Parameters
+/// - `key` - The key of the object, used in errors.
+/// - `body` - The content of the object.
+/// - `expected` - The stored SHA-256 digest of the object, or `None` to skip the verification.
+/// - `path` - The path of the file to write.
+///
+/// # Returns
+/// - `Ok(u64)`: The number of bytes written.
+/// - `Err(AppError)`: If the content can't be read or written, or doesn't match the digest.
+pub async fn write_verified(
+    key: &str,
+    body: impl AsyncRead + Unpin,
+    expected: Option<String>,
+    path: &Path,
+) -> Result<u64, AppError> {
+    let result = copy_hashed(body, path).await.map_err(AppError::from).and_then(|(actual, size)| {
+        match expected {
+            Some(expected) if !actual.eq_ignore_ascii_case(&expected) => Err(AppError::ValidationError(format!(
+                "Checksum mismatch for '{}': expected {}, got {}",
+                key, expected, actual
+            ))),
+            Some(_) => Ok(size),
+            None => {
+                warn!("No SHA-256 digest stored for '{}', skipping verification", key);
+                Ok(size)
+            }
+        }
+    });
+
+    if result.is_err() {
+        if let Err(e) = tokio::fs::remove_file(path).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to remove partial download {:?}: {}", path, e);
+            }
+        }
+    }
+
+    result
+}
+
+/// Copies a stream into a file through a fixed-size buffer, hashing it on the way.
+///
+/// # Returns
+/// The hex-encoded SHA-256 digest and the size of the content.
+pub(crate) async fn copy_hashed(mut body: impl AsyncRead + Unpin, path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = tokio::fs::File::create(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; COPY_BUFFER_SIZE];
+    let mut size = 0;
+
+    loop {
+        let read = body.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        file.write_all(&buffer[..read]).await?;
+        size += read as u64;
+    }
+    file.flush().await?;
+
+    Ok((format!("{:x}", hasher.finalize()), size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_byte_ranges_are_parsed() {
+        assert_eq!(ByteRange::parse("bytes=0-1023").unwrap(), Some(ByteRange::From(0, Some(1023))));
+        assert_eq!(ByteRange::parse("bytes=1024-").unwrap(), Some(ByteRange::From(1024, None)));
+        assert_eq!(ByteRange::parse("bytes=-512").unwrap(), Some(ByteRange::Suffix(512)));
+    }
+
+    #[test]
+    fn other_units_and_multiple_ranges_are_ignored() {
+        assert_eq!(ByteRange::parse("items=0-10").unwrap(), None);
+        assert_eq!(ByteRange::parse("bytes=0-10,20-30").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_ranges_are_rejected() {
+        for range in ["bytes=10-5", "bytes=a-b", "bytes=5", "bytes=-"] {
+            assert!(matches!(ByteRange::parse(range), Err(AppError::RangeNotSatisfiable(_))), "{}", range);
+        }
+    }
+
+    #[test]
+    fn ranges_are_resolved_against_the_object_size() {
+        assert_eq!(ByteRange::From(4, Some(11)).resolve(100).unwrap(), (4, 11));
+        assert_eq!(ByteRange::From(90, Some(200)).resolve(100).unwrap(), (90, 99));
+        assert_eq!(ByteRange::From(10, None).resolve(100).unwrap(), (10, 99));
+        assert_eq!(ByteRange::Suffix(5).resolve(100).unwrap(), (95, 99));
+        assert_eq!(ByteRange::Suffix(500).resolve(100).unwrap(), (0, 99));
+
+        assert!(ByteRange::From(100, None).resolve(100).is_err());
+        assert!(ByteRange::Suffix(0).resolve(100).is_err());
+        assert!(ByteRange::From(0, None).resolve(0).is_err());
+    }
+
+    /// A stream generating `remaining` bytes on the fly, recording the largest read asked of it.
+    struct Generated {
+        remaining: usize,
+        largest_read: usize,
+    }
+
+    impl AsyncRead for Generated {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _cx: &mut std::task::Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> std::task::Poll<std::io::Result<()>> {
+            self.largest_read = self.largest_read.max(buf.remaining());
+            let length = buf.remaining().min(self.remaining);
+            buf.put_slice(&vec![b'a'; length]);
+            self.remaining -= length;
+            std::task::Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn large_object_is_streamed_through_a_fixed_buffer() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("large.bin");
+        let mut body = Generated { remaining: 32 * 1024 * 1024, largest_read: 0 };
+
+        let size = write_verified("large.bin", &mut body, None, &path).await.unwrap();
+        assert_eq!(size, 32 * 1024 * 1024);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), size);
+        assert!(body.largest_read <= COPY_BUFFER_SIZE, "read {} bytes at once", body.largest_read);
+    }
+
+    #[tokio::test]
+    async fn download_not_matching_its_digest_is_removed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("corrupt.bin");
+
+        let result = write_verified("corrupt.bin", &b"tampered"[..], Some("0".repeat(64)), &path).await;
+        assert!(matches!(result, Err(AppError::ValidationError(_))));
+        assert!(!path.exists());
+    }
+}
@@ -1,6 +1,57 @@
+use std::collections::HashMap;
 use std::env;
+use std::str::FromStr;
+use glob::Pattern;
+use url::Url;
 use crate::error::AppError;
 
+/// The response compression algorithms `COMPRESSION_ALGORITHMS` may list.
+pub const COMPRESSION_ALGORITHMS: &[&str] = &["gzip", "br"];
+
+/// The probe used by the PostgreSQL connection test.
+///
+/// - `Lightweight`: Only checks that a connection can run `SELECT 1`.
+/// - `Full`: Additionally checks that the expected schema is present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PostgresProbe {
+    Lightweight,
+    Full,
+}
+
+impl FromStr for PostgresProbe {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "lightweight" => Ok(PostgresProbe::Lightweight),
+            "full" => Ok(PostgresProbe::Full),
+            other => Err(format!("unknown PostgreSQL probe: {}", other)),
+        }
+    }
+}
+
+/// The storage backend holding uploaded files.
+///
+/// - `S3`: Stores files in the configured S3 bucket.
+/// - `Local`: Stores files under `LOCAL_STORAGE_DIR`, for development without AWS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageBackend {
+    S3,
+    Local,
+}
+
+impl FromStr for StorageBackend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "s3" => Ok(StorageBackend::S3),
+            "local" => Ok(StorageBackend::Local),
+            other => Err(format!("unknown storage backend: {}", other)),
+        }
+    }
+}
+
 /// Represents the application configuration loaded from environment variables.
 ///
 /// This struct holds all the necessary configuration values required to connect
@@ -8,10 +59,12 @@ use crate::error::AppError;
 #[derive(Clone)]
 pub struct AppConfig {
     /// AWS access key ID for authenticating with AWS services.
-    pub aws_access_key_id: String,
+    /// When unset, credentials come from the default AWS provider chain.
+    pub aws_access_key_id: Option<String>,
 
     /// AWS secret access key for authenticating with AWS services.
-    pub aws_secret_access_key: String,
+    /// When unset, credentials come from the default AWS provider chain.
+    pub aws_secret_access_key: Option<String>,
 
     /// AWS region where the S3 bucket is located.
     pub aws_region: String,
@@ -19,11 +72,201 @@ pub struct AppConfig {
     /// Name of the S3 bucket used for file storage.
     pub s3_bucket_name: String,
 
+    /// Optional custom endpoint URL for S3-compatible services such as MinIO or LocalStack.
+    pub s3_endpoint_url: Option<String>,
+
+    /// Whether to use path-style addressing (`endpoint/bucket/key`) for S3 requests.
+    pub s3_force_path_style: bool,
+
+    /// Maximum number of attempts of an S3 request failing with a transient error.
+    pub s3_retry_max_attempts: u32,
+
+    /// Base delay in milliseconds of the exponential backoff between S3 request attempts.
+    pub s3_retry_base_delay_ms: u64,
+
     /// Connection URL for the PostgreSQL database (RDS).
     pub database_url: String,
 
+    /// The probe used when testing the PostgreSQL connection.
+    pub postgres_probe: PostgresProbe,
+
     /// Connection URL for the Redis server.
     pub redis_url: String,
+
+    /// Number of seconds a last-known-good health status may be served after a failed check.
+    /// A value of `0` disables serving stale health.
+    pub health_stale_grace_secs: u64,
+
+    /// Maximum allowed size in bytes of an upload request body.
+    pub max_upload_size_bytes: usize,
+
+    /// Per file type overrides of the maximum allowed file size in bytes, keyed by file type name.
+    /// Loaded from `MAX_SIZE_<NAME>` variables, e.g. `MAX_SIZE_ZIP` or `MAX_SIZE_TAR_GZ`.
+    pub max_file_sizes: HashMap<String, usize>,
+
+    /// Name of the file type used to validate uploads whose extension is not recognized.
+    /// When unset, such uploads are rejected.
+    pub default_file_type: Option<String>,
+
+    /// Maximum total number of bytes that may be written when extracting a single archive.
+    pub max_total_extracted_bytes: u64,
+
+    /// Maximum number of bytes that may be written for a single archive entry.
+    pub max_entry_extracted_bytes: u64,
+
+    /// Maximum number of entries an archive may declare.
+    pub max_archive_entries: usize,
+
+    /// Maximum compression ratio of an extracted entry, above which extraction is aborted.
+    pub max_extract_ratio: f64,
+
+    /// Origins allowed to make cross-origin requests. Empty means no origin is allowed,
+    /// and `*` allows any origin.
+    pub cors_allowed_origins: Vec<String>,
+
+    /// HTTP methods allowed in cross-origin requests.
+    pub cors_allowed_methods: Vec<String>,
+
+    /// Request headers allowed in cross-origin requests.
+    pub cors_allowed_headers: Vec<String>,
+
+    /// Seconds browsers may cache the result of a CORS preflight request.
+    pub cors_max_age_secs: u64,
+
+    /// Maximum number of uploads per client IP within the rate limit window. `0` disables rate limiting.
+    pub rate_limit_uploads: u64,
+
+    /// Length of the upload rate limit window in seconds.
+    pub rate_limit_window_secs: u64,
+
+    /// Whether to identify clients by the `X-Forwarded-For` header set by a trusted proxy.
+    pub trust_proxy: bool,
+
+    /// Approximate maximum size in bytes of the serialized codebase JSON tree.
+    pub max_json_response_bytes: usize,
+
+    /// Default maximum depth of folders descended into when generating the codebase JSON tree.
+    pub tree_max_depth: usize,
+
+    /// Default glob patterns of entries left out of the codebase JSON tree.
+    pub tree_ignore_patterns: Vec<Pattern>,
+
+    /// Seconds a connection may stay idle or take to send request headers before it is closed.
+    pub http_idle_timeout_secs: u64,
+
+    /// Prefix prepended to every Redis key owned by the application.
+    pub redis_key_prefix: String,
+
+    /// Bearer token guarding the admin endpoints, which are disabled when unset.
+    pub admin_token: Option<String>,
+
+    /// Milliseconds each service health check may take before it is reported as timed out.
+    pub health_check_timeout_ms: u64,
+
+    /// Optional path of a TOML or JSON file defining additional upload file types.
+    pub file_types_config: Option<String>,
+
+    /// Whether uploads whose content is already stored are answered with the existing object.
+    pub deduplicate_uploads: bool,
+
+    /// Maximum number of files accepted in a single upload request.
+    pub max_upload_files: usize,
+
+    /// Maximum combined size in bytes of the files of a single upload request.
+    pub max_batch_upload_bytes: usize,
+
+    /// Seconds an in-progress chunked upload is kept without activity before it is aborted.
+    pub chunked_upload_ttl_secs: u64,
+
+    /// Maximum size in bytes of a single part of a chunked upload.
+    pub chunked_upload_max_part_bytes: usize,
+
+    /// The storage backend holding uploaded files.
+    pub storage_backend: StorageBackend,
+
+    /// Root directory of the local storage backend.
+    pub local_storage_dir: String,
+
+    /// Size in bytes past which an uploaded file is streamed to a temporary file instead of memory.
+    pub upload_spool_threshold_bytes: usize,
+
+    /// Seconds in-flight requests are given to finish once shutdown starts.
+    pub shutdown_drain_timeout_secs: u64,
+
+    /// Seconds in-flight uploads are given to finish once shutdown starts, when longer than
+    /// the general drain timeout.
+    pub upload_drain_timeout_secs: u64,
+
+    /// Health checks whose failure doesn't make the application unready (`s3`, `postgres`, `redis`).
+    pub health_optional_checks: Vec<String>,
+
+    /// Compression ratio above which archive inspection flags an entry.
+    pub archive_ratio_threshold: f64,
+
+    /// API keys accepted on the mutating file routes, mapped to the identity they authenticate.
+    pub api_keys: HashMap<String, String>,
+
+    /// Whether responses are compressed with gzip or brotli when the client accepts it.
+    pub response_compression: bool,
+
+    /// Size in bytes a response body must exceed to be compressed.
+    pub compression_min_size_bytes: u16,
+
+    /// Compression algorithms offered to clients, among `gzip` and `br`.
+    pub compression_algorithms: Vec<String>,
+
+    /// S3 storage classes uploads may request with `?storage_class=`.
+    pub allowed_storage_classes: Vec<String>,
+
+    /// Whether request, upload, and error metrics are recorded and served on `/metrics`.
+    pub metrics_enabled: bool,
+
+    /// Interval in seconds between two cleanups of the extracted competitions, `0` disabling them.
+    pub cleanup_interval_secs: u64,
+
+    /// Time in seconds after its last access past which an extracted competition is removed.
+    pub competition_ttl_secs: u64,
+
+    /// Whether uploads are also sniffed with the `infer` crate and checked against their type.
+    pub infer_file_types: bool,
+
+    /// Time in seconds a request waits for a concurrent extraction of the same competition.
+    pub extraction_wait_secs: u64,
+
+    /// Directory the competitions are extracted into.
+    pub competitions_dir: String,
+
+    /// Interval in seconds between two readiness checks of the required services, `0` disabling them.
+    pub readiness_check_interval_secs: u64,
+
+    /// Time in seconds a soft-deleted file is kept before the cleanup task purges it.
+    pub deleted_file_retention_secs: u64,
+
+    /// Whether uploads declared as `application/octet-stream`, or without a content type, are
+    /// rejected instead of being validated from their extension and magic number only.
+    pub strict_content_type: bool,
+
+    /// Whether uploads are stored under `sha256/<digest>` rather than their file name, so
+    /// identical uploads share a single object. Chunked uploads keep their file name as key,
+    /// their digest being unknown until they complete.
+    pub content_addressed_storage: bool,
+
+    /// Extensions of the entries rejected in uploaded ZIP archives, e.g. `exe`, none by default.
+    pub blocked_archive_entry_extensions: Vec<String>,
+
+    /// Maximum width in pixels of uploaded PNG and JPEG images, `0` allowing any width.
+    pub max_image_width: u32,
+
+    /// Maximum height in pixels of uploaded PNG and JPEG images, `0` allowing any height.
+    pub max_image_height: u32,
+
+    /// Whether uploaded ZIP archives are inspected, rejecting those holding links, setuid files,
+    /// entries escaping the archive or above the extraction limits.
+    pub inspect_uploaded_archives: bool,
+
+    /// Whether `INSPECT_UPLOADED_ARCHIVES` also applies to tar.gz archives, which have to be
+    /// decompressed to be inspected.
+    pub inspect_uploaded_tar_gz: bool,
 }
 
 /// Fetches an environment variable by its key.
@@ -38,6 +281,141 @@ fn get_env_var(key: &str) -> Result<String, AppError> {
     env::var(key).map_err(|_| AppError::EnvVarError(format!("{} not set", key)))
 }
 
+/// Fetches an environment variable holding a URL, checking it parses with one of the
+/// expected schemes.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+/// - `schemes`: The schemes the URL may use.
+///
+/// # Returns
+/// - `Ok(String)`: The value of the environment variable if it is a valid URL.
+/// - `Err(AppError)`: An error if the environment variable is not set or not a valid URL.
+fn get_url_env_var(key: &str, schemes: &[&str]) -> Result<String, AppError> {
+    let value = get_env_var(key)?;
+    let url = Url::parse(&value)
+        .map_err(|e| AppError::EnvVarError(format!("{} is not a valid URL: {}", key, e)))?;
+
+    if !schemes.contains(&url.scheme()) {
+        return Err(AppError::EnvVarError(format!(
+            "{} must use one of the schemes {}, got '{}'",
+            key, schemes.join(", "), url.scheme()
+        )));
+    }
+
+    Ok(value)
+}
+
+/// Fetches an optional environment variable by its key.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+///
+/// # Returns
+/// - `Some(String)`: The value of the environment variable if it is set and not empty.
+/// - `None`: If the environment variable is not set or empty.
+fn get_optional_env_var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+/// Fetches an optional comma-separated environment variable as a list, falling back to a default.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+/// - `default`: The values to use if the environment variable is not set.
+///
+/// # Returns
+/// The trimmed, non-empty values of the list.
+fn get_list_env_var(key: &str, default: &[&str]) -> Vec<String> {
+    match get_optional_env_var(key) {
+        Some(value) => value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        None => default.iter().map(|item| item.to_string()).collect(),
+    }
+}
+
+/// Fetches an optional comma-separated environment variable as a list of parsed values,
+/// falling back to a default.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+/// - `default`: The values to use if the environment variable is not set.
+///
+/// # Returns
+/// - `Ok(Vec<T>)`: The parsed values of the list.
+/// - `Err(AppError)`: An error if a value cannot be parsed.
+fn get_parsed_list_env_var<T: FromStr>(key: &str, default: &[&str]) -> Result<Vec<T>, AppError> {
+    get_list_env_var(key, default)
+        .into_iter()
+        .map(|item| {
+            item.parse()
+                .map_err(|_| AppError::EnvVarError(format!("{} has an invalid value: {}", key, item)))
+        })
+        .collect()
+}
+
+/// Fetches the API keys from a comma-separated environment variable.
+///
+/// Each entry is either `identity:key`, or a bare key whose identity is its position
+/// in the list, as `api-key-1`, `api-key-2` and so on.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+///
+/// # Returns
+/// The API keys, mapped to their identity.
+fn get_api_keys(key: &str) -> HashMap<String, String> {
+    get_list_env_var(key, &[])
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| match entry.split_once(':') {
+            Some((identity, api_key)) => (api_key.trim().to_string(), identity.trim().to_string()),
+            None => (entry, format!("api-key-{}", index + 1)),
+        })
+        .collect()
+}
+
+/// Fetches every environment variable starting with the given prefix and parses its value.
+///
+/// # Arguments
+/// - `prefix`: The prefix of the environment variables to fetch.
+///
+/// # Returns
+/// - `Ok(HashMap<String, T>)`: The parsed values, keyed by the variable name without the prefix.
+/// - `Err(AppError)`: An error if a value cannot be parsed.
+fn get_prefixed_env_vars<T: FromStr>(prefix: &str) -> Result<HashMap<String, T>, AppError> {
+    env::vars()
+        .filter_map(|(key, value)| key.strip_prefix(prefix).map(|name| (key.clone(), name.to_string(), value)))
+        .map(|(key, name, value)| {
+            value
+                .parse()
+                .map(|parsed| (name, parsed))
+                .map_err(|_| AppError::EnvVarError(format!("{} has an invalid value: {}", key, value)))
+        })
+        .collect()
+}
+
+/// Fetches an optional environment variable and parses it, falling back to a default.
+///
+/// # Arguments
+/// - `key`: The name of the environment variable to fetch.
+/// - `default`: The value to use if the environment variable is not set.
+///
+/// # Returns
+/// - `Ok(T)`: The parsed value, or `default` if the variable is not set.
+/// - `Err(AppError)`: An error if the variable is set but cannot be parsed.
+fn get_env_var_or<T: FromStr>(key: &str, default: T) -> Result<T, AppError> {
+    match env::var(key) {
+        Ok(value) => value
+            .parse()
+            .map_err(|_| AppError::EnvVarError(format!("{} has an invalid value: {}", key, value))),
+        Err(_) => Ok(default),
+    }
+}
+
 impl AppConfig {
     /// Loads the application configuration from environment variables.
     ///
@@ -46,18 +424,201 @@ impl AppConfig {
     ///
     /// # Returns
     /// - `Ok(Self)`: The loaded configuration if all environment variables are set.
-    /// - `Err(AppError)`: An error if any required environment variable is missing.
+    /// - `Err(AppError)`: An error naming the variable if a required one is missing, or if a
+    ///   URL or the S3 region is malformed.
     pub fn from_env() -> Result<Self, AppError> {
         // Load the `.env` file if it exists.
         dotenv::dotenv().ok();
 
+        // The AWS settings are only required when files are stored in S3
+        let storage_backend = get_env_var_or("STORAGE_BACKEND", StorageBackend::S3)?;
+        let get_aws_env_var = |key: &str| match storage_backend {
+            StorageBackend::S3 => get_env_var(key),
+            StorageBackend::Local => Ok(get_optional_env_var(key).unwrap_or_default()),
+        };
+
+        // Without static keys, the default provider chain supplies the credentials
+        let aws_access_key_id = get_optional_env_var("AWS_ACCESS_KEY_ID");
+        let aws_secret_access_key = get_optional_env_var("AWS_SECRET_ACCESS_KEY");
+        if aws_access_key_id.is_some() != aws_secret_access_key.is_some() {
+            return Err(AppError::EnvVarError(
+                "AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY must be set together".to_string(),
+            ));
+        }
+
+        let aws_region = get_aws_env_var("AWS_REGION")?;
+        if storage_backend == StorageBackend::S3 && aws_region.trim().is_empty() {
+            return Err(AppError::EnvVarError("AWS_REGION must not be empty".to_string()));
+        }
+
+        let compression_algorithms = get_list_env_var("COMPRESSION_ALGORITHMS", &["gzip", "br"]);
+        let unknown = compression_algorithms
+            .iter()
+            .find(|algorithm| !COMPRESSION_ALGORITHMS.contains(&algorithm.as_str()));
+        if let Some(unknown) = unknown {
+            return Err(AppError::EnvVarError(format!(
+                "COMPRESSION_ALGORITHMS holds unknown algorithm '{}', expected one of: {}",
+                unknown, COMPRESSION_ALGORITHMS.join(", ")
+            )));
+        }
+
+        let s3_endpoint_url = get_optional_env_var("S3_ENDPOINT_URL");
+        if let Some(endpoint) = &s3_endpoint_url {
+            Url::parse(endpoint)
+                .map_err(|e| AppError::EnvVarError(format!("S3_ENDPOINT_URL is not a valid URL: {}", e)))?;
+        }
+
         Ok(Self {
-            aws_access_key_id: get_env_var("AWS_ACCESS_KEY_ID")?,
-            aws_secret_access_key: get_env_var("AWS_SECRET_ACCESS_KEY")?,
-            aws_region: get_env_var("AWS_REGION")?,
-            s3_bucket_name: get_env_var("S3_BUCKET_NAME")?,
-            database_url: get_env_var("DATABASE_URL")?,
-            redis_url: get_env_var("REDIS_URL")?,
+            aws_access_key_id,
+            aws_secret_access_key,
+            aws_region,
+            s3_bucket_name: get_aws_env_var("S3_BUCKET_NAME")?,
+            s3_endpoint_url,
+            s3_force_path_style: get_env_var_or("S3_FORCE_PATH_STYLE", false)?,
+            s3_retry_max_attempts: get_env_var_or("S3_RETRY_MAX_ATTEMPTS", 3)?,
+            s3_retry_base_delay_ms: get_env_var_or("S3_RETRY_BASE_DELAY_MS", 100)?,
+            database_url: get_url_env_var("DATABASE_URL", &["postgres", "postgresql"])?,
+            postgres_probe: get_env_var_or("POSTGRES_HEALTH_PROBE", PostgresProbe::Lightweight)?,
+            redis_url: get_url_env_var("REDIS_URL", &["redis", "rediss", "redis+unix", "unix"])?,
+            health_stale_grace_secs: get_env_var_or("HEALTH_STALE_GRACE_SECS", 0)?,
+            max_upload_size_bytes: get_env_var_or("MAX_UPLOAD_SIZE_BYTES", 128 * 1024 * 1024)?,
+            max_file_sizes: get_prefixed_env_vars("MAX_SIZE_")?,
+            default_file_type: get_optional_env_var("DEFAULT_FILE_TYPE"),
+            // `MAX_EXTRACT_BYTES` and `MAX_EXTRACT_FILES` take precedence over the older names
+            max_total_extracted_bytes: get_env_var_or(
+                "MAX_EXTRACT_BYTES",
+                get_env_var_or("MAX_TOTAL_EXTRACTED_BYTES", 1024 * 1024 * 1024)?,
+            )?,
+            max_entry_extracted_bytes: get_env_var_or("MAX_ENTRY_EXTRACTED_BYTES", 512 * 1024 * 1024)?,
+            max_archive_entries: get_env_var_or(
+                "MAX_EXTRACT_FILES",
+                get_env_var_or("MAX_ARCHIVE_ENTRIES", 10_000)?,
+            )?,
+            max_extract_ratio: get_env_var_or("MAX_EXTRACT_RATIO", 250.0)?,
+            cors_allowed_origins: get_list_env_var("CORS_ALLOWED_ORIGINS", &[]),
+            cors_allowed_methods: get_list_env_var("CORS_ALLOWED_METHODS", &["GET", "POST", "PUT", "DELETE"]),
+            cors_allowed_headers: get_list_env_var("CORS_ALLOWED_HEADERS", &["authorization", "content-type", "x-api-key"]),
+            cors_max_age_secs: get_env_var_or("CORS_MAX_AGE", 600)?,
+            rate_limit_uploads: get_env_var_or("RATE_LIMIT_UPLOADS", 10)?,
+            rate_limit_window_secs: get_env_var_or("RATE_LIMIT_WINDOW_SECS", 60)?,
+            trust_proxy: get_env_var_or("TRUST_PROXY", false)?,
+            max_json_response_bytes: get_env_var_or("MAX_JSON_RESPONSE_BYTES", 10 * 1024 * 1024)?,
+            tree_max_depth: get_env_var_or("TREE_MAX_DEPTH", 32)?,
+            tree_ignore_patterns: get_parsed_list_env_var("TREE_IGNORE_PATTERNS", &[".git", "node_modules", "target"])?,
+            http_idle_timeout_secs: get_env_var_or("HTTP_IDLE_TIMEOUT_SECS", 30)?,
+            redis_key_prefix: get_env_var_or("REDIS_KEY_PREFIX", "rustler:".to_string())?,
+            admin_token: get_optional_env_var("ADMIN_TOKEN"),
+            health_check_timeout_ms: get_env_var_or("HEALTH_CHECK_TIMEOUT_MS", 3000)?,
+            file_types_config: get_optional_env_var("FILE_TYPES_CONFIG"),
+            deduplicate_uploads: get_env_var_or("DEDUPLICATE_UPLOADS", false)?,
+            max_upload_files: get_env_var_or("MAX_UPLOAD_FILES", 10)?,
+            max_batch_upload_bytes: get_env_var_or("MAX_BATCH_UPLOAD_BYTES", 128 * 1024 * 1024)?,
+            chunked_upload_ttl_secs: get_env_var_or("CHUNKED_UPLOAD_TTL_SECS", 24 * 60 * 60)?,
+            chunked_upload_max_part_bytes: get_env_var_or("CHUNKED_UPLOAD_MAX_PART_BYTES", 64 * 1024 * 1024)?,
+            storage_backend,
+            local_storage_dir: get_env_var_or("LOCAL_STORAGE_DIR", "./storage".to_string())?,
+            upload_spool_threshold_bytes: get_env_var_or("UPLOAD_SPOOL_THRESHOLD_BYTES", 16 * 1024 * 1024)?,
+            shutdown_drain_timeout_secs: get_env_var_or("SHUTDOWN_DRAIN_TIMEOUT_SECS", 10)?,
+            upload_drain_timeout_secs: get_env_var_or("UPLOAD_DRAIN_TIMEOUT_SECS", 300)?,
+            health_optional_checks: get_list_env_var("HEALTH_OPTIONAL_CHECKS", &[]),
+            archive_ratio_threshold: get_env_var_or("ARCHIVE_RATIO_THRESHOLD", 100.0)?,
+            api_keys: get_api_keys("API_KEYS"),
+            response_compression: get_env_var_or("RESPONSE_COMPRESSION", true)?,
+            compression_min_size_bytes: get_env_var_or("COMPRESSION_MIN_SIZE_BYTES", 32)?,
+            compression_algorithms,
+            allowed_storage_classes: get_list_env_var(
+                "ALLOWED_STORAGE_CLASSES",
+                &["STANDARD", "STANDARD_IA", "INTELLIGENT_TIERING", "GLACIER_IR"],
+            ),
+            metrics_enabled: get_env_var_or("METRICS_ENABLED", true)?,
+            cleanup_interval_secs: get_env_var_or("CLEANUP_INTERVAL_SECS", 3600)?,
+            competition_ttl_secs: get_env_var_or("COMPETITION_TTL_SECS", 24 * 3600)?,
+            infer_file_types: get_env_var_or("INFER_FILE_TYPES", false)?,
+            extraction_wait_secs: get_env_var_or("EXTRACTION_WAIT_SECS", 30)?,
+            competitions_dir: get_env_var_or("COMPETITIONS_DIR", "./competitions".to_string())?,
+            readiness_check_interval_secs: get_env_var_or("READINESS_CHECK_INTERVAL_SECS", 10)?,
+            deleted_file_retention_secs: get_env_var_or("DELETED_FILE_RETENTION_SECS", 7 * 24 * 3600)?,
+            strict_content_type: get_env_var_or("STRICT_CONTENT_TYPE", false)?,
+            content_addressed_storage: get_env_var_or("CONTENT_ADDRESSED_STORAGE", false)?,
+            blocked_archive_entry_extensions: get_list_env_var("BLOCKED_ARCHIVE_ENTRY_EXTENSIONS", &[]),
+            max_image_width: get_env_var_or("MAX_IMAGE_WIDTH", 0)?,
+            max_image_height: get_env_var_or("MAX_IMAGE_HEIGHT", 0)?,
+            inspect_uploaded_archives: get_env_var_or("INSPECT_UPLOADED_ARCHIVES", false)?,
+            inspect_uploaded_tar_gz: get_env_var_or("INSPECT_UPLOADED_TAR_GZ", false)?,
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+impl AppConfig {
+    /// Returns the configuration of the unit tests: the default of every setting, with
+    /// files stored locally and service URLs nothing is expected to listen on.
+    pub fn for_tests() -> Self {
+        static CONFIG: std::sync::OnceLock<AppConfig> = std::sync::OnceLock::new();
+
+        CONFIG.get_or_init(|| {
+            env::set_var("STORAGE_BACKEND", "local");
+            env::set_var("DATABASE_URL", "postgres://127.0.0.1:1/rustler");
+            env::set_var("REDIS_URL", "redis://127.0.0.1:1");
+            AppConfig::from_env().expect("Failed to load the test configuration")
+        }).clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postgres_probe_is_lightweight_by_default() {
+        assert_eq!(AppConfig::for_tests().postgres_probe, PostgresProbe::Lightweight);
+    }
+
+    #[test]
+    fn postgres_probe_is_parsed_case_insensitively() {
+        assert_eq!("full".parse(), Ok(PostgresProbe::Full));
+        assert_eq!("Lightweight".parse(), Ok(PostgresProbe::Lightweight));
+        assert!("schema".parse::<PostgresProbe>().is_err());
+    }
+
+    /// Returns the message of a configuration error.
+    fn env_var_error<T: std::fmt::Debug>(result: Result<T, AppError>) -> String {
+        match result {
+            Err(AppError::EnvVarError(message)) => message,
+            other => panic!("expected a configuration error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn malformed_urls_name_the_variable() {
+        // Variables only these tests use, so the tests running concurrently don't see them
+        env::set_var("RUSTLER_TEST_MALFORMED_URL", "postgres//localhost:5432");
+        env::set_var("RUSTLER_TEST_WRONG_SCHEME_URL", "http://localhost:6379");
+        env::set_var("RUSTLER_TEST_VALID_URL", "rediss://:secret@localhost:6380/0");
+        let schemes = &["redis", "rediss"];
+
+        let message = env_var_error(get_url_env_var("RUSTLER_TEST_MALFORMED_URL", schemes));
+        assert!(message.starts_with("RUSTLER_TEST_MALFORMED_URL is not a valid URL"), "{}", message);
+        assert_eq!(
+            env_var_error(get_url_env_var("RUSTLER_TEST_WRONG_SCHEME_URL", schemes)),
+            "RUSTLER_TEST_WRONG_SCHEME_URL must use one of the schemes redis, rediss, got 'http'",
+        );
+        assert_eq!(env_var_error(get_url_env_var("RUSTLER_TEST_UNSET_URL", schemes)), "RUSTLER_TEST_UNSET_URL not set");
+        assert_eq!(get_url_env_var("RUSTLER_TEST_VALID_URL", schemes).unwrap(), "rediss://:secret@localhost:6380/0");
+    }
+
+    #[test]
+    fn malformed_values_name_the_variable() {
+        env::set_var("RUSTLER_TEST_MALFORMED_NUMBER", "ten");
+        env::set_var("RUSTLER_TEST_MALFORMED_BACKEND", "ftp");
+
+        assert_eq!(
+            env_var_error(get_env_var_or("RUSTLER_TEST_MALFORMED_NUMBER", 10u64)),
+            "RUSTLER_TEST_MALFORMED_NUMBER has an invalid value: ten",
+        );
+        assert_eq!(
+            env_var_error(get_env_var_or("RUSTLER_TEST_MALFORMED_BACKEND", StorageBackend::S3)),
+            "RUSTLER_TEST_MALFORMED_BACKEND has an invalid value: ftp",
+        );
+        assert_eq!(get_env_var_or("RUSTLER_TEST_UNSET_NUMBER", 10u64).unwrap(), 10);
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use crate::app_state::AppState;
+use crate::middleware::request_id::RequestId;
+use crate::services::cleanup_service::CleanupService;
+
+/// Query parameters accepted when triggering a cleanup.
+///
+/// # Fields
+/// - `dry_run`: Whether to only report the competitions that would be removed.
+///
+#[derive(Deserialize)]
+pub struct CleanupQuery {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Handles flushing every Redis key owned by the application.
+///
+/// Only keys carrying the configured `REDIS_KEY_PREFIX` are deleted.
+///
+/// # Parameters
+/// - `clients`: The application clients.
+/// - `request_id`: The id of the request, included in error responses.
+///
+/// # Returns
+/// The number of deleted keys as JSON.
+///
+pub async fn flush_cache_handler(
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
+) -> impl IntoResponse {
+    match state.get_clients().get_redis_client().flush_prefixed_keys().await {
+        Ok(deleted) => {
+            info!("Flushed {} cache keys", deleted);
+            (StatusCode::OK, Json(json!({
+                "message": "Cache flushed successfully",
+                "deleted": deleted,
+            }))).into_response()
+        }
+        Err(e) => {
+            error!("Failed to flush cache: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({
+                "error": "Failed to flush cache",
+                "request_id": request_id.0,
+            }))).into_response()
+        }
+    }
+}
+
+/// Handles removing the extracted competitions not accessed for `COMPETITION_TTL_SECS`.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `request_id`: The id of the request, included in error responses.
+/// - `Query(query)`: Whether to only report what would be removed, via `?dry_run=true`.
+///
+/// # Returns
+/// The removed competitions, and those skipped because they are being extracted, as JSON.
+///
+pub async fn cleanup_handler(
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
+    Query(query): Query<CleanupQuery>,
+) -> impl IntoResponse {
+    match CleanupService::new(state.get_clients().clone(), state.get_shared_config()).clean(query.dry_run).await {
+        Ok(report) => {
+            info!("Cleanup removed {} competitions (dry run: {})", report.removed.len(), report.dry_run);
+            (StatusCode::OK, Json(json!(report))).into_response()
+        }
+        Err(e) => {
+            error!("Failed to clean up competitions: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({
+                "error": "Failed to clean up competitions",
+                "request_id": request_id.0,
+            }))).into_response()
+        }
+    }
+}
+
+/// Handles listing the file types accepted by uploads, with the number of uploads of each
+/// type which passed and failed validation, and the number of rejections by reason.
+///
+/// # Parameters
+/// - `state`: The application state.
+///
+/// # Returns
+/// The registered file types and the validation counters since startup, as JSON.
+///
+pub async fn file_types_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    (StatusCode::OK, Json(state.get_validator().summary()))
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::response::IntoResponse;
+use axum::Json;
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::app_state::AppState;
+use crate::middleware::api_key_auth::ApiKeyIdentity;
+use crate::services::chunked_upload_service::ChunkedUploadService;
+
+/// The body of a request starting a chunked upload.
+///
+/// # Fields
+/// - `file_name`: The name of the file to upload.
+/// - `content_type`: The declared content type of the file.
+///
+#[derive(Deserialize)]
+pub struct InitChunkedUploadRequest {
+    file_name: String,
+    #[serde(default)]
+    content_type: String,
+}
+
+/// Handles starting a chunked upload.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Json(request)`: The name and content type of the file.
+///
+/// # Returns
+/// The id of the upload, used to send its parts.
+///
+pub async fn init_chunked_upload_handler(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<InitChunkedUploadRequest>,
+) -> impl IntoResponse {
+    ChunkedUploadService::new(
+        state.get_clients().clone(),
+        state.get_shared_config(),
+        state.get_validator(),
+        state.get_shared_file_service(),
+    )
+        .init(request.file_name, request.content_type)
+        .await
+}
+
+/// Handles uploading a part of a chunked upload, sent as the raw request body.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path((id, part_number))`: The id of the upload and the number of the part.
+/// - `body`: The content of the part.
+///
+/// # Returns
+/// The size and ETag of the stored part.
+///
+pub async fn upload_part_handler(
+    State(state): State<Arc<AppState>>,
+    Path((id, part_number)): Path<(Uuid, i32)>,
+    body: Bytes,
+) -> impl IntoResponse {
+    ChunkedUploadService::new(
+        state.get_clients().clone(),
+        state.get_shared_config(),
+        state.get_validator(),
+        state.get_shared_file_service(),
+    )
+        .put_part(id, part_number, body)
+        .await
+}
+
+/// Handles completing a chunked upload once all its parts were sent.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(id)`: The id of the upload.
+/// - `identity`: The identity of the API key the request was authenticated with.
+///
+/// # Returns
+/// The details of the uploaded file.
+///
+pub async fn complete_chunked_upload_handler(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<Uuid>,
+    identity: ApiKeyIdentity,
+) -> impl IntoResponse {
+    ChunkedUploadService::new(
+        state.get_clients().clone(),
+        state.get_shared_config(),
+        state.get_validator(),
+        state.get_shared_file_service(),
+    )
+        .complete(id, &identity.0)
+        .await
+}
@@ -0,0 +1,41 @@
+use axum::response::{Html, IntoResponse};
+use axum::Json;
+use crate::utils::openapi::openapi_document;
+
+/// The Swagger UI page, loading the bundle from a CDN and the spec from `/api-docs/openapi.json`.
+const SWAGGER_UI_HTML: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>Rustler API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api-docs/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"##;
+
+/// Handles serving the OpenAPI document of the API.
+///
+/// # Returns
+/// The OpenAPI document as JSON.
+///
+pub async fn openapi_handler() -> impl IntoResponse {
+    Json(openapi_document())
+}
+
+/// Handles serving the Swagger UI, browsing the OpenAPI document.
+///
+/// # Returns
+/// The Swagger UI page.
+///
+pub async fn swagger_ui_handler() -> impl IntoResponse {
+    Html(SWAGGER_UI_HTML)
+}
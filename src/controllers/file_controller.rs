@@ -1,30 +1,431 @@
-use std::path::{Path as FilePath, PathBuf};
+use std::path::{Component, Path as FilePath, PathBuf};
 use std::{fs, io};
+use std::future::Future;
+use std::time::{Duration, UNIX_EPOCH};
 use axum::{extract::{Multipart, State}, response::IntoResponse, Json};
 use std::sync::Arc;
-use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::extract::{Path, Query};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::Response;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use chrono::{DateTime, Utc};
+use glob::Pattern;
 use indexmap::IndexMap;
 use log::{error, info, warn};
+use serde::Deserialize;
 use serde_json::{json, Value};
-use crate::clients::clients::Clients;
-use crate::services::file_service::FileService;
+use uuid::Uuid;
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::middleware::api_key_auth::ApiKeyIdentity;
+use crate::middleware::request_id::RequestId;
+use crate::models::codebase::{CodebaseFilesResponse, CodebaseTreeResponse};
+use crate::models::error::ErrorResponse;
+use crate::models::upload::UploadExtractResponse;
+use crate::services::cleanup_service::{competition_lock_key, record_access, CleanupService, CompetitionDeletion, COMPETITION_LOCK_TTL};
+use crate::services::file_service::{is_valid_competition_name, Extraction, FileService, EXTRACTION_MANIFEST};
+use crate::utils::file_utils::{compute_sha256, guess_text_content_type, is_text, normalize_line_endings};
+use crate::utils::response_format::ResponseFormat;
+
+/// Query parameters accepted when reading a single extracted file.
+///
+/// # Fields
+/// - `raw`: Whether to return the file content as the response body instead of wrapping it in JSON.
+/// - `normalize_eol`: Whether to convert the CRLF line endings of text files to LF.
+///
+#[derive(Deserialize)]
+pub struct FileContentQuery {
+    #[serde(default)]
+    raw: bool,
+    #[serde(default)]
+    normalize_eol: bool,
+}
+
+/// Query parameters accepted when uploading files.
+///
+/// # Fields
+/// - `atomic`: Whether the whole batch must be rejected when one of its files fails validation.
+/// - `storage_class`: The S3 storage class to store the files in, one of `ALLOWED_STORAGE_CLASSES`.
+///
+#[derive(Deserialize)]
+pub struct UploadQuery {
+    #[serde(default)]
+    atomic: bool,
+    storage_class: Option<String>,
+}
 
 /// Handles file uploads.
 ///
 /// # Parameters
-/// - `clients`: The application clients.
-/// - `multipart`: The multipart request containing the file.
+/// - `state`: The application state.
+/// - `Query(query)`: Whether the batch is atomic, via `?atomic=true`, and the storage class of the files.
+/// - `identity`: The identity of the API key the request was authenticated with.
+/// - `multipart`: The multipart request containing the files.
 ///
 /// # Returns
-/// The response to return to the client.
+/// The per-file results to return to the client.
 ///
 pub async fn upload_handler(
-    State(clients): State<Arc<Clients>>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<UploadQuery>,
+    identity: ApiKeyIdentity,
     multipart: Multipart,
 ) -> impl IntoResponse {
-    let file_service = FileService::new(clients);
-    file_service.upload_file(multipart).await
+    state.get_file_service()
+        .upload_file(multipart, query.atomic, &identity.0, query.storage_class.as_deref())
+        .await
+}
+
+/// Query parameters accepted when viewing a codebase.
+///
+/// # Fields
+/// - `text_only`: Whether to skip binary entries when extracting the archive.
+///
+#[derive(Deserialize)]
+pub struct ViewCodebaseQuery {
+    #[serde(default)]
+    text_only: bool,
+}
+
+/// The level of detail included in each node of the codebase JSON tree.
+///
+/// - `Minimal`: Only `name`, `type`, and `children`.
+/// - `Full`: Additionally `size` and `modified` for files, and `size` and `children_count` for folders.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TreeDetail {
+    Full,
+    #[default]
+    Minimal,
+}
+
+impl TreeDetail {
+    /// Returns the query parameter value of the detail level.
+    fn as_str(&self) -> &'static str {
+        match self {
+            TreeDetail::Full => "full",
+            TreeDetail::Minimal => "minimal",
+        }
+    }
+}
+
+/// Query parameters accepted when generating the codebase JSON tree.
+///
+/// # Fields
+/// - `detail`: The level of detail to include, `minimal` by default.
+/// - `refresh`: Whether to regenerate the tree instead of serving it from the cache.
+/// - `max_depth`: The maximum depth of folders to descend into, `TREE_MAX_DEPTH` by default.
+/// - `ignore`: Comma-separated glob patterns of entries to leave out, `TREE_IGNORE_PATTERNS` by default.
+///   An empty value leaves nothing out.
+///
+#[derive(Deserialize)]
+pub struct CodebaseJsonQuery {
+    #[serde(default)]
+    detail: TreeDetail,
+    #[serde(default)]
+    refresh: bool,
+    max_depth: Option<usize>,
+    ignore: Option<String>,
+}
+
+/// What a codebase JSON tree includes.
+///
+/// # Fields
+/// - `detail`: The level of detail included in each node.
+/// - `max_depth`: The maximum depth of folders descended into, the top-level entries being at depth 0.
+/// - `ignore`: The glob patterns of entries left out, matched against their name and their path
+///   relative to the repository.
+///
+struct TreeOptions {
+    detail: TreeDetail,
+    max_depth: usize,
+    ignore: Vec<Pattern>,
+}
+
+impl TreeOptions {
+    /// Returns whether an entry of the tree is left out.
+    fn is_ignored(&self, name: &str, relative_path: &FilePath) -> bool {
+        self.ignore
+            .iter()
+            .any(|pattern| pattern.matches(name) || pattern.matches_path(relative_path))
+    }
+
+    /// Returns a string identifying the options, used to cache trees generated with them.
+    fn cache_variant(&self) -> String {
+        let ignore: Vec<&str> = self.ignore.iter().map(Pattern::as_str).collect();
+        let ignore_digest = compute_sha256(ignore.join(",").as_bytes());
+        format!("{}:{}:{}", self.detail.as_str(), self.max_depth, &ignore_digest[..16])
+    }
+}
+
+/// Query parameters accepted when fetching uploads.
+///
+/// # Fields
+/// - `include_deleted`: Whether to also return soft-deleted uploads.
+///
+#[derive(Deserialize)]
+pub struct IncludeDeletedQuery {
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+/// Handles fetching the stored metadata of an upload.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `request_id`: The id of the request, included in error responses.
+/// - `Path(id)`: The id of the upload.
+/// - `Query(query)`: Whether a soft-deleted upload is returned, via `?include_deleted=true`.
+/// - `format`: The response format, negotiated from the `Accept` header.
+///
+/// # Returns
+/// The upload metadata as JSON or MessagePack, or 404 if no upload has this id.
+///
+pub async fn get_upload_handler(
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
+    Path(id): Path<Uuid>,
+    Query(query): Query<IncludeDeletedQuery>,
+    format: ResponseFormat,
+) -> impl IntoResponse {
+    match state.get_clients().get_postgres_client().get_upload(id, query.include_deleted).await {
+        Ok(Some(record)) => format.respond(StatusCode::OK, &record),
+        Ok(None) => ErrorResponse::new(StatusCode::NOT_FOUND, format!("Upload '{}' not found", id)).into_response(),
+        Err(e) => {
+            error!("Failed to fetch upload {}: {}", id, e);
+            ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch upload metadata")
+                .with_request_id(&request_id)
+                .into_response()
+        }
+    }
+}
+
+/// Handles downloading a stored file, streamed from storage.
+/// Single byte ranges are supported through the `Range` header.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the file, with any `/` percent-encoded.
+/// - `headers`: The request headers, read for the `Range` header.
+///
+/// # Returns
+/// The file as an attachment, or 404 if no file has this key.
+///
+pub async fn download_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let range = headers.get(header::RANGE).and_then(|range| range.to_str().ok());
+
+    state.get_file_service()
+        .download_file(&key, range)
+        .await
+}
+
+/// Handles fetching the checksum stored with a file.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the file, with any `/` percent-encoded.
+///
+/// # Returns
+/// The SHA-256 checksum of the file, or 404 if no checksum is stored for this key.
+///
+pub async fn file_checksum_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .get_checksum(&key)
+        .await
+}
+
+/// Handles fetching the metadata of a file without downloading it.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the file, with any `/` percent-encoded.
+///
+/// # Returns
+/// The size, ETag, modification time, and content type of the file, or 404 if no file has this key.
+///
+pub async fn file_metadata_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .get_metadata(&key)
+        .await
+}
+
+/// Handles soft-deleting a stored file, which can be restored until it is purged.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the file, with any `/` percent-encoded.
+///
+/// # Returns
+/// The key the file was moved to, or 404 if no file has this key.
+///
+pub async fn delete_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .soft_delete_file(&key)
+        .await
+}
+
+/// Handles restoring a soft-deleted file.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The key the file was stored under, with any `/` percent-encoded.
+///
+/// # Returns
+/// The restored key, 404 if no deleted file had this key, or 409 if the key is taken.
+///
+pub async fn restore_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .restore_file(&key)
+        .await
+}
+
+/// The body of a request renaming a file.
+///
+/// # Fields
+/// - `to`: The key to rename the file to.
+///
+#[derive(Deserialize)]
+pub struct RenameFileRequest {
+    to: String,
+}
+
+/// Query parameters accepted when renaming a file.
+///
+/// # Fields
+/// - `overwrite`: Whether to replace a file already stored under the new key.
+///
+#[derive(Deserialize)]
+pub struct RenameFileQuery {
+    #[serde(default)]
+    overwrite: bool,
+}
+
+/// Handles renaming a stored file, moving its extraction and cached data along.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the file, with any `/` percent-encoded.
+/// - `Query(query)`: Whether to replace an existing file, via `?overwrite=true`.
+/// - `Json(request)`: The key to rename the file to.
+///
+/// # Returns
+/// The old and new keys of the file, 404 if no file has this key, or 409 if the new key is taken.
+///
+pub async fn rename_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+    Query(query): Query<RenameFileQuery>,
+    Json(request): Json<RenameFileRequest>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .rename_file(&key, &request.to, query.overwrite)
+        .await
+}
+
+/// The body of a request copying a file.
+///
+/// # Fields
+/// - `src`: The key of the file to copy.
+/// - `dst`: The key of the copy.
+///
+#[derive(Deserialize)]
+pub struct CopyFileRequest {
+    src: String,
+    dst: String,
+}
+
+/// Handles copying a stored file to another key, without downloading it.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Query(query)`: Whether to replace an existing file, via `?overwrite=true`.
+/// - `Json(request)`: The keys of the file and of its copy.
+///
+/// # Returns
+/// The source and destination keys, 404 if no file has the source key, or 409 if the
+/// destination key is taken.
+///
+pub async fn copy_file_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<RenameFileQuery>,
+    Json(request): Json<CopyFileRequest>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .copy_file(&request.src, &request.dst, query.overwrite)
+        .await
+}
+
+/// Handles inspecting a stored ZIP archive without extracting it.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(key)`: The storage key of the archive, with any `/` percent-encoded.
+///
+/// # Returns
+/// The entries of the archive with their sizes and compression ratios.
+///
+pub async fn inspect_archive_handler(
+    State(state): State<Arc<AppState>>,
+    Path(key): Path<String>,
+) -> impl IntoResponse {
+    state.get_file_service()
+        .inspect_archive(&key)
+        .await
+}
+
+/// Tracks the remaining serialized size allowed for a codebase JSON tree.
+///
+/// # Fields
+/// - `remaining`: The number of bytes still available.
+/// - `truncated`: Whether a node was dropped because it didn't fit.
+///
+struct JsonBudget {
+    remaining: usize,
+    truncated: bool,
+}
+
+impl JsonBudget {
+    /// Creates a budget allowing `max_bytes` of serialized JSON.
+    ///
+    /// Each node is accounted for with a separating comma, one more than an array holds,
+    /// which covers the brackets of the nested arrays but one byte short of the top-level one.
+    fn new(max_bytes: usize) -> Self {
+        Self { remaining: max_bytes.saturating_sub(1), truncated: false }
+    }
+
+    /// Consumes the serialized size of a node from the budget.
+    ///
+    /// # Returns
+    /// - `true` if the node fits in the remaining budget.
+    /// - `false` if it doesn't, in which case the budget is marked as truncated.
+    fn consume(&mut self, node: &IndexMap<String, Value>) -> bool {
+        // +1 for the separating comma
+        let size = serde_json::to_vec(node).map(|bytes| bytes.len() + 1).unwrap_or(0);
+
+        if size > self.remaining {
+            self.truncated = true;
+            return false;
+        }
+
+        self.remaining -= size;
+        true
+    }
 }
 
 /// Recursively traverses a directory and returns its structure as a JSON-compatible `Value`.
@@ -34,26 +435,96 @@ pub async fn upload_handler(
 /// - `type`: The type of the item, either "file" or "folder".
 /// - `children`: An array of objects representing the children of the folder.
 ///
+/// With `TreeDetail::Full`, files also contain `size` (bytes) and `modified` (RFC3339),
+/// and folders contain their aggregate `size` and `children_count`. When the metadata of
+/// a file can't be read, its `size` and `modified` are `null` and the traversal goes on.
+///
+/// Entries are sorted by name so the output is deterministic. Symlinks are never followed,
+/// so they are reported as files and can't cause infinite recursion.
+///
+/// Entries matching an ignore pattern are left out. Folders deeper than the maximum depth
+/// are listed without their children and flagged with `depth_limited: true`.
+///
+/// Traversal stops once the serialized tree would exceed the budget, leaving the budget
+/// marked as truncated.
+///
 /// # Parameters
 /// - `path`: The path to the directory to traverse.
+/// - `relative_path`: The path of the directory relative to the repository.
+/// - `depth`: The depth of the directory's entries, 0 for the top-level entries.
+/// - `options`: The level of detail, maximum depth and ignore patterns of the tree.
+/// - `budget`: The remaining serialized size allowed for the tree.
 ///
 /// # Returns
-/// A `Value` representing the directory structure.
-fn traverse_directory(path: &FilePath) -> Result<Vec<Value>, io::Error> {
+/// A `Value` representing the directory structure, along with its aggregate size in bytes.
+fn traverse_directory(
+    path: &FilePath,
+    relative_path: &FilePath,
+    depth: usize,
+    options: &TreeOptions,
+    budget: &mut JsonBudget,
+) -> Result<(Vec<Value>, u64), io::Error> {
+    let detail = options.detail;
     let mut items = Vec::new();
+    let mut total_size = 0;
+
+    let mut entries = fs::read_dir(path)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        if budget.truncated {
+            break;
+        }
+
+        if entry.file_name() == EXTRACTION_MANIFEST {
+            continue;
+        }
 
-    for entry in fs::read_dir(path)? {
-        let entry = entry?;
         let entry_path = entry.path();
         let entry_name = entry.file_name().to_string_lossy().to_string();
+        let entry_relative_path = relative_path.join(entry.file_name());
 
-        if entry_path.is_dir() {
+        if options.is_ignored(&entry_name, &entry_relative_path) {
+            continue;
+        }
+
+        let is_dir = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+
+        if is_dir {
             let mut folder = IndexMap::new(); // Use IndexMap to preserve insertion order
 
             folder.insert("name".to_string(), Value::String(entry_name.clone()));
             folder.insert("type".to_string(), Value::String("folder".to_string()));
 
-            let children = traverse_directory(&entry_path)?;
+            let depth_limited = depth >= options.max_depth;
+            if depth_limited {
+                folder.insert("depth_limited".to_string(), Value::Bool(true));
+            }
+
+            // The folder is accounted for with an empty list of children, which are accounted
+            // for as they are traversed, and with the largest size and count it could report
+            let mut reserved = folder.clone();
+            if detail == TreeDetail::Full {
+                reserved.insert("size".to_string(), json!(u64::MAX));
+                reserved.insert("children_count".to_string(), json!(u64::MAX));
+            }
+            reserved.insert("children".to_string(), Value::Array(Vec::new()));
+            if !budget.consume(&reserved) {
+                break;
+            }
+
+            let (children, size) = if depth_limited {
+                (Vec::new(), 0)
+            } else {
+                traverse_directory(&entry_path, &entry_relative_path, depth + 1, options, budget)?
+            };
+            total_size += size;
+
+            if detail == TreeDetail::Full {
+                folder.insert("size".to_string(), json!(size));
+                folder.insert("children_count".to_string(), json!(children.len()));
+            }
+
             folder.insert("children".to_string(), Value::Array(children));
 
             let folder_value = Value::Object(folder.into_iter().collect());
@@ -65,48 +536,325 @@ fn traverse_directory(path: &FilePath) -> Result<Vec<Value>, io::Error> {
             file.insert("name".to_string(), Value::String(entry_name));
             file.insert("type".to_string(), Value::String("file".to_string()));
 
+            // `DirEntry::metadata` doesn't follow symlinks
+            let metadata = entry
+                .metadata()
+                .inspect_err(|e| warn!("Failed to read metadata of {:?}: {}", entry_path, e))
+                .ok();
+            let size = metadata.as_ref().map(|metadata| metadata.len());
+            total_size += size.unwrap_or(0);
+
+            if detail == TreeDetail::Full {
+                let modified = metadata
+                    .and_then(|metadata| metadata.modified().ok())
+                    .map(|time| DateTime::<Utc>::from(time).to_rfc3339());
+
+                file.insert("size".to_string(), json!(size));
+                file.insert("modified".to_string(), json!(modified));
+            }
+
+            if !budget.consume(&file) {
+                break;
+            }
+
             let file_value = Value::Object(file.into_iter().collect());
 
             items.push(file_value);
         }
     }
 
-    Ok(items)
+    Ok((items, total_size))
+}
+
+/// Maps a traversal failure to a response, without exposing filesystem paths.
+///
+/// # Parameters
+/// - `repo_name`: The name of the repository being traversed.
+/// - `error`: The error raised by the traversal.
+///
+/// # Returns
+/// 403 when a directory can't be read, 404 when part of the repository vanished
+/// during the traversal, and 500 otherwise.
+///
+fn traversal_error(repo_name: &str, error: &io::Error) -> ErrorResponse {
+    error!("Failed to traverse repository {}: {}", repo_name, error);
+
+    match error.kind() {
+        io::ErrorKind::PermissionDenied => ErrorResponse::new(
+            StatusCode::FORBIDDEN,
+            format!("Permission denied while reading repository '{}'", repo_name),
+        ),
+        io::ErrorKind::NotFound => ErrorResponse::new(
+            StatusCode::NOT_FOUND,
+            format!("Repository '{}' not found in 'competitions' directory", repo_name),
+        ),
+        _ => ErrorResponse::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("Failed to traverse repository '{}'", repo_name),
+        ),
+    }
 }
 
 /// Axum handler to view the codebase structure as JSON.
 ///
+/// The generated tree is cached in Redis, keyed by the repository name, the detail level, and
+/// the modification time of the repository directory, so a re-extracted repository is never
+/// served a stale tree. Pass `?refresh=true` to bypass the cache and regenerate the tree.
+///
+/// Trees larger than `MAX_JSON_RESPONSE_BYTES` are cut short and flagged with `truncated: true`.
+///
 /// # Parameters
+/// - `State(state)`: The application state, whose file service caches the tree in Redis.
 /// - `Path(repo_name)`: The name of the repository to generate the codebase JSON for.
+/// - `Query(query)`: The level of detail to include, via `?detail=full|minimal`, whether to refresh,
+///   and the maximum depth and ignore patterns, via `?max_depth=` and `?ignore=`.
+/// - `format`: The response format, MessagePack when the client sends `Accept: application/msgpack`.
 ///
 /// # Returns
-/// The JSON response containing the codebase structure.
-pub async fn generate_codebase_json(Path(repo_name): Path<String>) -> Result<Json<Value>, (StatusCode, String)> {
-    let base_path = PathBuf::from("competitions");
+/// The response containing the codebase structure, 400 when an ignore pattern is invalid or
+/// the name could escape the competitions directory, or 403/404/500 when the repository can't be traversed.
+pub async fn generate_codebase_json(
+    State(state): State<Arc<AppState>>,
+    Path(repo_name): Path<String>,
+    Query(query): Query<CodebaseJsonQuery>,
+    format: ResponseFormat,
+) -> Result<Response, ErrorResponse> {
+    if !is_valid_competition_name(&repo_name) {
+        return Err(ErrorResponse::new(StatusCode::BAD_REQUEST, format!("Invalid repository name '{}'", repo_name)));
+    }
+
+    let base_path = PathBuf::from(&state.get_config().competitions_dir);
     let repo_path = base_path.join(&repo_name);
 
     if !repo_path.exists() {
-        return Err((
+        return Err(ErrorResponse::new(
             StatusCode::NOT_FOUND,
             format!("Repository '{}' not found in 'competitions' directory", repo_name),
         ));
     }
+    record_access(&repo_path);
 
-    let structure = match traverse_directory(&repo_path) {
-        Ok(s) => s,
-        Err(e) => {
-            return Err((
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to traverse directory: {}", e),
-            ));
+    let file_service = state.get_file_service();
+    let config = file_service.get_config();
+    let ignore = match &query.ignore {
+        Some(patterns) => patterns
+            .split(',')
+            .map(str::trim)
+            .filter(|pattern| !pattern.is_empty())
+            .map(|pattern| {
+                Pattern::new(pattern)
+                    .map_err(|e| ErrorResponse::new(StatusCode::BAD_REQUEST, format!("Invalid ignore pattern '{}': {}", pattern, e)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => config.tree_ignore_patterns.clone(),
+    };
+    let options = TreeOptions {
+        detail: query.detail,
+        max_depth: query.max_depth.unwrap_or(config.tree_max_depth),
+        ignore,
+    };
+    let variant = options.cache_variant();
+
+    let modified = fs::metadata(&repo_path)
+        .and_then(|metadata| metadata.modified())
+        .map(|time| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos())
+        .unwrap_or_default();
+
+    if !query.refresh {
+        match file_service.get_cached_codebase_json(&repo_name, &variant, modified).await {
+            Ok(Some(body)) => {
+                info!("Returning cached codebase JSON for: {}", repo_name);
+                return Ok(format.respond(StatusCode::OK, &body));
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed to read cached codebase JSON for {}: {}", repo_name, e),
         }
+    }
+
+    let max_bytes = file_service.get_config().max_json_response_bytes;
+    let mut budget = JsonBudget::new(max_bytes);
+
+    let structure = match traverse_directory(&repo_path, FilePath::new(""), 0, &options, &mut budget) {
+        Ok((s, _)) => s,
+        Err(e) => return Err(traversal_error(&repo_name, &e)),
     };
 
+    let mut body = CodebaseTreeResponse {
+        status: "success".to_string(),
+        message: "Codebase JSON generated successfully".to_string(),
+        data: structure,
+        truncated: budget.truncated,
+        hint: None,
+    };
+
+    if budget.truncated {
+        warn!("Codebase JSON for {} truncated at {} bytes", repo_name, max_bytes);
+        body.hint = Some(format!(
+            "The tree exceeds {} bytes and was truncated; request ?detail=minimal or a smaller subtree",
+            max_bytes
+        ));
+    }
+
+    if let Err(e) = file_service.cache_codebase_json(&repo_name, &variant, modified, &body).await {
+        warn!("Failed to cache codebase JSON for {}: {}", repo_name, e);
+    }
+
+    Ok(format.respond(StatusCode::OK, &body))
+}
+
+/// Resolves a path relative to a competition directory, rejecting paths that escape it.
+///
+/// # Parameters
+/// - `competitions_dir`: The directory holding the extracted competitions.
+/// - `name`: The name of the competition.
+/// - `relative_path`: The path of the file relative to the competition directory.
+///
+/// # Returns
+/// - `Ok(PathBuf)`: The canonical path of the file.
+/// - `Err(ErrorResponse)`: 404 if the file does not exist, 403 if it escapes the competition directory.
+fn resolve_competition_file(
+    competitions_dir: &str,
+    name: &str,
+    relative_path: &str,
+) -> Result<PathBuf, ErrorResponse> {
+    let not_found = || ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found in '{}'", relative_path, name));
+
+    if FilePath::new(relative_path).components().any(|component| component == Component::ParentDir) {
+        return Err(ErrorResponse::new(StatusCode::FORBIDDEN, "Path escapes the competition directory"));
+    }
+
+    let base_path = fs::canonicalize(competitions_dir).map_err(|_| not_found())?;
+    let repo_path = fs::canonicalize(base_path.join(name)).map_err(|_| not_found())?;
+    if !repo_path.starts_with(&base_path) || repo_path == base_path {
+        return Err(ErrorResponse::new(StatusCode::FORBIDDEN, "Path escapes the competitions directory"));
+    }
+
+    let file_path = fs::canonicalize(repo_path.join(relative_path)).map_err(|_| not_found())?;
+    if !file_path.starts_with(&repo_path) {
+        return Err(ErrorResponse::new(StatusCode::FORBIDDEN, "Path escapes the competition directory"));
+    }
+
+    if !file_path.is_file() || file_path == repo_path.join(EXTRACTION_MANIFEST) {
+        return Err(not_found());
+    }
+
+    record_access(&repo_path);
+    Ok(file_path)
+}
+
+/// Axum handler to read the contents of a single extracted file.
+///
+/// Text files are returned as UTF-8 with a content type guessed from their extension.
+/// Binary files are only returned with `?raw=true`, as `application/octet-stream`;
+/// requesting them wrapped in JSON yields a 415.
+/// With `?normalize_eol=true`, CRLF line endings of text files are converted to LF, so
+/// files display the same whichever platform they were written on. Binaries are never modified.
+///
+/// # Parameters
+/// - `State(state)`: The application state, holding the competitions directory.
+/// - `Path((name, path))`: The name of the competition and the path of the file within it.
+/// - `Query(query)`: Whether to return the raw file content instead of a JSON wrapper,
+///   and whether to normalize line endings.
+///
+/// # Returns
+/// The file content, either raw or wrapped in JSON.
+pub async fn view_codebase_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path((name, path)): Path<(String, String)>,
+    Query(query): Query<FileContentQuery>,
+) -> Result<Response, ErrorResponse> {
+    let file_path = resolve_competition_file(&state.get_config().competitions_dir, &name, &path)?;
+
+    let data = fs::read(&file_path).map_err(|e| {
+        error!("Failed to read file {:?}: {}", file_path, e);
+        ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file")
+    })?;
+
+    if !is_text(&data) {
+        if query.raw {
+            return Ok(([(header::CONTENT_TYPE, "application/octet-stream")], data).into_response());
+        }
+
+        return Err(ErrorResponse::new(
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            format!("File '{}' is binary; use ?raw=true to download it", path),
+        ));
+    }
+
+    let content_type = guess_text_content_type(&file_path);
+    let data = if query.normalize_eol { normalize_line_endings(&data) } else { data };
+
+    if query.raw {
+        return Ok(([(header::CONTENT_TYPE, content_type)], data).into_response());
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "path": path,
+        "content_type": content_type,
+        "content": String::from_utf8_lossy(&data),
+    })).into_response())
+}
+
+/// Query parameters accepted when fetching a file of a competition.
+///
+/// # Fields
+/// - `path`: The path of the file relative to the competition directory.
+///
+#[derive(Deserialize)]
+pub struct CompetitionFileQuery {
+    path: String,
+}
+
+/// Axum handler to fetch a single file of an extracted competition.
+///
+/// Text files are returned as is, with a content type guessed from their extension.
+/// Binary files are returned as raw bytes when the client accepts `application/octet-stream`,
+/// and base64-encoded in JSON otherwise.
+///
+/// # Parameters
+/// - `State(state)`: The application state, holding the competitions directory.
+/// - `Path(name)`: The name of the competition.
+/// - `Query(query)`: The path of the file within the competition.
+/// - `headers`: The request headers, read for the `Accept` header.
+///
+/// # Returns
+/// The file content, 404 if the file does not exist, or 403 if the path escapes the competition.
+pub async fn competition_file_handler(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<CompetitionFileQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ErrorResponse> {
+    let file_path = resolve_competition_file(&state.get_config().competitions_dir, &name, &query.path)?;
+
+    let data = fs::read(&file_path).map_err(|e| {
+        error!("Failed to read file {:?}: {}", file_path, e);
+        ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to read file")
+    })?;
+
+    if is_text(&data) {
+        return Ok(([(header::CONTENT_TYPE, guess_text_content_type(&file_path))], data).into_response());
+    }
+
+    let accepts_bytes = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|media_type| media_type.split(';').next().unwrap_or("").trim() == "application/octet-stream");
+
+    if accepts_bytes {
+        return Ok(([(header::CONTENT_TYPE, "application/octet-stream")], data).into_response());
+    }
+
     Ok(Json(json!({
         "status": "success",
-        "message": "Codebase JSON generated successfully",
-        "data": structure,
-    })))
+        "path": query.path,
+        "content_type": "application/octet-stream",
+        "encoding": "base64",
+        "content": BASE64_STANDARD.encode(&data),
+    })).into_response())
 }
 
 /// Handles the view codebase request.
@@ -114,53 +862,562 @@ pub async fn generate_codebase_json(Path(repo_name): Path<String>) -> Result<Jso
 /// This function first checks if the requested codebase is already available locally,
 /// then checks the Redis cache for the file. If the file is not found, it proceeds to
 /// download and extract the archive. The extracted files are then cached in Redis.
+/// The listed files are relative to the extraction directory, for ZIP and tar.gz archives
+/// alike. Successful responses include `root_dir`, the single top-level directory wrapping the
+/// extracted content, or `null` when there isn't one.
+///
+/// With `?text_only=true`, entries detected as binary are not extracted and are listed under
+/// `skipped` in the response. This only applies when the archive is extracted by this request.
+///
+/// Extraction holds the lock of the competition. A request finding it held waits up to
+/// `EXTRACTION_WAIT_SECS` for the concurrent extraction, then serves its result, or returns
+/// 202 with a `processing` status if it is still running.
 ///
 /// # Parameters
-/// - `State(clients)`: The application clients to interact with Redis, S3, and other services.
+/// - `State(state)`: The application state, whose file service interacts with Redis, S3, and other services.
+/// - `request_id`: The id of the request, included in error responses.
 /// - `Path(name)`: The name of the codebase being requested.
+/// - `Query(query)`: Whether to skip binary entries during extraction.
+///
+/// # Returns
+/// The extracted files, 202 if the competition is being extracted by another request, 400 if
+/// the name could escape the competitions directory, or the error of the extraction.
 ///
 pub async fn view_codebase_handler(
-    State(clients): State<Arc<Clients>>,
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
     Path(name): Path<String>,
+    Query(query): Query<ViewCodebaseQuery>,
 ) -> impl IntoResponse {
-    let file_service = FileService::new(clients);
-    let output_dir = format!("./competitions/{}", name);
+    if !is_valid_competition_name(&name) {
+        return ErrorResponse::new(StatusCode::BAD_REQUEST, format!("Invalid competition name '{}'", name))
+            .with_request_id(&request_id)
+            .into_response();
+    }
 
-    if fs::metadata(&output_dir).is_ok() {
-        info!("File already exists locally: {}", name);
+    let file_service = state.get_file_service();
+    let output_dir = format!("{}/{}", state.get_config().competitions_dir, name);
 
-        match file_service.get_cached_file(&name).await {
-            Ok(Some(cached_file)) => {
-                info!("Returning cached file for: {}", name);
-                (StatusCode::OK, Json(json!({ "file": cached_file }))).into_response()
-            }
-            Ok(None) => {
-                warn!("File not found in cache for: {}", name);
-                file_service.cache_files(&name, &vec![output_dir.clone()]).await.unwrap();
-                info!("Cached file for: {}", name);
-                (StatusCode::OK, Json(json!({ "files": vec![output_dir] }))).into_response()
-            },
-            Err(_) => {
-                error!("Failed to retrieve cached file for: {}", name);
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": "Failed to retrieve cached file" }))).into_response()
+    if let Some(response) = serve_extracted_codebase(file_service, &name, &output_dir, &request_id).await {
+        return response;
+    }
+
+    // Extraction holds the lock of the competition, so concurrent requests don't extract it
+    // twice and the cleanup task never removes it meanwhile
+    let clients = state.get_clients();
+    let (lock_key, token) = match lock_competition(&state, &name, &request_id).await {
+        Ok(lock) => lock,
+        Err(response) => return response,
+    };
+
+    // The lock may have been held by a request that extracted the competition meanwhile
+    let response = match serve_extracted_codebase(file_service, &name, &output_dir, &request_id).await {
+        Some(response) => response,
+        None => {
+            let extraction = file_service.download_and_extract_archive(&name, &output_dir, query.text_only);
+            match extract_codebase(file_service, &name, &output_dir, query.text_only, extraction, &request_id).await {
+                Ok(body) => (StatusCode::OK, Json(body)).into_response(),
+                Err(response) => response,
             }
         }
-    } else {
-        match file_service.download_and_extract_archive(&name, &output_dir).await {
-            Ok(files) => {
-                info!("Successfully extracted files for: {}", name);
+    };
+
+    if let Err(e) = clients.get_redis_client().release_lock(&lock_key, &token).await {
+        warn!("Failed to release the lock of competition {}: {}", name, e);
+    }
+
+    response
+}
+
+/// Query parameters accepted when uploading and extracting an archive.
+///
+/// # Fields
+/// - `text_only`: Whether to skip binary entries when extracting the archive.
+/// - `storage_class`: The S3 storage class to store the archive in, one of `ALLOWED_STORAGE_CLASSES`.
+///
+#[derive(Deserialize)]
+pub struct UploadExtractQuery {
+    #[serde(default)]
+    text_only: bool,
+    storage_class: Option<String>,
+}
+
+/// Handles uploading a competition archive and extracting it right away.
+///
+/// The archive is validated and stored like those sent to `/upload`, then extracted from
+/// its local copy, sparing the download a separate `view-codebase` request would make.
+/// The archive must be named after its competition, with a `.zip` or `.tar.gz` extension,
+/// and names failing `is_valid_competition_name` are rejected with a 400.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `request_id`: The id of the request, included in error responses.
+/// - `Query(query)`: Whether to skip binary entries, and the storage class of the archive.
+/// - `identity`: The identity of the API key the request was authenticated with.
+/// - `multipart`: The multipart request containing the archive.
+///
+/// # Returns
+/// The upload result and the extracted files, 202 if the competition is being extracted by
+/// another request, or the error of the upload or extraction.
+///
+pub async fn upload_extract_handler(
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
+    Query(query): Query<UploadExtractQuery>,
+    identity: ApiKeyIdentity,
+    multipart: Multipart,
+) -> Response {
+    let file_service = state.get_file_service();
+    let uploaded = match file_service.upload_archive(multipart, &identity.0, query.storage_class.as_deref()).await {
+        Ok(uploaded) => uploaded,
+        Err(response) => return response,
+    };
+
+    let name = uploaded.competition.clone();
+    let upload = uploaded.upload.clone();
+    let output_dir = format!("{}/{}", state.get_config().competitions_dir, name);
+
+    let clients = state.get_clients();
+    let (lock_key, token) = match lock_competition(&state, &name, &request_id).await {
+        Ok(lock) => lock,
+        Err(response) => return response,
+    };
+
+    let extraction = file_service.extract_uploaded_archive(uploaded, &output_dir, query.text_only);
+    let response = match extract_codebase(file_service, &name, &output_dir, query.text_only, extraction, &request_id).await {
+        Ok(codebase) => (StatusCode::OK, Json(UploadExtractResponse { upload, codebase })).into_response(),
+        Err(response) => response,
+    };
+
+    if let Err(e) = clients.get_redis_client().release_lock(&lock_key, &token).await {
+        warn!("Failed to release the lock of competition {}: {}", name, e);
+    }
+
+    response
+}
+
+/// Acquires the lock of a competition, waiting up to `EXTRACTION_WAIT_SECS` for a
+/// concurrent extraction to release it.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `name`: The name of the competition.
+/// - `request_id`: The id of the request, included in error responses.
+///
+/// # Returns
+/// - `Ok((String, String))`: The key and token of the lock, to release it.
+/// - `Err(Response)`: A 202 if the competition is still being extracted, or a 500 if Redis fails.
+async fn lock_competition(
+    state: &AppState,
+    name: &str,
+    request_id: &RequestId,
+) -> Result<(String, String), Response> {
+    let clients = state.get_clients();
+    let lock_key = competition_lock_key(clients, name);
+    let wait = Duration::from_secs(state.get_config().extraction_wait_secs);
+
+    match clients.get_redis_client().wait_for_lock(&lock_key, COMPETITION_LOCK_TTL, wait).await {
+        Ok(Some(token)) => Ok((lock_key, token)),
+        Ok(None) => {
+            info!("Competition {} is still being processed, not waiting any longer", name);
+            Err((StatusCode::ACCEPTED, Json(json!({
+                "status": "processing",
+                "message": "Competition is being extracted, retry later",
+                "request_id": request_id.0,
+            }))).into_response())
+        }
+        Err(e) => {
+            error!("Failed to lock competition {}: {}", name, e);
+            Err(ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to lock competition")
+                .with_request_id(request_id)
+                .into_response())
+        }
+    }
+}
+
+/// Serves a codebase that is already extracted and up to date with its archive.
+///
+/// # Parameters
+/// - `file_service`: The file service holding the cached file lists.
+/// - `name`: The name of the codebase.
+/// - `output_dir`: The extraction directory of the codebase.
+/// - `request_id`: The id of the request, included in error responses.
+///
+/// # Returns
+/// The response listing the extracted files, or `None` if the codebase must be extracted.
+async fn serve_extracted_codebase(
+    file_service: &FileService,
+    name: &str,
+    output_dir: &str,
+    request_id: &RequestId,
+) -> Option<Response> {
+    if fs::metadata(output_dir).is_err() {
+        return None;
+    }
+
+    if file_service.extraction_is_stale(name, output_dir).await {
+        info!("Local extraction of {} is out of date, extracting again", name);
+        return None;
+    }
+
+    info!("File already exists locally and is up to date: {}", name);
+    record_access(FilePath::new(output_dir));
+
+    let files = match file_service.get_cached_files(name).await {
+        Ok(Some(files)) => {
+            info!("Returning cached file list for: {}", name);
+            files
+        }
+        cached => {
+            match cached {
+                Err(e) => warn!("Failed to retrieve cached file list for {}, listing the files: {}", name, e),
+                _ => info!("File list not found in cache for: {}", name),
+            }
 
-                if let Err(e) = file_service.cache_files(&name, &files).await {
-                    error!("Error caching extracted files for {}: {}", name, e);
-                    return (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response();
+            let files = match file_service.list_extracted_files(output_dir) {
+                Ok(files) => files,
+                Err(e) => {
+                    error!("Failed to list extracted files for {}: {}", name, e);
+                    return Some(ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to list extracted files")
+                        .with_request_id(request_id)
+                        .into_response());
                 }
+            };
+            if let Err(e) = file_service.cache_files(name, &files).await {
+                warn!("Failed to cache the file list of {}: {}", name, e);
+            }
+            files
+        }
+    };
+
+    let root_dir = file_service.detect_root_dir(output_dir);
+    Some((StatusCode::OK, Json(CodebaseFilesResponse { files, root_dir, skipped: None })).into_response())
+}
+
+/// Extracts a codebase, replacing its out-of-date extraction if any, and caches its file list.
+///
+/// # Parameters
+/// - `file_service`: The file service extracting and caching the codebase.
+/// - `name`: The name of the codebase.
+/// - `output_dir`: The directory to extract the codebase into.
+/// - `text_only`: Whether binary entries are skipped during extraction.
+/// - `extraction`: The extraction of the archive, run once the out-of-date extraction is removed.
+/// - `request_id`: The id of the request, included in error responses.
+///
+/// # Returns
+/// The extracted and skipped files, or the error response preventing the extraction.
+async fn extract_codebase(
+    file_service: &FileService,
+    name: &str,
+    output_dir: &str,
+    text_only: bool,
+    extraction: impl Future<Output = Result<Extraction, AppError>>,
+    request_id: &RequestId,
+) -> Result<CodebaseFilesResponse, Response> {
+    if fs::metadata(output_dir).is_ok() {
+        if let Err(e) = fs::remove_dir_all(output_dir) {
+            error!("Failed to remove stale extraction {}: {}", output_dir, e);
+            return Err(ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove stale extraction")
+                .with_request_id(request_id)
+                .into_response());
+        }
+    }
+
+    match extraction.await {
+        Ok(extraction) => {
+            info!("Successfully extracted files for: {}", name);
+
+            if let Err(e) = file_service.invalidate_codebase_json(name).await {
+                warn!("Failed to invalidate cached codebase JSON for {}: {}", name, e);
+            }
+
+            if let Err(e) = file_service.cache_files(name, &extraction.files).await {
+                error!("Error caching extracted files for {}: {}", name, e);
+                return Err(ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                    .with_request_id(request_id)
+                    .into_response());
+            }
+
+            let root_dir = file_service.detect_root_dir(output_dir);
+            Ok(CodebaseFilesResponse {
+                files: extraction.files,
+                root_dir,
+                skipped: text_only.then_some(extraction.skipped),
+            })
+        }
+        Err(AppError::ObjectNotFound(message)) => {
+            warn!("No archive to extract for {}: {}", name, message);
+            Err(ErrorResponse::new(StatusCode::NOT_FOUND, format!("No archive is stored for '{}'", name))
+                .with_request_id(request_id)
+                .into_response())
+        }
+        Err(e) => {
+            error!("Failed to extract files for {}: {}", name, e);
+            Err(ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+                .with_request_id(request_id)
+                .into_response())
+        }
+    }
+}
+
+/// The number of versions returned per page of the upload history, by default.
+const DEFAULT_HISTORY_LIMIT: i64 = 20;
+
+/// The maximum number of versions returned per page of the upload history.
+const MAX_HISTORY_LIMIT: i64 = 100;
+
+/// Query parameters accepted when fetching the upload history of a competition.
+///
+/// # Fields
+/// - `latest`: Whether to return only the latest version.
+/// - `limit`: The maximum number of versions to return, up to 100.
+/// - `offset`: The number of most recent versions to skip.
+///
+#[derive(Deserialize)]
+pub struct UploadHistoryQuery {
+    #[serde(default)]
+    latest: bool,
+    limit: Option<i64>,
+    offset: Option<i64>,
+}
+
+/// Handles fetching the upload history of a competition.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `Path(name)`: The name of the competition.
+/// - `Query(query)`: The page of the history, via `?limit=` and `?offset=`, or `?latest=true`
+///   for the latest version alone.
+///
+/// # Returns
+/// The versions of the archive of the competition, the latest first, 400 if the page is
+/// invalid, or 404 if no upload of the competition was recorded.
+///
+pub async fn upload_history_handler(
+    State(state): State<Arc<AppState>>,
+    Path(name): Path<String>,
+    Query(query): Query<UploadHistoryQuery>,
+) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_HISTORY_LIMIT);
+    let offset = query.offset.unwrap_or(0);
+
+    if !(1..=MAX_HISTORY_LIMIT).contains(&limit) || offset < 0 {
+        return ErrorResponse::new(
+            StatusCode::BAD_REQUEST,
+            format!("limit must be between 1 and {}, and offset can't be negative", MAX_HISTORY_LIMIT),
+        ).into_response();
+    }
+
+    state.get_file_service()
+        .upload_history(&name, limit, offset, query.latest)
+        .await
+}
+
+/// Handles deleting an extracted competition, to reclaim its disk space. Its stored archive
+/// is kept, so viewing the codebase again extracts it again.
+///
+/// # Parameters
+/// - `state`: The application state.
+/// - `request_id`: The id of the request, included in error responses.
+/// - `Path(name)`: The name of the competition.
+///
+/// # Returns
+/// The number of files removed, 400 if the name could escape the competitions directory,
+/// 404 if the competition isn't extracted, or 409 if it is being extracted.
+///
+pub async fn delete_competition_handler(
+    State(state): State<Arc<AppState>>,
+    request_id: RequestId,
+    Path(name): Path<String>,
+) -> Response {
+    if !is_valid_competition_name(&name) {
+        return ErrorResponse::new(StatusCode::BAD_REQUEST, format!("Invalid competition name '{}'", name))
+            .with_request_id(&request_id)
+            .into_response();
+    }
+
+    match CleanupService::new(state.get_clients().clone(), state.get_shared_config()).delete_competition(&name).await {
+        Ok(CompetitionDeletion::Removed(files)) => {
+            (StatusCode::OK, Json(json!({ "name": name, "files_removed": files }))).into_response()
+        }
+        Ok(CompetitionDeletion::NotFound) => {
+            ErrorResponse::new(StatusCode::NOT_FOUND, format!("Competition '{}' is not extracted", name))
+                .with_request_id(&request_id)
+                .into_response()
+        }
+        Ok(CompetitionDeletion::Locked) => {
+            ErrorResponse::new(StatusCode::CONFLICT, format!("Competition '{}' is being extracted, retry later", name))
+                .with_request_id(&request_id)
+                .into_response()
+        }
+        Err(e) => {
+            error!("Failed to delete competition {}: {}", name, e);
+            ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete competition")
+                .with_request_id(&request_id)
+                .into_response()
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    use tempfile::TempDir;
+
+    fn options(detail: TreeDetail) -> TreeOptions {
+        TreeOptions { detail, max_depth: 32, ignore: Vec::new() }
+    }
+
+    fn traverse(root: &FilePath, options: &TreeOptions) -> (Vec<Value>, u64) {
+        traverse_directory(root, FilePath::new(""), 0, options, &mut JsonBudget::new(usize::MAX)).unwrap()
+    }
+
+    fn names(nodes: &[Value]) -> Vec<&str> {
+        nodes.iter().map(|node| node["name"].as_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn tree_is_sorted_by_name_with_aggregate_sizes() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir_all(dir.path().join("src/utils")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), b"fn main() {}").unwrap();
+        fs::write(dir.path().join("src/utils/mod.rs"), b"pub mod a;").unwrap();
+        fs::write(dir.path().join("README.md"), b"# Readme").unwrap();
+        fs::write(dir.path().join("Cargo.toml"), b"[package]").unwrap();
+        fs::write(dir.path().join(EXTRACTION_MANIFEST), b"{}").unwrap();
+
+        let (tree, size) = traverse(dir.path(), &options(TreeDetail::Full));
+
+        assert_eq!(names(&tree), ["Cargo.toml", "README.md", "src"]);
+        let src = &tree[2];
+        assert_eq!(names(src["children"].as_array().unwrap()), ["main.rs", "utils"]);
+        assert_eq!(src["children_count"], 2);
+        assert_eq!(src["size"], 22);
+        assert_eq!(size, 39);
+        assert_eq!(tree[0]["size"], 9);
+        assert!(tree[0]["modified"].is_string());
+    }
+
+    #[test]
+    fn minimal_tree_has_no_detail() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), b"fn main() {}").unwrap();
+
+        let (tree, _) = traverse(dir.path(), &options(TreeDetail::Minimal));
+
+        assert_eq!(tree, vec![json!({
+            "name": "src",
+            "type": "folder",
+            "children": [{ "name": "main.rs", "type": "file" }],
+        })]);
+    }
+
+    #[test]
+    fn symlinks_are_listed_as_files_without_being_followed() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/main.rs"), b"fn main() {}").unwrap();
+        symlink("..", dir.path().join("src/parent")).unwrap();
+        symlink(".", dir.path().join("src/itself")).unwrap();
 
-                (StatusCode::OK, Json(json!({ "files": files }))).into_response()
+        let (tree, size) = traverse(dir.path(), &options(TreeDetail::Full));
+
+        let children = tree[0]["children"].as_array().unwrap();
+        assert_eq!(names(children), ["itself", "main.rs", "parent"]);
+        assert_eq!(children[0]["type"], "file");
+        assert_eq!(children[2]["type"], "file");
+        // A symlink only accounts for its own size, the length of its target
+        assert_eq!(size, 12 + 1 + 2);
+    }
+
+    #[test]
+    fn tree_exceeding_the_budget_is_truncated_within_it() {
+        let dir = TempDir::new().unwrap();
+        for folder in 0..5 {
+            let folder_path = dir.path().join(format!("folder-{}", folder)).join("nested");
+            fs::create_dir_all(&folder_path).unwrap();
+            for file in 0..20 {
+                fs::write(folder_path.join(format!("file-{:02}.rs", file)), b"fn main() {}").unwrap();
             }
-            Err(e) => {
-                error!("Failed to extract files for {}: {}", name, e);
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({ "error": e.to_string() }))).into_response()
+        }
+
+        for detail in [TreeDetail::Minimal, TreeDetail::Full] {
+            for max_depth in [0, 32] {
+                let options = TreeOptions { detail, max_depth, ignore: Vec::new() };
+                for max_bytes in 16..2048 {
+                    let mut budget = JsonBudget::new(max_bytes);
+                    let (tree, _) = traverse_directory(dir.path(), FilePath::new(""), 0, &options, &mut budget).unwrap();
+
+                    let size = serde_json::to_vec(&tree).unwrap().len();
+                    assert!(size <= max_bytes, "{} bytes for a budget of {}", size, max_bytes);
+                    assert!(budget.truncated || max_depth == 0, "a budget of {} was not exceeded", max_bytes);
+                }
             }
         }
+
+        let mut budget = JsonBudget::new(usize::MAX);
+        let (tree, _) = traverse_directory(dir.path(), FilePath::new(""), 0, &options(TreeDetail::Full), &mut budget).unwrap();
+        assert!(!budget.truncated);
+        assert_eq!(tree.len(), 5);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn unreadable_directory_is_forbidden() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let locked = dir.path().join("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+
+        let result = traverse_directory(dir.path(), FilePath::new(""), 0, &options(TreeDetail::Full), &mut JsonBudget::new(usize::MAX));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+
+        // Permissions don't apply to root, which reads the directory anyway
+        let Err(error) = result else {
+            eprintln!("the locked directory is readable, skipping");
+            return;
+        };
+        let response = traversal_error("repo", &error);
+        assert_eq!(response.code, 403);
+        assert_eq!(response.error, "Permission denied while reading repository 'repo'");
+    }
+
+    #[test]
+    fn traversal_errors_map_to_their_status_without_leaking_paths() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        let error = traverse_directory(&missing, FilePath::new(""), 0, &options(TreeDetail::Full), &mut JsonBudget::new(usize::MAX)).unwrap_err();
+
+        let response = traversal_error("repo", &error);
+        assert_eq!(response.code, 404);
+        assert_eq!(response.error, "Repository 'repo' not found in 'competitions' directory");
+
+        let denied = io::Error::new(io::ErrorKind::PermissionDenied, format!("{}: permission denied", missing.display()));
+        let response = traversal_error("repo", &denied);
+        assert_eq!(response.code, 403);
+        assert!(!response.error.contains(&*dir.path().to_string_lossy()));
+
+        let response = traversal_error("repo", &io::Error::other("disk failure"));
+        assert_eq!(response.code, 500);
+        assert_eq!(response.error, "Failed to traverse repository 'repo'");
+    }
+
+    #[tokio::test]
+    async fn requests_are_served_by_the_same_file_service() {
+        let dir = TempDir::new().unwrap();
+        let mut config = crate::config::AppConfig::for_tests();
+        config.local_storage_dir = dir.path().to_string_lossy().into_owned();
+        let clients = Arc::new(crate::clients::clients::Clients::for_tests(&config));
+        let state = Arc::new(AppState::new(config, clients, None).unwrap());
+
+        for _ in 0..2 {
+            let response = download_file_handler(State(state.clone()), Path("missing.pdf".to_string()), HeaderMap::new())
+                .await
+                .into_response();
+            assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        }
+        assert_eq!(state.get_file_service().downloads.load(std::sync::atomic::Ordering::Relaxed), 2);
+    }
+}
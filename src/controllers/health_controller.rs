@@ -1,24 +1,35 @@
-use axum::{Extension, response::IntoResponse};
-use crate::services::health_service::{perform_health_check, HealthCheckType};
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use serde_json::json;
+use crate::services::health_service::{perform_health_check, perform_readiness_check, HealthCheckType};
 use std::sync::Arc;
-use crate::clients::clients::Clients;
+use crate::app_state::AppState;
 
 /// Handler for checking all services
-pub async fn health_check_handler(Extension(state): Extension<Arc<Clients>>) -> impl IntoResponse {
-    perform_health_check(state.as_ref(), HealthCheckType::All).await
+pub async fn health_check_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    perform_health_check(&state, HealthCheckType::All).await
 }
 
 /// Handler for checking S3 health
-pub async fn s3_health_check_handler(Extension(state): Extension<Arc<Clients>>) -> impl IntoResponse {
-    perform_health_check(state.as_ref(), HealthCheckType::S3).await
+pub async fn s3_health_check_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    perform_health_check(&state, HealthCheckType::S3).await
 }
 
 /// Handler for checking PostgreSQL health
-pub async fn postgres_health_check_handler(Extension(state): Extension<Arc<Clients>>) -> impl IntoResponse {
-    perform_health_check(state.as_ref(), HealthCheckType::Postgres).await
+pub async fn postgres_health_check_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    perform_health_check(&state, HealthCheckType::Postgres).await
 }
 
 /// Handler for checking Redis health
-pub async fn redis_health_check_handler(Extension(state): Extension<Arc<Clients>>) -> impl IntoResponse {
-    perform_health_check(state.as_ref(), HealthCheckType::Redis).await
+pub async fn redis_health_check_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    perform_health_check(&state, HealthCheckType::Redis).await
+}
+
+/// Handler for the liveness probe, answering without calling any external service
+pub async fn liveness_handler() -> impl IntoResponse {
+    (StatusCode::OK, Json(json!({ "status": "alive" })))
+}
+
+/// Handler for the readiness probe
+pub async fn readiness_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    perform_readiness_check(&state).await
 }
@@ -0,0 +1,25 @@
+use std::sync::Arc;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::json;
+use crate::app_state::AppState;
+
+/// The content type of the Prometheus text exposition format.
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Handles rendering the application metrics in the Prometheus text format.
+///
+/// # Parameters
+/// - `state`: The application state, holding the metrics handle.
+///
+/// # Returns
+/// The metrics, or 404 when metrics are disabled.
+///
+pub async fn metrics_handler(State(state): State<Arc<AppState>>) -> Response {
+    match state.get_metrics() {
+        Some(metrics) => ([(header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)], metrics.render()).into_response(),
+        None => (StatusCode::NOT_FOUND, Json(json!({ "error": "Not found" }))).into_response(),
+    }
+}
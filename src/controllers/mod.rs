@@ -1,2 +1,6 @@
 pub mod health_controller;
-pub mod file_controller;
\ No newline at end of file
+pub mod file_controller;
+pub mod admin_controller;
+pub mod chunked_upload_controller;
+pub mod metrics_controller;
+pub mod docs_controller;
\ No newline at end of file
@@ -6,15 +6,29 @@ use redis::RedisError;
 use std::io;
 use aws_sdk_s3::config::http::HttpResponse;
 use aws_sdk_s3::operation::get_object::GetObjectError;
+use aws_sdk_s3::operation::put_object::PutObjectError;
+use aws_sdk_s3::operation::abort_multipart_upload::AbortMultipartUploadError;
+use aws_sdk_s3::operation::complete_multipart_upload::CompleteMultipartUploadError;
+use aws_sdk_s3::operation::copy_object::CopyObjectError;
+use aws_sdk_s3::operation::create_multipart_upload::CreateMultipartUploadError;
+use aws_sdk_s3::operation::delete_object::DeleteObjectError;
+use aws_sdk_s3::operation::head_object::HeadObjectError;
+use aws_sdk_s3::operation::upload_part::UploadPartError;
+use aws_sdk_s3::operation::upload_part_copy::UploadPartCopyError;
 use aws_sdk_s3::primitives::ByteStreamError;
 use serde_json::Error;
+use crate::utils::metrics::{POSTGRES_ERRORS_TOTAL, REDIS_ERRORS_TOTAL};
 
 /// Represents custom errors that can occur in the application.
 ///
 /// This enum encapsulates all possible errors that might arise during the execution
 /// of the application, including environment variable errors, S3 connection errors,
 /// PostgreSQL connection errors, and Redis connection errors.
+///
+/// The S3 SDK errors are several hundred bytes large, so they are boxed to keep every
+/// `Result<_, AppError>` small.
 #[derive(Error, Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum AppError {
     /// An error indicating that a required environment variable is missing or invalid.
     #[error("Environment variable error: {0}")]
@@ -22,15 +36,23 @@ pub enum AppError {
 
     /// An error indicating a failure to connect to or interact with AWS S3.
     #[error("S3 connection error: {0}")]
-    S3ConnectionError(#[from] SdkError<ListObjectsV2Error>),
+    S3ConnectionError(Box<SdkError<ListObjectsV2Error>>),
 
     /// An error indicating a failure to connect to or interact with a PostgreSQL database.
     #[error("PostgreSQL connection error: {0}")]
-    PostgresConnectionError(#[from] SqlxError),
+    PostgresConnectionError(SqlxError),
+
+    /// An error indicating that the database schema is not in the expected state.
+    #[error("Database schema error: {0}")]
+    DatabaseSchemaError(String),
+
+    /// An error indicating a failure while applying database migrations.
+    #[error("Database migration error: {0}")]
+    MigrationError(#[from] sqlx::migrate::MigrateError),
 
     /// An error indicating a failure to connect to or interact with a Redis server.
     #[error("Redis connection error: {0}")]
-    RedisConnectionError(#[from] RedisError),
+    RedisConnectionError(RedisError),
 
     /// An error indicating a failure during file validation or extraction.
     #[error("File validation or extraction error: {0}")]
@@ -46,7 +68,51 @@ pub enum AppError {
 
     /// An error indicating a failure during S3 object download.
     #[error("Unable to download the object: {0}")]
-    SdkDownloadObjectError(#[from] SdkError<GetObjectError, HttpResponse>),
+    SdkDownloadObjectError(Box<SdkError<GetObjectError, HttpResponse>>),
+
+    /// An error indicating a failure during S3 object upload.
+    #[error("Unable to upload the object: {0}")]
+    SdkUploadObjectError(Box<SdkError<PutObjectError, HttpResponse>>),
+
+    /// An error indicating a failure to fetch the metadata of an S3 object.
+    #[error("Unable to fetch the object metadata: {0}")]
+    SdkHeadObjectError(Box<SdkError<HeadObjectError, HttpResponse>>),
+
+    /// An error indicating a failure during S3 object deletion.
+    #[error("Unable to delete the object: {0}")]
+    SdkDeleteObjectError(Box<SdkError<DeleteObjectError, HttpResponse>>),
+
+    /// An error indicating a failure during S3 object copy.
+    #[error("Unable to copy the object: {0}")]
+    SdkCopyObjectError(Box<SdkError<CopyObjectError, HttpResponse>>),
+
+    /// An error indicating a failure to copy a part of an S3 object into a multipart upload.
+    #[error("Unable to copy the part: {0}")]
+    SdkUploadPartCopyError(Box<SdkError<UploadPartCopyError, HttpResponse>>),
+
+    /// An error indicating that a stored object does not exist.
+    #[error("Object not found: {0}")]
+    ObjectNotFound(String),
+
+    /// An error indicating that a requested byte range lies outside a stored object.
+    #[error("Range not satisfiable: {0}")]
+    RangeNotSatisfiable(String),
+
+    /// An error indicating a failure to start an S3 multipart upload.
+    #[error("Unable to start the multipart upload: {0}")]
+    SdkCreateMultipartUploadError(Box<SdkError<CreateMultipartUploadError, HttpResponse>>),
+
+    /// An error indicating a failure to upload a part of an S3 multipart upload.
+    #[error("Unable to upload the part: {0}")]
+    SdkUploadPartError(Box<SdkError<UploadPartError, HttpResponse>>),
+
+    /// An error indicating a failure to complete an S3 multipart upload.
+    #[error("Unable to complete the multipart upload: {0}")]
+    SdkCompleteMultipartUploadError(Box<SdkError<CompleteMultipartUploadError, HttpResponse>>),
+
+    /// An error indicating a failure to abort an S3 multipart upload.
+    #[error("Unable to abort the multipart upload: {0}")]
+    SdkAbortMultipartUploadError(Box<SdkError<AbortMultipartUploadError, HttpResponse>>),
 
     /// An error indicating a failure during byte stream operations.
     #[error("Byte Stream Error: {0}")]
@@ -54,4 +120,86 @@ pub enum AppError {
 
     #[error("Serialization error: {0}")]
     SerializationError(#[from] Error),
-}
\ No newline at end of file
+}
+
+impl From<SdkError<ListObjectsV2Error>> for AppError {
+    fn from(error: SdkError<ListObjectsV2Error>) -> Self {
+        AppError::S3ConnectionError(Box::new(error))
+    }
+}
+
+impl From<SdkError<GetObjectError, HttpResponse>> for AppError {
+    fn from(error: SdkError<GetObjectError, HttpResponse>) -> Self {
+        AppError::SdkDownloadObjectError(Box::new(error))
+    }
+}
+
+impl From<SdkError<PutObjectError, HttpResponse>> for AppError {
+    fn from(error: SdkError<PutObjectError, HttpResponse>) -> Self {
+        AppError::SdkUploadObjectError(Box::new(error))
+    }
+}
+
+impl From<SdkError<HeadObjectError, HttpResponse>> for AppError {
+    fn from(error: SdkError<HeadObjectError, HttpResponse>) -> Self {
+        AppError::SdkHeadObjectError(Box::new(error))
+    }
+}
+
+impl From<SdkError<DeleteObjectError, HttpResponse>> for AppError {
+    fn from(error: SdkError<DeleteObjectError, HttpResponse>) -> Self {
+        AppError::SdkDeleteObjectError(Box::new(error))
+    }
+}
+
+impl From<SdkError<CopyObjectError, HttpResponse>> for AppError {
+    fn from(error: SdkError<CopyObjectError, HttpResponse>) -> Self {
+        AppError::SdkCopyObjectError(Box::new(error))
+    }
+}
+
+impl From<SdkError<UploadPartCopyError, HttpResponse>> for AppError {
+    fn from(error: SdkError<UploadPartCopyError, HttpResponse>) -> Self {
+        AppError::SdkUploadPartCopyError(Box::new(error))
+    }
+}
+
+impl From<SdkError<CreateMultipartUploadError, HttpResponse>> for AppError {
+    fn from(error: SdkError<CreateMultipartUploadError, HttpResponse>) -> Self {
+        AppError::SdkCreateMultipartUploadError(Box::new(error))
+    }
+}
+
+impl From<SdkError<UploadPartError, HttpResponse>> for AppError {
+    fn from(error: SdkError<UploadPartError, HttpResponse>) -> Self {
+        AppError::SdkUploadPartError(Box::new(error))
+    }
+}
+
+impl From<SdkError<CompleteMultipartUploadError, HttpResponse>> for AppError {
+    fn from(error: SdkError<CompleteMultipartUploadError, HttpResponse>) -> Self {
+        AppError::SdkCompleteMultipartUploadError(Box::new(error))
+    }
+}
+
+impl From<SdkError<AbortMultipartUploadError, HttpResponse>> for AppError {
+    fn from(error: SdkError<AbortMultipartUploadError, HttpResponse>) -> Self {
+        AppError::SdkAbortMultipartUploadError(Box::new(error))
+    }
+}
+
+impl From<SqlxError> for AppError {
+    /// Wraps a PostgreSQL error, counting it in `postgres_errors_total`.
+    fn from(error: SqlxError) -> Self {
+        metrics::counter!(POSTGRES_ERRORS_TOTAL).increment(1);
+        AppError::PostgresConnectionError(error)
+    }
+}
+
+impl From<RedisError> for AppError {
+    /// Wraps a Redis error, counting it in `redis_errors_total`.
+    fn from(error: RedisError) -> Self {
+        metrics::counter!(REDIS_ERRORS_TOTAL).increment(1);
+        AppError::RedisConnectionError(error)
+    }
+}
@@ -0,0 +1,16 @@
+//! The Rustler library, holding the application served by the `Rustler` binary.
+//!
+//! The modules are public so that the integration tests under `tests/` can build the
+//! application state and the router the same way the binary does.
+
+pub mod app_state;
+pub mod config;
+pub mod error;
+pub mod clients;
+pub mod routes;
+pub mod services;
+pub mod controllers;
+pub mod middleware;
+pub mod utils;
+pub mod models;
+pub mod server;
@@ -4,30 +4,32 @@
 //! to external services (AWS S3, PostgreSQL, Redis). It also handles errors and logs
 //! application events.
 
-mod config;
-mod error;
-mod clients;
-mod routes;
-mod services;
-mod controllers;
-mod utils;
-
 use std::sync::Arc;
+use std::time::Duration;
 use log::{error, info};
-use config::AppConfig;
 
 use anyhow::{Context, Result};
-use axum::{serve, Router};
 use tokio::net::TcpListener;
-use crate::clients::clients::Clients;
-use crate::routes::file_routes::file_routes;
-use crate::routes::health_routes::health_routes;
+use rustler::app_state::AppState;
+use rustler::clients::clients::Clients;
+use rustler::config::AppConfig;
+use rustler::routes::app_routes;
+use rustler::server::{self, ServerOptions};
+use rustler::services::chunked_upload_service::run_expiry_task;
+use rustler::services::cleanup_service::run_cleanup_task;
+use rustler::services::health_service::run_readiness_task;
+use rustler::utils::logging::init_logging;
+use rustler::utils::metrics::install_recorder;
 
 /// The main application logic.
 ///
 /// This function initializes the logger, loads the application configuration,
 /// creates clients for external services, and tests their connections.
 ///
+/// The server starts right away, so `/health/live` answers during startup, while
+/// `/health/ready` reports 503 until the connection tests and migrations complete. The
+/// other requests are rejected with a 503 meanwhile.
+///
 /// # Returns
 /// - `Ok(())`: If all connections are successful.
 /// - `Err(anyhow::Error)`: If any step fails.
@@ -40,29 +42,116 @@ async fn run() -> Result<()> {
         .context("Failed to initialize clients")?;
     info!("Clients initialized successfully");
 
+    let metrics = match config.metrics_enabled {
+        true => Some(install_recorder().context("Failed to install the metrics recorder")?),
+        false => None,
+    };
+
+    let app_state = AppState::new(config, Arc::new(clients), metrics)
+        .context("Failed to initialize the application state")?;
+    let app_state = Arc::new(app_state);
+    tokio::try_join!(startup(app_state.clone()), run_server(app_state))?;
+
+    Ok(())
+}
+
+/// Tests the connections to the external services and applies the database migrations,
+/// then marks the application as ready.
+///
+/// Once ready, the required services are re-checked every `READINESS_CHECK_INTERVAL_SECS`,
+/// and traffic is rejected while one of them is down.
+///
+/// # Arguments
+/// - `state`: The application state.
+///
+/// # Returns
+/// - `Ok(())`: If the application is ready.
+/// - `Err(anyhow::Error)`: If a service can't be reached or the migrations fail.
+async fn startup(state: Arc<AppState>) -> Result<()> {
+    let clients = state.get_clients();
     clients.test_connections().await.context("Failed to connect to external services")?;
     info!("Successfully connected to all external services");
 
-    let app_state = Arc::new(clients);
-    run_server(app_state).await;
+    clients.get_postgres_client().run_migrations().await.context("Failed to apply database migrations")?;
+    info!("Database migrations applied successfully");
+
+    tokio::spawn(run_expiry_task(state.clone()));
+    if state.get_config().cleanup_interval_secs > 0 {
+        tokio::spawn(run_cleanup_task(state.clone()));
+    }
+    clients.mark_ready();
+    info!("Application is ready");
+
+    if state.get_config().readiness_check_interval_secs > 0 {
+        tokio::spawn(run_readiness_task(state.clone()));
+    }
 
     Ok(())
 }
 
 /// Starts the Axum server.
 ///
+/// The server runs until a shutdown signal is received and the in-flight requests drain.
+/// Handlers and middleware share the `AppState`, holding the configuration, the validator,
+/// the clients and the file service.
+///
 /// # Arguments
-/// - `state`: A shared state containing the application clients.
+/// - `state`: The application state.
 ///
-async fn run_server(state: Arc<Clients>) {
-    let listener = TcpListener::bind("0.0.0.0:3000").await.unwrap();
+/// # Returns
+/// - `Ok(())`: When the server shuts down.
+/// - `Err(anyhow::Error)`: If the server can't be configured or started.
+async fn run_server(state: Arc<AppState>) -> Result<()> {
+    let app = app_routes(state.clone()).context("Failed to configure the routes")?;
+
+    let listener = TcpListener::bind("0.0.0.0:3000").await.context("Failed to bind to port 3000")?;
     info!("Server running on http://0.0.0.0:3000");
 
-    let app = Router::new()
-        .merge(file_routes(state))
-        .merge(health_routes());
+    let config = state.get_config();
+    let options = ServerOptions {
+        idle_timeout: Duration::from_secs(config.http_idle_timeout_secs),
+        drain_timeout: Duration::from_secs(config.shutdown_drain_timeout_secs),
+        upload_drain_timeout: Duration::from_secs(config.upload_drain_timeout_secs),
+        active_uploads: state.get_clients().get_active_uploads(),
+    };
+
+    server::serve(listener, app, options, shutdown_signal()).await;
+    info!("Server stopped");
+
+    Ok(())
+}
+
+/// Resolves when the process is asked to stop, with Ctrl+C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 
-    serve(listener, app).await.unwrap();
+    info!("Shutdown signal received");
 }
 
 /// The entry point of the application.
@@ -71,7 +160,7 @@ async fn run_server(state: Arc<Clients>) {
 /// If an error occurs, it logs the error and exits the application.
 #[tokio::main]
 async fn main() {
-    env_logger::init();
+    init_logging();
 
     if let Err(e) = run().await {
         error!("Application error: {}", e);
@@ -0,0 +1,50 @@
+use std::sync::Arc;
+use axum::extract::{Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::warn;
+use serde_json::json;
+use crate::app_state::AppState;
+
+/// Guards the admin endpoints behind the `ADMIN_TOKEN` bearer token.
+///
+/// The admin endpoints are disabled (404) when no token is configured, and requests
+/// without the matching `Authorization: Bearer <token>` header are rejected with 401.
+///
+/// # Arguments
+/// - `state`: The application state, holding the configuration.
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler, or an error response when the request is not authorized.
+pub async fn admin_auth_middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(admin_token) = state.get_config().admin_token.as_deref() else {
+        return (StatusCode::NOT_FOUND, Json(json!({ "error": "Not found" }))).into_response();
+    };
+
+    let provided = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match provided {
+        Some(token) if constant_time_eq(token.as_bytes(), admin_token.as_bytes()) => next.run(request).await,
+        _ => {
+            warn!("Rejected unauthorized request to {}", request.uri().path());
+            (StatusCode::UNAUTHORIZED, Json(json!({ "error": "Unauthorized" }))).into_response()
+        }
+    }
+}
+
+/// Compares two byte strings in time independent of where they differ.
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
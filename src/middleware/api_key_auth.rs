@@ -0,0 +1,103 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use log::{error, warn};
+use crate::app_state::AppState;
+use crate::middleware::admin_auth::constant_time_eq;
+use crate::models::error::ErrorResponse;
+use crate::utils::file_utils::compute_sha256;
+
+/// The header carrying the API key.
+pub static API_KEY_HEADER: HeaderName = HeaderName::from_static("x-api-key");
+
+/// The identity of the API key a request was authenticated with.
+///
+/// It is stored in the request extensions by `api_key_auth_middleware`, and can be extracted
+/// in handlers to record who uploaded a file.
+#[derive(Clone, Debug)]
+pub struct ApiKeyIdentity(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for ApiKeyIdentity {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<ApiKeyIdentity>()
+            .cloned()
+            .unwrap_or_else(|| ApiKeyIdentity("anonymous".to_string())))
+    }
+}
+
+/// Guards the mutating file routes behind an `X-Api-Key` header.
+///
+/// The key is checked against the keys of `API_KEYS` first, then against the active keys of
+/// the `api_keys` table. Requests without a known key are rejected with 401, otherwise the
+/// identity of the key is stored in the request extensions.
+///
+/// # Arguments
+/// - `state`: The application state, holding the configuration and the database.
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler, or an error response when the request is not authorized.
+pub async fn api_key_auth_middleware(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let provided = request
+        .headers()
+        .get(&API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string);
+
+    let Some(api_key) = provided else {
+        warn!("Rejected request to {} without an API key", request.uri().path());
+        return unauthorized("Missing API key");
+    };
+
+    match find_identity(&state, &api_key).await {
+        Some(identity) => {
+            request.extensions_mut().insert(ApiKeyIdentity(identity));
+            next.run(request).await
+        }
+        None => {
+            warn!("Rejected request to {} with an unknown API key", request.uri().path());
+            unauthorized("Invalid API key")
+        }
+    }
+}
+
+/// Returns the identity of an API key, looking it up in the configuration then the database.
+/// A failing database lookup is logged and treated as an unknown key.
+async fn find_identity(state: &AppState, api_key: &str) -> Option<String> {
+    let configured = state
+        .get_config()
+        .api_keys
+        .iter()
+        .find(|(key, _)| constant_time_eq(key.as_bytes(), api_key.as_bytes()))
+        .map(|(_, identity)| identity.clone());
+    if configured.is_some() {
+        return configured;
+    }
+
+    match state.get_clients().get_postgres_client().find_api_key_identity(&compute_sha256(api_key.as_bytes())).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            error!("Failed to look up API key: {:?}", e);
+            None
+        }
+    }
+}
+
+/// Returns a 401 response with the given message.
+fn unauthorized(message: &str) -> Response {
+    ErrorResponse::new(StatusCode::UNAUTHORIZED, message).into_response()
+}
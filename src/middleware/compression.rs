@@ -0,0 +1,118 @@
+use axum::http::{header, Extensions, HeaderMap, StatusCode, Version};
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+use crate::config::AppConfig;
+
+/// Content types that are already compressed, and gain nothing from being compressed again.
+const COMPRESSED_CONTENT_TYPES: &[&str] = &[
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/x-bzip2",
+    "application/x-xz",
+    "application/zstd",
+    "application/x-7z-compressed",
+    "application/vnd.rar",
+    "application/x-rar-compressed",
+];
+
+/// Builds the layer compressing responses with the algorithms of `COMPRESSION_ALGORITHMS`,
+/// as negotiated by `Accept-Encoding`.
+///
+/// Like the defaults of `tower-http`, images, event streams and bodies up to
+/// `COMPRESSION_MIN_SIZE_BYTES` are skipped. Responses that are already compressed archives,
+/// partial content responses and file downloads are sent as is too.
+///
+/// # Parameters
+/// - `config`: The application configuration.
+///
+/// # Returns
+/// The configured compression layer.
+pub fn compression_layer(config: &AppConfig) -> CompressionLayer<impl Predicate> {
+    let enabled = |algorithm: &str| config.compression_algorithms.iter().any(|enabled| enabled == algorithm);
+    let predicate = SizeAbove::new(config.compression_min_size_bytes)
+        .and(NotForContentType::GRPC)
+        .and(NotForContentType::IMAGES)
+        .and(NotForContentType::SSE)
+        .and(is_compressible);
+
+    CompressionLayer::new()
+        .gzip(enabled("gzip"))
+        .br(enabled("br"))
+        .compress_when(predicate)
+}
+
+/// Returns whether a response is worth compressing, based on its headers.
+/// A range of an object must be sent as the bytes requested, so ranges are never compressed,
+/// and downloads are served as stored, whatever their content type.
+fn is_compressible(_: StatusCode, _: Version, headers: &HeaderMap, _: &Extensions) -> bool {
+    if headers.contains_key(header::CONTENT_RANGE) {
+        return false;
+    }
+
+    let is_attachment = headers
+        .get(header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|disposition| disposition.trim_start().to_ascii_lowercase().starts_with("attachment"));
+    if is_attachment {
+        return false;
+    }
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("");
+
+    !COMPRESSED_CONTENT_TYPES
+        .iter()
+        .any(|compressed| content_type.starts_with(compressed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, Response};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use tower::ServiceExt;
+
+    /// A router serving a JSON listing, a ZIP archive and a file download, all large enough
+    /// to be compressed.
+    fn router() -> Router {
+        let listing = serde_json::json!({ "files": vec!["src/main.rs"; 64] });
+        let archive = [(header::CONTENT_TYPE, "application/zip")];
+        let download = [(header::CONTENT_TYPE, "text/plain"), (header::CONTENT_DISPOSITION, "attachment; filename=\"notes.txt\"")];
+
+        Router::new()
+            .route("/json", get(move || async move { Json(listing) }))
+            .route("/archive", get(move || async move { (archive, vec![b'a'; 4096]) }))
+            .route("/download", get(move || async move { (download, vec![b'a'; 4096]) }))
+            .layer(compression_layer(&AppConfig::for_tests()))
+    }
+
+    async fn get_encoded(path: &str, accept_encoding: &str) -> Response<Body> {
+        let request = Request::get(path)
+            .header(header::ACCEPT_ENCODING, accept_encoding)
+            .body(Body::empty())
+            .unwrap();
+        router().oneshot(request).await.unwrap().map(Body::new)
+    }
+
+    fn content_encoding(response: &Response<Body>) -> Option<&str> {
+        response.headers().get(header::CONTENT_ENCODING).map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn json_is_compressed_as_negotiated() {
+        assert_eq!(content_encoding(&get_encoded("/json", "gzip").await), Some("gzip"));
+        assert_eq!(content_encoding(&get_encoded("/json", "br").await), Some("br"));
+        assert_eq!(content_encoding(&get_encoded("/json", "identity").await), None);
+    }
+
+    #[tokio::test]
+    async fn archives_and_downloads_are_sent_as_is() {
+        assert_eq!(content_encoding(&get_encoded("/archive", "gzip").await), None);
+        assert_eq!(content_encoding(&get_encoded("/download", "gzip").await), None);
+    }
+}
@@ -0,0 +1,150 @@
+use std::time::Duration;
+use axum::http::{HeaderName, HeaderValue, Method};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use crate::config::AppConfig;
+use crate::error::AppError;
+
+/// Builds the CORS layer from the application configuration.
+///
+/// Only the origins listed in `CORS_ALLOWED_ORIGINS` are allowed, so with the default empty
+/// list no cross-origin request succeeds. Listing `*` allows any origin, in which case
+/// credentialed requests are not allowed, as browsers reject them with a wildcard origin.
+/// Otherwise origins are listed explicitly, and credentialed requests are allowed.
+///
+/// Preflight requests are answered by the layer itself, and cached by browsers for
+/// `CORS_MAX_AGE` seconds.
+///
+/// # Arguments
+/// - `config`: The application configuration holding the allowed origins, methods, headers, and max age.
+///
+/// # Returns
+/// - `Ok(CorsLayer)`: The configured CORS layer.
+/// - `Err(AppError)`: An error if an origin, method, or header can't be parsed.
+pub fn cors_layer(config: &AppConfig) -> Result<CorsLayer, AppError> {
+    let any_origin = config.cors_allowed_origins.iter().any(|origin| origin == "*");
+    let origins = config.cors_allowed_origins
+        .iter()
+        .filter(|origin| *origin != "*")
+        .map(|origin| parse(origin, "CORS_ALLOWED_ORIGINS"))
+        .collect::<Result<Vec<HeaderValue>, _>>()?;
+
+    let methods = config.cors_allowed_methods
+        .iter()
+        .map(|method| parse(method, "CORS_ALLOWED_METHODS"))
+        .collect::<Result<Vec<Method>, _>>()?;
+
+    let headers = config.cors_allowed_headers
+        .iter()
+        .map(|header| parse(header, "CORS_ALLOWED_HEADERS"))
+        .collect::<Result<Vec<HeaderName>, _>>()?;
+
+    let origin = if any_origin { AllowOrigin::any() } else { AllowOrigin::list(origins) };
+
+    Ok(CorsLayer::new()
+        .allow_origin(origin)
+        .allow_methods(methods)
+        .allow_headers(headers)
+        .allow_credentials(!any_origin)
+        .max_age(Duration::from_secs(config.cors_max_age_secs)))
+}
+
+/// Parses a single configured CORS value.
+///
+/// # Arguments
+/// - `value`: The value to parse.
+/// - `key`: The name of the environment variable the value came from.
+///
+/// # Returns
+/// - `Ok(T)`: The parsed value.
+/// - `Err(AppError)`: An error naming the environment variable if the value is invalid.
+fn parse<T: std::str::FromStr>(value: &str, key: &str) -> Result<T, AppError> {
+    value
+        .parse()
+        .map_err(|_| AppError::EnvVarError(format!("{} has an invalid value: {}", key, value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{header, Request, Response, StatusCode};
+    use axum::routing::{get, post};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn router(allowed_origins: &[&str]) -> Router {
+        let mut config = AppConfig::for_tests();
+        config.cors_allowed_origins = allowed_origins.iter().map(|origin| origin.to_string()).collect();
+        Router::new()
+            .route("/upload", post(|| async { StatusCode::CREATED }))
+            .route("/generate-codebase-json/{name}", get(|| async { "{}" }))
+            .layer(cors_layer(&config).unwrap())
+    }
+
+    async fn preflight(allowed_origins: &[&str], origin: &str) -> Response<Body> {
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/upload")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(header::ACCESS_CONTROL_REQUEST_HEADERS, "authorization")
+            .body(Body::empty())
+            .unwrap();
+        router(allowed_origins).oneshot(request).await.unwrap()
+    }
+
+    fn header_value(response: &Response<Body>, name: HeaderName) -> Option<&str> {
+        response.headers().get(name).map(|value| value.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn preflight_from_an_allowed_origin_allows_credentials() {
+        let response = preflight(&["https://app.example.com"], "https://app.example.com").await;
+
+        // Answered by the layer, without reaching the upload handler
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_MAX_AGE), Some("600"));
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some("https://app.example.com"));
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+        assert!(header_value(&response, header::ACCESS_CONTROL_ALLOW_METHODS).unwrap().contains("POST"));
+        assert!(header_value(&response, header::ACCESS_CONTROL_ALLOW_HEADERS).unwrap().to_ascii_lowercase().contains("authorization"));
+    }
+
+    #[tokio::test]
+    async fn simple_get_from_an_allowed_origin_carries_the_cors_headers() {
+        let request = Request::get("/generate-codebase-json/demo")
+            .header(header::ORIGIN, "https://app.example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = router(&["https://app.example.com"]).oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some("https://app.example.com"));
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), Some("true"));
+        assert!(header_value(&response, header::VARY).unwrap().contains("origin"));
+    }
+
+    #[tokio::test]
+    async fn preflight_from_an_unlisted_origin_is_not_allowed() {
+        let response = preflight(&["https://app.example.com"], "https://evil.example.com").await;
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+
+        let response = preflight(&[], "https://app.example.com").await;
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), None);
+    }
+
+    #[tokio::test]
+    async fn wildcard_origin_does_not_allow_credentials() {
+        let response = preflight(&["*"], "https://app.example.com").await;
+
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_ORIGIN), Some("*"));
+        assert_eq!(header_value(&response, header::ACCESS_CONTROL_ALLOW_CREDENTIALS), None);
+    }
+
+    #[test]
+    fn invalid_origin_is_rejected() {
+        let mut config = AppConfig::for_tests();
+        config.cors_allowed_origins = vec!["https://bad\norigin".to_string()];
+        assert!(matches!(cors_layer(&config), Err(AppError::EnvVarError(_))));
+    }
+}
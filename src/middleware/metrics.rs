@@ -0,0 +1,37 @@
+use std::time::Instant;
+use axum::extract::{MatchedPath, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use crate::utils::metrics::{HTTP_REQUESTS_TOTAL, HTTP_REQUEST_DURATION_SECONDS};
+
+/// Records the count and latency of every request.
+///
+/// Requests are labelled by their route pattern, such as `/files/{key}`, rather than their
+/// path, so the number of label values stays bounded. Requests matching no route are
+/// labelled `unmatched`.
+///
+/// # Arguments
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler.
+pub async fn metrics_middleware(request: Request, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::counter!(HTTP_REQUESTS_TOTAL, "method" => method.clone(), "route" => route.clone(), "status" => status)
+        .increment(1);
+    metrics::histogram!(HTTP_REQUEST_DURATION_SECONDS, "method" => method, "route" => route)
+        .record(started.elapsed().as_secs_f64());
+
+    response
+}
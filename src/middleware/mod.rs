@@ -0,0 +1,9 @@
+pub mod admin_auth;
+pub mod api_key_auth;
+pub mod compression;
+pub mod cors;
+pub mod metrics;
+pub mod rate_limit;
+pub mod readiness;
+pub mod request_id;
+pub mod upload_tracker;
\ No newline at end of file
@@ -0,0 +1,81 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::{error, warn};
+use serde_json::json;
+use crate::app_state::AppState;
+
+/// Limits the number of uploads per client IP within the configured window.
+///
+/// Counters are kept in Redis under `rate_limit:upload:{ip}`. Once a client exceeds
+/// `RATE_LIMIT_UPLOADS` within `RATE_LIMIT_WINDOW_SECS`, it receives a 429 with a
+/// `Retry-After` header until the window ends. If Redis is unreachable, requests are let through.
+///
+/// # Arguments
+/// - `State(state)`: The application state.
+/// - `ConnectInfo(addr)`: The address of the connected peer.
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler, or a 429 when the limit is exceeded.
+pub async fn upload_rate_limit_middleware(
+    State(state): State<Arc<AppState>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let clients = state.get_clients();
+    let config = state.get_config();
+    if config.rate_limit_uploads == 0 {
+        return next.run(request).await;
+    }
+
+    let ip = client_ip(request.headers(), addr, config.trust_proxy);
+    let key = clients.get_redis_client().key(&format!("rate_limit:upload:{}", ip));
+
+    match clients.get_redis_client().increment_in_window(&key, config.rate_limit_window_secs).await {
+        Ok((count, _)) if count <= config.rate_limit_uploads => next.run(request).await,
+        Ok((_, retry_after)) => {
+            warn!("Upload rate limit exceeded for {}", ip);
+            (
+                StatusCode::TOO_MANY_REQUESTS,
+                [(header::RETRY_AFTER, retry_after.to_string())],
+                Json(json!({ "error": "Too many uploads, please retry later" })),
+            ).into_response()
+        }
+        Err(e) => {
+            error!("Failed to check upload rate limit for {}: {}", ip, e);
+            next.run(request).await
+        }
+    }
+}
+
+/// Identifies the client of a request.
+///
+/// # Arguments
+/// - `headers`: The request headers.
+/// - `addr`: The address of the connected peer.
+/// - `trust_proxy`: Whether to use the first address of the `X-Forwarded-For` header.
+///
+/// # Returns
+/// The IP address of the client.
+fn client_ip(headers: &HeaderMap, addr: SocketAddr, trust_proxy: bool) -> IpAddr {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .and_then(|value| value.trim().parse().ok());
+
+        if let Some(ip) = forwarded {
+            return ip;
+        }
+    }
+
+    addr.ip()
+}
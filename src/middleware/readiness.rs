@@ -0,0 +1,48 @@
+use std::sync::Arc;
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use crate::app_state::AppState;
+use crate::middleware::request_id::RequestId;
+use crate::models::error::ErrorResponse;
+
+/// Paths that are served whether or not the application is ready.
+const UNGATED_PATHS: [&str; 5] = ["/health", "/readyz", "/metrics", "/docs", "/api-docs"];
+
+/// Rejects the requests with a 503 while the application can't serve traffic.
+///
+/// Traffic is gated until the startup checks complete, and whenever the periodic
+/// readiness check finds a required service down. The health checks, `/readyz`,
+/// `/metrics`, and the API documentation are always served, so probes can observe the state.
+///
+/// # Arguments
+/// - `state`: The application state, holding the clients.
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler, or a 503 when the application isn't ready.
+pub async fn readiness_middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.get_clients().is_serving() || is_ungated(request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let mut error = ErrorResponse::new(StatusCode::SERVICE_UNAVAILABLE, "Service is not ready");
+    if let Some(request_id) = request.extensions().get::<RequestId>() {
+        error = error.with_request_id(request_id);
+    }
+
+    error.into_response()
+}
+
+/// Returns whether the path is served regardless of readiness.
+fn is_ungated(path: &str) -> bool {
+    UNGATED_PATHS.iter().any(|ungated| {
+        path.strip_prefix(ungated).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+    })
+}
@@ -0,0 +1,76 @@
+use std::convert::Infallible;
+use std::time::Instant;
+use axum::extract::{FromRequestParts, Request};
+use axum::http::request::Parts;
+use axum::http::{HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::Response;
+use tracing::{info, info_span, Instrument};
+use uuid::Uuid;
+
+/// The header carrying the request id.
+pub static REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+/// The id correlating a request across log lines and responses.
+///
+/// It is stored in the request extensions by `request_id_middleware`, and can be extracted
+/// in handlers to include it in error responses.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for RequestId {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts
+            .extensions
+            .get::<RequestId>()
+            .cloned()
+            .unwrap_or_else(|| RequestId("unknown".to_string())))
+    }
+}
+
+/// Assigns a request id to every request and logs it once the response is ready.
+///
+/// The id is taken from the `X-Request-Id` header when present, or generated otherwise.
+/// It is stored in the request extensions and echoed back in the response header. The
+/// request is handled within a `request` span, so every line logged meanwhile carries the id.
+///
+/// # Arguments
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response, with the `X-Request-Id` header set.
+pub async fn request_id_middleware(mut request: Request, next: Next) -> Response {
+    let request_id = request
+        .headers()
+        .get(&REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    let started = Instant::now();
+
+    let span = info_span!("request", request_id = %request_id, method = %method, path = %path);
+
+    request.extensions_mut().insert(RequestId(request_id.clone()));
+    let mut response = next.run(request).instrument(span.clone()).await;
+
+    span.in_scope(|| {
+        info!(
+            status = response.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "Request completed"
+        );
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER.clone(), value);
+    }
+
+    response
+}
@@ -0,0 +1,76 @@
+use std::sync::Arc;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use tokio::sync::watch;
+use crate::app_state::AppState;
+
+/// Counts the upload requests in progress, so shutdown can give them time to finish.
+#[derive(Clone)]
+pub struct ActiveUploads {
+    count: Arc<watch::Sender<usize>>,
+}
+
+impl Default for ActiveUploads {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ActiveUploads {
+    /// Creates a new tracker with no upload in progress.
+    pub fn new() -> Self {
+        Self {
+            count: Arc::new(watch::Sender::new(0)),
+        }
+    }
+
+    /// Returns the number of uploads in progress.
+    pub fn count(&self) -> usize {
+        *self.count.borrow()
+    }
+
+    /// Waits until no upload is in progress.
+    pub async fn wait_idle(&self) {
+        let mut receiver = self.count.subscribe();
+        let _ = receiver.wait_for(|count| *count == 0).await;
+    }
+
+    /// Counts an upload as in progress until the returned guard is dropped.
+    pub(crate) fn start(&self) -> UploadGuard {
+        self.count.send_modify(|count| *count += 1);
+        UploadGuard {
+            count: self.count.clone(),
+        }
+    }
+}
+
+/// Counts an upload as in progress while alive.
+pub(crate) struct UploadGuard {
+    count: Arc<watch::Sender<usize>>,
+}
+
+impl Drop for UploadGuard {
+    fn drop(&mut self) {
+        self.count.send_modify(|count| *count -= 1);
+    }
+}
+
+/// Tracks the upload requests in progress, so shutdown waits up to `UPLOAD_DRAIN_TIMEOUT_SECS`
+/// for them instead of cutting them off after the general drain timeout.
+///
+/// # Arguments
+/// - `State(state)`: The application state.
+/// - `request`: The incoming request.
+/// - `next`: The rest of the middleware stack.
+///
+/// # Returns
+/// The response of the handler.
+pub async fn track_upload_middleware(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let _guard = state.get_clients().get_active_uploads().start();
+    next.run(request).await
+}
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// The files of an extracted codebase.
+///
+/// # Fields
+/// - `files`: The paths of the extracted files, relative to the extraction directory.
+/// - `root_dir`: The single top-level directory of the archive, if it has one.
+/// - `skipped`: The binary entries skipped by a `?text_only=true` extraction.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CodebaseFilesResponse {
+    pub files: Vec<String>,
+    pub root_dir: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skipped: Option<Vec<String>>,
+}
+
+/// The directory tree of an extracted codebase, as cached and served by `generate-codebase-json`.
+///
+/// # Fields
+/// - `status`: Always `success`.
+/// - `message`: A summary of the result.
+/// - `data`: The top-level nodes of the tree, one per file or folder.
+/// - `truncated`: Whether nodes were dropped to fit `MAX_JSON_RESPONSE_BYTES`.
+/// - `hint`: How to get a complete tree, when it was truncated.
+///
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CodebaseTreeResponse {
+    pub status: String,
+    pub message: String,
+    pub data: Vec<Value>,
+    pub truncated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn codebase_files_response_field_names_are_pinned() {
+        let response = CodebaseFilesResponse {
+            files: vec!["src/main.rs".to_string()],
+            root_dir: None,
+            skipped: Some(vec!["logo.png".to_string()]),
+        };
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({ "files": ["src/main.rs"], "root_dir": null, "skipped": ["logo.png"] }));
+        assert_eq!(serde_json::from_value::<CodebaseFilesResponse>(value).unwrap(), response);
+    }
+
+    #[test]
+    fn codebase_tree_response_field_names_are_pinned() {
+        let response = CodebaseTreeResponse {
+            status: "success".to_string(),
+            message: "Codebase JSON generated".to_string(),
+            data: vec![json!({ "name": "main.rs", "type": "file" })],
+            truncated: true,
+            hint: Some("Request ?detail=minimal".to_string()),
+        };
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({
+            "status": "success",
+            "message": "Codebase JSON generated",
+            "data": [{ "name": "main.rs", "type": "file" }],
+            "truncated": true,
+            "hint": "Request ?detail=minimal",
+        }));
+        assert_eq!(serde_json::from_value::<CodebaseTreeResponse>(value).unwrap(), response);
+
+        let response = CodebaseTreeResponse { truncated: false, hint: None, ..response };
+        assert!(serde_json::to_value(&response).unwrap().get("hint").is_none());
+    }
+}
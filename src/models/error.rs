@@ -0,0 +1,77 @@
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::{Deserialize, Serialize};
+use crate::middleware::request_id::RequestId;
+
+/// The JSON body of an error response.
+///
+/// # Fields
+/// - `error`: A message describing the error.
+/// - `code`: The HTTP status code of the response.
+/// - `request_id`: The id of the request, to find it in the logs, when the handler has it.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorResponse {
+    pub error: String,
+    pub code: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+}
+
+impl ErrorResponse {
+    /// Creates an error response with the given status and message.
+    pub fn new(status: StatusCode, error: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            code: status.as_u16(),
+            request_id: None,
+        }
+    }
+
+    /// Includes the id of the request in the response.
+    pub fn with_request_id(mut self, request_id: &RequestId) -> Self {
+        self.request_id = Some(request_id.0.clone());
+        self
+    }
+
+    /// Returns the HTTP status of the response.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.code).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl From<(StatusCode, String)> for ErrorResponse {
+    fn from((status, error): (StatusCode, String)) -> Self {
+        Self::new(status, error)
+    }
+}
+
+impl IntoResponse for ErrorResponse {
+    fn into_response(self) -> Response {
+        (self.status(), Json(self)).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn error_response_field_names_are_pinned() {
+        let response = ErrorResponse::new(StatusCode::NOT_FOUND, "File 'a.pdf' not found")
+            .with_request_id(&RequestId("req-1".to_string()));
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({ "error": "File 'a.pdf' not found", "code": 404, "request_id": "req-1" }));
+        assert_eq!(serde_json::from_value::<ErrorResponse>(value).unwrap(), response);
+    }
+
+    #[test]
+    fn error_response_without_a_request_id_omits_it() {
+        let response = ErrorResponse::new(StatusCode::BAD_REQUEST, "Invalid key");
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({ "error": "Invalid key", "code": 400 }));
+        assert_eq!(serde_json::from_value::<ErrorResponse>(value).unwrap(), response);
+    }
+}
@@ -0,0 +1,58 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+
+/// A magic number of a file type, in the format of the file types config file: a hex
+/// string expected at the start of the file, or a list of hex segments expected at offsets.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum MagicNumberSummary {
+    Hex(String),
+    Segments(Vec<MagicSegmentSummary>),
+}
+
+/// A hex segment of a magic number expected at an offset.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MagicSegmentSummary {
+    pub offset: usize,
+    pub hex: String,
+}
+
+/// A file type registered with the validator, and the outcome of the uploads resolved to it.
+///
+/// # Fields
+/// - `name`: The name of the file type.
+/// - `extensions`: The allowed file extensions.
+/// - `content_types`: The allowed content types, any content type being accepted when empty.
+/// - `magic_numbers`: The magic numbers identifying the file content.
+/// - `max_size`: The maximum file size in bytes.
+/// - `accepted`: The number of uploads of this type which passed validation.
+/// - `rejected`: The number of uploads of this type which failed validation.
+///
+#[derive(Clone, Debug, Serialize)]
+pub struct FileTypeSummary {
+    pub name: String,
+    pub extensions: Vec<String>,
+    pub content_types: Vec<String>,
+    pub magic_numbers: Vec<MagicNumberSummary>,
+    pub max_size: usize,
+    pub accepted: u64,
+    pub rejected: u64,
+}
+
+/// The file types accepted by the running instance, and the validation counters since it started.
+///
+/// # Fields
+/// - `file_types`: The registered file types, sorted by name.
+/// - `default_file_type`: The type of uploads matching no registered type, if any.
+/// - `accepted`: The number of uploads which passed validation.
+/// - `rejected`: The number of uploads which failed validation, including those of no known type.
+/// - `rejections`: The number of rejected uploads, keyed by rejection reason.
+///
+#[derive(Clone, Debug, Serialize)]
+pub struct FileTypesResponse {
+    pub file_types: Vec<FileTypeSummary>,
+    pub default_file_type: Option<String>,
+    pub accepted: u64,
+    pub rejected: u64,
+    pub rejections: IndexMap<&'static str, u64>,
+}
@@ -0,0 +1,93 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// The status of a single service
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceStatus {
+    Up,
+    Down,
+}
+
+/// The health of a single service
+///
+/// # Fields
+///
+/// - `status`: `up` when the service check passed, `down` otherwise.
+/// - `latency_ms`: How long the service check took, in milliseconds.
+/// - `error`: Why the service check failed, if it did.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ServiceHealth {
+    pub status: ServiceStatus,
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The JSON body returned by the health endpoints
+///
+/// # Fields
+///
+/// - `status`: `healthy` when every checked service is up, `degraded` when only some of
+///   them are down, and `unhealthy` when all of them are.
+/// - `message`: A summary of the check, listing the failures if any.
+/// - `checks`: The health of each checked service, keyed by service name.
+/// - `stale`: Set when a last-known-good report is served because the check failed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub message: String,
+    pub checks: IndexMap<String, ServiceHealth>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub stale: bool,
+}
+
+impl HealthResponse {
+    /// Returns whether every checked service is up
+    pub fn is_healthy(&self) -> bool {
+        self.checks.values().all(|check| check.status == ServiceStatus::Up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    #[test]
+    fn health_response_field_names_are_pinned() {
+        let mut checks = IndexMap::new();
+        checks.insert("postgres".to_string(), ServiceHealth { status: ServiceStatus::Up, latency_ms: 3, error: None });
+        checks.insert(
+            "redis".to_string(),
+            ServiceHealth { status: ServiceStatus::Down, latency_ms: 2000, error: Some("timed out".to_string()) },
+        );
+        let response = HealthResponse {
+            status: "degraded".to_string(),
+            message: "redis: timed out".to_string(),
+            checks,
+            stale: true,
+        };
+
+        let value = serde_json::to_value(&response).unwrap();
+        assert_eq!(value, json!({
+            "status": "degraded",
+            "message": "redis: timed out",
+            "checks": {
+                "postgres": { "status": "up", "latency_ms": 3 },
+                "redis": { "status": "down", "latency_ms": 2000, "error": "timed out" },
+            },
+            "stale": true,
+        }));
+
+        let parsed: HealthResponse = serde_json::from_value(value).unwrap();
+        assert_eq!(parsed.checks.keys().collect::<Vec<_>>(), ["postgres", "redis"]);
+        assert!(parsed.stale && !parsed.is_healthy());
+    }
+
+    #[test]
+    fn fresh_health_response_omits_the_stale_flag() {
+        let response = HealthResponse { status: "healthy".to_string(), message: "OK".to_string(), checks: IndexMap::new(), stale: false };
+        assert!(serde_json::to_value(&response).unwrap().get("stale").is_none());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod codebase;
+pub mod error;
+pub mod file_type;
+pub mod health;
+pub mod upload;
\ No newline at end of file
@@ -0,0 +1,154 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use crate::clients::postgres_client::UploadEvent;
+use crate::models::codebase::CodebaseFilesResponse;
+
+/// The result of a file stored by an upload, or found already stored.
+///
+/// # Fields
+/// - `message`: A summary of the result.
+/// - `id`: The id of the upload record of the content.
+/// - `file_name`: The name the file was uploaded under.
+/// - `key`: The storage key holding the content.
+/// - `size`: The size of the file in bytes.
+/// - `sha256`: The hex-encoded SHA-256 digest of the content.
+/// - `sniffed_mime_type`: The MIME type sniffed from the content, if any.
+/// - `deduplicated`: Whether the content was already stored, and not uploaded again.
+/// - `existing_file_name`: The name the stored content was first uploaded under, when deduplicated.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadResponse {
+    pub message: String,
+    pub id: Uuid,
+    pub file_name: String,
+    pub key: String,
+    pub size: u64,
+    pub sha256: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sniffed_mime_type: Option<String>,
+    pub deduplicated: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub existing_file_name: Option<String>,
+}
+
+/// The result of a file that could not be uploaded.
+///
+/// # Fields
+/// - `file_name`: The name the file was uploaded under.
+/// - `error`: Why the file was rejected.
+/// - `code`: The HTTP status describing the failure.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadFailure {
+    pub file_name: String,
+    pub error: String,
+    pub code: u16,
+}
+
+/// The result of one file of an upload batch.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UploadResult {
+    Stored(UploadResponse),
+    Failed(UploadFailure),
+}
+
+/// A version of the archive of a competition, as recorded when it was uploaded.
+///
+/// # Fields
+/// - `event`: The recorded upload.
+/// - `exists`: Whether an object is still stored under the key of the version, or `None`
+///   when storage couldn't tell.
+///
+#[derive(Debug, Serialize)]
+pub struct UploadVersion {
+    #[serde(flatten)]
+    pub event: UploadEvent,
+    pub exists: Option<bool>,
+}
+
+/// A page of the upload history of a competition, the latest version first.
+///
+/// # Fields
+/// - `competition`: The name of the competition.
+/// - `versions`: The versions of the page.
+/// - `total`: The number of versions recorded for the competition.
+/// - `limit`: The maximum number of versions of the page.
+/// - `offset`: The number of most recent versions skipped.
+///
+#[derive(Debug, Serialize)]
+pub struct UploadHistoryResponse {
+    pub competition: String,
+    pub versions: Vec<UploadVersion>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+/// The result of an archive uploaded and extracted by `/upload-extract`.
+///
+/// # Fields
+/// - `upload`: The result of the upload.
+/// - `codebase`: The files extracted from the archive.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadExtractResponse {
+    pub upload: UploadResponse,
+    pub codebase: CodebaseFilesResponse,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn upload() -> UploadResponse {
+        UploadResponse {
+            message: "File uploaded successfully".to_string(),
+            id: Uuid::nil(),
+            file_name: "report.pdf".to_string(),
+            key: "report.pdf".to_string(),
+            size: 42,
+            sha256: "ab".repeat(32),
+            sniffed_mime_type: Some("application/pdf".to_string()),
+            deduplicated: true,
+            existing_file_name: Some("first.pdf".to_string()),
+        }
+    }
+
+    #[test]
+    fn upload_response_field_names_are_pinned() {
+        let value = serde_json::to_value(upload()).unwrap();
+        assert_eq!(value, json!({
+            "message": "File uploaded successfully",
+            "id": "00000000-0000-0000-0000-000000000000",
+            "file_name": "report.pdf",
+            "key": "report.pdf",
+            "size": 42,
+            "sha256": "ab".repeat(32),
+            "sniffed_mime_type": "application/pdf",
+            "deduplicated": true,
+            "existing_file_name": "first.pdf",
+        }));
+        assert_eq!(serde_json::from_value::<UploadResponse>(value).unwrap(), upload());
+    }
+
+    #[test]
+    fn upload_response_omits_the_unset_optional_fields() {
+        let upload = UploadResponse { sniffed_mime_type: None, deduplicated: false, existing_file_name: None, ..upload() };
+        let value = serde_json::to_value(&upload).unwrap();
+        assert!(value.get("sniffed_mime_type").is_none());
+        assert!(value.get("existing_file_name").is_none());
+        assert_eq!(serde_json::from_value::<UploadResponse>(value).unwrap(), upload);
+    }
+
+    #[test]
+    fn upload_results_are_told_apart_by_their_fields() {
+        let failure = UploadFailure { file_name: "bad.exe".to_string(), error: "Unsupported file type".to_string(), code: 415 };
+        let results = vec![UploadResult::Stored(upload()), UploadResult::Failed(failure)];
+
+        let value = serde_json::to_value(&results).unwrap();
+        assert_eq!(value[1], json!({ "file_name": "bad.exe", "error": "Unsupported file type", "code": 415 }));
+        assert_eq!(serde_json::from_value::<Vec<UploadResult>>(value).unwrap(), results);
+    }
+}
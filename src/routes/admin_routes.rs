@@ -0,0 +1,26 @@
+use std::sync::Arc;
+use axum::{Router, routing::{get, post}};
+use axum::middleware::from_fn_with_state;
+use crate::app_state::AppState;
+use crate::controllers::admin_controller::{cleanup_handler, file_types_handler, flush_cache_handler};
+use crate::middleware::admin_auth::admin_auth_middleware;
+
+/// Returns a router with the admin endpoints, guarded by the `ADMIN_TOKEN` bearer token.
+///
+/// # Parameters
+/// - `state`: The application state.
+///
+/// # Returns
+/// A Router containing the following endpoints:
+/// - POST /admin/cache/flush - Deletes every Redis key owned by the application
+/// - POST /admin/cleanup - Removes the expired competitions, or lists them with `?dry_run=true`
+/// - GET /admin/file-types - Lists the accepted file types and the validation counters
+///
+pub fn admin_routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/admin/cache/flush", post(flush_cache_handler))
+        .route("/admin/cleanup", post(cleanup_handler))
+        .route("/admin/file-types", get(file_types_handler))
+        .route_layer(from_fn_with_state(state.clone(), admin_auth_middleware))
+        .with_state(state)
+}
@@ -0,0 +1,15 @@
+use axum::{Router, routing::get};
+use crate::controllers::docs_controller::{openapi_handler, swagger_ui_handler};
+
+/// Returns a router with the API documentation.
+///
+/// # Returns
+/// A Router containing the following endpoints:
+/// - GET /api-docs/openapi.json - The OpenAPI document of the API
+/// - GET /docs - Swagger UI, browsing the OpenAPI document
+///
+pub fn docs_routes() -> Router {
+    Router::new()
+        .route("/api-docs/openapi.json", get(openapi_handler))
+        .route("/docs", get(swagger_ui_handler))
+}
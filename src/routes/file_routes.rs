@@ -1,25 +1,100 @@
 use std::sync::Arc;
-use axum::{Router, routing::{post, get}};
+use axum::{Router, routing::{delete, post, put, get}};
 use axum::extract::DefaultBodyLimit;
-use crate::clients::clients::Clients;
-use crate::controllers::file_controller::{generate_codebase_json, upload_handler, view_codebase_handler};
+use axum::middleware::from_fn_with_state;
+use crate::app_state::AppState;
+use crate::middleware::api_key_auth::api_key_auth_middleware;
+use crate::middleware::rate_limit::upload_rate_limit_middleware;
+use crate::middleware::upload_tracker::track_upload_middleware;
+use crate::controllers::chunked_upload_controller::{complete_chunked_upload_handler, init_chunked_upload_handler, upload_part_handler};
+use crate::controllers::file_controller::{competition_file_handler, delete_competition_handler, copy_file_handler, delete_file_handler, restore_file_handler, download_file_handler, file_checksum_handler, file_metadata_handler, inspect_archive_handler, rename_file_handler, generate_codebase_json, get_upload_handler, upload_extract_handler, upload_handler, upload_history_handler, view_codebase_file_handler, view_codebase_handler};
 
 /// Defines the file routes.
 ///
 /// # Parameters
-/// - `state`: The application clients.
+/// - `state`: The application state.
 ///
 /// # Returns
 /// A Router containing the file routes.
-/// Disable the default body limit for the `/upload` route to allow large file uploads.
+/// The `/upload` route accepts bodies up to `MAX_UPLOAD_SIZE_BYTES` to allow large file uploads,
+/// and is rate limited per client IP. `/upload-extract` accepts a single archive the same
+/// way, and extracts it right away.
+/// Chunked uploads are started with `/upload/init`, which is rate limited the same way, and
+/// each part accepts bodies up to `CHUNKED_UPLOAD_MAX_PART_BYTES`.
+/// Routes receiving file content are tracked as uploads, which shutdown waits for.
+/// Every upload route, `/files/{key}/rename`, `/files/copy`, `DELETE /files/{key}`,
+/// `/files/{key}/restore` and `DELETE /competitions/{name}` require an `X-Api-Key` header,
+/// read-only routes don't.
+/// Deleted files are moved aside until purged, and can be restored with `/files/{key}/restore`.
+/// Stored files are streamed back by `/files/{key}`, also served as `/download/{key}`, and
+/// their metadata is served without their content by `/files/{key}/metadata`.
+/// The versions of the archive of a competition are listed by `/competitions/{name}/uploads`.
 ///
-pub fn file_routes(state: Arc<Clients>) -> Router {
+pub fn file_routes(state: Arc<AppState>) -> Router {
+    let max_upload_size = state.get_config().max_upload_size_bytes;
+    let max_part_size = state.get_config().chunked_upload_max_part_bytes;
+
     Router::new()
         .route("/upload", post(upload_handler)
-            .layer(DefaultBodyLimit::disable())
+            .layer(DefaultBodyLimit::max(max_upload_size))
+            .layer(from_fn_with_state(state.clone(), upload_rate_limit_middleware))
+            .layer(from_fn_with_state(state.clone(), track_upload_middleware))
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/upload-extract", post(upload_extract_handler)
+            .layer(DefaultBodyLimit::max(max_upload_size))
+            .layer(from_fn_with_state(state.clone(), upload_rate_limit_middleware))
+            .layer(from_fn_with_state(state.clone(), track_upload_middleware))
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/upload/init", post(init_chunked_upload_handler)
+            .layer(from_fn_with_state(state.clone(), upload_rate_limit_middleware))
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/upload/{id}/part/{part_number}", put(upload_part_handler)
+            .layer(DefaultBodyLimit::max(max_part_size))
+            .layer(from_fn_with_state(state.clone(), track_upload_middleware))
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/upload/{id}/complete", post(complete_chunked_upload_handler)
+            .layer(from_fn_with_state(state.clone(), track_upload_middleware))
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/uploads/{id}", get(get_upload_handler)
+            .with_state(state.clone()))
+        .route("/files/{key}", get(download_file_handler)
+            .with_state(state.clone()))
+        .route("/files/{key}", delete(delete_file_handler)
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/files/{key}/restore", post(restore_file_handler)
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/download/{key}", get(download_file_handler)
+            .with_state(state.clone()))
+        .route("/files/{key}/checksum", get(file_checksum_handler)
+            .with_state(state.clone()))
+        .route("/files/{key}/metadata", get(file_metadata_handler)
+            .with_state(state.clone()))
+        .route("/files/{key}/rename", post(rename_file_handler)
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/files/copy", post(copy_file_handler)
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/archives/{key}/inspect", get(inspect_archive_handler)
             .with_state(state.clone()))
         .route("/view-codebase/{name}", get(view_codebase_handler)
             .with_state(state.clone()))
+        .route("/view-codebase/{name}/file/{*path}", get(view_codebase_file_handler)
+            .with_state(state.clone()))
+        .route("/competitions/{name}/file", get(competition_file_handler)
+            .with_state(state.clone()))
+        .route("/competitions/{name}", delete(delete_competition_handler)
+            .layer(from_fn_with_state(state.clone(), api_key_auth_middleware))
+            .with_state(state.clone()))
+        .route("/competitions/{name}/uploads", get(upload_history_handler)
+            .with_state(state.clone()))
         .route("/generate-codebase-json/{name}", get(generate_codebase_json)
             .with_state(state))
 }
\ No newline at end of file
@@ -1,19 +1,31 @@
+use std::sync::Arc;
 use axum::{Router, routing::get};
-use crate::controllers::health_controller::{health_check_handler, s3_health_check_handler, postgres_health_check_handler, redis_health_check_handler};
+use crate::app_state::AppState;
+use crate::controllers::health_controller::{health_check_handler, s3_health_check_handler, postgres_health_check_handler, redis_health_check_handler, liveness_handler, readiness_handler};
 
 /// Returns a router with all health check endpoints
 ///
+/// # Parameters
+/// - `state`: The application state.
+///
 /// # Returns
 /// A Router containing the following endpoints:
 /// - GET /health - Checks all services
 /// - GET /health/s3 - Checks S3 only
 /// - GET /health/postgres - Checks PostgreSQL only
 /// - GET /health/redis - Checks Redis only
+/// - GET /health/live - Liveness probe, without external calls
+/// - GET /health/ready - Readiness probe, checking the required services
+/// - GET /readyz - Alias of /health/ready
 ///
-pub fn health_routes() -> Router {
+pub fn health_routes(state: Arc<AppState>) -> Router {
     Router::new()
         .route("/health", get(health_check_handler))
         .route("/health/s3", get(s3_health_check_handler))
         .route("/health/postgres", get(postgres_health_check_handler))
         .route("/health/redis", get(redis_health_check_handler))
+        .route("/health/live", get(liveness_handler))
+        .route("/health/ready", get(readiness_handler))
+        .route("/readyz", get(readiness_handler))
+        .with_state(state)
 }
@@ -0,0 +1,19 @@
+use std::sync::Arc;
+use axum::{Router, routing::get};
+use crate::app_state::AppState;
+use crate::controllers::metrics_controller::metrics_handler;
+
+/// Returns a router with the metrics endpoint, merged only when `METRICS_ENABLED` is set.
+///
+/// # Parameters
+/// - `state`: The application state.
+///
+/// # Returns
+/// A Router containing the following endpoints:
+/// - GET /metrics - Renders the application metrics in the Prometheus text format
+///
+pub fn metrics_routes(state: Arc<AppState>) -> Router {
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(state)
+}
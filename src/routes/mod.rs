@@ -1,2 +1,57 @@
 pub mod health_routes;
-pub mod file_routes;
\ No newline at end of file
+pub mod file_routes;
+pub mod admin_routes;
+pub mod metrics_routes;
+pub mod docs_routes;
+
+use std::sync::Arc;
+use axum::middleware::{from_fn, from_fn_with_state};
+use axum::Router;
+use crate::app_state::AppState;
+use crate::error::AppError;
+use crate::middleware::compression::compression_layer;
+use crate::middleware::cors::cors_layer;
+use crate::middleware::metrics::metrics_middleware;
+use crate::middleware::readiness::readiness_middleware;
+use crate::middleware::request_id::request_id_middleware;
+use admin_routes::admin_routes;
+use docs_routes::docs_routes;
+use file_routes::file_routes;
+use health_routes::health_routes;
+use metrics_routes::metrics_routes;
+
+/// Defines the routes of the application, wrapped in the middleware shared by every route.
+///
+/// # Parameters
+/// - `state`: The application state.
+///
+/// # Returns
+/// - `Ok(Router)`: The router serving the application.
+/// - `Err(AppError)`: If the CORS settings are invalid.
+///
+/// The metrics routes and middleware are only added when `METRICS_ENABLED` is set, and
+/// responses are compressed when `RESPONSE_COMPRESSION` is set.
+pub fn app_routes(state: Arc<AppState>) -> Result<Router, AppError> {
+    let config = state.get_config();
+    let cors = cors_layer(config)?;
+
+    let mut app = Router::new()
+        .merge(file_routes(state.clone()))
+        .merge(health_routes(state.clone()))
+        .merge(admin_routes(state.clone()))
+        .merge(docs_routes());
+    if config.metrics_enabled {
+        app = app.merge(metrics_routes(state.clone()));
+    }
+    app = app.layer(from_fn_with_state(state.clone(), readiness_middleware));
+    if config.metrics_enabled {
+        app = app.layer(from_fn(metrics_middleware));
+    }
+    if config.response_compression {
+        app = app.layer(compression_layer(config));
+    }
+
+    Ok(app
+        .layer(cors)
+        .layer(from_fn(request_id_middleware)))
+}
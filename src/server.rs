@@ -0,0 +1,383 @@
+//! HTTP server setup for the Rustler application.
+//!
+//! `axum::serve` does not expose hyper's connection settings, so connections are accepted
+//! here and served with hyper directly, dropping idle or slow clients after a timeout.
+//!
+//! To check the timeout by hand, open a connection without sending anything
+//! (e.g. `nc localhost 3000`): the server closes it after `HTTP_IDLE_TIMEOUT_SECS`.
+//!
+//! On shutdown, the listener is closed and connections are asked to finish their current
+//! request. To check the upload grace period by hand, start a large upload with a slow
+//! client (e.g. `curl --limit-rate 1M -F file=@big.zip localhost:3000/upload`) and send
+//! SIGTERM: the upload still completes if it finishes within `UPLOAD_DRAIN_TIMEOUT_SECS`,
+//! while other requests are cut off after `SHUTDOWN_DRAIN_TIMEOUT_SECS`.
+
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::Router;
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::server::graceful::GracefulShutdown;
+use hyper_util::service::TowerToHyperService;
+use log::{debug, error, info, warn};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, timeout, Sleep};
+use tower::Service;
+use crate::middleware::upload_tracker::ActiveUploads;
+
+/// The connection and shutdown settings of the server.
+///
+/// # Fields
+/// - `idle_timeout`: The idle and header-read timeout for each connection.
+/// - `drain_timeout`: How long in-flight requests may run once shutdown starts.
+/// - `upload_drain_timeout`: How long in-flight uploads may run once shutdown starts.
+/// - `active_uploads`: The tracker of the upload requests in progress.
+///
+pub struct ServerOptions {
+    pub idle_timeout: Duration,
+    pub drain_timeout: Duration,
+    pub upload_drain_timeout: Duration,
+    pub active_uploads: ActiveUploads,
+}
+
+/// A connection closed when the client sends nothing for a timeout after connecting or after
+/// data was written to it.
+///
+/// hyper's header-read timeout only starts once the HTTP version of the connection is known,
+/// which takes its first bytes, and is not restarted between HTTP/1 keep-alive requests, so a
+/// client connecting without sending anything, or going quiet after a response, would
+/// otherwise hold the connection forever.
+struct IdleTimeout {
+    stream: TcpStream,
+    timeout: Duration,
+    deadline: Option<Pin<Box<Sleep>>>,
+    written: bool,
+}
+
+impl IdleTimeout {
+    fn new(stream: TcpStream, timeout: Duration) -> Self {
+        Self { stream, timeout, deadline: Some(Box::pin(sleep(timeout))), written: false }
+    }
+}
+
+impl AsyncRead for IdleTimeout {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let filled = buf.filled().len();
+        let polled = Pin::new(&mut this.stream).poll_read(cx, buf);
+
+        if let Some(deadline) = &mut this.deadline {
+            match polled {
+                Poll::Ready(Ok(())) if buf.filled().len() > filled => this.deadline = None,
+                Poll::Pending if deadline.as_mut().poll(cx).is_ready() => {
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, "no request received")));
+                }
+                _ => {}
+            }
+        }
+
+        polled
+    }
+}
+
+impl AsyncWrite for IdleTimeout {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let written = Pin::new(&mut self.stream).poll_write(cx, buf);
+        self.written |= matches!(written, Poll::Ready(Ok(n)) if n > 0);
+        written
+    }
+
+    fn poll_write_vectored(mut self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[io::IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let written = Pin::new(&mut self.stream).poll_write_vectored(cx, bufs);
+        self.written |= matches!(written, Poll::Ready(Ok(n)) if n > 0);
+        written
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = &mut *self;
+        let flushed = Pin::new(&mut this.stream).poll_flush(cx);
+        if flushed.is_ready() && std::mem::take(&mut this.written) {
+            let mut deadline = Box::pin(sleep(this.timeout));
+            let _ = deadline.as_mut().poll(cx);
+            this.deadline = Some(deadline);
+        }
+        flushed
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+/// Serves the application on the provided listener until `shutdown` resolves and the
+/// in-flight requests are drained.
+///
+/// Each connection must send its first bytes, then its request headers, within `idle_timeout`,
+/// which also bounds how long an HTTP/1 keep-alive connection may sit idle between requests.
+/// HTTP/2 connections are pinged at the same interval and closed when a ping is not
+/// acknowledged in time.
+///
+/// # Parameters
+/// - `listener`: The bound TCP listener.
+/// - `app`: The application router.
+/// - `options`: The connection and shutdown settings.
+/// - `shutdown`: Resolves when the server should shut down.
+///
+pub async fn serve(
+    listener: TcpListener,
+    app: Router,
+    options: ServerOptions,
+    shutdown: impl Future<Output = ()>,
+) {
+    let idle_timeout = options.idle_timeout;
+    let mut make_service: IntoMakeServiceWithConnectInfo<Router, SocketAddr> =
+        app.into_make_service_with_connect_info::<SocketAddr>();
+
+    let mut builder = Builder::new(TokioExecutor::new());
+    builder
+        .http1()
+        .timer(TokioTimer::new())
+        .header_read_timeout(idle_timeout);
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(idle_timeout)
+        .keep_alive_timeout(idle_timeout);
+
+    let graceful = GracefulShutdown::new();
+    tokio::pin!(shutdown);
+
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            _ = &mut shutdown => break,
+        };
+
+        let (stream, remote_addr) = match accepted {
+            Ok(connection) => connection,
+            Err(e) => {
+                error!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+
+        let service = match make_service.call(remote_addr).await {
+            Ok(service) => service,
+            Err(e) => match e {},
+        };
+
+        let service = TowerToHyperService::new(service);
+        let connection = builder
+            .serve_connection_with_upgrades(TokioIo::new(IdleTimeout::new(stream, idle_timeout)), service)
+            .into_owned();
+        let connection = graceful.watch(connection);
+
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                debug!("Connection from {} closed: {}", remote_addr, e);
+            }
+        });
+    }
+
+    drop(listener);
+    drain(graceful, &options).await;
+}
+
+/// Waits for the in-flight requests to finish after the listener was closed.
+///
+/// Every connection is asked to close once its current request is answered. Requests still
+/// running after `drain_timeout` are aborted, unless uploads are in progress, which are
+/// given up to `upload_drain_timeout` in total to finish.
+///
+/// # Parameters
+/// - `graceful`: The shutdown handle watching every connection.
+/// - `options`: The shutdown settings.
+///
+async fn drain(graceful: GracefulShutdown, options: &ServerOptions) {
+    info!("Shutting down, waiting up to {:?} for in-flight requests", options.drain_timeout);
+
+    let drained = graceful.shutdown();
+    tokio::pin!(drained);
+
+    if timeout(options.drain_timeout, &mut drained).await.is_ok() {
+        info!("All connections closed");
+        return;
+    }
+
+    let upload_grace = options.upload_drain_timeout.saturating_sub(options.drain_timeout);
+    let uploads = options.active_uploads.count();
+    if uploads > 0 && !upload_grace.is_zero() {
+        info!("Waiting up to {:?} more for {} uploads in progress", upload_grace, uploads);
+        let _ = timeout(upload_grace, async {
+            tokio::select! {
+                _ = &mut drained => {},
+                _ = options.active_uploads.wait_idle() => {},
+            }
+        }).await;
+    }
+
+    match options.active_uploads.count() {
+        0 => info!("Closing the remaining connections"),
+        remaining => warn!("Aborting {} uploads still in progress after the shutdown grace period", remaining),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use axum::routing::{get, post};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio::sync::oneshot;
+
+    const IDLE_TIMEOUT: Duration = Duration::from_millis(200);
+
+    /// Serves a router answering `GET /` until the returned sender is dropped or fired.
+    async fn spawn_server(app: Router, options: ServerOptions) -> (SocketAddr, oneshot::Sender<()>, tokio::task::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (shutdown, stop) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(listener, app, options, async { let _ = stop.await; }));
+        (addr, shutdown, server)
+    }
+
+    fn options() -> ServerOptions {
+        ServerOptions {
+            idle_timeout: IDLE_TIMEOUT,
+            drain_timeout: Duration::from_secs(5),
+            upload_drain_timeout: Duration::from_secs(5),
+            active_uploads: ActiveUploads::new(),
+        }
+    }
+
+    fn router() -> Router {
+        Router::new().route("/", get(|| async { "ok" }))
+    }
+
+    /// Reads from a connection until the server closes it, returning what was read.
+    async fn read_until_closed(stream: &mut TcpStream) -> Vec<u8> {
+        let mut received = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), stream.read_to_end(&mut received))
+            .await
+            .expect("the connection was not closed")
+            .unwrap();
+        received
+    }
+
+    #[tokio::test]
+    async fn idle_connection_is_closed_after_the_timeout() {
+        let (addr, _shutdown, _server) = spawn_server(router(), options()).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let started = Instant::now();
+
+        read_until_closed(&mut stream).await;
+        assert!(started.elapsed() >= IDLE_TIMEOUT);
+    }
+
+    #[tokio::test]
+    async fn connection_sending_partial_headers_is_closed_after_the_timeout() {
+        let (addr, _shutdown, _server) = spawn_server(router(), options()).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n").await.unwrap();
+        let started = Instant::now();
+
+        let received = read_until_closed(&mut stream).await;
+        assert!(started.elapsed() >= IDLE_TIMEOUT);
+        assert!(!String::from_utf8_lossy(&received).contains("200 OK"));
+    }
+
+    #[tokio::test]
+    async fn keep_alive_connection_is_closed_once_idle() {
+        let (addr, _shutdown, _server) = spawn_server(router(), options()).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await.unwrap();
+
+        let received = read_until_closed(&mut stream).await;
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn response_slower_than_the_timeout_is_not_cut_off() {
+        let app = Router::new().route("/", get(|| async {
+            sleep(IDLE_TIMEOUT * 3).await;
+            "ok"
+        }));
+        let (addr, _shutdown, _server) = spawn_server(app, options()).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let received = read_until_closed(&mut stream).await;
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    #[tokio::test]
+    async fn request_sent_in_time_is_answered() {
+        let (addr, _shutdown, _server) = spawn_server(router(), options()).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").await.unwrap();
+
+        let received = read_until_closed(&mut stream).await;
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 200 OK"));
+    }
+
+    /// Serves `POST /upload`, counted as an upload in progress for `duration`, and fires the
+    /// shutdown once the upload has started.
+    async fn upload_during_shutdown(duration: Duration, options: ServerOptions) -> (TcpStream, tokio::task::JoinHandle<()>) {
+        let uploads = options.active_uploads.clone();
+        let app = Router::new().route("/upload", post(move || async move {
+            let _guard = uploads.start();
+            sleep(duration).await;
+            "stored"
+        }));
+        let active_uploads = options.active_uploads.clone();
+        let (addr, shutdown, server) = spawn_server(app, options).await;
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\n\r\n").await.unwrap();
+        while active_uploads.count() == 0 {
+            sleep(Duration::from_millis(10)).await;
+        }
+        shutdown.send(()).unwrap();
+        (stream, server)
+    }
+
+    fn shutdown_options() -> ServerOptions {
+        ServerOptions {
+            drain_timeout: Duration::from_millis(100),
+            upload_drain_timeout: Duration::from_millis(600),
+            ..options()
+        }
+    }
+
+    #[tokio::test]
+    async fn upload_finishing_within_the_grace_period_completes_during_shutdown() {
+        let (mut stream, server) = upload_during_shutdown(Duration::from_millis(300), shutdown_options()).await;
+
+        let received = read_until_closed(&mut stream).await;
+        assert!(String::from_utf8_lossy(&received).starts_with("HTTP/1.1 200 OK"));
+        assert!(String::from_utf8_lossy(&received).ends_with("stored"));
+        tokio::time::timeout(Duration::from_secs(1), server).await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn upload_outlasting_the_grace_period_is_given_up_on() {
+        let options = shutdown_options();
+        let active_uploads = options.active_uploads.clone();
+        let started = Instant::now();
+        let (_stream, server) = upload_during_shutdown(Duration::from_secs(10), options).await;
+
+        tokio::time::timeout(Duration::from_secs(2), server).await.unwrap().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(600));
+        assert_eq!(active_uploads.count(), 1);
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::{error, info, warn};
+use redis::AsyncCommands;
+use serde_json::json;
+use uuid::Uuid;
+use crate::app_state::AppState;
+use crate::clients::clients::Clients;
+use crate::config::AppConfig;
+use crate::clients::storage::copy_hashed;
+use crate::error::AppError;
+use crate::services::file_service::{FileService, StoredObject};
+use crate::utils::file_utils::{FileContent, FileValidationError, FileValidator, RejectionReason, SpooledFile, ValidatedFile};
+
+/// The sorted set holding the id of every in-progress upload, scored by its expiry time.
+const DEADLINES_KEY: &str = "chunked_uploads:deadlines";
+
+/// How long an expired session is kept after its deadline, so the expiry task can still
+/// find its multipart upload to abort it.
+const SESSION_GRACE_SECS: u64 = 3600;
+
+/// How often expired uploads are looked for.
+const EXPIRY_INTERVAL_SECS: u64 = 60;
+
+/// The minimum size of every part but the last, as required by S3.
+const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// The highest part number accepted by S3.
+const MAX_PART_NUMBER: i32 = 10_000;
+
+/// The prefix uploads are assembled under with `CONTENT_ADDRESSED_STORAGE` or
+/// `DEDUPLICATE_UPLOADS`, until they are stored like a regular upload once completed.
+const STAGING_PREFIX: &str = "chunked/";
+
+/// An in-progress chunked upload, as stored in Redis.
+///
+/// # Fields
+/// - `file_name`: The name of the file.
+/// - `content_type`: The declared content type of the file.
+/// - `s3_key`: The key the parts are assembled under, the file name unless the upload is staged.
+/// - `s3_upload_id`: The id of the storage multipart upload backing this upload.
+/// - `file_type`: The file type resolved from the first part, once it was uploaded.
+/// - `parts`: The size and ETag of every uploaded part, by part number.
+///
+struct ChunkedUpload {
+    file_name: String,
+    content_type: String,
+    s3_key: String,
+    s3_upload_id: String,
+    file_type: Option<String>,
+    parts: BTreeMap<i32, (u64, String)>,
+}
+
+impl ChunkedUpload {
+    /// Parses an upload from the fields of its Redis hash.
+    /// Returns `None` if a required field is missing.
+    fn from_fields(mut fields: HashMap<String, String>) -> Option<Self> {
+        let parts = fields
+            .iter()
+            .filter_map(|(field, value)| {
+                let part_number = field.strip_prefix("part:")?.parse().ok()?;
+                let (size, etag) = value.split_once(':')?;
+                Some((part_number, (size.parse().ok()?, etag.to_string())))
+            })
+            .collect();
+
+        let file_name = fields.remove("file_name")?;
+        Some(Self {
+            s3_key: fields.remove("s3_key").unwrap_or_else(|| file_name.clone()),
+            file_name,
+            content_type: fields.remove("content_type").unwrap_or_default(),
+            s3_upload_id: fields.remove("s3_upload_id")?,
+            file_type: fields.remove("file_type"),
+            parts,
+        })
+    }
+
+    /// Returns the combined size of the uploaded parts, excluding one part number.
+    fn size_without(&self, part_number: i32) -> u64 {
+        self.parts
+            .iter()
+            .filter(|(number, _)| **number != part_number)
+            .map(|(_, (size, _))| size)
+            .sum()
+    }
+}
+
+/// A service handling resumable uploads sent in parts.
+///
+/// Each upload maps onto a multipart upload of the storage backend, and its state is kept in Redis under
+/// `chunked_upload:{id}` until it is completed or expires after `CHUNKED_UPLOAD_TTL_SECS`
+/// without activity. Expired uploads are aborted by `run_expiry_task`.
+///
+/// With `CONTENT_ADDRESSED_STORAGE` or `DEDUPLICATE_UPLOADS`, the key of an upload depends on
+/// its content, so it is assembled under `chunked/{id}` and then stored by the file service
+/// like a regular upload once completed.
+pub struct ChunkedUploadService {
+    clients: Arc<Clients>,
+    config: Arc<AppConfig>,
+    validator: Arc<FileValidator>,
+    file_service: Arc<FileService>,
+}
+
+impl ChunkedUploadService {
+    /// Creates a new instance of `ChunkedUploadService`.
+    pub fn new(
+        clients: Arc<Clients>,
+        config: Arc<AppConfig>,
+        validator: Arc<FileValidator>,
+        file_service: Arc<FileService>,
+    ) -> Self {
+        Self { clients, config, validator, file_service }
+    }
+
+    /// Starts a chunked upload.
+    ///
+    /// # Parameters
+    /// - `file_name`: The name of the file to upload.
+    /// - `content_type`: The declared content type of the file.
+    ///
+    /// # Returns
+    /// A 201 with the upload id, or an error response.
+    pub async fn init(&self, file_name: String, content_type: String) -> Response {
+        if file_name.trim().is_empty() {
+            return self.error_response(StatusCode::BAD_REQUEST, "No filename provided");
+        }
+
+        let id = Uuid::new_v4();
+        let s3_key = if self.config.content_addressed_storage || self.config.deduplicate_uploads {
+            format!("{}{}", STAGING_PREFIX, id)
+        } else {
+            file_name.clone()
+        };
+
+        let s3_upload_id = match self.clients.get_storage().create_multipart_upload(&s3_key).await {
+            Ok(s3_upload_id) => s3_upload_id,
+            Err(e) => {
+                error!("Failed to start multipart upload for '{}': {:?}", file_name, e);
+                return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to start upload");
+            }
+        };
+
+        let fields = [
+            ("file_name", file_name.as_str()),
+            ("content_type", content_type.as_str()),
+            ("s3_key", s3_key.as_str()),
+            ("s3_upload_id", s3_upload_id.as_str()),
+        ];
+
+        if let Err(e) = self.save_fields(id, &fields).await {
+            error!("Failed to store upload {} of '{}': {}", id, file_name, e);
+            self.abort_s3_upload(&s3_key, &s3_upload_id).await;
+            return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to start upload");
+        }
+
+        info!("Started chunked upload {} of '{}'", id, file_name);
+        (StatusCode::CREATED, Json(json!({
+            "upload_id": id,
+            "file_name": file_name,
+            "expires_in": self.config.chunked_upload_ttl_secs,
+        }))).into_response()
+    }
+
+    /// Uploads a part of a chunked upload. Parts can be sent in any order, and sending a
+    /// part again replaces it, so a dropped part can simply be retried.
+    ///
+    /// The first part is validated like a regular upload, resolving the file type from the
+    /// file name and magic number, and the combined size of the parts is checked against
+    /// the maximum size of that type. The checks needing the whole content run once the
+    /// upload is completed.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the upload.
+    /// - `part_number`: The number of the part, from 1 to 10000.
+    /// - `data`: The content of the part.
+    ///
+    /// # Returns
+    /// The size and ETag of the stored part, or an error response.
+    pub async fn put_part(&self, id: Uuid, part_number: i32, data: Bytes) -> Response {
+        if !(1..=MAX_PART_NUMBER).contains(&part_number) {
+            return self.error_response(
+                StatusCode::BAD_REQUEST,
+                &format!("Part number must be between 1 and {}", MAX_PART_NUMBER),
+            );
+        }
+
+        let upload = match self.load(id).await {
+            Ok(Some(upload)) => upload,
+            Ok(None) => return self.not_found(id),
+            Err(e) => {
+                error!("Failed to load upload {}: {}", id, e);
+                return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load upload");
+            }
+        };
+
+        let validator = &self.validator;
+        let file_type = if part_number == 1 {
+            match validator.validate_head(&upload.file_name, &upload.content_type, &data) {
+                Ok(file_type) => Some(file_type),
+                Err(validation_error) => {
+                    warn!("File validation failed for upload {}: {}", id, validation_error.message);
+                    validator.record_rejected(&validation_error);
+                    return self.error_response(validation_error.code, &validation_error.message);
+                }
+            }
+        } else {
+            upload.file_type.as_deref().and_then(|name| validator.get_file_type(name))
+        };
+
+        if let Some(file_type) = file_type {
+            if upload.size_without(part_number) + data.len() as u64 > file_type.max_size as u64 {
+                let validation_error = FileValidationError::new(
+                    RejectionReason::TooLarge,
+                    format!("File exceeds maximum allowed size of {} bytes", file_type.max_size),
+                ).for_type(file_type);
+                validator.record_rejected(&validation_error);
+                return self.error_response(validation_error.code, &validation_error.message);
+            }
+
+            if part_number == 1 {
+                validator.record_accepted(&file_type.name);
+            }
+        }
+
+        let size = data.len() as u64;
+        let etag = match self.clients
+            .get_storage()
+            .upload_part(&upload.s3_key, &upload.s3_upload_id, part_number, data.to_vec())
+            .await
+        {
+            Ok(etag) => etag,
+            Err(e) => {
+                error!("Failed to upload part {} of upload {}: {:?}", part_number, id, e);
+                return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to upload part");
+            }
+        };
+
+        let part_field = format!("part:{}", part_number);
+        let part_value = format!("{}:{}", size, etag);
+        let mut fields = vec![(part_field.as_str(), part_value.as_str())];
+        if part_number == 1 {
+            if let Some(file_type) = file_type {
+                fields.push(("file_type", file_type.name.as_str()));
+            }
+        }
+
+        if let Err(e) = self.save_fields(id, &fields).await {
+            error!("Failed to record part {} of upload {}: {}", part_number, id, e);
+            return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to record part");
+        }
+
+        (StatusCode::OK, Json(json!({
+            "upload_id": id,
+            "part_number": part_number,
+            "size": size,
+            "etag": etag,
+        }))).into_response()
+    }
+
+    /// Completes a chunked upload, assembling its parts into the final stored object.
+    ///
+    /// The parts must be numbered contiguously from 1, and the completed object is read
+    /// back into a temporary file to compute its SHA-256 digest and check its size before it
+    /// is recorded. The checks registered with `FileValidator::register_check`, e.g. the
+    /// archive inspection, then run on the whole content, and an oversized or rejected object
+    /// is deleted again. A staged upload is then stored by `FileService::put_file`, so it is
+    /// deduplicated and content-addressed like a regular upload, and its staged object deleted.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the upload.
+    /// - `uploaded_by`: The identity of the API key the upload is completed with.
+    ///
+    /// # Returns
+    /// The details of the uploaded file, or an error response.
+    pub async fn complete(&self, id: Uuid, uploaded_by: &str) -> Response {
+        let upload = match self.load(id).await {
+            Ok(Some(upload)) => upload,
+            Ok(None) => return self.not_found(id),
+            Err(e) => {
+                error!("Failed to load upload {}: {}", id, e);
+                return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to load upload");
+            }
+        };
+
+        let last_part = upload.parts.keys().next_back().copied().unwrap_or(0);
+        let missing: Vec<i32> = (1..=last_part).filter(|n| !upload.parts.contains_key(n)).collect();
+        if upload.parts.is_empty() || !missing.is_empty() {
+            return (StatusCode::BAD_REQUEST, Json(json!({
+                "error": "Upload is missing parts",
+                "missing_parts": missing,
+            }))).into_response();
+        }
+
+        let validator = &self.validator;
+        let Some(file_type) = upload.file_type.as_deref().and_then(|name| validator.get_file_type(name)) else {
+            return self.error_response(StatusCode::BAD_REQUEST, "The first part was not validated");
+        };
+
+        let too_small: Vec<i32> = upload.parts
+            .iter()
+            .filter(|(number, (size, _))| **number != last_part && *size < MIN_PART_SIZE)
+            .map(|(number, _)| *number)
+            .collect();
+        if !too_small.is_empty() {
+            return (StatusCode::BAD_REQUEST, Json(json!({
+                "error": format!("Every part but the last must be at least {} bytes", MIN_PART_SIZE),
+                "parts": too_small,
+            }))).into_response();
+        }
+
+        let storage = self.clients.get_storage();
+        let parts = upload.parts
+            .iter()
+            .map(|(number, (_, etag))| (*number, etag.clone()))
+            .collect();
+        if let Err(e) = storage.complete_multipart_upload(&upload.s3_key, &upload.s3_upload_id, parts).await {
+            error!("Failed to complete upload {} of '{}': {:?}", id, upload.file_name, e);
+            return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to complete upload");
+        }
+
+        // The session is over once the storage assembled the object
+        if let Err(e) = self.remove(id).await {
+            warn!("Failed to remove completed upload {}: {}", id, e);
+        }
+
+        let staged = upload.s3_key != upload.file_name;
+        let (spooled, sha256, size) = match self.spool(&upload.s3_key).await {
+            Ok(spooled) => spooled,
+            Err(e) => {
+                error!("Failed to read back '{}' of upload {}: {:?}", upload.s3_key, id, e);
+                if staged {
+                    self.delete_object(&upload.s3_key).await;
+                }
+                return self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to verify uploaded file");
+            }
+        };
+
+        if size > file_type.max_size as u64 {
+            warn!("Completed upload {} of '{}' exceeds {} bytes", id, upload.file_name, file_type.max_size);
+            self.delete_object(&upload.s3_key).await;
+            return self.error_response(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                &format!("File exceeds maximum allowed size of {} bytes", file_type.max_size),
+            );
+        }
+
+        // The checks need the whole content, so they only run once the parts are assembled
+        let content = FileContent::Spooled(spooled);
+        if let Err(validation_error) = validator
+            .check_content(&upload.file_name, &upload.content_type, file_type, &content)
+            .await
+        {
+            warn!("File validation failed for upload {}: {}", id, validation_error.message);
+            validator.record_rejected(&validation_error);
+            self.delete_object(&upload.s3_key).await;
+            return self.error_response(validation_error.code, &validation_error.message);
+        }
+
+        info!("Completed chunked upload {} of '{}'", id, upload.file_name);
+        let file = ValidatedFile {
+            content,
+            size: size as usize,
+            sha256,
+            file_type: file_type.name.clone(),
+            sniffed_mime_type: None,
+        };
+
+        let object = if staged {
+            let object = self.file_service.put_file(&upload.file_name, &upload.content_type, &file, None).await;
+            self.delete_object(&upload.s3_key).await;
+            match object {
+                Ok(object) => object,
+                Err((status, failure)) => return (status, Json(failure)).into_response(),
+            }
+        } else {
+            StoredObject::Put { key: upload.s3_key, already_stored: false }
+        };
+
+        let (status, result) = self.file_service
+            .record_file(upload.file_name, upload.content_type, &file, object, uploaded_by)
+            .await;
+        (status, Json(result)).into_response()
+    }
+
+    /// Aborts every upload whose deadline has passed, discarding their stored parts.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of aborted uploads.
+    /// - `Err(AppError)`: If the expired uploads can't be listed.
+    pub async fn abort_expired(&self) -> Result<usize, AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let mut con = redis_client.get_connection().await?;
+
+        let expired: Vec<String> = con
+            .zrangebyscore(redis_client.key(DEADLINES_KEY), "-inf", unix_now())
+            .await?;
+
+        for member in &expired {
+            if let Ok(id) = Uuid::parse_str(member) {
+                match self.load(id).await {
+                    Ok(Some(upload)) => {
+                        info!("Aborting expired upload {} of '{}'", id, upload.file_name);
+                        self.abort_s3_upload(&upload.s3_key, &upload.s3_upload_id).await;
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!("Failed to load expired upload {}: {}", id, e);
+                        continue;
+                    }
+                }
+
+                if let Err(e) = self.remove(id).await {
+                    warn!("Failed to remove expired upload {}: {}", id, e);
+                }
+            } else {
+                let _: () = con.zrem(redis_client.key(DEADLINES_KEY), member).await?;
+            }
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Loads an upload from Redis.
+    async fn load(&self, id: Uuid) -> Result<Option<ChunkedUpload>, AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let mut con = redis_client.get_connection().await?;
+
+        let fields: HashMap<String, String> = con.hgetall(self.session_key(id)).await?;
+        Ok(ChunkedUpload::from_fields(fields))
+    }
+
+    /// Stores fields of an upload in Redis and pushes its deadline back.
+    async fn save_fields(&self, id: Uuid, fields: &[(&str, &str)]) -> Result<(), AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let mut con = redis_client.get_connection().await?;
+        let ttl = self.config.chunked_upload_ttl_secs;
+        let session_key = self.session_key(id);
+
+        let _: () = con.hset_multiple(&session_key, fields).await?;
+        let _: () = con.expire(&session_key, (ttl + SESSION_GRACE_SECS) as i64).await?;
+        let _: () = con.zadd(redis_client.key(DEADLINES_KEY), id.to_string(), unix_now() + ttl).await?;
+
+        Ok(())
+    }
+
+    /// Removes an upload from Redis.
+    async fn remove(&self, id: Uuid) -> Result<(), AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let mut con = redis_client.get_connection().await?;
+
+        let _: () = con.del(self.session_key(id)).await?;
+        let _: () = con.zrem(redis_client.key(DEADLINES_KEY), id.to_string()).await?;
+
+        Ok(())
+    }
+
+    /// Downloads a completed upload into a temporary file.
+    ///
+    /// # Returns
+    /// - `Ok((SpooledFile, String, u64))`: The temporary file, and the hex-encoded SHA-256
+    ///   digest and size of its content.
+    /// - `Err(AppError)`: If the object can't be read or the temporary file written.
+    async fn spool(&self, key: &str) -> Result<(SpooledFile, String, u64), AppError> {
+        let (spooled, _) = SpooledFile::create("rustler-chunked").await?;
+        let object = self.clients.get_storage().open(key, None).await?;
+        let (sha256, size) = copy_hashed(object.body, spooled.path()).await?;
+        Ok((spooled, sha256, size))
+    }
+
+    /// Deletes an object of the storage backend, logging failures.
+    async fn delete_object(&self, key: &str) {
+        if let Err(e) = self.clients.get_storage().delete(key).await {
+            error!("Failed to delete '{}': {:?}", key, e);
+        }
+    }
+
+    /// Aborts a multipart upload of the storage backend, logging failures.
+    async fn abort_s3_upload(&self, key: &str, s3_upload_id: &str) {
+        if let Err(e) = self.clients.get_storage().abort_multipart_upload(key, s3_upload_id).await {
+            warn!("Failed to abort multipart upload of '{}': {:?}", key, e);
+        }
+    }
+
+    /// Returns the Redis key of an upload.
+    fn session_key(&self, id: Uuid) -> String {
+        self.clients.get_redis_client().key(&format!("chunked_upload:{}", id))
+    }
+
+    fn not_found(&self, id: Uuid) -> Response {
+        self.error_response(StatusCode::NOT_FOUND, &format!("Upload '{}' not found or expired", id))
+    }
+
+    fn error_response(&self, status_code: StatusCode, message: &str) -> Response {
+        (status_code, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// Periodically aborts the chunked uploads that expired without being completed.
+///
+/// Uploads abandoned while the application is down for longer than the grace period can't
+/// be found anymore, so the bucket should also have a lifecycle rule aborting incomplete
+/// multipart uploads.
+///
+/// # Parameters
+/// - `state`: The application state.
+pub async fn run_expiry_task(state: Arc<AppState>) {
+    let service = ChunkedUploadService::new(
+        state.get_clients().clone(),
+        state.get_shared_config(),
+        state.get_validator(),
+        state.get_shared_file_service(),
+    );
+    let mut interval = tokio::time::interval(Duration::from_secs(EXPIRY_INTERVAL_SECS));
+
+    loop {
+        interval.tick().await;
+        match service.abort_expired().await {
+            Ok(0) => {}
+            Ok(count) => info!("Aborted {} expired chunked uploads", count),
+            Err(e) => warn!("Failed to abort expired chunked uploads: {}", e),
+        }
+    }
+}
+
+/// Returns the current Unix time in seconds.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,323 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use log::{error, info, warn};
+use redis::AsyncCommands;
+use serde::Serialize;
+use crate::app_state::AppState;
+use crate::clients::clients::Clients;
+use crate::config::AppConfig;
+use crate::clients::redis_client::escape_glob;
+use crate::error::AppError;
+use crate::services::file_service::{deleted_key, is_valid_competition_name, EXTRACTION_MANIFEST};
+
+/// How long the lock of a competition is held at most, so a crashed holder doesn't block
+/// its extraction or cleanup forever.
+pub const COMPETITION_LOCK_TTL: Duration = Duration::from_secs(600);
+
+/// Returns the prefixed Redis key of the lock held while a competition is extracted or removed.
+///
+/// # Parameters
+/// - `clients`: The application clients.
+/// - `name`: The name of the competition.
+pub fn competition_lock_key(clients: &Clients, name: &str) -> String {
+    clients.get_redis_client().key(&format!("lock:competition:{}", name))
+}
+
+/// Records an access to an extracted competition, postponing its cleanup.
+///
+/// The access time is the modification time of the extraction manifest, so extractions
+/// without a manifest are removed once `COMPETITION_TTL_SECS` elapsed since extraction.
+///
+/// # Parameters
+/// - `output_dir`: The extraction directory of the competition.
+pub fn record_access(output_dir: &Path) {
+    let result = File::options()
+        .write(true)
+        .open(output_dir.join(EXTRACTION_MANIFEST))
+        .and_then(|manifest| manifest.set_modified(SystemTime::now()));
+
+    match result {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            warn!("Failed to record access to {:?}: {}", output_dir, e);
+        }
+        _ => {}
+    }
+}
+
+/// The outcome of a cleanup of the extracted competitions.
+///
+/// # Fields
+/// - `dry_run`: Whether nothing was actually removed.
+/// - `removed`: The competitions removed, or that would be removed in a dry run.
+/// - `skipped`: The expired competitions kept because they are being extracted or can't be removed.
+///
+#[derive(Debug, Default, Serialize)]
+pub struct CleanupReport {
+    pub dry_run: bool,
+    pub removed: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// The outcome of deleting an extracted competition on request.
+///
+/// - `Removed(files)`: The competition was removed, along with `files` files.
+/// - `NotFound`: The competition isn't extracted.
+/// - `Locked`: The competition is being extracted or removed by another request.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CompetitionDeletion {
+    Removed(usize),
+    NotFound,
+    Locked,
+}
+
+/// A service removing the extracted competitions that haven't been accessed for
+/// `COMPETITION_TTL_SECS`, along with their cached file lists and trees.
+pub struct CleanupService {
+    clients: Arc<Clients>,
+    config: Arc<AppConfig>,
+}
+
+impl CleanupService {
+    /// Creates a new instance of `CleanupService`.
+    pub fn new(clients: Arc<Clients>, config: Arc<AppConfig>) -> Self {
+        Self { clients, config }
+    }
+
+    /// Removes the expired competitions.
+    ///
+    /// A competition is only removed while holding its lock, so one being extracted is
+    /// skipped. Staging directories left behind by interrupted extractions are removed too.
+    ///
+    /// # Parameters
+    /// - `dry_run`: Whether to only report what would be removed.
+    ///
+    /// # Returns
+    /// - `Ok(CleanupReport)`: The removed and skipped competitions.
+    /// - `Err(AppError)`: If the competitions directory can't be read or Redis fails.
+    pub async fn clean(&self, dry_run: bool) -> Result<CleanupReport, AppError> {
+        let ttl = Duration::from_secs(self.config.competition_ttl_secs);
+        let mut report = CleanupReport { dry_run, ..CleanupReport::default() };
+
+        let entries = match fs::read_dir(&self.config.competitions_dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(report),
+            Err(e) => return Err(AppError::FileIoError(e)),
+        };
+
+        for entry in entries {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+            let path = entry.path();
+            if idle_time(&path) < ttl {
+                continue;
+            }
+
+            if dry_run {
+                report.removed.push(name);
+                continue;
+            }
+
+            // Staging directories are named `.{name}.partial-{uuid}`, and only left behind by
+            // extractions that were interrupted, as the others rename or remove them
+            if name.starts_with('.') {
+                match fs::remove_dir_all(&path) {
+                    Ok(()) => report.removed.push(name),
+                    Err(e) => {
+                        warn!("Failed to remove staging directory {:?}: {}", path, e);
+                        report.skipped.push(name);
+                    }
+                }
+                continue;
+            }
+
+            if self.remove_competition(&name, &path).await? {
+                report.removed.push(name);
+            } else {
+                report.skipped.push(name);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Deletes an extracted competition and its cache entries, holding its lock, whatever
+    /// its last access. The stored archive is kept, so the competition can be extracted again.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the competition.
+    ///
+    /// # Returns
+    /// - `Ok(CompetitionDeletion)`: Whether the competition was removed, and how many files it held.
+    ///   Names failing `is_valid_competition_name` are never extracted, so they are not found.
+    /// - `Err(AppError)`: If the directory can't be removed or Redis fails.
+    pub async fn delete_competition(&self, name: &str) -> Result<CompetitionDeletion, AppError> {
+        if !is_valid_competition_name(name) {
+            warn!("Refusing to delete competition '{}', whose name could escape the competitions directory", name);
+            return Ok(CompetitionDeletion::NotFound);
+        }
+
+        let path = Path::new(&self.config.competitions_dir).join(name);
+        let redis_client = self.clients.get_redis_client();
+        let lock_key = competition_lock_key(&self.clients, name);
+
+        let Some(token) = redis_client.acquire_lock(&lock_key, COMPETITION_LOCK_TTL).await? else {
+            return Ok(CompetitionDeletion::Locked);
+        };
+
+        let result = match count_files(&path) {
+            Ok(files) => async {
+                fs::remove_dir_all(&path)?;
+                self.invalidate_cache(name).await?;
+                info!("Deleted competition {} holding {} files", name, files);
+                Ok(CompetitionDeletion::Removed(files))
+            }.await,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CompetitionDeletion::NotFound),
+            Err(e) => Err(AppError::FileIoError(e)),
+        };
+
+        redis_client.release_lock(&lock_key, &token).await?;
+        result
+    }
+
+    /// Removes an extracted competition and its cache entries, holding its lock.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the competition.
+    /// - `path`: The extraction directory of the competition.
+    ///
+    /// # Returns
+    /// - `Ok(true)`: If the competition was removed.
+    /// - `Ok(false)`: If it is locked, or its directory can't be removed.
+    /// - `Err(AppError)`: If Redis fails.
+    async fn remove_competition(&self, name: &str, path: &Path) -> Result<bool, AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let lock_key = competition_lock_key(&self.clients, name);
+
+        let Some(token) = redis_client.acquire_lock(&lock_key, COMPETITION_LOCK_TTL).await? else {
+            info!("Competition {} is being extracted, skipping its cleanup", name);
+            return Ok(false);
+        };
+
+        let removed = match fs::remove_dir_all(path) {
+            Ok(()) => {
+                self.invalidate_cache(name).await?;
+                info!("Removed expired competition {}", name);
+                true
+            }
+            Err(e) => {
+                error!("Failed to remove expired competition {:?}: {}", path, e);
+                false
+            }
+        };
+
+        redis_client.release_lock(&lock_key, &token).await?;
+        Ok(removed)
+    }
+
+    /// Permanently deletes the files soft-deleted for longer than `DELETED_FILE_RETENTION_SECS`,
+    /// along with their upload records.
+    ///
+    /// A file whose object can't be deleted keeps its records, so the next cleanup retries it.
+    ///
+    /// # Returns
+    /// - `Ok(usize)`: The number of purged files.
+    /// - `Err(AppError)`: If the expired files can't be listed.
+    pub async fn purge_deleted_files(&self) -> Result<usize, AppError> {
+        let postgres_client = self.clients.get_postgres_client();
+        let retention = self.config.deleted_file_retention_secs;
+        let storage = self.clients.get_storage();
+        let mut purged = 0;
+
+        for key in postgres_client.list_expired_deletions(retention).await? {
+            let result = async {
+                storage.delete(&deleted_key(&key)).await?;
+                postgres_client.purge_deleted_uploads(&key).await
+            }.await;
+
+            match result {
+                Ok(_) => {
+                    info!("Purged soft-deleted file '{}'", key);
+                    purged += 1;
+                }
+                Err(e) => warn!("Failed to purge soft-deleted file '{}': {}", key, e),
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Deletes the cached file list and codebase trees of a competition.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the competition.
+    async fn invalidate_cache(&self, name: &str) -> Result<(), AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let mut con = redis_client.get_connection().await?;
+
+        let _: () = con.del(redis_client.key(&format!("file_cache:{}", name))).await?;
+        let pattern = escape_glob(&redis_client.key(&format!("codebase_json:{}:", name)));
+        redis_client.delete_matching(&format!("{}*", pattern)).await?;
+
+        Ok(())
+    }
+}
+
+/// Counts the files under a directory, recursively.
+///
+/// # Parameters
+/// - `path`: The directory to count the files of.
+fn count_files(path: &Path) -> io::Result<usize> {
+    let mut count = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            count += count_files(&entry.path())?;
+        } else {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Returns how long ago an extraction directory was last accessed, as recorded by
+/// `record_access`, falling back to the modification time of the directory.
+fn idle_time(path: &Path) -> Duration {
+    fs::metadata(path.join(EXTRACTION_MANIFEST))
+        .or_else(|_| fs::metadata(path))
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .unwrap_or_default()
+}
+
+/// Periodically removes the expired competitions and purges the expired soft-deleted files,
+/// every `CLEANUP_INTERVAL_SECS`.
+///
+/// # Parameters
+/// - `state`: The application state.
+pub async fn run_cleanup_task(state: Arc<AppState>) {
+    let interval_secs = state.get_config().cleanup_interval_secs;
+    let service = CleanupService::new(state.get_clients().clone(), state.get_shared_config());
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+
+    loop {
+        interval.tick().await;
+        match service.clean(false).await {
+            Ok(report) if report.removed.is_empty() => {}
+            Ok(report) => info!("Removed {} expired competitions", report.removed.len()),
+            Err(e) => warn!("Failed to clean up expired competitions: {}", e),
+        }
+        match service.purge_deleted_files().await {
+            Ok(0) => {}
+            Ok(purged) => info!("Purged {} soft-deleted files", purged),
+            Err(e) => warn!("Failed to purge soft-deleted files: {}", e),
+        }
+    }
+}
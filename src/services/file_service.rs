@@ -1,26 +1,107 @@
 use std::fs::{create_dir_all, File};
 use std::{fs, io};
-use std::io::{copy, Write};
-use std::path::Path;
-use std::process::Command;
+use std::io::{copy, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
 use axum::{
+    body::Body,
     extract::Multipart,
-    http::StatusCode,
+    http::{header, StatusCode},
     response::IntoResponse,
     Json,
 };
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
 use axum::response::Response;
 use log::{error, info, warn};
 use redis::{AsyncCommands};
+use tokio::io::AsyncWriteExt;
+use tokio_util::io::ReaderStream;
+use uuid::Uuid;
 use zip::ZipArchive;
+use flate2::read::GzDecoder;
 use crate::clients::clients::Clients;
+use crate::clients::postgres_client::{UploadMeta, UploadRecord};
+use crate::clients::redis_client::escape_glob;
+use crate::clients::storage::ByteRange;
+use crate::config::AppConfig;
 use crate::error::AppError;
-use crate::utils::file_utils::FileValidator;
+use crate::models::codebase::CodebaseTreeResponse;
+use crate::models::error::ErrorResponse;
+use crate::models::upload::{UploadFailure, UploadHistoryResponse, UploadResponse, UploadResult, UploadVersion};
+use crate::services::cleanup_service::{competition_lock_key, CleanupService, CompetitionDeletion, COMPETITION_LOCK_TTL};
+use crate::utils::file_checks::register_configured_checks;
+use crate::utils::metrics::record_upload_size;
+use crate::utils::file_utils::{attachment_filename, has_binary_extension, looks_binary, FileContent, FileValidator, SpooledFile, ValidatedFile, BINARY_SNIFF_BYTES};
+
+/// The name of the manifest written into each extraction directory.
+pub const EXTRACTION_MANIFEST: &str = ".rustler-manifest.json";
+
+/// The prefix soft-deleted files are moved under until they are restored or purged.
+pub const DELETED_PREFIX: &str = "deleted/";
+
+/// Returns the key a file is moved to while soft-deleted.
+///
+/// # Parameters
+/// - `key`: The key the file was stored under.
+pub fn deleted_key(key: &str) -> String {
+    format!("{}{}", DELETED_PREFIX, key)
+}
+
+/// The prefix uploads are stored under with `CONTENT_ADDRESSED_STORAGE`.
+pub const CONTENT_ADDRESSED_PREFIX: &str = "sha256/";
+
+/// Returns the key content is stored under with `CONTENT_ADDRESSED_STORAGE`.
+///
+/// # Parameters
+/// - `sha256`: The hex-encoded SHA-256 digest of the content.
+pub fn content_addressed_key(sha256: &str) -> String {
+    format!("{}{}", CONTENT_ADDRESSED_PREFIX, sha256)
+}
+
+/// Returns whether a name can name a competition, whose extraction directory is the
+/// competitions directory joined with it.
+///
+/// Names that are empty, start with `.`, contain `..` or a path separator are rejected, as
+/// they could resolve to the competitions directory itself or escape it.
+///
+/// # Parameters
+/// - `name`: The name of the competition.
+pub fn is_valid_competition_name(name: &str) -> bool {
+    !name.is_empty() && !name.starts_with('.') && !name.contains("..") && !name.contains(['/', '\\'])
+}
+
+/// The object holding the content of a file stored by `FileService::put_file`.
+///
+/// - `Deduplicated`: The content was already recorded, with `DEDUPLICATE_UPLOADS`, and nothing was stored.
+/// - `Put`: The content is stored under `key`, and `already_stored` tells whether it already was,
+///   with `CONTENT_ADDRESSED_STORAGE`.
+pub(crate) enum StoredObject {
+    Deduplicated(UploadRecord),
+    Put { key: String, already_stored: bool },
+}
+
+/// The manifest of an extraction directory, recording which S3 object it was extracted from.
+///
+/// # Fields
+/// - `s3_key`: The key of the extracted archive.
+/// - `etag`: The ETag of the archive when it was extracted.
+///
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ExtractionManifest {
+    s3_key: String,
+    etag: String,
+}
+
+/// The magic number starting ZIP archives.
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+
+/// The magic number starting gzip streams.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
 
 /// Supported archive file types
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum ArchiveType {
     Zip,
     TarGz,
@@ -33,41 +114,329 @@ impl ArchiveType {
             ArchiveType::TarGz => ".tar.gz",
         }
     }
+
+    /// Detects the archive type from the first bytes of an archive.
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(ZIP_MAGIC) {
+            Some(ArchiveType::Zip)
+        } else if header.starts_with(GZIP_MAGIC) {
+            Some(ArchiveType::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// Resolves the type of a downloaded archive from its content, falling back to the type
+    /// its key declares when the content matches no supported format.
+    ///
+    /// # Parameters
+    /// - `path`: The path of the downloaded archive.
+    /// - `s3_key`: The storage key of the archive, used in the error message.
+    /// - `declared`: The archive type of the key extension, if it has one.
+    ///
+    /// # Returns
+    /// - `Ok(ArchiveType)`: The type to extract the archive as.
+    /// - `Err(AppError::ValidationError)`: If neither the content nor the key tell the type.
+    fn resolve(path: &Path, s3_key: &str, declared: Option<ArchiveType>) -> Result<Self, AppError> {
+        let mut header = Vec::with_capacity(ZIP_MAGIC.len());
+        File::open(path)?
+            .take(ZIP_MAGIC.len() as u64)
+            .read_to_end(&mut header)?;
+
+        match (Self::from_magic(&header), declared) {
+            (Some(detected), Some(declared)) => {
+                if detected != declared {
+                    warn!("Archive {} is a {:?} archive despite its extension", s3_key, detected);
+                }
+                Ok(detected)
+            }
+            (Some(detected), None) => Ok(detected),
+            (None, Some(declared)) => Ok(declared),
+            (None, None) => Err(AppError::ValidationError(format!(
+                "File '{}' is neither a ZIP nor a tar.gz archive",
+                s3_key
+            ))),
+        }
+    }
+
+    /// Returns the archive type declared by the extension of a key, if any.
+    fn from_key(key: &str) -> Option<Self> {
+        [ArchiveType::Zip, ArchiveType::TarGz]
+            .into_iter()
+            .find(|archive_type| key.ends_with(archive_type.extension()))
+    }
+
+    /// Returns the name of the competition a key holds the archive of, if any.
+    ///
+    /// Keys whose name fails `is_valid_competition_name`, e.g. `...zip`, hold no competition.
+    fn competition_name(key: &str) -> Option<&str> {
+        [ArchiveType::Zip, ArchiveType::TarGz]
+            .iter()
+            .find_map(|archive_type| key.strip_suffix(archive_type.extension()))
+            .filter(|name| is_valid_competition_name(name))
+    }
+}
+
+/// Resolves the path of an archive entry relative to the extraction directory.
+///
+/// `.` components are dropped and `..` components resolved, as long as they stay within
+/// the extraction directory.
+///
+/// # Parameters
+/// - `path`: The path of the entry, as stored in the archive.
+///
+/// # Returns
+/// The relative path of the entry, or `None` if it is absolute or escapes the extraction directory.
+fn enclosed_path(path: &Path) -> Option<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => relative.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !relative.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(relative)
+}
+
+/// Records an upload in the history of the competition its key holds the archive of.
+///
+/// The history is informative, so a failure to record it is logged without failing the upload.
+///
+/// # Parameters
+/// - `clients`: The application clients.
+/// - `meta`: The metadata of the stored upload.
+async fn record_competition_upload(clients: &Clients, meta: &UploadMeta) {
+    let Some(competition) = ArchiveType::competition_name(&meta.file_name) else {
+        return;
+    };
+
+    match clients.get_postgres_client().record_upload_event(competition, meta).await {
+        Ok(version) => info!("Recorded version {} of competition {}", version, competition),
+        Err(e) => warn!("Failed to record the upload of competition {}: {:?}", competition, e),
+    }
+}
+
+/// An archive stored by `upload_archive`, kept on disk so it is extracted without
+/// downloading it again.
+///
+/// # Fields
+/// - `upload`: The result of the upload.
+/// - `competition`: The name of the competition the archive holds.
+/// - `archive`: The temporary file holding the archive.
+/// - `declared_type`: The archive type declared by the file name.
+///
+pub struct UploadedArchive {
+    pub upload: UploadResponse,
+    pub competition: String,
+    archive: SpooledFile,
+    declared_type: Option<ArchiveType>,
+}
+
+/// A directory an archive is extracted into before being moved to its final location.
+///
+/// It is created next to the final directory so that moving it is an atomic rename, and it
+/// is removed when dropped without being committed, so a failed extraction never leaves a
+/// directory that later requests would take as complete.
+struct StagingDir {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl StagingDir {
+    /// Creates a uniquely named staging directory next to `output_dir`.
+    fn create(output_dir: &str) -> Result<Self, AppError> {
+        let output_path = Path::new(output_dir);
+        let parent = output_path.parent().unwrap_or(Path::new("."));
+        let name = output_path.file_name().unwrap_or_default().to_string_lossy();
+        let path = parent.join(format!(".{}.partial-{}", name, Uuid::new_v4()));
+
+        create_dir_all(&path).map_err(|e| {
+            error!("Failed to create staging directory: {:?}. Error: {:?}", path, e);
+            AppError::FileIoError(e)
+        })?;
+
+        Ok(Self { path, committed: false })
+    }
+
+    fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Moves the staging directory to `output_dir`.
+    ///
+    /// If `output_dir` was created meanwhile by a concurrent extraction of the same archive,
+    /// that extraction is kept and this one discarded.
+    fn commit(mut self, output_dir: &str) -> Result<(), AppError> {
+        match fs::rename(&self.path, output_dir) {
+            Ok(()) => {
+                self.committed = true;
+                Ok(())
+            }
+            Err(_) if Path::new(output_dir).is_dir() => {
+                warn!("{} was extracted concurrently, discarding this extraction", output_dir);
+                Ok(())
+            }
+            Err(e) => {
+                error!("Failed to move {:?} to {}. Error: {:?}", self.path, output_dir, e);
+                Err(AppError::FileIoError(e))
+            }
+        }
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        if self.committed {
+            return;
+        }
+
+        if let Err(e) = fs::remove_dir_all(&self.path) {
+            if e.kind() != io::ErrorKind::NotFound {
+                warn!("Failed to remove staging directory: {:?}. Error: {:?}", self.path, e);
+            }
+        }
+    }
+}
+
+/// The outcome of extracting an archive.
+///
+/// # Fields
+/// - `files`: The paths of the extracted files, relative to the extraction directory.
+/// - `skipped`: The entries that were skipped because they were detected as binary.
+///
+#[derive(Debug, Default)]
+pub struct Extraction {
+    pub files: Vec<String>,
+    pub skipped: Vec<String>,
+}
+
+/// The sizes of an archive entry, as listed by archive inspection.
+///
+/// # Fields
+/// - `name`: The path of the entry within the archive.
+/// - `compressed_size`: The size of the entry in the archive, in bytes.
+/// - `uncompressed_size`: The size of the entry once extracted, in bytes.
+/// - `ratio`: The uncompressed size divided by the compressed size.
+/// - `flagged`: Whether the ratio exceeds `ARCHIVE_RATIO_THRESHOLD`.
+///
+#[derive(Debug, Serialize)]
+pub struct ArchiveEntry {
+    pub name: String,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub ratio: f64,
+    pub flagged: bool,
+}
+
+/// The result of inspecting an archive without extracting it.
+///
+/// # Fields
+/// - `key`: The storage key of the archive.
+/// - `entries`: The file entries of the archive.
+/// - `compressed_size`: The combined compressed size of the entries, in bytes.
+/// - `uncompressed_size`: The combined uncompressed size of the entries, in bytes.
+/// - `ratio`: The compression ratio of the archive as a whole.
+/// - `ratio_threshold`: The ratio above which entries are flagged.
+/// - `flagged_entries`: The number of flagged entries.
+///
+#[derive(Debug, Serialize)]
+pub struct ArchiveInspection {
+    pub key: String,
+    pub entries: Vec<ArchiveEntry>,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+    pub ratio: f64,
+    pub ratio_threshold: f64,
+    pub flagged_entries: usize,
+}
+
+/// Returns the compression ratio of an entry, treating an empty compressed entry as one byte.
+fn compression_ratio(compressed_size: u64, uncompressed_size: u64) -> f64 {
+    uncompressed_size as f64 / compressed_size.max(1) as f64
+}
+
+/// The size under which entries aren't checked against `MAX_EXTRACT_RATIO`, as small
+/// repetitive files legitimately compress far beyond it.
+const RATIO_CHECK_MIN_BYTES: u64 = 1024 * 1024;
+
+/// Checks whether an entry inflates beyond the maximum compression ratio.
+fn exceeds_ratio(compressed_size: u64, uncompressed_size: u64, max_ratio: f64) -> bool {
+    uncompressed_size >= RATIO_CHECK_MIN_BYTES && compression_ratio(compressed_size, uncompressed_size) > max_ratio
 }
 
 /// A service to handle file-related operations.
+///
+/// A single instance is created at startup and shared by every request.
 pub struct FileService {
     clients: Arc<Clients>,
-    validator: FileValidator,
+    config: Arc<AppConfig>,
+    validator: Arc<FileValidator>,
+    /// The number of downloads served by this instance, for the tests to tell instances apart.
+    #[cfg(test)]
+    pub(crate) downloads: std::sync::atomic::AtomicUsize,
 }
 
 impl FileService {
-    /// Creates a new instance of `FileService`.
-    pub fn new(clients: Arc<Clients>) -> Self {
+    /// Creates a new instance of `FileService`, registering the configured file checks
+    /// with the validator.
+    pub fn new(clients: Arc<Clients>, config: Arc<AppConfig>, validator: Arc<FileValidator>) -> Self {
         info!("FileService initialized");
+        register_configured_checks(&validator, &config);
         Self {
             clients,
-            validator: FileValidator::new(),
+            config,
+            validator,
+            #[cfg(test)]
+            downloads: Default::default(),
         }
     }
 
-    /// Detects the type of archive file based on the base name.
+    /// Finds the archive stored for a base name.
     ///
-    /// This function checks if the archive file exists in S3 with the given base name
+    /// The keys with a supported archive extension are looked up first, then the base name
+    /// itself, for archives stored without an extension. With `CONTENT_ADDRESSED_STORAGE` or
+    /// `DEDUPLICATE_UPLOADS`, an archive isn't necessarily stored under its own name, so the
+    /// uploads recorded under these names are looked up before the keys. Failures to reach
+    /// storage are returned rather than reported as a missing archive.
     ///
     /// # Parameters
     /// - `base_name`: The base name of the archive file
-    async fn detect_archive_type(&self, base_name: &str) -> Result<(String, ArchiveType), AppError> {
+    ///
+    /// # Returns
+    /// - `Ok((String, Option<ArchiveType>))`: The key of the archive, and the archive type its
+    ///   extension declares, if any.
+    /// - `Err(AppError::ObjectNotFound)`: If no archive is stored for the base name.
+    async fn find_archive(&self, base_name: &str) -> Result<(String, Option<ArchiveType>), AppError> {
+        let config = self.get_config();
+        if config.content_addressed_storage || config.deduplicate_uploads {
+            for archive_type in [ArchiveType::Zip, ArchiveType::TarGz] {
+                let file_name = format!("{}{}", base_name, archive_type.extension());
+                if let Some(record) = self.clients.get_postgres_client().find_upload_by_file_name(&file_name).await? {
+                    return Ok((record.s3_key, Some(archive_type)));
+                }
+            }
+        }
+
         for archive_type in [ArchiveType::Zip, ArchiveType::TarGz] {
             let key = format!("{}{}", base_name, archive_type.extension());
-            if self.clients.get_s3_client().file_exists(&key).await {
-                return Ok((key, archive_type));
+            if self.clients.get_storage().exists(&key).await? {
+                return Ok((key, Some(archive_type)));
             }
         }
 
+        if self.clients.get_storage().exists(base_name).await? {
+            return Ok((base_name.to_string(), None));
+        }
+
         error!("No archive file found for base name: {}", base_name);
         Err(
-            AppError::ValidationError(format!(
+            AppError::ObjectNotFound(format!(
                 "No archive file found for base name: {}",
                 base_name
             )))
@@ -75,287 +444,2379 @@ impl FileService {
 
     /// Downloads and extracts an archive file from S3, automatically detecting the type
     ///
+    /// The type is detected from the magic number of the downloaded archive, so an archive
+    /// with a wrong or missing extension is still extracted. The extension is only relied
+    /// upon when the content matches no supported format.
+    ///
+    /// The archive is extracted into a staging directory next to `output_dir` on the blocking
+    /// thread pool, then renamed to `output_dir` once complete. The extraction finishes even if
+    /// the request is cancelled meanwhile, and a failed one is removed.
+    ///
     /// # Parameters
     /// - `base_name`: The base name of the archive file
     /// - `output_dir`: The directory where the file will be extracted
+    /// - `text_only`: Whether to skip entries detected as binary by extension or content
     pub async fn download_and_extract_archive(
         &self,
         base_name: &str,
         output_dir: &str,
-    ) -> Result<Vec<String>, AppError> {
+        text_only: bool,
+    ) -> Result<Extraction, AppError> {
         info!("Attempting to detect and extract archive for: {}", base_name);
 
-        let (s3_key, archive_type) = self.detect_archive_type(base_name).await?;
+        let (s3_key, declared_type) = self.find_archive(base_name).await?;
 
-        info!("Detected archive type {:?} at key: {}", archive_type, s3_key);
+        info!("Found archive for {} at key: {}", base_name, s3_key);
 
-        match archive_type {
-            ArchiveType::Zip => self.download_and_extract_zip(&s3_key, output_dir).await,
-            ArchiveType::TarGz => self.download_and_extract_tar_gz(&s3_key, output_dir).await,
-        }
+        let etag = self.clients.get_storage().etag(&s3_key).await;
+
+        // The archive is kept out of the staging directory so no entry can overwrite it
+        let archive = self.download_to_temp_file(&s3_key).await?;
+
+        self.extract_archive(archive, s3_key, declared_type, etag, output_dir, text_only).await
     }
 
-    /// Generic function to handle file download and temporary storage
-    async fn download_to_temp_file(
+    /// Extracts an archive stored by `upload_archive`, without downloading it again.
+    ///
+    /// # Parameters
+    /// - `uploaded`: The stored archive.
+    /// - `output_dir`: The directory where the archive will be extracted
+    /// - `text_only`: Whether to skip entries detected as binary by extension or content
+    pub async fn extract_uploaded_archive(
         &self,
-        s3_key: &str,
+        uploaded: UploadedArchive,
         output_dir: &str,
-        temp_filename: &str,
-    ) -> Result<std::path::PathBuf, AppError> {
-        create_dir_all(output_dir).map_err(|e| {
-            error!("Failed to create output directory: {}. Error: {:?}", output_dir, e);
-            AppError::FileIoError(e)
-        })?;
+        text_only: bool,
+    ) -> Result<Extraction, AppError> {
+        let s3_key = uploaded.upload.key;
+        let etag = self.clients.get_storage().etag(&s3_key).await;
 
-        let file_data = self.clients.get_s3_client().download_file(s3_key).await?;
+        self.extract_archive(uploaded.archive, s3_key, uploaded.declared_type, etag, output_dir, text_only).await
+    }
 
-        let temp_path = Path::new(output_dir).join(temp_filename);
-        let mut file = File::create(&temp_path).map_err(|e| {
-            error!("Failed to create temporary file: {:?}. Error: {:?}", temp_path, e);
-            AppError::FileIoError(e)
-        })?;
+    /// Extracts a local copy of an archive into a staging directory next to `output_dir` on the
+    /// blocking thread pool, then renames it to `output_dir` once complete, recording the
+    /// archive in its manifest.
+    ///
+    /// # Parameters
+    /// - `archive`: The temporary file holding the archive, removed once extracted.
+    /// - `s3_key`: The storage key of the archive.
+    /// - `declared_type`: The archive type declared by the extension of the key, if any.
+    /// - `etag`: The ETag of the stored archive, recorded in the manifest.
+    /// - `output_dir`: The directory where the archive will be extracted
+    /// - `text_only`: Whether to skip entries detected as binary by extension or content
+    async fn extract_archive(
+        &self,
+        archive: SpooledFile,
+        s3_key: String,
+        declared_type: Option<ArchiveType>,
+        etag: Option<String>,
+        output_dir: &str,
+        text_only: bool,
+    ) -> Result<Extraction, AppError> {
+        let staging = StagingDir::create(output_dir)?;
 
-        file.write_all(&file_data).map_err(|e| {
-            error!("Failed to write data to temporary file: {:?}. Error: {:?}", temp_path, e);
-            AppError::FileIoError(e)
-        })?;
+        let config = self.config.clone();
+        let output_dir = output_dir.to_string();
+        let task = tokio::task::spawn_blocking(move || {
+            let archive_type = ArchiveType::resolve(archive.path(), &s3_key, declared_type)?;
+            info!("Detected archive type {:?} of {}", archive_type, s3_key);
+
+            let extraction = match archive_type {
+                ArchiveType::Zip => {
+                    Self::extract_zip(archive.path(), staging.path(), text_only, &config)?
+                }
+                ArchiveType::TarGz => {
+                    Self::extract_tar_gz(archive.path(), staging.path(), text_only, &config)?
+                }
+            };
+
+            match etag {
+                Some(etag) => Self::write_manifest(staging.path(), ExtractionManifest { s3_key, etag }),
+                None => warn!("No ETag for {}, the extraction won't be checked for updates", s3_key),
+            }
+
+            staging.commit(&output_dir)?;
+            Ok(extraction)
+        });
 
-        Ok(temp_path)
+        task.await.map_err(|e| {
+            error!("Extraction task failed: {:?}", e);
+            AppError::FileIoError(io::Error::other(e))
+        })?
     }
 
-    /// Handles file uploads.
-    /// This function reads the file content from the multipart request,
-    /// validates the file type and size, and uploads the file to S3.
-    /// If successful, it returns a success response with the file details.
-    /// If an error occurs, it returns an error response.
+    /// Checks whether an extraction directory is out of date with its archive in S3.
+    ///
+    /// The extraction is stale when its manifest is missing or records another key or ETag
+    /// than the archive currently in S3. When S3 can't tell (no archive, no ETag, or
+    /// unreachable), the local extraction is kept.
     ///
     /// # Parameters
-    /// - `multipart`: The multipart request containing the file.
+    /// - `base_name`: The base name of the archive.
+    /// - `output_dir`: The extraction directory.
     ///
     /// # Returns
-    /// The response to return to the client.
-    pub async fn upload_file(&self, mut multipart: Multipart) -> Response {
-        let mut field = match multipart.next_field().await {
-            Ok(Some(field)) => field,
-            Ok(None) => {
-                warn!("No file provided in the request");
-                return self.error_response(StatusCode::BAD_REQUEST, "No file provided");
-            }
+    /// `true` if the archive should be extracted again.
+    pub async fn extraction_is_stale(&self, base_name: &str, output_dir: &str) -> bool {
+        let s3_key = match self.find_archive(base_name).await {
+            Ok((s3_key, _)) => s3_key,
             Err(e) => {
-                error!("Failed to parse multipart data: {:?}", e);
-                return self.error_response(StatusCode::BAD_REQUEST, "Failed to parse multipart data");
+                warn!("Failed to detect archive for {}, keeping local extraction: {}", base_name, e);
+                return false;
             }
         };
 
-        let file_name = field.file_name().unwrap_or("").to_string();
-        let extension = if file_name.ends_with(".tar.gz") {
-            "tar.gz".to_string()
-        } else {
-            file_name.split('.').last().unwrap_or("").to_lowercase()
+        let Some(etag) = self.clients.get_storage().etag(&s3_key).await else {
+            warn!("No ETag for {}, keeping local extraction", s3_key);
+            return false;
         };
 
-        let file_type = match self.validator.find_file_type_by_extension(&extension) {
-            Some(file_type) => file_type,
-            None => {
-                warn!("Unsupported file extension: {}", extension);
-                return self.error_response(StatusCode::UNSUPPORTED_MEDIA_TYPE, "Unsupported file extension");
-            }
-        };
+        let manifest = fs::read(Path::new(output_dir).join(EXTRACTION_MANIFEST))
+            .ok()
+            .and_then(|data| serde_json::from_slice::<ExtractionManifest>(&data).ok());
 
-        match self.validator.validate_file(&file_type.name, &mut field).await {
-            Ok(buffer) => {
-                match self.clients.get_s3_client().upload_file(&file_name, &buffer).await {
-                    Ok(_) => {
-                        info!("Successfully uploaded file to S3: '{}'. Size: {} bytes", file_name, buffer.len());
-                        self.success_response(file_name, buffer.len())
-                    }
-                    Err(e) => {
-                        error!("Error uploading file to S3: '{}'. Error: {:?}", file_name, e);
-                        self.error_response(
-                            StatusCode::INTERNAL_SERVER_ERROR,
-                            &format!("Failed to upload file to S3: {:?}", e),
-                        )
-                    }
-                }
-            }
-            Err(validation_error) => {
-                warn!("File validation failed for '{}': {}", file_name, validation_error.message);
-                self.error_response(validation_error.code, &validation_error.message)
-            }
+        manifest != Some(ExtractionManifest { s3_key, etag })
+    }
+
+    /// Writes the manifest of an extraction directory.
+    /// Failing to write it only means the next request extracts the archive again.
+    ///
+    /// # Parameters
+    /// - `output_dir`: The extraction directory.
+    /// - `manifest`: The manifest to write.
+    fn write_manifest(output_dir: &Path, manifest: ExtractionManifest) {
+        let path = output_dir.join(EXTRACTION_MANIFEST);
+        let result = serde_json::to_vec(&manifest)
+            .map_err(io::Error::other)
+            .and_then(|data| fs::write(&path, data));
+
+        if let Err(e) = result {
+            warn!("Failed to write extraction manifest {:?}: {}", path, e);
         }
     }
 
-    /// Downloads and extracts a ZIP or tar.gz file from S3.
+    /// Streams a file from storage into a uniquely named temporary file, removed when dropped,
+    /// so archives are never held in memory.
     ///
     /// # Parameters
-    /// - `s3_key`: The S3 key of the ZIP or tar.gz file.
-    /// - `output_dir`: The directory where the file will be extracted.
+    /// - `s3_key`: The key of the file to download.
     ///
     /// # Returns
-    /// The response to return to the client.
-    async fn download_and_extract_zip(
-        &self,
-        s3_key: &str,
-        output_dir: &str,
-    ) -> Result<Vec<String>, AppError> {
-        info!("Starting download and extraction of ZIP file: {}", s3_key);
-
-        let zip_path = self.download_to_temp_file(s3_key, output_dir, "temp.zip").await?;
-        let mut extracted_files = Vec::new();
-
-        let file = File::open(&zip_path).map_err(|e| {
-            error!("Failed to open ZIP file for extraction: {:?}. Error: {:?}", zip_path, e);
+    /// - `Ok(SpooledFile)`: The temporary file holding the download.
+    /// - `Err(AppError)`: If the download fails or the temporary file can't be written.
+    async fn download_to_temp_file(&self, s3_key: &str) -> Result<SpooledFile, AppError> {
+        let (temp_file, _) = SpooledFile::create("rustler-archive").await.map_err(|e| {
+            error!("Failed to create temporary file for {}. Error: {:?}", s3_key, e);
             AppError::FileIoError(e)
         })?;
 
-        let mut archive = ZipArchive::new(file).map_err(|e| {
-            error!("Failed to read ZIP archive: {:?}. Error: {:?}", zip_path, e);
-            AppError::FileIoError(io::Error::new(io::ErrorKind::Other, e))
-        })?;
+        let size = self.clients.get_storage().download_to_file(s3_key, temp_file.path()).await?;
+        info!("Downloaded {} bytes of {} to {:?}", size, s3_key, temp_file.path());
 
-        for i in 0..archive.len() {
-            let mut file = match archive.by_index(i) {
-                Ok(file) => file,
+        Ok(temp_file)
+    }
+
+    /// Uploads every file of a multipart request to storage and records their metadata.
+    ///
+    /// Each file is validated and stored independently, and its outcome reported in the
+    /// returned JSON array, so one bad file doesn't fail the others. In atomic mode, every
+    /// file is validated before any is stored, nothing is stored if one of them fails, and
+    /// the objects already stored are deleted if one fails to store, see `store_atomic_batch`.
+    /// Batches are limited to `MAX_UPLOAD_FILES` files and `MAX_BATCH_UPLOAD_BYTES` bytes.
+    /// A requested storage class must be listed in `ALLOWED_STORAGE_CLASSES`.
+    ///
+    /// # Parameters
+    /// - `multipart`: The multipart request holding the files.
+    /// - `atomic`: Whether the batch must be stored entirely or not at all.
+    /// - `uploaded_by`: The identity of the API key the files are uploaded with.
+    /// - `storage_class`: The S3 storage class to store the files in, or `None` for the bucket default.
+    ///
+    /// # Returns
+    /// The per-file results, with a 200 if every file was stored, a 207 if only some were,
+    /// and the status of the failure if none was.
+    pub async fn upload_file(
+        &self,
+        mut multipart: Multipart,
+        atomic: bool,
+        uploaded_by: &str,
+        storage_class: Option<&str>,
+    ) -> Response {
+        if let Some(response) = self.check_storage_class(storage_class) {
+            return response;
+        }
+
+        let max_files = self.get_config().max_upload_files;
+        let max_batch_bytes = self.get_config().max_batch_upload_bytes;
+
+        let mut results: Vec<(StatusCode, UploadResult)> = Vec::new();
+        let mut pending: Vec<(String, String, ValidatedFile)> = Vec::new();
+        let mut file_count = 0;
+        let mut batch_bytes = 0;
+
+        loop {
+            let mut field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                    let error = self.validator.chunk_error(e);
+                    return self.error_response(error.code, &error.message);
+                }
                 Err(e) => {
-                    warn!("Failed to read file at index {} in ZIP archive. Error: {:?}", i, e);
-                    continue;
+                    error!("Failed to parse multipart data: {:?}", e);
+                    return self.error_response(StatusCode::BAD_REQUEST, "Failed to parse multipart data");
                 }
             };
 
-            let outpath = Path::new(output_dir).join(file.mangled_name());
+            // Plain form fields carry no file
+            let Some(file_name) = field.file_name().map(str::to_string) else {
+                continue;
+            };
+            let content_type = field.content_type().unwrap_or("").to_string();
 
-            if file.is_dir() {
-                if let Err(e) = create_dir_all(&outpath) {
-                    warn!("Failed to create directory: {:?}. Error: {:?}", outpath, e);
+            file_count += 1;
+            if file_count > max_files {
+                warn!("Rejected '{}': more than {} files in the batch", file_name, max_files);
+                results.push(self.failure_result(
+                    file_name,
+                    StatusCode::BAD_REQUEST,
+                    &format!("Too many files in the batch, the maximum is {}", max_files),
+                ));
+                continue;
+            }
+
+            let file = match self.validator.validate_file(&mut field).await {
+                Ok(file) => file,
+                Err(validation_error) => {
+                    warn!("File validation failed for '{}': {}", file_name, validation_error.message);
+                    results.push(self.failure_result(file_name, validation_error.code, &validation_error.message));
                     continue;
                 }
+            };
+
+            if batch_bytes + file.size > max_batch_bytes {
+                warn!("Rejected '{}': the batch exceeds {} bytes", file_name, max_batch_bytes);
+                results.push(self.failure_result(
+                    file_name,
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    &format!("Batch exceeds maximum allowed size of {} bytes", max_batch_bytes),
+                ));
+                continue;
+            }
+            batch_bytes += file.size;
+
+            if atomic {
+                pending.push((file_name, content_type, file));
             } else {
-                if let Ok(mut outfile) = File::create(&outpath) {
-                    if copy(&mut file, &mut outfile).is_ok() {
-                        extracted_files.push(outpath.to_string_lossy().to_string());
-                    }
-                }
+                results.push(self.store_file(file_name, content_type, &file, uploaded_by, storage_class).await);
             }
         }
 
-        if let Err(e) = fs::remove_file(&zip_path) {
-            warn!("Failed to remove temporary ZIP file: {:?}. Error: {:?}", zip_path, e);
+        if file_count == 0 {
+            warn!("No file provided in the request");
+            return self.error_response(StatusCode::BAD_REQUEST, "No file provided");
+        }
+
+        if atomic {
+            if !results.is_empty() {
+                warn!("Atomic upload rejected, {} of {} files failed validation", results.len(), file_count);
+                let status = results[0].0;
+                let failures: Vec<UploadResult> = results.into_iter().map(|(_, result)| result).collect();
+                return (status, Json(failures)).into_response();
+            }
+
+            results = self.store_atomic_batch(pending, uploaded_by, storage_class).await;
         }
 
-        Ok(extracted_files)
+        let stored = results.iter().filter(|(status, _)| status.is_success()).count();
+        let status = match stored {
+            n if n == results.len() => StatusCode::OK,
+            0 => results[0].0,
+            _ => StatusCode::MULTI_STATUS,
+        };
+        let results: Vec<UploadResult> = results.into_iter().map(|(_, result)| result).collect();
+
+        (status, Json(results)).into_response()
     }
 
-    /// Downloads and extracts a tar.gz file from S3.
+    /// Uploads the single archive of a multipart request to storage, keeping it on disk so
+    /// it can be extracted right away with `extract_uploaded_archive`.
+    ///
+    /// The archive must be named after its competition, with a `.zip` or `.tar.gz` extension,
+    /// and is validated and stored like those uploaded to `/upload`.
     ///
     /// # Parameters
-    /// - `s3_key`: The S3 key of the tar.gz file.
-    /// - `output_dir`: The directory where the tar.gz file will be extracted.
+    /// - `multipart`: The multipart request holding the archive.
+    /// - `uploaded_by`: The identity of the API key the archive is uploaded with.
+    /// - `storage_class`: The S3 storage class to store the archive in, or `None` for the bucket default.
     ///
     /// # Returns
-    /// - `Ok(Vec<String>)`: A list of file paths extracted from the tar.gz file.
-    /// - `Err(AppError)`: An error if the download or extraction fails.
-    async fn download_and_extract_tar_gz(
+    /// - `Ok(UploadedArchive)`: The stored archive.
+    /// - `Err(Response)`: A 400 if the request doesn't hold a single archive, or the error
+    ///   response of the upload.
+    pub async fn upload_archive(
         &self,
-        s3_key: &str,
-        output_dir: &str,
-    ) -> Result<Vec<String>, AppError> {
-        info!("Starting download and extraction of tar.gz file: {}", s3_key);
-
-        let tar_gz_path = self.download_to_temp_file(s3_key, output_dir, "temp.tar.gz").await?;
-
-        let status = Command::new("tar")
-            .arg("-xzf")
-            .arg(&tar_gz_path)
-            .arg("-C")
-            .arg(output_dir)
-            .status()
-            .map_err(|e| {
-                error!("Failed to execute tar command. Error: {:?}", e);
-                AppError::FileIoError(e)
-            })?;
-
-        if !status.success() {
-            error!("tar command failed with status: {:?}", status);
-            return Err(AppError::FileIoError(io::Error::new(
-                io::ErrorKind::Other,
-                "Failed to extract tar.gz",
-            )));
+        mut multipart: Multipart,
+        uploaded_by: &str,
+        storage_class: Option<&str>,
+    ) -> Result<UploadedArchive, Response> {
+        if let Some(response) = self.check_storage_class(storage_class) {
+            return Err(response);
         }
 
-        if let Err(e) = fs::remove_file(&tar_gz_path) {
-            warn!("Failed to remove temporary tar.gz file: {:?}. Error: {:?}", tar_gz_path, e);
+        let mut archive = None;
+        loop {
+            let mut field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                    let error = self.validator.chunk_error(e);
+                    return Err(self.error_response(error.code, &error.message));
+                }
+                Err(e) => {
+                    error!("Failed to parse multipart data: {:?}", e);
+                    return Err(self.error_response(StatusCode::BAD_REQUEST, "Failed to parse multipart data"));
+                }
+            };
+
+            // Plain form fields carry no file
+            let Some(file_name) = field.file_name().map(str::to_string) else {
+                continue;
+            };
+
+            if archive.is_some() {
+                return Err(self.error_response(StatusCode::BAD_REQUEST, "Only one archive can be uploaded at a time"));
+            }
+
+            let Some(competition) = ArchiveType::competition_name(&file_name).map(str::to_string) else {
+                let message = match ArchiveType::from_key(&file_name) {
+                    Some(_) => format!("File '{}' isn't named after a valid competition name", file_name),
+                    None => format!("File '{}' must be a .zip or .tar.gz archive", file_name),
+                };
+                return Err(self.error_response(StatusCode::BAD_REQUEST, &message));
+            };
+
+            let content_type = field.content_type().unwrap_or("").to_string();
+            let file = match self.validator.validate_file(&mut field).await {
+                Ok(file) => file,
+                Err(validation_error) => {
+                    warn!("File validation failed for '{}': {}", file_name, validation_error.message);
+                    return Err(self.error_response(validation_error.code, &validation_error.message));
+                }
+            };
+
+            archive = Some((file_name, content_type, competition, file));
         }
 
-        Ok(vec!["Extraction completed successfully".to_string()])
+        let Some((file_name, content_type, competition, file)) = archive else {
+            warn!("No file provided in the request");
+            return Err(self.error_response(StatusCode::BAD_REQUEST, "No file provided"));
+        };
+
+        let declared_type = ArchiveType::from_key(&file_name);
+        let upload = match self.store_file(file_name, content_type, &file, uploaded_by, storage_class).await {
+            (_, UploadResult::Stored(upload)) => upload,
+            (status, failed) => return Err((status, Json(failed)).into_response()),
+        };
+
+        let archive = match file.content {
+            FileContent::Spooled(spooled) => spooled,
+            FileContent::Memory(data) => self.spool(&data).await.map_err(|e| {
+                error!("Failed to spool archive '{}' for extraction: {:?}", upload.key, e);
+                self.error_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to prepare archive for extraction")
+            })?,
+        };
+
+        Ok(UploadedArchive { upload, competition, archive, declared_type })
     }
 
-    /// Helper function to create an error response.
+    /// Writes content held in memory to a temporary file.
     ///
     /// # Parameters
-    /// - `status_code`: The HTTP status code.
-    /// - `message`: The error message.
+    /// - `data`: The content to write.
+    async fn spool(&self, data: &[u8]) -> io::Result<SpooledFile> {
+        let (spooled, mut file) = SpooledFile::create("rustler-archive").await?;
+        file.write_all(data).await?;
+        file.flush().await?;
+        Ok(spooled)
+    }
+
+    /// Checks that a requested storage class is listed in `ALLOWED_STORAGE_CLASSES`.
+    ///
+    /// # Parameters
+    /// - `storage_class`: The requested storage class, if any.
     ///
     /// # Returns
-    /// The response to return to the client.
-    fn error_response(&self, status_code: StatusCode, message: &str) -> Response {
-        error!("Returning error response: {} - {}", status_code, message);
-        (status_code, Json(json!({ "error": message }))).into_response()
+    /// A 400 response if the storage class isn't allowed, `None` otherwise.
+    fn check_storage_class(&self, storage_class: Option<&str>) -> Option<Response> {
+        let storage_class = storage_class?;
+        let allowed = &self.get_config().allowed_storage_classes;
+        if allowed.iter().any(|class| class == storage_class) {
+            return None;
+        }
+
+        warn!("Rejected upload with storage class '{}'", storage_class);
+        Some(self.error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("Storage class '{}' is not allowed, expected one of: {}", storage_class, allowed.join(", ")),
+        ))
     }
 
-    /// Helper function to create a success response.
+    /// Stores a validated file in storage and records its metadata, unless its content is
+    /// already stored and `DEDUPLICATE_UPLOADS` is enabled.
+    ///
+    /// With `CONTENT_ADDRESSED_STORAGE`, the file is stored under `sha256/<digest>` with its
+    /// file name kept in the object metadata, and an object already stored under that key
+    /// isn't uploaded again.
     ///
     /// # Parameters
-    /// - `file_name`: The name of the uploaded file.
-    /// - `size`: The size of the uploaded file.
+    /// - `file_name`: The name the file was uploaded under, also used as its S3 key unless
+    ///   storage is content-addressed.
+    /// - `content_type`: The declared content type of the file.
+    /// - `file`: The validated file.
+    /// - `uploaded_by`: The identity of the API key the file is uploaded with.
+    /// - `storage_class`: The S3 storage class to store the file in, or `None` for the bucket default.
     ///
     /// # Returns
-    /// The response to return to the client.
-    fn success_response(&self, file_name: String, size: usize) -> Response {
-        info!("Returning success response for file: {} ({} bytes)", file_name, size);
-        (
-            StatusCode::OK,
-            Json(json!({
-                "message": "File uploaded successfully",
-                "file_name": file_name,
-                "size": size
-            })),
-        )
-            .into_response()
-    }
-
-    pub async fn get_cached_file(&self, base_name: &str) -> Result<Option<String>, AppError> {
-        let mut con = self.clients
-            .get_redis_client()
-            .get_client()
-            .get_multiplexed_async_connection()
-            .await
-            .map_err(AppError::RedisConnectionError)?;
+    /// The status and result of the file.
+    async fn store_file(
+        &self,
+        file_name: String,
+        content_type: String,
+        file: &ValidatedFile,
+        uploaded_by: &str,
+        storage_class: Option<&str>,
+    ) -> (StatusCode, UploadResult) {
+        match self.put_file(&file_name, &content_type, file, storage_class).await {
+            Ok(object) => self.record_file(file_name, content_type, file, object, uploaded_by).await,
+            Err(failure) => failure,
+        }
+    }
 
-        let cached_file: Option<String> = con
-            .get(format!("file_cache:{}", base_name))
-            .await
-            .map_err(AppError::RedisConnectionError)?;
+    /// Stores the validated files of an atomic batch, all of them or none.
+    ///
+    /// Every file is stored before any upload is recorded. When a file fails to store, the
+    /// objects the batch stored so far are deleted, so nothing of the batch is kept. Objects
+    /// which were already stored under their key before the batch, e.g. replaced by a file of
+    /// the same name, aren't deleted, as their previous content can't be brought back.
+    ///
+    /// # Parameters
+    /// - `pending`: The name, declared content type and validated content of each file.
+    /// - `uploaded_by`: The identity of the API key the files are uploaded with.
+    /// - `storage_class`: The S3 storage class to store the files in, or `None` for the bucket default.
+    ///
+    /// # Returns
+    /// The status and result of each file.
+    async fn store_atomic_batch(
+        &self,
+        pending: Vec<(String, String, ValidatedFile)>,
+        uploaded_by: &str,
+        storage_class: Option<&str>,
+    ) -> Vec<(StatusCode, UploadResult)> {
+        let storage = self.clients.get_storage();
+        let mut stored = Vec::with_capacity(pending.len());
 
-        Ok(cached_file)
-    }
+        for (index, (file_name, content_type, file)) in pending.iter().enumerate() {
+            // Unknown keys are assumed taken, so a failed lookup never gets an object deleted
+            let key = self.storage_key(file_name, file);
+            let existed = storage.exists(&key).await.unwrap_or(true);
 
-    /// Caches the extracted files in Redis after downloading and extracting.
-    pub async fn cache_files(&self, base_name: &str, files: &[String]) -> Result<(), AppError> {
-        let mut con = self.clients
-            .get_redis_client()
-            .get_client()
-            .get_multiplexed_async_connection()
-            .await
-            .map_err(AppError::RedisConnectionError)?;
+            match self.put_file(file_name, content_type, file, storage_class).await {
+                Ok(object) => stored.push((object, existed)),
+                Err((status, failure)) => {
+                    warn!("Atomic upload rolled back, '{}' failed to store", file_name);
+                    for (object, existed) in &stored {
+                        let StoredObject::Put { key, already_stored: false } = object else {
+                            continue;
+                        };
+                        if *existed {
+                            continue;
+                        }
+                        match storage.delete(key).await {
+                            Ok(()) => info!("Deleted '{}' stored by the rolled back batch", key),
+                            Err(e) => error!("Failed to delete '{}' stored by the rolled back batch: {:?}", key, e),
+                        }
+                    }
 
-        let cache_key = format!("file_cache:{}", base_name);
-        let files_json = serde_json::to_string(&files)
-            .map_err(AppError::SerializationError)?;
+                    let message = format!("Not stored, '{}' of the atomic batch failed to store", file_name);
+                    return pending
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, (other_name, _, _))| {
+                            if i == index {
+                                (status, failure.clone())
+                            } else {
+                                self.failure_result(other_name, status, &message)
+                            }
+                        })
+                        .collect();
+                }
+            }
+        }
 
-        let _: () = con.set_ex(cache_key, files_json, 3600)
-            .await
-            .map_err(AppError::RedisConnectionError)?;
+        let mut results = Vec::with_capacity(pending.len());
+        for ((file_name, content_type, file), (object, _)) in pending.into_iter().zip(stored) {
+            results.push(self.record_file(file_name, content_type, &file, object, uploaded_by).await);
+        }
+        results
+    }
 
-        Ok(())
+    /// Returns the storage key a validated file is stored under.
+    ///
+    /// # Parameters
+    /// - `file_name`: The name the file was uploaded under.
+    /// - `file`: The validated file.
+    fn storage_key(&self, file_name: &str, file: &ValidatedFile) -> String {
+        if self.get_config().content_addressed_storage {
+            content_addressed_key(&file.sha256)
+        } else {
+            file_name.to_string()
+        }
     }
-}
\ No newline at end of file
+
+    /// Stores a validated file in storage, without recording it, unless its content is
+    /// already stored.
+    ///
+    /// # Parameters
+    /// - `file_name`: The name the file was uploaded under.
+    /// - `content_type`: The declared content type of the file, stored with the object unless empty.
+    /// - `file`: The validated file.
+    /// - `storage_class`: The S3 storage class to store the file in, or `None` for the bucket default.
+    ///
+    /// # Returns
+    /// - `Ok(StoredObject)`: The object holding the content of the file.
+    /// - `Err((StatusCode, UploadResult))`: The failure to report if the file can't be stored.
+    pub(crate) async fn put_file(
+        &self,
+        file_name: &str,
+        content_type: &str,
+        file: &ValidatedFile,
+        storage_class: Option<&str>,
+    ) -> Result<StoredObject, (StatusCode, UploadResult)> {
+        if self.get_config().deduplicate_uploads {
+            match self.clients.get_postgres_client().find_upload_by_sha256(&file.sha256).await {
+                Ok(Some(existing)) => {
+                    info!(
+                        "Deduplicated upload '{}' against existing object '{}'",
+                        file_name, existing.s3_key
+                    );
+                    return Ok(StoredObject::Deduplicated(existing));
+                }
+                Ok(None) => {}
+                Err(e) => {
+                    warn!("Failed to look up uploads by hash, uploading '{}' anyway: {:?}", file_name, e);
+                }
+            }
+        }
+
+        let storage = self.clients.get_storage();
+        let content_addressed = self.get_config().content_addressed_storage;
+        let key = self.storage_key(file_name, file);
+
+        let already_stored = content_addressed && match storage.exists(&key).await {
+            Ok(exists) => exists,
+            Err(e) => {
+                warn!("Failed to look up '{}', uploading '{}' anyway: {:?}", key, file_name, e);
+                false
+            }
+        };
+
+        let stored = if already_stored {
+            info!("Content of '{}' is already stored under '{}', skipping the upload", file_name, key);
+            Ok(())
+        } else {
+            let kept_name = content_addressed.then_some(file_name);
+            let content_type = Some(content_type).filter(|content_type| !content_type.is_empty());
+            match &file.content {
+                FileContent::Memory(data) => {
+                    storage.upload(&key, data, &file.sha256, kept_name, content_type, storage_class).await
+                }
+                FileContent::Spooled(spooled) => {
+                    storage.upload_from_path(&key, spooled.path(), &file.sha256, kept_name, content_type, storage_class).await
+                }
+            }
+        };
+
+        if let Err(e) = stored {
+            error!("Error uploading file to storage: '{}'. Error: {:?}", file_name, e);
+            return Err(self.failure_result(
+                file_name.to_string(),
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &format!("Failed to upload file to storage: {:?}", e),
+            ));
+        }
+
+        Ok(StoredObject::Put { key, already_stored })
+    }
+
+    /// Records the metadata of a file stored by `put_file`. A deduplicated file gets its own
+    /// record too, pointing at the object already holding its content.
+    ///
+    /// # Parameters
+    /// - `file_name`: The name the file was uploaded under.
+    /// - `content_type`: The declared content type of the file.
+    /// - `file`: The validated file.
+    /// - `object`: The object holding the content of the file.
+    /// - `uploaded_by`: The identity of the API key the file is uploaded with.
+    ///
+    /// # Returns
+    /// The status and result of the file.
+    pub(crate) async fn record_file(
+        &self,
+        file_name: String,
+        content_type: String,
+        file: &ValidatedFile,
+        object: StoredObject,
+        uploaded_by: &str,
+    ) -> (StatusCode, UploadResult) {
+        let (key, already_stored, existing_file_name) = match object {
+            StoredObject::Deduplicated(existing) => (existing.s3_key, true, Some(existing.file_name)),
+            StoredObject::Put { key, already_stored } => (key, already_stored, None),
+        };
+        if already_stored {
+            info!("Recorded '{}' against the object already stored under '{}'. Uploaded by: {}", file_name, key, uploaded_by);
+        } else {
+            info!(
+                "Successfully uploaded file to storage: '{}'. Type: {}. Size: {} bytes. Uploaded by: {}",
+                file_name, file.file_type, file.size, uploaded_by
+            );
+            record_upload_size(file.size as u64);
+        }
+
+        let meta = UploadMeta {
+            file_name: file_name.clone(),
+            s3_key: key.clone(),
+            size_bytes: file.size as i64,
+            content_type,
+            sha256: file.sha256.clone(),
+            uploaded_by: uploaded_by.to_string(),
+        };
+
+        let recorded = self.clients.get_postgres_client().record_upload(&meta).await;
+        if recorded.is_ok() {
+            record_competition_upload(&self.clients, &meta).await;
+        }
+
+        match recorded {
+            Ok(id) => (StatusCode::OK, self.success_result(id, file_name, key, already_stored, existing_file_name, file)),
+            Err(e) => {
+                error!("Error recording upload metadata for '{}'. Error: {:?}", file_name, e);
+                self.failure_result(file_name, StatusCode::INTERNAL_SERVER_ERROR, "Failed to record upload metadata")
+            }
+        }
+    }
+
+    /// Streams a stored file back to the client, without buffering it in memory.
+    ///
+    /// A single-range `Range` header is passed to the storage backend, answering with a 206 and the
+    /// requested bytes, so clients can resume downloads and seek. Malformed byte ranges are
+    /// rejected with a 416, while multiple ranges and other units are ignored and the whole
+    /// file is returned.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    /// - `range`: The `Range` header of the request, if any.
+    ///
+    /// # Returns
+    /// The file as an attachment, a 404 if it doesn't exist or is soft-deleted, or a 416 if the range can't
+    /// be satisfied.
+    pub async fn download_file(&self, key: &str, range: Option<&str>) -> Response {
+        #[cfg(test)]
+        self.downloads.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+
+        if let Some(response) = Self::deleted_file_not_found(key) {
+            return response;
+        }
+
+        let range = match range.map(ByteRange::parse).transpose() {
+            Ok(range) => range.flatten(),
+            Err(e) => {
+                warn!("Rejected download of '{}': {}", key, e);
+                return ErrorResponse::new(StatusCode::RANGE_NOT_SATISFIABLE, "Malformed range").into_response();
+            }
+        };
+
+        let object = match self.clients.get_storage().open(key, range).await {
+            Ok(object) => object,
+            Err(AppError::ObjectNotFound(_)) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response();
+            }
+            Err(AppError::RangeNotSatisfiable(_)) => {
+                return ErrorResponse::new(StatusCode::RANGE_NOT_SATISFIABLE, "Requested range not satisfiable")
+                    .into_response();
+            }
+            Err(e) => {
+                error!("Failed to fetch '{}' from storage: {:?}", key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch file").into_response();
+            }
+        };
+
+        // S3 stores objects uploaded without a content type as `binary/octet-stream`
+        let content_type = object
+            .content_type
+            .filter(|content_type| content_type != "binary/octet-stream")
+            .or_else(|| {
+                // The whole name is matched, so double extensions like `.tar.gz` are recognized
+                let file_type = self.validator.find_file_type_by_extension(&attachment_filename(key))?;
+                file_type.content_types.first().cloned()
+            })
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+
+        let mut response = Response::builder()
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", attachment_filename(key)))
+            .header(header::ACCEPT_RANGES, "bytes");
+
+        if let Some(content_length) = object.content_length {
+            response = response.header(header::CONTENT_LENGTH, content_length);
+        }
+        if let Some(etag) = object.etag {
+            response = response.header(header::ETAG, etag);
+        }
+        response = match object.content_range {
+            Some(content_range) => response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .header(header::CONTENT_RANGE, content_range),
+            None => response.status(StatusCode::OK),
+        };
+
+        let body = Body::from_stream(ReaderStream::new(object.body));
+        response.body(body).unwrap_or_else(|e| {
+            error!("Failed to build download response for '{}': {}", key, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        })
+    }
+
+    /// Returns the SHA-256 checksum stored with a file when it was uploaded, so clients can
+    /// check their copy without downloading the file again.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    ///
+    /// # Returns
+    /// The key and checksum of the file, or a 404 if the file doesn't exist or was stored
+    /// without a checksum.
+    pub async fn get_checksum(&self, key: &str) -> Response {
+        if let Some(response) = Self::deleted_file_not_found(key) {
+            return response;
+        }
+
+        match self.clients.get_storage().checksum(key).await {
+            Ok(Some(sha256)) => (StatusCode::OK, Json(json!({ "key": key, "sha256": sha256 }))).into_response(),
+            Ok(None) => {
+                ErrorResponse::new(StatusCode::NOT_FOUND, format!("No checksum stored for '{}'", key)).into_response()
+            }
+            Err(AppError::ObjectNotFound(_)) => {
+                ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response()
+            }
+            Err(e) => {
+                error!("Failed to fetch the checksum of '{}': {:?}", key, e);
+                ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch checksum").into_response()
+            }
+        }
+    }
+
+    /// Renames a stored file by copying it then deleting the original.
+    ///
+    /// The upload record of the file is pointed at the new key, and when the file is the
+    /// archive of a competition, its extraction and cached file lists and trees are moved
+    /// along, so it isn't extracted again.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    /// - `new_key`: The key to rename the file to.
+    /// - `overwrite`: Whether to replace a file already stored under `new_key`.
+    ///
+    /// # Returns
+    /// The old and new keys of the file, a 400 if the new key is empty or unchanged or either
+    /// key is under the `deleted/` prefix, a 404 if the file doesn't exist, a 409 if the new key is taken and `overwrite` is unset, or a
+    /// 500 if the file can't be copied or deleted.
+    pub async fn rename_file(&self, key: &str, new_key: &str, overwrite: bool) -> Response {
+        if new_key.is_empty() || new_key == key {
+            return ErrorResponse::new(StatusCode::BAD_REQUEST, "The new key must differ from the current one")
+                .into_response();
+        }
+        // Moving files in or out of the prefix would bypass the bookkeeping of deletions and restores
+        if key.starts_with(DELETED_PREFIX) || new_key.starts_with(DELETED_PREFIX) {
+            let message = format!("Files can't be renamed into or out of the '{}' prefix", DELETED_PREFIX);
+            return ErrorResponse::new(StatusCode::BAD_REQUEST, message).into_response();
+        }
+
+        let storage = self.clients.get_storage();
+        match (storage.exists(key).await, storage.exists(new_key).await) {
+            (Ok(false), _) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response();
+            }
+            (Ok(true), Ok(true)) if !overwrite => {
+                let message = format!("File '{}' already exists, pass ?overwrite=true to replace it", new_key);
+                return ErrorResponse::new(StatusCode::CONFLICT, message).into_response();
+            }
+            (Ok(true), Ok(_)) => {}
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to check the keys of the rename of '{}' to '{}': {:?}", key, new_key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to rename file").into_response();
+            }
+        }
+
+        let renamed = async {
+            storage.copy_file(key, new_key).await?;
+            storage.delete(key).await
+        };
+        if let Err(e) = renamed.await {
+            error!("Failed to rename '{}' to '{}': {:?}", key, new_key, e);
+            return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to rename file").into_response();
+        }
+        info!("Renamed '{}' to '{}'", key, new_key);
+
+        match self.clients.get_postgres_client().rename_upload_key(key, new_key).await {
+            Ok(records) => info!("Pointed {} upload records at '{}'", records, new_key),
+            Err(e) => warn!("Failed to update the upload record of '{}': {}", key, e),
+        }
+
+        if let Some(name) = ArchiveType::competition_name(key) {
+            if let Err(e) = self.move_competition(name, ArchiveType::competition_name(new_key), new_key).await {
+                warn!("Failed to move the extraction of competition {}: {}", name, e);
+            }
+        }
+
+        (StatusCode::OK, Json(json!({ "previous_key": key, "key": new_key }))).into_response()
+    }
+
+    /// Copies a stored file to another key within storage, without downloading it, e.g. to
+    /// promote an archive from a staging prefix.
+    ///
+    /// # Parameters
+    /// - `src`: The storage key of the file to copy.
+    /// - `dst`: The key of the copy.
+    /// - `overwrite`: Whether to replace a file already stored under `dst`.
+    ///
+    /// # Returns
+    /// The source and destination keys, a 400 if the destination is empty, unchanged or under
+    /// the `deleted/` prefix, a 404 if the source doesn't exist, a 409 if the destination is
+    /// taken and `overwrite` is unset, or a 500 if the file can't be copied.
+    pub async fn copy_file(&self, src: &str, dst: &str, overwrite: bool) -> Response {
+        if dst.is_empty() || dst == src {
+            return ErrorResponse::new(StatusCode::BAD_REQUEST, "The destination key must differ from the source")
+                .into_response();
+        }
+        if dst.starts_with(DELETED_PREFIX) {
+            let message = format!("Files can't be copied under the '{}' prefix", DELETED_PREFIX);
+            return ErrorResponse::new(StatusCode::BAD_REQUEST, message).into_response();
+        }
+        if let Some(response) = Self::deleted_file_not_found(src) {
+            return response;
+        }
+
+        let storage = self.clients.get_storage();
+        match (storage.exists(src).await, storage.exists(dst).await) {
+            (Ok(false), _) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", src)).into_response();
+            }
+            (Ok(true), Ok(true)) if !overwrite => {
+                let message = format!("File '{}' already exists, pass ?overwrite=true to replace it", dst);
+                return ErrorResponse::new(StatusCode::CONFLICT, message).into_response();
+            }
+            (Ok(true), Ok(_)) => {}
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to check the keys of the copy of '{}' to '{}': {:?}", src, dst, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to copy file").into_response();
+            }
+        }
+
+        match storage.copy_file(src, dst).await {
+            Ok(()) => {
+                info!("Copied '{}' to '{}'", src, dst);
+                (StatusCode::OK, Json(json!({ "src": src, "dst": dst }))).into_response()
+            }
+            Err(AppError::ObjectNotFound(_)) => {
+                ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", src)).into_response()
+            }
+            Err(e) => {
+                error!("Failed to copy '{}' to '{}': {:?}", src, dst, e);
+                ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to copy file").into_response()
+            }
+        }
+    }
+
+    /// Returns the 404 answered for a key under the `deleted/` prefix, as soft-deleted files
+    /// are only served again once restored.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    fn deleted_file_not_found(key: &str) -> Option<Response> {
+        key.starts_with(DELETED_PREFIX).then(|| {
+            ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response()
+        })
+    }
+
+    /// Soft-deletes a stored file: it is moved under the `deleted/` prefix and its upload
+    /// records are flagged, until it is restored or purged once `DELETED_FILE_RETENTION_SECS`
+    /// elapsed. The extraction and cached data of the competition it holds are removed.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    ///
+    /// # Returns
+    /// The key the file was moved to, a 400 if the key is already under the `deleted/` prefix,
+    /// a 404 if no file has this key, or a 500 if the file can't be moved.
+    pub async fn soft_delete_file(&self, key: &str) -> Response {
+        if key.starts_with(DELETED_PREFIX) {
+            return ErrorResponse::new(StatusCode::BAD_REQUEST, format!("File '{}' is already deleted", key))
+                .into_response();
+        }
+
+        let storage = self.clients.get_storage();
+        match storage.exists(key).await {
+            Ok(true) => {}
+            Ok(false) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response();
+            }
+            Err(e) => {
+                error!("Failed to check whether '{}' exists: {:?}", key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete file").into_response();
+            }
+        }
+
+        let deleted_key = deleted_key(key);
+        let moved = async {
+            storage.copy_file(key, &deleted_key).await?;
+            storage.delete(key).await
+        };
+        if let Err(e) = moved.await {
+            error!("Failed to move '{}' to '{}': {:?}", key, deleted_key, e);
+            return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete file").into_response();
+        }
+        info!("Soft-deleted '{}'", key);
+
+        match self.clients.get_postgres_client().mark_upload_deleted(key).await {
+            Ok(records) => info!("Marked {} upload records of '{}' as deleted", records, key),
+            Err(e) => warn!("Failed to mark the upload record of '{}' as deleted: {}", key, e),
+        }
+
+        if let Some(name) = ArchiveType::competition_name(key) {
+            match CleanupService::new(self.clients.clone(), self.config.clone()).delete_competition(name).await {
+                Ok(CompetitionDeletion::Locked) => {
+                    warn!("Competition {} is being extracted, leaving its extraction to the cleanup", name);
+                }
+                Ok(_) => {}
+                Err(e) => warn!("Failed to remove the extraction of competition {}: {}", name, e),
+            }
+        }
+
+        (StatusCode::OK, Json(json!({
+            "key": key,
+            "deleted_key": deleted_key,
+            "retention_secs": self.get_config().deleted_file_retention_secs,
+        }))).into_response()
+    }
+
+    /// Restores a soft-deleted file to its key, and clears the deletion of its upload records.
+    ///
+    /// # Parameters
+    /// - `key`: The key the file was stored under before its deletion.
+    ///
+    /// # Returns
+    /// The restored key, a 404 if no soft-deleted file had this key, a 409 if a file was
+    /// stored under the key meanwhile, or a 500 if the file can't be moved.
+    pub async fn restore_file(&self, key: &str) -> Response {
+        let storage = self.clients.get_storage();
+        let deleted_key = deleted_key(key);
+
+        match (storage.exists(&deleted_key).await, storage.exists(key).await) {
+            (Ok(false), _) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("No deleted file '{}'", key)).into_response();
+            }
+            (Ok(true), Ok(true)) => {
+                let message = format!("File '{}' was stored again since its deletion", key);
+                return ErrorResponse::new(StatusCode::CONFLICT, message).into_response();
+            }
+            (Ok(true), Ok(false)) => {}
+            (Err(e), _) | (_, Err(e)) => {
+                error!("Failed to check the keys of the restore of '{}': {:?}", key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to restore file").into_response();
+            }
+        }
+
+        let moved = async {
+            storage.copy_file(&deleted_key, key).await?;
+            storage.delete(&deleted_key).await
+        };
+        if let Err(e) = moved.await {
+            error!("Failed to move '{}' back to '{}': {:?}", deleted_key, key, e);
+            return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to restore file").into_response();
+        }
+        info!("Restored '{}'", key);
+
+        match self.clients.get_postgres_client().restore_upload(key).await {
+            Ok(records) => info!("Restored {} upload records of '{}'", records, key),
+            Err(e) => warn!("Failed to restore the upload record of '{}': {}", key, e),
+        }
+
+        (StatusCode::OK, Json(json!({ "key": key }))).into_response()
+    }
+
+    /// Moves the extraction and cached data of a competition whose archive was renamed,
+    /// holding the locks of both competitions. When the archive no longer holds a
+    /// competition, they are removed instead.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the competition.
+    /// - `new_name`: The name of the competition the archive now holds, if any.
+    /// - `new_key`: The new key of the archive.
+    async fn move_competition(&self, name: &str, new_name: Option<&str>, new_key: &str) -> Result<(), AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let lock_key = competition_lock_key(&self.clients, name);
+        let Some(token) = redis_client.acquire_lock(&lock_key, COMPETITION_LOCK_TTL).await? else {
+            warn!("Competition {} is being extracted, leaving its extraction to the cleanup", name);
+            return Ok(());
+        };
+
+        let new_lock = match new_name {
+            Some(new_name) => {
+                let new_lock_key = competition_lock_key(&self.clients, new_name);
+                match redis_client.acquire_lock(&new_lock_key, COMPETITION_LOCK_TTL).await {
+                    Ok(Some(new_token)) => Some((new_lock_key, new_token)),
+                    result => {
+                        redis_client.release_lock(&lock_key, &token).await?;
+                        result?;
+                        warn!("Competition {} is being extracted, leaving the extraction of {} to the cleanup", new_name, name);
+                        return Ok(());
+                    }
+                }
+            }
+            None => None,
+        };
+
+        let result = self.move_competition_locked(name, new_name, new_key).await;
+
+        redis_client.release_lock(&lock_key, &token).await?;
+        if let Some((new_lock_key, new_token)) = new_lock {
+            redis_client.release_lock(&new_lock_key, &new_token).await?;
+        }
+
+        result
+    }
+
+    /// Moves the extraction and cached data of a competition, once its locks are held.
+    ///
+    /// Names failing `is_valid_competition_name` are left alone, as their directories could
+    /// lie outside the competitions directory.
+    async fn move_competition_locked(&self, name: &str, new_name: Option<&str>, new_key: &str) -> Result<(), AppError> {
+        if !is_valid_competition_name(name) || new_name.is_some_and(|new_name| !is_valid_competition_name(new_name)) {
+            warn!("Not moving the extraction of competition '{}', whose names could escape the competitions directory", name);
+            return Ok(());
+        }
+
+        let redis_client = self.clients.get_redis_client();
+        let competitions_dir = Path::new(&self.get_config().competitions_dir);
+        let output_dir = competitions_dir.join(name);
+        let file_cache_key = redis_client.key(&format!("file_cache:{}", name));
+        let codebase_json_prefix = redis_client.key(&format!("codebase_json:{}:", name));
+
+        let Some(new_name) = new_name else {
+            if output_dir.exists() {
+                fs::remove_dir_all(&output_dir)?;
+            }
+            let mut con = redis_client.get_connection().await?;
+            let _: () = con.del(&file_cache_key).await?;
+            self.invalidate_codebase_json(name).await?;
+            return Ok(());
+        };
+
+        // Whatever was extracted under the new name came from the object the rename replaced
+        let new_output_dir = competitions_dir.join(new_name);
+        if new_output_dir.exists() {
+            fs::remove_dir_all(&new_output_dir)?;
+        }
+        let new_file_cache_key = redis_client.key(&format!("file_cache:{}", new_name));
+        let mut con = redis_client.get_connection().await?;
+        let _: () = con.del(&new_file_cache_key).await?;
+        self.invalidate_codebase_json(new_name).await?;
+
+        if output_dir.exists() {
+            fs::rename(&output_dir, &new_output_dir)?;
+            // Rewriting the manifest in place leaves the directory modification time, which
+            // keys the cached trees, unchanged
+            match self.clients.get_storage().etag(new_key).await {
+                Some(etag) => Self::write_manifest(&new_output_dir, ExtractionManifest { s3_key: new_key.to_string(), etag }),
+                None => warn!("No ETag for {}, its extraction will be redone", new_key),
+            }
+        }
+
+        redis_client.rename_matching(&escape_glob(&file_cache_key), &file_cache_key, &new_file_cache_key).await?;
+        let new_codebase_json_prefix = redis_client.key(&format!("codebase_json:{}:", new_name));
+        redis_client
+            .rename_matching(
+                &format!("{}*", escape_glob(&codebase_json_prefix)),
+                &codebase_json_prefix,
+                &new_codebase_json_prefix,
+            )
+            .await?;
+
+        info!("Moved competition {} to {}", name, new_name);
+        Ok(())
+    }
+
+    /// Returns the metadata of a stored file, without downloading it.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the file.
+    ///
+    /// # Returns
+    /// The size, ETag, modification time, and content type of the file, a 404 if it doesn't
+    /// exist, or a 500 if storage can't be reached or denies the request.
+    pub async fn get_metadata(&self, key: &str) -> Response {
+        if let Some(response) = Self::deleted_file_not_found(key) {
+            return response;
+        }
+
+        match self.clients.get_storage().head_file(key).await {
+            Ok(Some(metadata)) => (StatusCode::OK, Json(json!({
+                "key": key,
+                "size": metadata.size,
+                "etag": metadata.etag,
+                "last_modified": metadata.last_modified.map(|time| time.to_rfc3339()),
+                "content_type": metadata.content_type,
+            }))).into_response(),
+            Ok(None) => {
+                ErrorResponse::new(StatusCode::NOT_FOUND, format!("File '{}' not found", key)).into_response()
+            }
+            Err(e) => {
+                error!("Failed to fetch the metadata of '{}': {:?}", key, e);
+                ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch metadata").into_response()
+            }
+        }
+    }
+
+    /// Returns the upload history of a competition, the latest version first, telling for
+    /// each version whether an object is still stored under its key.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the competition.
+    /// - `limit`: The maximum number of versions to return.
+    /// - `offset`: The number of most recent versions to skip.
+    /// - `latest`: Whether to return only the latest version, instead of a page.
+    ///
+    /// # Returns
+    /// The page of versions, or the latest version alone, a 404 if no upload of the
+    /// competition was recorded, or a 500 if the history can't be fetched.
+    pub async fn upload_history(&self, name: &str, limit: i64, offset: i64, latest: bool) -> Response {
+        let (limit, offset) = if latest { (1, 0) } else { (limit, offset) };
+
+        let (events, total) = match self.clients.get_postgres_client().list_upload_events(name, limit, offset).await {
+            Ok(history) => history,
+            Err(e) => {
+                error!("Failed to fetch the upload history of {}: {:?}", name, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fetch upload history")
+                    .into_response();
+            }
+        };
+
+        if total == 0 {
+            return ErrorResponse::new(StatusCode::NOT_FOUND, format!("No upload recorded for competition '{}'", name))
+                .into_response();
+        }
+
+        // Versions usually share their key, so each key is only looked up once
+        let mut existing: HashMap<String, Option<bool>> = HashMap::new();
+        let mut versions = Vec::with_capacity(events.len());
+        for event in events {
+            let exists = match existing.get(&event.s3_key) {
+                Some(exists) => *exists,
+                None => {
+                    let exists = match self.clients.get_storage().head_file(&event.s3_key).await {
+                        Ok(metadata) => Some(metadata.is_some()),
+                        Err(e) => {
+                            warn!("Failed to check whether '{}' is still stored: {:?}", event.s3_key, e);
+                            None
+                        }
+                    };
+                    existing.insert(event.s3_key.clone(), exists);
+                    exists
+                }
+            };
+            versions.push(UploadVersion { event, exists });
+        }
+
+        if latest {
+            return (StatusCode::OK, Json(versions.remove(0))).into_response();
+        }
+
+        (StatusCode::OK, Json(UploadHistoryResponse {
+            competition: name.to_string(),
+            versions,
+            total,
+            limit,
+            offset,
+        })).into_response()
+    }
+
+    /// Lists the entries of a stored ZIP archive with their compressed and uncompressed
+    /// sizes, flagging entries whose compression ratio exceeds `ARCHIVE_RATIO_THRESHOLD`,
+    /// so suspicious archives can be reviewed before they are extracted.
+    ///
+    /// The archive is streamed to a temporary file and only its central directory is read,
+    /// nothing is decompressed. Tar.gz archives can't be inspected, since gzip compresses
+    /// the archive as a whole rather than per entry.
+    ///
+    /// # Parameters
+    /// - `key`: The storage key of the archive.
+    ///
+    /// # Returns
+    /// The inspection of the archive, a 404 if it doesn't exist, or a 422 if it isn't a
+    /// ZIP archive.
+    pub async fn inspect_archive(&self, key: &str) -> Response {
+        if let Some(response) = Self::deleted_file_not_found(key) {
+            return response;
+        }
+
+        let temp_file = match self.download_to_temp_file(key).await {
+            Ok(temp_file) => temp_file,
+            Err(AppError::ObjectNotFound(_)) => {
+                return ErrorResponse::new(StatusCode::NOT_FOUND, format!("Archive '{}' not found", key))
+                    .into_response();
+            }
+            Err(e) => {
+                error!("Failed to download '{}' for inspection: {:?}", key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to download archive")
+                    .into_response();
+            }
+        };
+
+        let file = match File::open(temp_file.path()) {
+            Ok(file) => io::BufReader::new(file),
+            Err(e) => {
+                error!("Failed to open the download of '{}': {:?}", key, e);
+                return ErrorResponse::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to download archive")
+                    .into_response();
+            }
+        };
+
+        let mut archive = match ZipArchive::new(file) {
+            Ok(archive) => archive,
+            Err(e) => {
+                warn!("Failed to read '{}' as a ZIP archive: {}", key, e);
+                return ErrorResponse::new(StatusCode::UNPROCESSABLE_ENTITY, format!("'{}' is not a ZIP archive", key))
+                    .into_response();
+            }
+        };
+
+        let ratio_threshold = self.get_config().archive_ratio_threshold;
+        let mut entries = Vec::new();
+        for index in 0..archive.len() {
+            let entry = match archive.by_index_raw(index) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    warn!("Failed to read entry {} of '{}': {}", index, key, e);
+                    return ErrorResponse::new(StatusCode::UNPROCESSABLE_ENTITY, format!("Corrupt ZIP archive: {}", e))
+                        .into_response();
+                }
+            };
+
+            if entry.is_dir() {
+                continue;
+            }
+
+            let ratio = compression_ratio(entry.compressed_size(), entry.size());
+            entries.push(ArchiveEntry {
+                name: entry.name().to_string(),
+                compressed_size: entry.compressed_size(),
+                uncompressed_size: entry.size(),
+                ratio,
+                flagged: ratio > ratio_threshold,
+            });
+        }
+
+        let compressed_size = entries.iter().map(|entry| entry.compressed_size).sum();
+        let uncompressed_size = entries.iter().map(|entry| entry.uncompressed_size).sum();
+        let flagged_entries = entries.iter().filter(|entry| entry.flagged).count();
+        if flagged_entries > 0 {
+            warn!("Archive '{}' has {} entries above the compression ratio threshold", key, flagged_entries);
+        }
+
+        (StatusCode::OK, Json(ArchiveInspection {
+            key: key.to_string(),
+            entries,
+            compressed_size,
+            uncompressed_size,
+            ratio: compression_ratio(compressed_size, uncompressed_size),
+            ratio_threshold,
+            flagged_entries,
+        })).into_response()
+    }
+
+    /// Extracts a downloaded ZIP file.
+    ///
+    /// Extraction is aborted when the archive declares more
+    /// entries than `MAX_EXTRACT_FILES`, when a single entry or the archive as a whole
+    /// decompresses past `MAX_ENTRY_EXTRACTED_BYTES` or `MAX_EXTRACT_BYTES`, or when an entry
+    /// inflates by more than `MAX_EXTRACT_RATIO` times its compressed size. An entry failing
+    /// to extract midway, e.g. on a bad CRC, aborts it too, as its bytes can't be accounted for.
+    ///
+    /// # Parameters
+    /// - `zip_path`: The path of the downloaded ZIP file.
+    /// - `output_dir`: The directory where the file will be extracted.
+    /// - `text_only`: Whether to skip entries detected as binary by extension or content.
+    /// - `config`: The configuration holding the extraction limits.
+    ///
+    /// # Returns
+    /// The extracted and skipped files.
+    fn extract_zip(
+        zip_path: &Path,
+        output_dir: &Path,
+        text_only: bool,
+        config: &AppConfig,
+    ) -> Result<Extraction, AppError> {
+        info!("Starting extraction of ZIP file: {:?}", zip_path);
+
+        let mut extraction = Extraction::default();
+
+        let file = File::open(zip_path).map_err(|e| {
+            error!("Failed to open ZIP file for extraction: {:?}. Error: {:?}", zip_path, e);
+            AppError::FileIoError(e)
+        })?;
+
+        let mut archive = ZipArchive::new(file).map_err(|e| {
+            error!("Failed to read ZIP archive: {:?}. Error: {:?}", zip_path, e);
+            AppError::FileIoError(io::Error::other(e))
+        })?;
+
+        if archive.len() > config.max_archive_entries {
+            return Err(Self::abort_extraction(output_dir, format!(
+                "Archive contains {} entries, exceeding the limit of {}",
+                archive.len(), config.max_archive_entries
+            )));
+        }
+
+        let mut total_bytes: u64 = 0;
+
+        for i in 0..archive.len() {
+            let mut file = match archive.by_index(i) {
+                Ok(file) => file,
+                Err(e) => {
+                    warn!("Failed to read file at index {} in ZIP archive. Error: {:?}", i, e);
+                    continue;
+                }
+            };
+
+            let relative = file.mangled_name();
+            let outpath = output_dir.join(&relative);
+
+            if file.is_dir() {
+                if let Err(e) = create_dir_all(&outpath) {
+                    warn!("Failed to create directory: {:?}. Error: {:?}", outpath, e);
+                    continue;
+                }
+            } else {
+                let entry_name = file.name().to_string();
+
+                if file.size() > config.max_entry_extracted_bytes {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Entry '{}' declares {} bytes, exceeding the per-entry limit of {} bytes",
+                        entry_name, file.size(), config.max_entry_extracted_bytes
+                    )));
+                }
+
+                let compressed_size = file.compressed_size();
+                if exceeds_ratio(compressed_size, file.size(), config.max_extract_ratio) {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Entry '{}' declares a compression ratio above the limit of {}",
+                        entry_name, config.max_extract_ratio
+                    )));
+                }
+
+                if text_only && has_binary_extension(&outpath) {
+                    extraction.skipped.push(entry_name);
+                    continue;
+                }
+
+                let budget = config.max_entry_extracted_bytes
+                    .min(config.max_total_extracted_bytes.saturating_sub(total_bytes));
+                let written = match Self::write_entry(&mut file, &outpath, budget, text_only) {
+                    Ok(Some(written)) => written,
+                    Ok(None) => {
+                        extraction.skipped.push(entry_name);
+                        continue;
+                    }
+                    // What was written of the entry is unaccounted for, so the limits no longer hold
+                    Err(e) => {
+                        return Err(Self::abort_extraction(output_dir, format!(
+                            "Failed to extract entry '{}': {}",
+                            entry_name, e
+                        )));
+                    }
+                };
+                total_bytes += written;
+
+                if written > config.max_entry_extracted_bytes {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Entry '{}' exceeds the per-entry limit of {} bytes",
+                        entry_name, config.max_entry_extracted_bytes
+                    )));
+                }
+
+                if total_bytes > config.max_total_extracted_bytes {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Archive exceeds the total extraction limit of {} bytes",
+                        config.max_total_extracted_bytes
+                    )));
+                }
+
+                // The declared size may understate the entry, so check what was written
+                if exceeds_ratio(compressed_size, written, config.max_extract_ratio) {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Entry '{}' exceeds the compression ratio limit of {}",
+                        entry_name, config.max_extract_ratio
+                    )));
+                }
+
+                extraction.files.push(relative.to_string_lossy().to_string());
+            }
+        }
+
+        Ok(extraction)
+    }
+
+    /// Extracts a downloaded tar.gz file.
+    ///
+    /// The same limits as ZIP extraction apply, the compression ratio being checked for the
+    /// archive as a whole since tar entries aren't compressed individually, and an entry
+    /// failing to extract aborts the extraction as well. Entries other than
+    /// files and directories, such as symbolic links, are skipped. Files not starting with the
+    /// gzip magic number are rejected before anything is extracted.
+    ///
+    /// # Parameters
+    /// - `tar_gz_path`: The path of the downloaded tar.gz file.
+    /// - `output_dir`: The directory where the tar.gz file will be extracted.
+    /// - `text_only`: Whether to skip entries detected as binary by extension or content.
+    /// - `config`: The configuration holding the extraction limits.
+    ///
+    /// # Returns
+    /// - `Ok(Extraction)`: The files extracted from the tar.gz file and the binaries skipped.
+    /// - `Err(AppError)`: An error if the extraction fails.
+    fn extract_tar_gz(
+        tar_gz_path: &Path,
+        output_dir: &Path,
+        text_only: bool,
+        config: &AppConfig,
+    ) -> Result<Extraction, AppError> {
+        info!("Starting extraction of tar.gz file: {:?}", tar_gz_path);
+
+        let mut extraction = Extraction::default();
+
+        let mut file = File::open(tar_gz_path).map_err(|e| {
+            error!("Failed to open tar.gz file for extraction: {:?}. Error: {:?}", tar_gz_path, e);
+            AppError::FileIoError(e)
+        })?;
+        let compressed_size = file.metadata()?.len();
+
+        // A plain tar or a truncated file would otherwise only fail as an opaque decoding error
+        let mut header = [0u8; 2];
+        if file.read_exact(&mut header).is_err() || header != GZIP_MAGIC {
+            return Err(Self::abort_extraction(output_dir, "Archive is not a gzip stream".to_string()));
+        }
+        file.seek(SeekFrom::Start(0))?;
+
+        let mut archive = tar::Archive::new(GzDecoder::new(file));
+        let entries = archive.entries().map_err(|e| {
+            error!("Failed to read tar.gz archive: {:?}. Error: {:?}", tar_gz_path, e);
+            AppError::FileIoError(e)
+        })?;
+
+        let mut total_bytes: u64 = 0;
+
+        for (i, entry) in entries.enumerate() {
+            // The entries are read from a single stream, so nothing past a corrupt one is readable
+            let mut entry = entry.map_err(|e| {
+                error!("Failed to read entry {} of tar.gz archive: {:?}. Error: {:?}", i, tar_gz_path, e);
+                AppError::FileIoError(e)
+            })?;
+
+            if i >= config.max_archive_entries {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Archive contains more than {} entries",
+                    config.max_archive_entries
+                )));
+            }
+
+            let entry_name = entry.path().map(|path| path.to_string_lossy().to_string()).unwrap_or_default();
+
+            // Entries escaping the extraction directory can only come from a malicious archive
+            let Some(relative) = entry.path().ok().and_then(|path| enclosed_path(&path)) else {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Entry '{}' has an absolute path or escapes the extraction directory",
+                    entry_name
+                )));
+            };
+            if relative.as_os_str().is_empty() {
+                continue;
+            }
+
+            let outpath = output_dir.join(&relative);
+            let entry_type = entry.header().entry_type();
+
+            if entry_type.is_dir() {
+                if let Err(e) = create_dir_all(&outpath) {
+                    warn!("Failed to create directory: {:?}. Error: {:?}", outpath, e);
+                }
+                continue;
+            }
+
+            if !entry_type.is_file() {
+                warn!("Skipping tar entry '{}' of unsupported type {:?}", entry_name, entry_type);
+                continue;
+            }
+
+            let declared_size = entry.header().size().unwrap_or(0);
+            if declared_size > config.max_entry_extracted_bytes {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Entry '{}' declares {} bytes, exceeding the per-entry limit of {} bytes",
+                    entry_name, declared_size, config.max_entry_extracted_bytes
+                )));
+            }
+
+            if text_only && has_binary_extension(&outpath) {
+                extraction.skipped.push(entry_name);
+                continue;
+            }
+
+            let budget = config.max_entry_extracted_bytes
+                .min(config.max_total_extracted_bytes.saturating_sub(total_bytes));
+            let written = match Self::write_entry(&mut entry, &outpath, budget, text_only) {
+                Ok(Some(written)) => written,
+                Ok(None) => {
+                    extraction.skipped.push(entry_name);
+                    continue;
+                }
+                // What was written of the entry is unaccounted for, so the limits no longer hold
+                Err(e) => {
+                    return Err(Self::abort_extraction(output_dir, format!(
+                        "Failed to extract entry '{}': {}",
+                        entry_name, e
+                    )));
+                }
+            };
+            total_bytes += written;
+
+            if written > config.max_entry_extracted_bytes {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Entry '{}' exceeds the per-entry limit of {} bytes",
+                    entry_name, config.max_entry_extracted_bytes
+                )));
+            }
+
+            if total_bytes > config.max_total_extracted_bytes {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Archive exceeds the total extraction limit of {} bytes",
+                    config.max_total_extracted_bytes
+                )));
+            }
+
+            if exceeds_ratio(compressed_size, total_bytes, config.max_extract_ratio) {
+                return Err(Self::abort_extraction(output_dir, format!(
+                    "Archive exceeds the compression ratio limit of {}",
+                    config.max_extract_ratio
+                )));
+            }
+
+            extraction.files.push(relative.to_string_lossy().to_string());
+        }
+
+        Ok(extraction)
+    }
+
+    /// Writes an archive entry to disk, creating its parent directories.
+    ///
+    /// At most one byte past `budget` is read, so an entry overflowing it is detectable
+    /// regardless of what its header declares.
+    ///
+    /// # Parameters
+    /// - `reader`: The content of the entry.
+    /// - `outpath`: The path to write the entry to.
+    /// - `budget`: The number of bytes the entry may take.
+    /// - `text_only`: Whether to skip the entry if its leading bytes look binary.
+    ///
+    /// # Returns
+    /// - `Ok(Some(u64))`: The number of bytes written.
+    /// - `Ok(None)`: If the entry was skipped as binary, before anything was written.
+    /// - `Err(io::Error)`: If the entry can't be read or written.
+    fn write_entry(reader: impl Read, outpath: &Path, budget: u64, text_only: bool) -> io::Result<Option<u64>> {
+        let mut limited = reader.take(budget + 1);
+
+        // In text-only mode, sniff the leading bytes before anything is written
+        let mut prefix = Vec::new();
+        if text_only {
+            (&mut limited).take(BINARY_SNIFF_BYTES).read_to_end(&mut prefix)?;
+
+            if looks_binary(&prefix) {
+                return Ok(None);
+            }
+        }
+
+        if let Some(parent) = outpath.parent() {
+            create_dir_all(parent)?;
+        }
+
+        let mut outfile = File::create(outpath)?;
+        outfile.write_all(&prefix)?;
+        let copied = copy(&mut limited, &mut outfile)?;
+
+        Ok(Some(prefix.len() as u64 + copied))
+    }
+
+    /// Lists the files of an extraction directory, relative to it, as extraction reports them.
+    ///
+    /// # Parameters
+    /// - `output_dir`: The directory the archive was extracted into.
+    ///
+    /// # Returns
+    /// - `Ok(Vec<String>)`: The sorted paths of the extracted files.
+    /// - `Err(AppError)`: If the directory can't be read.
+    pub fn list_extracted_files(&self, output_dir: &str) -> Result<Vec<String>, AppError> {
+        let root = Path::new(output_dir);
+        let mut files = Self::collect_files(root, root)?;
+        files.retain(|file| file != EXTRACTION_MANIFEST);
+        files.sort();
+        Ok(files)
+    }
+
+    /// Recursively collects the paths of the files under a directory.
+    ///
+    /// # Parameters
+    /// - `root`: The extraction root, used to report paths relative to it.
+    /// - `dir`: The directory to scan.
+    ///
+    /// # Returns
+    /// The paths of the files, relative to `root`.
+    fn collect_files(root: &Path, dir: &Path) -> Result<Vec<String>, AppError> {
+        let mut files = Vec::new();
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            if entry.file_type()?.is_dir() {
+                files.extend(Self::collect_files(root, &path)?);
+            } else {
+                files.push(path.strip_prefix(root).unwrap_or(&path).to_string_lossy().to_string());
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Detects the common top-level directory of an extracted archive.
+    ///
+    /// # Parameters
+    /// - `output_dir`: The directory the archive was extracted into.
+    ///
+    /// # Returns
+    /// - `Some(String)`: The name of the directory if it is the only entry in `output_dir`.
+    /// - `None`: If the entries don't share a single root directory.
+    pub fn detect_root_dir(&self, output_dir: &str) -> Option<String> {
+        let mut entries = fs::read_dir(output_dir)
+            .ok()?
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name() != EXTRACTION_MANIFEST);
+
+        let root = entries.next()?;
+        if entries.next().is_some() || !root.path().is_dir() {
+            return None;
+        }
+
+        Some(root.file_name().to_string_lossy().to_string())
+    }
+
+    /// Removes a partially extracted output directory and builds the validation error to return.
+    ///
+    /// # Parameters
+    /// - `output_dir`: The directory the archive was being extracted into.
+    /// - `message`: The reason extraction was aborted.
+    ///
+    /// # Returns
+    /// The `AppError::ValidationError` describing why extraction was aborted.
+    fn abort_extraction(output_dir: &Path, message: String) -> AppError {
+        error!("Aborting extraction into {:?}: {}", output_dir, message);
+
+        if let Err(e) = fs::remove_dir_all(output_dir) {
+            warn!("Failed to clean up partial extraction: {:?}. Error: {:?}", output_dir, e);
+        }
+
+        AppError::ValidationError(message)
+    }
+
+    /// Helper function to create an error response.
+    ///
+    /// # Parameters
+    /// - `status_code`: The HTTP status code.
+    /// - `message`: The error message.
+    ///
+    /// # Returns
+    /// The response to return to the client.
+    fn error_response(&self, status_code: StatusCode, message: &str) -> Response {
+        error!("Returning error response: {} - {}", status_code, message);
+        ErrorResponse::new(status_code, message).into_response()
+    }
+
+    /// Helper function to create the result of a stored file.
+    ///
+    /// # Parameters
+    /// - `id`: The id of the recorded upload.
+    /// - `file_name`: The name of the uploaded file.
+    /// - `key`: The storage key of the file.
+    /// - `deduplicated`: Whether the content was already stored under the key.
+    /// - `existing_file_name`: The name the content was first uploaded under, with `DEDUPLICATE_UPLOADS`.
+    /// - `file`: The validated file.
+    ///
+    /// # Returns
+    /// The result of the file, stored under its key.
+    fn success_result(
+        &self,
+        id: Uuid,
+        file_name: String,
+        key: String,
+        deduplicated: bool,
+        existing_file_name: Option<String>,
+        file: &ValidatedFile,
+    ) -> UploadResult {
+        info!("Returning success result for file: {} ({} bytes)", file_name, file.size);
+        let message = if deduplicated { "File already uploaded" } else { "File uploaded successfully" };
+        UploadResult::Stored(UploadResponse {
+            message: message.to_string(),
+            id,
+            file_name,
+            key,
+            size: file.size as u64,
+            sha256: file.sha256.clone(),
+            sniffed_mime_type: file.sniffed_mime_type.clone(),
+            deduplicated,
+            existing_file_name,
+        })
+    }
+
+    /// Builds the result of a file that could not be uploaded.
+    ///
+    /// # Parameters
+    /// - `file_name`: The name the file was uploaded under.
+    /// - `status`: The status describing the failure.
+    /// - `message`: The error message.
+    fn failure_result(&self, file_name: String, status: StatusCode, message: &str) -> (StatusCode, UploadResult) {
+        (status, UploadResult::Failed(UploadFailure {
+            file_name,
+            error: message.to_string(),
+            code: status.as_u16(),
+        }))
+    }
+
+    /// Retrieves the cached list of the extracted files of a codebase from Redis.
+    ///
+    /// Lists are cached under `file_cache:{name}` as a JSON array of paths relative to the
+    /// extraction directory, and replaced whenever the codebase is extracted again.
+    ///
+    /// # Parameters
+    /// - `base_name`: The name of the codebase.
+    ///
+    /// # Returns
+    /// - `Ok(Some(Vec<String>))`: The cached paths of the extracted files.
+    /// - `Ok(None)`: If no list is cached for this codebase.
+    /// - `Err(AppError)`: If Redis can't be reached or the cached value can't be parsed.
+    pub async fn get_cached_files(&self, base_name: &str) -> Result<Option<Vec<String>>, AppError> {
+        let mut con = self.clients
+            .get_redis_client()
+            .get_connection()
+            .await?;
+
+        let cached: Option<String> = con
+            .get(self.clients.get_redis_client().key(&format!("file_cache:{}", base_name)))
+            .await?;
+
+        cached
+            .map(|cached| serde_json::from_str(&cached))
+            .transpose()
+            .map_err(AppError::SerializationError)
+    }
+
+    /// Returns a reference to the application configuration.
+    pub fn get_config(&self) -> &AppConfig {
+        &self.config
+    }
+
+    /// Retrieves the cached codebase JSON tree of a repository from Redis.
+    ///
+    /// Trees are cached under `codebase_json:{name}:{variant}:{modified}`, where `modified` is the
+    /// modification time of the repository directory in nanoseconds since the Unix epoch.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the repository.
+    /// - `variant`: The options the tree was generated with, such as its detail level.
+    /// - `modified`: The modification time of the repository directory.
+    ///
+    /// # Returns
+    /// - `Ok(Some(CodebaseTreeResponse))`: The cached response body holding the tree.
+    /// - `Ok(None)`: If no tree is cached for this directory state.
+    /// - `Err(AppError)`: If Redis can't be reached or the cached value can't be parsed.
+    pub async fn get_cached_codebase_json(
+        &self,
+        name: &str,
+        variant: &str,
+        modified: u128,
+    ) -> Result<Option<CodebaseTreeResponse>, AppError> {
+        let mut con = self.clients
+            .get_redis_client()
+            .get_connection()
+            .await?;
+
+        let cached: Option<String> = con
+            .get(self.clients.get_redis_client().key(&format!("codebase_json:{}:{}:{}", name, variant, modified)))
+            .await?;
+
+        cached
+            .map(|cached| serde_json::from_str(&cached))
+            .transpose()
+            .map_err(AppError::SerializationError)
+    }
+
+    /// Caches the codebase JSON tree of a repository in Redis.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the repository.
+    /// - `variant`: The options the tree was generated with, such as its detail level.
+    /// - `modified`: The modification time of the repository directory.
+    /// - `body`: The response body holding the tree.
+    pub async fn cache_codebase_json(
+        &self,
+        name: &str,
+        variant: &str,
+        modified: u128,
+        body: &CodebaseTreeResponse,
+    ) -> Result<(), AppError> {
+        let mut con = self.clients
+            .get_redis_client()
+            .get_connection()
+            .await?;
+
+        let body_json = serde_json::to_string(body)?;
+        let cache_key = self.clients
+            .get_redis_client()
+            .key(&format!("codebase_json:{}:{}:{}", name, variant, modified));
+
+        let _: () = con
+            .set_ex(cache_key, body_json, 3600)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Removes every cached codebase JSON tree of a repository from Redis.
+    ///
+    /// # Parameters
+    /// - `name`: The name of the repository.
+    pub async fn invalidate_codebase_json(&self, name: &str) -> Result<(), AppError> {
+        let redis_client = self.clients.get_redis_client();
+        let pattern = escape_glob(&redis_client.key(&format!("codebase_json:{}:", name)));
+        redis_client.delete_matching(&format!("{}*", pattern)).await?;
+
+        Ok(())
+    }
+
+    /// Caches the list of the extracted files of a codebase in Redis, under `file_cache:{name}`.
+    ///
+    /// # Parameters
+    /// - `base_name`: The name of the codebase.
+    /// - `files`: The paths of the extracted files, relative to the extraction directory.
+    pub async fn cache_files(&self, base_name: &str, files: &[String]) -> Result<(), AppError> {
+        let mut con = self.clients
+            .get_redis_client()
+            .get_connection()
+            .await?;
+
+        let cache_key = self.clients.get_redis_client().key(&format!("file_cache:{}", base_name));
+        let files_json = serde_json::to_string(&files)
+            .map_err(AppError::SerializationError)?;
+
+        let _: () = con.set_ex(cache_key, files_json, 3600)
+            .await?;
+
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+    use tempfile::TempDir;
+    use tar::EntryType;
+    use crate::utils::file_utils::compute_sha256;
+    use crate::utils::test_archives::{set_first_zip_entry_size, tar_gz_archive, tar_gz_files, zip_archive};
+
+    /// An archive written to a temporary directory, and the directory to extract it into.
+    struct Fixture {
+        _dir: TempDir,
+        archive: PathBuf,
+        output_dir: PathBuf,
+    }
+
+    impl Fixture {
+        fn new(file_name: &str, archive: &[u8]) -> Self {
+            let dir = TempDir::new().unwrap();
+            let path = dir.path().join(file_name);
+            fs::write(&path, archive).unwrap();
+            let output_dir = dir.path().join("output");
+            create_dir_all(&output_dir).unwrap();
+            Self { archive: path, output_dir, _dir: dir }
+        }
+
+        fn extract_zip(&self, config: &AppConfig) -> Result<Extraction, AppError> {
+            FileService::extract_zip(&self.archive, &self.output_dir, false, config)
+        }
+
+        fn extract_tar_gz(&self, config: &AppConfig) -> Result<Extraction, AppError> {
+            FileService::extract_tar_gz(&self.archive, &self.output_dir, false, config)
+        }
+    }
+
+    fn config_with(configure: impl FnOnce(&mut AppConfig)) -> AppConfig {
+        let mut config = AppConfig::for_tests();
+        configure(&mut config);
+        config
+    }
+
+    fn validation_message(result: Result<Extraction, AppError>) -> String {
+        match result {
+            Err(AppError::ValidationError(message)) => message,
+            Err(e) => panic!("unexpected error: {:?}", e),
+            Ok(extraction) => panic!("the archive was extracted: {:?}", extraction.files),
+        }
+    }
+
+    #[test]
+    fn high_ratio_zip_is_rejected_and_its_output_removed() {
+        let bomb = vec![0u8; 4 * 1024 * 1024];
+        let fixture = Fixture::new("bomb.zip", &zip_archive(&[("readme.txt", b"hello"), ("zeros.bin", &bomb)]));
+        let config = config_with(|config| config.max_extract_ratio = 100.0);
+
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Entry 'zeros.bin' declares a compression ratio above the limit of 100");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn high_ratio_entry_understating_its_size_is_rejected() {
+        let bomb = vec![0u8; 4 * 1024 * 1024];
+        let mut archive = zip_archive(&[("zeros.bin", &bomb)]);
+        set_first_zip_entry_size(&mut archive, 10);
+        let fixture = Fixture::new("bomb.zip", &archive);
+        let config = config_with(|config| config.max_extract_ratio = 100.0);
+
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Entry 'zeros.bin' exceeds the compression ratio limit of 100");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn small_repetitive_entries_are_not_checked_against_the_ratio() {
+        let fixture = Fixture::new("small.zip", &zip_archive(&[("zeros.txt", &[b'0'; 64 * 1024])]));
+        let config = config_with(|config| config.max_extract_ratio = 2.0);
+
+        let extraction = fixture.extract_zip(&config).unwrap();
+        assert_eq!(extraction.files, ["zeros.txt"]);
+    }
+
+    #[test]
+    fn entry_failing_to_extract_aborts_the_extraction() {
+        // The second entry can't be created because the first one is a file at its parent path
+        let fixture = Fixture::new("clash.zip", &zip_archive(&[("src", b"a file"), ("src/main.rs", b"fn main() {}")]));
+
+        let message = validation_message(fixture.extract_zip(&AppConfig::for_tests()));
+        assert!(message.starts_with("Failed to extract entry 'src/main.rs'"), "{}", message);
+        assert!(!fixture.output_dir.exists());
+        assert!(fixture.archive.exists());
+    }
+
+    #[test]
+    fn archive_above_the_entry_count_limit_is_rejected() {
+        let files: &[(&str, &[u8])] = &[("a", b"a"), ("b", b"b"), ("c", b"c")];
+        let config = config_with(|config| config.max_archive_entries = 2);
+
+        let fixture = Fixture::new("many.zip", &zip_archive(files));
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Archive contains 3 entries, exceeding the limit of 2");
+        assert!(!fixture.output_dir.exists());
+
+        let fixture = Fixture::new("many.tar.gz", &tar_gz_files(files));
+        let message = validation_message(fixture.extract_tar_gz(&config));
+        assert_eq!(message, "Archive contains more than 2 entries");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn entry_above_the_size_limit_is_rejected_and_the_partial_output_removed() {
+        let files: &[(&str, &[u8])] = &[("small.txt", &[b'a'; 10]), ("large.txt", &[b'a'; 101])];
+        let config = config_with(|config| config.max_entry_extracted_bytes = 100);
+
+        let fixture = Fixture::new("large.zip", &zip_archive(files));
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Entry 'large.txt' declares 101 bytes, exceeding the per-entry limit of 100 bytes");
+        assert!(!fixture.output_dir.exists());
+
+        let fixture = Fixture::new("large.tar.gz", &tar_gz_files(files));
+        let message = validation_message(fixture.extract_tar_gz(&config));
+        assert_eq!(message, "Entry 'large.txt' declares 101 bytes, exceeding the per-entry limit of 100 bytes");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn entry_understating_its_size_is_cut_at_the_limit() {
+        let mut archive = zip_archive(&[("large.txt", &[b'a'; 1000])]);
+        set_first_zip_entry_size(&mut archive, 10);
+        let fixture = Fixture::new("large.zip", &archive);
+        let config = config_with(|config| config.max_entry_extracted_bytes = 100);
+
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Entry 'large.txt' exceeds the per-entry limit of 100 bytes");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn archive_above_the_total_size_limit_is_rejected_and_the_partial_output_removed() {
+        let files: &[(&str, &[u8])] = &[("first.txt", &[b'a'; 60]), ("second.txt", &[b'a'; 60])];
+        let config = config_with(|config| config.max_total_extracted_bytes = 100);
+
+        let fixture = Fixture::new("total.zip", &zip_archive(files));
+        let message = validation_message(fixture.extract_zip(&config));
+        assert_eq!(message, "Archive exceeds the total extraction limit of 100 bytes");
+        assert!(!fixture.output_dir.exists());
+
+        let fixture = Fixture::new("total.tar.gz", &tar_gz_files(files));
+        let message = validation_message(fixture.extract_tar_gz(&config));
+        assert_eq!(message, "Archive exceeds the total extraction limit of 100 bytes");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn archive_within_the_limits_is_extracted() {
+        let files: &[(&str, &[u8])] = &[("first.txt", &[b'a'; 50]), ("dir/second.txt", &[b'b'; 50])];
+        let config = config_with(|config| {
+            config.max_archive_entries = 2;
+            config.max_entry_extracted_bytes = 50;
+            config.max_total_extracted_bytes = 100;
+        });
+
+        let fixture = Fixture::new("ok.zip", &zip_archive(files));
+        let extraction = fixture.extract_zip(&config).unwrap();
+        assert_eq!(extraction.files, ["first.txt", "dir/second.txt"]);
+        assert_eq!(fs::read(fixture.output_dir.join("dir/second.txt")).unwrap(), [b'b'; 50]);
+    }
+
+    #[test]
+    fn tar_entry_escaping_the_extraction_directory_is_rejected() {
+        let archive = tar_gz_archive(&[
+            ("ok.txt", EntryType::Regular, b"ok"),
+            ("../../evil", EntryType::Regular, b"evil"),
+        ]);
+        let fixture = Fixture::new("slip.tar.gz", &archive);
+
+        let message = validation_message(fixture.extract_tar_gz(&AppConfig::for_tests()));
+        assert_eq!(message, "Entry '../../evil' has an absolute path or escapes the extraction directory");
+        assert!(!fixture.output_dir.exists());
+        assert!(!fixture.output_dir.join("../../evil").exists());
+        assert!(!fixture.output_dir.parent().unwrap().join("evil").exists());
+    }
+
+    #[test]
+    fn tar_entry_with_an_absolute_path_is_rejected() {
+        let fixture = Fixture::new("absolute.tar.gz", &tar_gz_archive(&[("/tmp/evil", EntryType::Regular, b"evil")]));
+
+        let message = validation_message(fixture.extract_tar_gz(&AppConfig::for_tests()));
+        assert_eq!(message, "Entry '/tmp/evil' has an absolute path or escapes the extraction directory");
+        assert!(!fixture.output_dir.exists());
+    }
+
+    #[test]
+    fn tar_entry_with_parent_components_staying_inside_is_extracted() {
+        let fixture = Fixture::new("inside.tar.gz", &tar_gz_files(&[("src/../README.md", b"# Readme"), ("./src/main.rs", b"fn main() {}")]));
+
+        let extraction = fixture.extract_tar_gz(&AppConfig::for_tests()).unwrap();
+        assert_eq!(extraction.files, ["README.md", "src/main.rs"]);
+        assert_eq!(fs::read(fixture.output_dir.join("README.md")).unwrap(), b"# Readme");
+    }
+
+    #[test]
+    fn tar_links_are_skipped() {
+        let archive = tar_gz_archive(&[("link", EntryType::Symlink, b""), ("file.txt", EntryType::Regular, b"text")]);
+        let fixture = Fixture::new("links.tar.gz", &archive);
+
+        let extraction = fixture.extract_tar_gz(&AppConfig::for_tests()).unwrap();
+        assert_eq!(extraction.files, ["file.txt"]);
+        assert!(fs::symlink_metadata(fixture.output_dir.join("link")).is_err());
+    }
+
+    #[test]
+    fn zip_entry_escaping_the_extraction_directory_stays_inside() {
+        let fixture = Fixture::new("slip.zip", &zip_archive(&[("../../evil", b"evil")]));
+
+        let extraction = fixture.extract_zip(&AppConfig::for_tests()).unwrap();
+        assert_eq!(extraction.files, ["evil"]);
+        assert!(fixture.output_dir.join("evil").exists());
+        assert!(!fixture.output_dir.parent().unwrap().join("evil").exists());
+    }
+
+    #[test]
+    fn enclosed_path_resolves_entries_within_the_directory() {
+        assert_eq!(enclosed_path(Path::new("a/./b/../c")), Some(PathBuf::from("a/c")));
+        assert_eq!(enclosed_path(Path::new("a/..")), Some(PathBuf::new()));
+        assert_eq!(enclosed_path(Path::new("a/../../b")), None);
+        assert_eq!(enclosed_path(Path::new("/etc/passwd")), None);
+    }
+
+    #[test]
+    fn tar_gz_without_a_gzip_header_is_rejected() {
+        for (name, content) in [("plain.tar.gz", b"plain text, not gzip".as_slice()), ("empty.tar.gz", b""), ("short.tar.gz", b"\x1f")] {
+            let fixture = Fixture::new(name, content);
+
+            let message = validation_message(fixture.extract_tar_gz(&AppConfig::for_tests()));
+            assert_eq!(message, "Archive is not a gzip stream", "{}", name);
+            assert!(!fixture.output_dir.exists(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn truncated_gzip_stream_fails_to_extract() {
+        let archive = tar_gz_files(&[("file.txt", &[b'a'; 4096])]);
+        let fixture = Fixture::new("truncated.tar.gz", &archive[..archive.len() / 2]);
+
+        assert!(fixture.extract_tar_gz(&AppConfig::for_tests()).is_err());
+    }
+
+    #[test]
+    fn archive_type_is_detected_from_the_content() {
+        let zip = Fixture::new("archive", &zip_archive(&[("a.txt", b"a")]));
+        let tar_gz = Fixture::new("archive", &tar_gz_files(&[("a.txt", b"a")]));
+
+        assert_eq!(ArchiveType::resolve(&zip.archive, "archive", None).unwrap(), ArchiveType::Zip);
+        assert_eq!(ArchiveType::resolve(&tar_gz.archive, "archive", None).unwrap(), ArchiveType::TarGz);
+    }
+
+    #[test]
+    fn mislabeled_archive_is_extracted_as_its_content_type() {
+        let zip = Fixture::new("archive.tar.gz", &zip_archive(&[("a.txt", b"a")]));
+        let tar_gz = Fixture::new("archive.zip", &tar_gz_files(&[("a.txt", b"a")]));
+
+        let declared = ArchiveType::from_key("archive.tar.gz");
+        assert_eq!(declared, Some(ArchiveType::TarGz));
+        assert_eq!(ArchiveType::resolve(&zip.archive, "archive.tar.gz", declared).unwrap(), ArchiveType::Zip);
+        assert_eq!(ArchiveType::resolve(&tar_gz.archive, "archive.zip", Some(ArchiveType::Zip)).unwrap(), ArchiveType::TarGz);
+    }
+
+    #[test]
+    fn archive_of_unknown_content_falls_back_to_its_extension() {
+        let fixture = Fixture::new("archive.zip", b"not an archive");
+
+        assert_eq!(ArchiveType::resolve(&fixture.archive, "archive.zip", Some(ArchiveType::Zip)).unwrap(), ArchiveType::Zip);
+        assert!(matches!(
+            ArchiveType::resolve(&fixture.archive, "archive", None),
+            Err(AppError::ValidationError(_))
+        ));
+    }
+
+    #[test]
+    fn zip_and_tar_gz_of_the_same_content_report_the_same_files() {
+        let entries: &[(&str, &[u8])] = &[("project/src/main.rs", b"fn main() {}"), ("project/README.md", b"# Project")];
+        let zip = Fixture::new("project.zip", &zip_archive(entries));
+        let tar_gz = Fixture::new("project.tar.gz", &tar_gz_files(entries));
+        let config = AppConfig::for_tests();
+
+        let mut zip_files = zip.extract_zip(&config).unwrap().files;
+        let mut tar_gz_files = tar_gz.extract_tar_gz(&config).unwrap().files;
+        zip_files.sort();
+        tar_gz_files.sort();
+
+        assert_eq!(zip_files, vec!["project/README.md", "project/src/main.rs"]);
+        assert_eq!(tar_gz_files, zip_files);
+        assert_eq!(FileService::collect_files(&zip.output_dir, &zip.output_dir).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn text_only_extraction_skips_binaries() {
+        let entries: &[(&str, &[u8])] = &[
+            ("src/lib.rs", b"pub fn run() {}"),
+            ("assets/logo.png", b"not even an image"),
+            ("build/output", b"\x7fELF\x02\x01\x01\x00"),
+        ];
+        let zip = Fixture::new("mixed.zip", &zip_archive(entries));
+        let tar_gz = Fixture::new("mixed.tar.gz", &tar_gz_files(entries));
+        let config = AppConfig::for_tests();
+
+        for (fixture, extraction) in [
+            (&zip, FileService::extract_zip(&zip.archive, &zip.output_dir, true, &config).unwrap()),
+            (&tar_gz, FileService::extract_tar_gz(&tar_gz.archive, &tar_gz.output_dir, true, &config).unwrap()),
+        ] {
+            let mut skipped = extraction.skipped;
+            skipped.sort();
+
+            assert_eq!(extraction.files, vec!["src/lib.rs"]);
+            assert_eq!(skipped, vec!["assets/logo.png", "build/output"]);
+            assert!(fixture.output_dir.join("src/lib.rs").is_file());
+            assert!(!fixture.output_dir.join("assets/logo.png").exists());
+            assert!(!fixture.output_dir.join("build/output").exists());
+        }
+    }
+
+    /// A file service storing its files locally under a temporary directory.
+    struct LocalService {
+        dir: TempDir,
+        service: FileService,
+    }
+
+    impl LocalService {
+        fn new() -> Self {
+            let dir = TempDir::new().unwrap();
+            let mut config = AppConfig::for_tests();
+            config.local_storage_dir = dir.path().join("storage").to_string_lossy().into_owned();
+            let validator = Arc::new(FileValidator::new(&config));
+            let clients = Arc::new(Clients::for_tests(&config));
+            Self { service: FileService::new(clients, Arc::new(config), validator), dir }
+        }
+
+        async fn store(&self, key: &str, data: &[u8]) {
+            self.service.clients.get_storage().upload(key, data, &compute_sha256(data), None, None, None).await.unwrap();
+        }
+
+        fn output_dir(&self, name: &str) -> String {
+            self.dir.path().join("competitions").join(name).to_string_lossy().into_owned()
+        }
+    }
+
+    #[tokio::test]
+    async fn stored_archives_are_extracted_from_the_local_backend() {
+        let local = LocalService::new();
+        local.store("zipped.zip", &zip_archive(&[("src/main.rs", b"fn main() {}")])).await;
+        local.store("tarred.tar.gz", &tar_gz_files(&[("src/lib.rs", b"pub fn run() {}")])).await;
+        // The type of an archive stored without extension is detected from its content
+        local.store("bare", &zip_archive(&[("README.md", b"# Test")])).await;
+
+        for (name, file) in [("zipped", "src/main.rs"), ("tarred", "src/lib.rs"), ("bare", "README.md")] {
+            let output_dir = local.output_dir(name);
+            let extraction = local.service.download_and_extract_archive(name, &output_dir, false).await.unwrap();
+            assert_eq!(extraction.files, vec![file], "{}", name);
+            assert!(Path::new(&output_dir).join(file).is_file());
+            assert!(!local.service.extraction_is_stale(name, &output_dir).await, "{}", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn extraction_is_stale_once_its_archive_is_replaced() {
+        let local = LocalService::new();
+        let output_dir = local.output_dir("competition");
+        local.store("competition.zip", &zip_archive(&[("src/main.rs", b"fn main() {}")])).await;
+        local.service.download_and_extract_archive("competition", &output_dir, false).await.unwrap();
+
+        local.store("competition.zip", &zip_archive(&[("src/main.rs", b"fn main() { run() }")])).await;
+        assert!(local.service.extraction_is_stale("competition", &output_dir).await);
+
+        fs::remove_file(Path::new(&output_dir).join(EXTRACTION_MANIFEST)).unwrap();
+        assert!(local.service.extraction_is_stale("competition", &output_dir).await);
+    }
+
+    #[tokio::test]
+    async fn missing_archive_is_not_found() {
+        let local = LocalService::new();
+
+        let result = local.service.download_and_extract_archive("missing", &local.output_dir("missing"), false).await;
+        assert!(matches!(result, Err(AppError::ObjectNotFound(_))));
+    }
+
+    async fn inspection(response: Response) -> (StatusCode, serde_json::Value) {
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn inspection_flags_the_highly_compressed_entries() {
+        let local = LocalService::new();
+        let zeros = vec![0u8; 1024 * 1024];
+        local.store("mixed.zip", &zip_archive(&[("readme.txt", b"hello"), ("zeros.bin", &zeros)])).await;
+
+        let (status, inspection) = inspection(local.service.inspect_archive("mixed.zip").await).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(inspection["ratio_threshold"], 100.0);
+        assert_eq!(inspection["flagged_entries"], 1);
+
+        let readme = &inspection["entries"][0];
+        assert_eq!(readme["name"], "readme.txt");
+        assert_eq!(readme["uncompressed_size"], 5);
+        assert_eq!(readme["flagged"], false);
+
+        let zeros = &inspection["entries"][1];
+        assert_eq!(zeros["name"], "zeros.bin");
+        assert_eq!(zeros["uncompressed_size"], 1024 * 1024);
+        let compressed_size = zeros["compressed_size"].as_u64().unwrap();
+        let ratio = zeros["ratio"].as_f64().unwrap();
+        assert!((ratio - (1024 * 1024) as f64 / compressed_size as f64).abs() < 1e-9);
+        assert!(ratio > 100.0);
+        assert_eq!(zeros["flagged"], true);
+    }
+
+    #[tokio::test]
+    async fn inspection_of_a_missing_or_non_zip_archive_fails() {
+        let local = LocalService::new();
+        local.store("sources.tar.gz", &tar_gz_files(&[("src/lib.rs", b"pub fn run() {}")])).await;
+
+        let (status, _) = inspection(local.service.inspect_archive("missing.zip").await).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, error) = inspection(local.service.inspect_archive("sources.tar.gz").await).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error["error"], "'sources.tar.gz' is not a ZIP archive");
+    }
+
+    #[tokio::test]
+    async fn extraction_does_not_block_the_runtime() {
+        let local = LocalService::new();
+        let content = "fn main() { println!(\"hello\"); }\n".repeat(128);
+        let names: Vec<String> = (0..2000).map(|index| format!("src/module_{}.rs", index)).collect();
+        let files: Vec<(&str, &[u8])> = names.iter().map(|name| (name.as_str(), content.as_bytes())).collect();
+        local.store("large.zip", &zip_archive(&files)).await;
+        let output_dir = local.output_dir("large");
+
+        // The test runtime has a single thread, which extraction would stall if run on it
+        let started = Instant::now();
+        let extraction = local.service.download_and_extract_archive("large", &output_dir, false);
+        tokio::pin!(extraction);
+        let mut longest_stall = Duration::ZERO;
+        let extraction = loop {
+            let tick = Instant::now();
+            tokio::select! {
+                extraction = &mut extraction => break extraction.unwrap(),
+                _ = tokio::time::sleep(Duration::from_millis(1)) => longest_stall = longest_stall.max(tick.elapsed()),
+            }
+        };
+        let elapsed = started.elapsed();
+
+        assert_eq!(extraction.files.len(), 2000);
+        assert!(longest_stall < Duration::from_millis(50) && longest_stall < elapsed / 4, "stalled for {:?} of {:?}", longest_stall, elapsed);
+    }
+
+    #[tokio::test]
+    async fn concurrent_extractions_into_the_same_directory_do_not_interfere() {
+        let local = LocalService::new();
+        local.store("shared.zip", &zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", b"# Shared")])).await;
+        let output_dir = local.output_dir("shared");
+
+        let (first, second) = tokio::join!(
+            local.service.download_and_extract_archive("shared", &output_dir, false),
+            local.service.download_and_extract_archive("shared", &output_dir, false),
+        );
+        for extraction in [first, second] {
+            assert_eq!(extraction.unwrap().files, ["src/main.rs", "README.md"]);
+        }
+
+        assert_eq!(fs::read(Path::new(&output_dir).join("src/main.rs")).unwrap(), b"fn main() {}");
+        // Nothing is left of the staging directories
+        let entries: Vec<_> = fs::read_dir(Path::new(&output_dir).parent().unwrap())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(entries, ["shared"]);
+    }
+}
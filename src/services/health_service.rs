@@ -1,10 +1,20 @@
-use crate::clients::clients::Clients;
+use crate::app_state::AppState;
 use crate::error::AppError;
-use axum::http::StatusCode;
-use redis::{AsyncCommands, RedisError};
-use axum::response::IntoResponse;
+use crate::models::health::{HealthResponse, ServiceHealth, ServiceStatus};
+use axum::http::{HeaderName, HeaderValue, StatusCode};
+use axum::Json;
+use indexmap::IndexMap;
+use log::{info, warn};
+use redis::AsyncCommands;
+use axum::response::{IntoResponse, Response};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
 
 static CACHE_EXPIRATION: u64 = 60; // Cache expiration in seconds
+static CACHE_KEY: &str = "health_check_status";
+static STALE_HEADER: HeaderName = HeaderName::from_static("x-health-stale");
 
 /// Health check types for different services
 #[derive(Clone)]
@@ -16,6 +26,31 @@ pub enum HealthCheckType {
 }
 
 impl HealthCheckType {
+    /// Returns the cache key of each health check type, so every endpoint is cached independently
+    ///
+    /// # Returns
+    ///
+    /// - `String`: The cache key, without the key prefix.
+    fn cache_key(&self) -> String {
+        let name = match self {
+            HealthCheckType::All => "all",
+            HealthCheckType::S3 => "s3",
+            HealthCheckType::Postgres => "postgres",
+            HealthCheckType::Redis => "redis",
+        };
+
+        format!("{}:{}", CACHE_KEY, name)
+    }
+
+    /// Returns the cache key of the last-known-good report of each health check type
+    ///
+    /// # Returns
+    ///
+    /// - `String`: The cache key, without the key prefix.
+    fn last_good_cache_key(&self) -> String {
+        format!("{}:last_good", self.cache_key())
+    }
+
     /// Returns the success message for each health check type
     ///
     /// # Returns
@@ -32,121 +67,379 @@ impl HealthCheckType {
 
     /// Performs the actual health check for the services
     ///
+    /// Each service check is bounded by `HEALTH_CHECK_TIMEOUT_MS`, so a hung connection
+    /// is reported as a failure instead of stalling the health endpoint. The `All` check
+    /// runs the service checks concurrently.
+    ///
     /// # Arguments
     ///
-    /// - `clients`: A reference to the `Clients` struct.
+    /// - `state`: The application state.
     ///
     /// # Returns
     ///
-    /// - `Ok(())`: If the health check is successful.
-    /// - `Err(String)`: If the health check fails.
-    async fn check_health(&self, clients: &Clients) -> Result<(), String> {
+    /// - `HealthResponse`: The health of every checked service, reporting every failure.
+    async fn check_health(&self, state: &AppState) -> HealthResponse {
+        let clients = state.get_clients();
+        let timeout_ms = state.get_config().health_check_timeout_ms;
+        let mut checks = IndexMap::new();
+
         match self {
             HealthCheckType::All => {
-                clients.get_s3_client().test_connection().await
-                    .map_err(|e| format!("S3 Health Check Failed: {}", e))?;
-                clients.get_postgres_client().test_connection().await
-                    .map_err(|e| format!("PostgreSQL Health Check Failed: {}", e))?;
-                clients.get_redis_client().test_connection().await
-                    .map_err(|e| format!("Redis Health Check Failed: {}", e))?;
+                let (storage, postgres_client, redis_client) = (
+                    clients.get_storage(),
+                    clients.get_postgres_client(),
+                    clients.get_redis_client(),
+                );
+
+                // Checks run concurrently, and every failing service is reported
+                let (s3, postgres, redis) = tokio::join!(
+                    run_check("S3", timeout_ms, storage.test_connection()),
+                    run_check("PostgreSQL", timeout_ms, postgres_client.test_connection()),
+                    run_check("Redis", timeout_ms, redis_client.test_connection()),
+                );
+
+                checks.insert("s3".to_string(), s3);
+                checks.insert("postgres".to_string(), postgres);
+                checks.insert("redis".to_string(), redis);
             },
             HealthCheckType::S3 => {
-                clients.get_s3_client().test_connection().await
-                    .map_err(|e| format!("S3 Health Check Failed: {}", e))?;
+                let s3 = run_check("S3", timeout_ms, clients.get_storage().test_connection()).await;
+                checks.insert("s3".to_string(), s3);
             },
             HealthCheckType::Postgres => {
-                clients.get_postgres_client().test_connection().await
-                    .map_err(|e| format!("PostgreSQL Health Check Failed: {}", e))?;
+                let postgres = run_check("PostgreSQL", timeout_ms, clients.get_postgres_client().test_connection()).await;
+                checks.insert("postgres".to_string(), postgres);
             },
             HealthCheckType::Redis => {
-                clients.get_redis_client().test_connection().await
-                    .map_err(|e| format!("Redis Health Check Failed: {}", e))?;
+                let redis = run_check("Redis", timeout_ms, clients.get_redis_client().test_connection()).await;
+                checks.insert("redis".to_string(), redis);
             },
         }
-        Ok(())
+
+        let failures: Vec<&str> = checks
+            .values()
+            .filter_map(|check| check.error.as_deref())
+            .collect();
+
+        let (status, message) = if failures.is_empty() {
+            ("healthy", self.get_success_message())
+        } else if failures.len() < checks.len() {
+            ("degraded", failures.join("; "))
+        } else {
+            ("unhealthy", failures.join("; "))
+        };
+
+        HealthResponse {
+            status: status.to_string(),
+            message,
+            checks,
+            stale: false,
+        }
+    }
+}
+
+/// Runs a single service check with a deadline, measuring its latency
+///
+/// # Arguments
+///
+/// - `service`: The name of the service, used in the error message.
+/// - `timeout_ms`: The deadline of the check in milliseconds.
+/// - `check`: The connection test of the service.
+///
+/// # Returns
+///
+/// - `ServiceHealth`: The outcome and latency of the check.
+async fn run_check(
+    service: &str,
+    timeout_ms: u64,
+    check: impl Future<Output = Result<(), AppError>>,
+) -> ServiceHealth {
+    let started = Instant::now();
+
+    let error = match timeout(Duration::from_millis(timeout_ms), check).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(format!("{} Health Check Failed: {}", service, e)),
+        Err(_) => Some(format!("{} Health Check timed out after {}ms", service, timeout_ms)),
+    };
+
+    ServiceHealth {
+        status: if error.is_none() { ServiceStatus::Up } else { ServiceStatus::Down },
+        latency_ms: started.elapsed().as_millis() as u64,
+        error,
     }
 }
 
 /// Perform the health check and cache the result if successful
 ///
+/// Responds with a `HealthResponse` as JSON, with a 200 when every checked service is
+/// up and a 503 naming the failing checks otherwise.
+///
+/// When the check fails and `HEALTH_STALE_GRACE_SECS` is set, the last-known-good
+/// report is served instead (if still within the grace window), flagged with
+/// `"stale": true` and the `x-health-stale: true` header.
+///
 /// # Arguments
 ///
-/// - `clients`: A reference to the `Clients` struct.
+/// - `state`: The application state.
 /// - `check_type`: The type of health check to perform.
 ///
 pub async fn perform_health_check(
-    clients: &Clients,
+    state: &AppState,
     check_type: HealthCheckType,
-) -> impl IntoResponse {
+) -> Response {
     // Try to return cached result first
-    if let Ok(cached_result) = get_cached_health_check_status(clients).await {
-        return cached_result;
+    if let Ok(Some(cached_report)) = get_cached_health_check_status(state, &check_type.cache_key()).await {
+        return (StatusCode::OK, Json(cached_report)).into_response();
     }
 
     // Perform the actual health check if cache miss
-    let response = match check_type.check_health(clients).await {
-        Ok(()) => {
-            let response = (StatusCode::OK, check_type.get_success_message());
-
-            // Cache the result after success
-            if let Err(e) = cache_health_check_status(clients, &response).await {
-                return (StatusCode::INTERNAL_SERVER_ERROR,
-                        format!("Failed to cache health check status: {}", e));
-            }
+    let report = check_type.check_health(state).await;
+
+    if report.is_healthy() {
+        // Cache the result after success
+        if let Err(e) = cache_health_check_status(state, &check_type, &report).await {
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json(HealthResponse {
+                status: "unhealthy".to_string(),
+                message: format!("Failed to cache health check status: {}", e),
+                ..report
+            })).into_response();
+        }
+
+        return (StatusCode::OK, Json(report)).into_response();
+    }
+
+    if let Ok(Some(mut stale)) = get_stale_health_check_status(state, &check_type).await {
+        warn!("Health check failed, serving stale status: {}", report.message);
+        stale.stale = true;
+        return (
+            StatusCode::OK,
+            [(STALE_HEADER.clone(), HeaderValue::from_static("true"))],
+            Json(stale),
+        ).into_response();
+    }
+
+    (StatusCode::SERVICE_UNAVAILABLE, Json(report)).into_response()
+}
+
+/// Report whether the application can serve traffic, for readiness probes
+///
+/// Every service is checked on each call, without caching, and each check is bounded by
+/// `HEALTH_CHECK_TIMEOUT_MS`. Responds with a 503 while the startup checks haven't
+/// completed or when a required service is down. Services listed in
+/// `HEALTH_OPTIONAL_CHECKS` are reported but only make the status `degraded`.
+///
+/// # Arguments
+///
+/// - `state`: The application state.
+///
+pub async fn perform_readiness_check(state: &AppState) -> Response {
+    if !state.get_clients().is_ready() {
+        return (StatusCode::SERVICE_UNAVAILABLE, Json(HealthResponse {
+            status: "starting".to_string(),
+            message: "Startup checks have not completed yet".to_string(),
+            checks: IndexMap::new(),
+            stale: false,
+        })).into_response();
+    }
+
+    let report = HealthCheckType::All.check_health(state).await;
+    let required_failures = required_failures(state, &report);
+    state.get_clients().set_connected(required_failures.is_empty());
 
-            response
-        },
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e),
+    let (status_code, status, message) = if !required_failures.is_empty() {
+        (StatusCode::SERVICE_UNAVAILABLE, "unready", required_failures.join("; "))
+    } else if report.is_healthy() {
+        (StatusCode::OK, "ready", "Ready to serve traffic".to_string())
+    } else {
+        (StatusCode::OK, "degraded", report.message.clone())
     };
 
-    response
+    (status_code, Json(HealthResponse {
+        status: status.to_string(),
+        message,
+        ..report
+    })).into_response()
 }
 
-/// Retrieve cached health check result from Redis
+/// Returns the failures of the checks that aren't listed in `HEALTH_OPTIONAL_CHECKS`
 ///
 /// # Arguments
 ///
-/// - `clients`: A reference to the `Clients` struct.
+/// - `state`: The application state.
+/// - `report`: The health check report.
+///
+fn required_failures<'a>(state: &AppState, report: &'a HealthResponse) -> Vec<&'a str> {
+    let optional = &state.get_config().health_optional_checks;
+
+    report.checks
+        .iter()
+        .filter(|(name, _)| !optional.iter().any(|optional| optional.eq_ignore_ascii_case(name)))
+        .filter_map(|(_, check)| check.error.as_deref())
+        .collect()
+}
+
+/// Periodically checks the required services, every `READINESS_CHECK_INTERVAL_SECS`
+///
+/// The outcome gates the traffic: while a required service is down, the requests other
+/// than the health checks are answered with a 503 until it recovers.
+///
+/// # Arguments
+///
+/// - `state`: The application state.
+///
+pub async fn run_readiness_task(state: Arc<AppState>) {
+    let interval_secs = state.get_config().readiness_check_interval_secs;
+    let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+
+    loop {
+        interval.tick().await;
+        let report = HealthCheckType::All.check_health(&state).await;
+        let failures = required_failures(&state, &report);
+
+        if state.get_clients().set_connected(failures.is_empty()) {
+            match failures.is_empty() {
+                true => info!("Required services recovered, serving traffic again"),
+                false => warn!("Required services are down, rejecting traffic: {}", failures.join("; ")),
+            }
+        }
+    }
+}
+
+/// Retrieve a cached health check report from Redis
+///
+/// Cached values that can't be parsed as a report are treated as missing, as is a lookup
+/// taking longer than `HEALTH_CHECK_TIMEOUT_MS`, so a hung Redis doesn't stall the checks.
+///
+/// # Arguments
+///
+/// - `state`: The application state.
+/// - `key`: The cache key of the report, without the key prefix.
 ///
 async fn get_cached_health_check_status(
-    clients: &Clients,
-) -> Result<(StatusCode, String), AppError> {
-    let mut con = clients.get_redis_client()
-        .get_client()
-        .get_multiplexed_async_connection()
-        .await?;
+    state: &AppState,
+    key: &str,
+) -> Result<Option<HealthResponse>, AppError> {
+    let redis_client = state.get_clients().get_redis_client();
+    let lookup = async {
+        let mut con = redis_client
+            .get_connection()
+            .await?;
+
+        let cached_result: Option<String> = con.get(redis_client.key(key)).await?;
+        Ok::<_, AppError>(cached_result)
+    };
+
+    let Ok(cached_result) = timeout(Duration::from_millis(state.get_config().health_check_timeout_ms), lookup).await else {
+        return Ok(None);
+    };
 
-    let cached_result: Option<String> = con.get("health_check_status").await?;
+    Ok(cached_result?.and_then(|cached| serde_json::from_str(&cached).ok()))
+}
 
-    if let Some(cached) = cached_result {
-        return Ok((StatusCode::OK, cached));
+/// Retrieve the last-known-good health check report from Redis
+///
+/// Returns `Ok(None)` when serving stale health is disabled or the grace window has passed.
+///
+/// # Arguments
+///
+/// - `state`: The application state.
+/// - `check_type`: The type of health check the report is for.
+///
+async fn get_stale_health_check_status(
+    state: &AppState,
+    check_type: &HealthCheckType,
+) -> Result<Option<HealthResponse>, AppError> {
+    if state.get_config().health_stale_grace_secs == 0 {
+        return Ok(None);
     }
 
-    Err(AppError::RedisConnectionError(
-        RedisError::from((redis::ErrorKind::TypeError, "Cache not found or expired"))
-    ))
+    get_cached_health_check_status(state, &check_type.last_good_cache_key()).await
 }
 
-/// Cache the health check result in Redis
+/// Cache the health check report in Redis
+///
+/// When serving stale health is enabled, the report is also kept as the last-known-good
+/// report for the cache expiration plus the configured grace window.
 ///
 /// # Arguments
-/// - `clients`: A reference to the `Clients` struct.
-/// - `status`: A tuple containing the status code and message to cache.
+/// - `state`: The application state.
+/// - `check_type`: The type of health check the report is for.
+/// - `report`: The health check report to cache.
 ///
 async fn cache_health_check_status(
-    clients: &Clients,
-    status: &(StatusCode, String),
+    state: &AppState,
+    check_type: &HealthCheckType,
+    report: &HealthResponse,
 ) -> Result<(), AppError> {
-    let mut con = clients.get_redis_client()
-        .get_client()
-        .get_multiplexed_async_connection()
+    let redis_client = state.get_clients().get_redis_client();
+    let mut con = redis_client
+        .get_connection()
         .await?;
 
+    let report_json = serde_json::to_string(report)?;
+
     let _: () = con.set_ex(
-        "health_check_status",
-        &status.1,
+        redis_client.key(&check_type.cache_key()),
+        &report_json,
         CACHE_EXPIRATION
     ).await?;
 
+    let grace = state.get_config().health_stale_grace_secs;
+    if grace > 0 {
+        let _: () = con.set_ex(
+            redis_client.key(&check_type.last_good_cache_key()),
+            &report_json,
+            CACHE_EXPIRATION + grace
+        ).await?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn slow_check_times_out() {
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(60)).await;
+            Ok(())
+        };
+
+        let health = run_check("Redis", 50, slow).await;
+        assert_eq!(health.status, ServiceStatus::Down);
+        assert_eq!(health.error.as_deref(), Some("Redis Health Check timed out after 50ms"));
+        assert!((50..1000).contains(&health.latency_ms));
+    }
+
+    #[tokio::test]
+    async fn failing_check_reports_its_error() {
+        let failing = async { Err(AppError::DatabaseSchemaError("missing table".to_string())) };
+
+        let health = run_check("PostgreSQL", 2000, failing).await;
+        assert_eq!(health.status, ServiceStatus::Down);
+        assert_eq!(health.error.as_deref(), Some("PostgreSQL Health Check Failed: Database schema error: missing table"));
+    }
+
+    #[tokio::test]
+    async fn passing_check_is_up() {
+        let health = run_check("S3", 2000, async { Ok(()) }).await;
+        assert_eq!(health.status, ServiceStatus::Up);
+        assert!(health.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn readiness_is_unavailable_until_the_startup_checks_complete() {
+        let config = crate::config::AppConfig::for_tests();
+        let clients = Arc::new(crate::clients::clients::Clients::for_tests(&config));
+        let state = AppState::new(config, clients, None).unwrap();
+
+        let response = perform_readiness_check(&state).await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(report["status"], "starting");
+        assert!(report["checks"].as_object().unwrap().is_empty());
+    }
+}
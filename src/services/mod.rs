@@ -1,2 +1,4 @@
 pub mod health_service;
-pub mod file_service;
\ No newline at end of file
+pub mod file_service;
+pub mod chunked_upload_service;
+pub mod cleanup_service;
\ No newline at end of file
@@ -0,0 +1,624 @@
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek};
+use std::path::{Component, Path};
+use async_trait::async_trait;
+use flate2::read::GzDecoder;
+use image::ImageReader;
+use log::warn;
+use tar::{Archive, EntryType};
+use zip::ZipArchive;
+use crate::config::AppConfig;
+use crate::utils::file_utils::{FileContent, FileType, FileValidationError, FileValidator, RejectionReason};
+
+/// The number of leading bytes of an upload exposed to the checks by `FileCheckContext::peek`.
+pub const FILE_CHECK_PEEK_BYTES: usize = 8 * 1024;
+
+/// The number of offending entries listed in the message of a rejected archive.
+const MAX_LISTED_ENTRIES: usize = 20;
+
+/// The bits of a Unix mode giving the file type, and the value of a symbolic link.
+const UNIX_FILE_TYPE_MASK: u32 = 0o170000;
+const UNIX_SYMLINK: u32 = 0o120000;
+
+/// The setuid and setgid bits of a Unix mode.
+const UNIX_SETID_BITS: u32 = 0o6000;
+
+/// A reader over the content of a file, which archive readers need to seek in.
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// What a `FileCheck` knows about the file it checks.
+///
+/// # Fields
+/// - `filename`: The name the file was uploaded under.
+/// - `content_type`: The declared content type of the file, empty when none was sent.
+/// - `file_type`: The file type the file was resolved to.
+/// - `head`: The first `FILE_CHECK_PEEK_BYTES` bytes of the content.
+/// - `content`: The whole content of the file, in memory or spooled to disk.
+///
+pub struct FileCheckContext<'a> {
+    pub filename: &'a str,
+    pub content_type: &'a str,
+    pub file_type: &'a FileType,
+    pub head: &'a [u8],
+    pub content: &'a FileContent,
+}
+
+impl FileCheckContext<'_> {
+    /// Returns the first `FILE_CHECK_PEEK_BYTES` bytes of the content, or all of it for smaller files.
+    pub fn peek(&self) -> &[u8] {
+        self.head
+    }
+
+    /// Builds the error rejecting the file, answered with a 422.
+    ///
+    /// # Parameters
+    /// - `message`: Why the file was rejected.
+    pub fn reject(&self, message: impl Into<String>) -> FileValidationError {
+        FileValidationError::new(RejectionReason::ContentRejected, message).for_type(self.file_type)
+    }
+}
+
+/// A check run by the `FileValidator` on every upload, after the built-in checks on its type,
+/// size and content type passed.
+///
+/// Checks are run in the order they were registered with `FileValidator::register_check`,
+/// and the first one failing rejects the upload.
+#[async_trait]
+pub trait FileCheck: Send + Sync {
+    /// Returns the name of the check, used in logs.
+    fn name(&self) -> &str;
+
+    /// Checks a file, returning the error to reject it with if it shouldn't be stored.
+    ///
+    /// # Parameters
+    /// - `ctx`: The file and what is known about it.
+    async fn check(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError>;
+}
+
+/// Rejects ZIP archives holding entries with a blocked extension, e.g. executables.
+///
+/// # Fields
+/// - `extensions`: The blocked extensions, lowercase and without the leading dot.
+///
+pub struct ArchiveEntryBlocklist {
+    extensions: Vec<String>,
+}
+
+impl ArchiveEntryBlocklist {
+    /// Creates a check blocking the provided entry extensions, matched case-insensitively.
+    pub fn new(extensions: &[String]) -> Self {
+        Self {
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.trim_start_matches('.').to_lowercase())
+                .collect(),
+        }
+    }
+
+    /// Returns whether an entry name has a blocked extension.
+    fn is_blocked(&self, name: &str) -> bool {
+        name.rsplit_once('.')
+            .map(|(_, extension)| extension.to_lowercase())
+            .is_some_and(|extension| self.extensions.contains(&extension))
+    }
+}
+
+#[async_trait]
+impl FileCheck for ArchiveEntryBlocklist {
+    fn name(&self) -> &str {
+        "archive_entry_blocklist"
+    }
+
+    /// Lists the entries of ZIP archives from their central directory. Other files, and
+    /// archives whose central directory can't be read, are left to the other checks.
+    async fn check(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError> {
+        if !ctx.peek().starts_with(b"PK\x03\x04") {
+            return Ok(());
+        }
+
+        let names = match read_content(ctx.content, |reader| zip_entry_names(reader)).await {
+            Ok(names) => names,
+            Err(e) => {
+                warn!("Failed to list the entries of '{}': {}", ctx.filename, e);
+                return Ok(());
+            }
+        };
+
+        let blocked: Vec<&str> = names.iter().map(String::as_str).filter(|name| self.is_blocked(name)).collect();
+        if blocked.is_empty() {
+            return Ok(());
+        }
+
+        Err(ctx.reject(format!("Archive holds blocked entries: {}", blocked.join(", "))))
+    }
+}
+
+/// Reads the content of a file with a blocking reader, on the blocking thread pool for
+/// files spooled to disk.
+///
+/// # Parameters
+/// - `content`: The content of the file.
+/// - `read`: The function reading the content.
+async fn read_content<T, F>(content: &FileContent, read: F) -> io::Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&mut dyn ReadSeek) -> io::Result<T> + Send + 'static,
+{
+    match content {
+        FileContent::Memory(data) => read(&mut Cursor::new(data.as_slice())),
+        FileContent::Spooled(spooled) => {
+            let path = spooled.path().to_path_buf();
+            tokio::task::spawn_blocking(move || read(&mut BufReader::new(File::open(path)?)))
+                .await
+                .unwrap_or_else(|e| Err(io::Error::other(e)))
+        }
+    }
+}
+
+/// Reads the names of the entries of a ZIP archive from its central directory.
+fn zip_entry_names(reader: &mut dyn ReadSeek) -> io::Result<Vec<String>> {
+    let archive = ZipArchive::new(reader).map_err(io::Error::other)?;
+    Ok(archive.file_names().map(str::to_string).collect())
+}
+
+/// Rejects uploaded archives holding entries which would be unsafe to extract: entries with
+/// absolute paths or `..` components, symbolic or hard links, setuid or setgid files, and
+/// entries larger than `MAX_ENTRY_EXTRACTED_BYTES`, as well as archives with more than
+/// `MAX_ARCHIVE_ENTRIES` entries.
+///
+/// ZIP archives are inspected from their central directory, while tar.gz archives have to
+/// be decompressed, which is why they are only inspected when enabled.
+///
+/// # Fields
+/// - `max_entry_bytes`: The maximum uncompressed size of an entry.
+/// - `max_entries`: The maximum number of entries of an archive.
+/// - `inspect_tar_gz`: Whether tar.gz archives are inspected too.
+///
+pub struct ArchiveSafety {
+    max_entry_bytes: u64,
+    max_entries: usize,
+    inspect_tar_gz: bool,
+}
+
+impl ArchiveSafety {
+    /// Creates a check rejecting unsafe archive entries.
+    pub fn new(max_entry_bytes: u64, max_entries: usize, inspect_tar_gz: bool) -> Self {
+        Self { max_entry_bytes, max_entries, inspect_tar_gz }
+    }
+}
+
+#[async_trait]
+impl FileCheck for ArchiveSafety {
+    fn name(&self) -> &str {
+        "archive_safety"
+    }
+
+    /// Inspects files starting with the ZIP magic number, and with `inspect_tar_gz`, gzip
+    /// files named `.tar.gz` or `.tgz`. Archives which can't be read are rejected.
+    async fn check(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError> {
+        let lowercase_name = ctx.filename.to_lowercase();
+        let is_zip = ctx.peek().starts_with(b"PK\x03\x04");
+        let is_tar_gz = self.inspect_tar_gz
+            && ctx.peek().starts_with(&[0x1f, 0x8b])
+            && (lowercase_name.ends_with(".tar.gz") || lowercase_name.ends_with(".tgz"));
+        if !is_zip && !is_tar_gz {
+            return Ok(());
+        }
+
+        let (max_entry_bytes, max_entries) = (self.max_entry_bytes, self.max_entries);
+        let inspection = read_content(ctx.content, move |reader| {
+            if is_zip {
+                unsafe_zip_entries(reader, max_entry_bytes, max_entries)
+            } else {
+                unsafe_tar_gz_entries(reader, max_entry_bytes, max_entries)
+            }
+        }).await;
+
+        let problems = match inspection {
+            Ok(problems) => problems,
+            Err(e) => return Err(ctx.reject(format!("Archive can't be read: {}", e))),
+        };
+        if problems.is_empty() {
+            return Ok(());
+        }
+
+        let mut message = format!("Archive holds unsafe entries: {}", problems.iter()
+            .take(MAX_LISTED_ENTRIES)
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(", "));
+        if problems.len() > MAX_LISTED_ENTRIES {
+            message.push_str(&format!(" and {} more", problems.len() - MAX_LISTED_ENTRIES));
+        }
+        Err(ctx.reject(message))
+    }
+}
+
+/// Returns why an entry path is unsafe to extract, if it is.
+fn unsafe_path(name: &str) -> Option<&'static str> {
+    let path = Path::new(name);
+    if name.starts_with('\\') || path.has_root() {
+        return Some("absolute path");
+    }
+    if path.components().any(|component| matches!(component, Component::ParentDir | Component::Prefix(_))) {
+        return Some("path escaping the archive");
+    }
+    None
+}
+
+/// Lists the unsafe entries of a ZIP archive from its central directory.
+///
+/// # Returns
+/// A description of each unsafe entry, or of the entry count when it is above the maximum.
+fn unsafe_zip_entries(reader: &mut dyn ReadSeek, max_entry_bytes: u64, max_entries: usize) -> io::Result<Vec<String>> {
+    let mut archive = ZipArchive::new(reader).map_err(io::Error::other)?;
+    if archive.len() > max_entries {
+        return Ok(vec![format!("{} entries, above the maximum of {}", archive.len(), max_entries)]);
+    }
+
+    let mut problems = Vec::new();
+    for index in 0..archive.len() {
+        let entry = archive.by_index_raw(index).map_err(io::Error::other)?;
+        let mode = entry.unix_mode().unwrap_or(0);
+        let problem = unsafe_path(entry.name())
+            .map(str::to_string)
+            .or_else(|| (mode & UNIX_FILE_TYPE_MASK == UNIX_SYMLINK).then(|| "symbolic link".to_string()))
+            .or_else(|| (mode & UNIX_SETID_BITS != 0).then(|| "setuid or setgid file".to_string()))
+            .or_else(|| (entry.size() > max_entry_bytes).then(|| format!("{} bytes", entry.size())));
+        if let Some(problem) = problem {
+            problems.push(format!("{} ({})", entry.name(), problem));
+        }
+    }
+    Ok(problems)
+}
+
+/// Lists the unsafe entries of a tar.gz archive, decompressing it.
+///
+/// # Returns
+/// A description of each unsafe entry, or of the entry count when it is above the maximum.
+fn unsafe_tar_gz_entries(reader: &mut dyn ReadSeek, max_entry_bytes: u64, max_entries: usize) -> io::Result<Vec<String>> {
+    let mut archive = Archive::new(GzDecoder::new(reader));
+    let mut problems = Vec::new();
+    for (index, entry) in archive.entries()?.enumerate() {
+        if index >= max_entries {
+            return Ok(vec![format!("more than {} entries", max_entries)]);
+        }
+
+        let entry = entry?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).into_owned();
+        let header = entry.header();
+        let mode = header.mode().unwrap_or(0);
+        let problem = unsafe_path(&name)
+            .map(str::to_string)
+            .or_else(|| matches!(header.entry_type(), EntryType::Symlink | EntryType::Link)
+                .then(|| "link".to_string()))
+            .or_else(|| (mode & UNIX_SETID_BITS != 0).then(|| "setuid or setgid file".to_string()))
+            .or_else(|| (entry.size() > max_entry_bytes).then(|| format!("{} bytes", entry.size())));
+        if let Some(problem) = problem {
+            problems.push(format!("{} ({})", name, problem));
+        }
+    }
+    Ok(problems)
+}
+
+/// Rejects PNG and JPEG images larger than the configured dimensions, read from their
+/// headers by the `image` crate without decoding the pixels.
+///
+/// # Fields
+/// - `max_width`: The maximum width in pixels, `0` allowing any width.
+/// - `max_height`: The maximum height in pixels, `0` allowing any height.
+///
+pub struct ImageDimensions {
+    max_width: u32,
+    max_height: u32,
+}
+
+impl ImageDimensions {
+    /// Creates a check limiting the dimensions of images, `0` lifting a limit.
+    pub fn new(max_width: u32, max_height: u32) -> Self {
+        Self { max_width, max_height }
+    }
+}
+
+/// Reads the width and height of an image from its header, without decoding the image.
+///
+/// # Parameters
+/// - `reader`: The image, from its start.
+///
+/// # Returns
+/// - `Ok((u32, u32))`: The dimensions of the image.
+/// - `Err(io::Error)`: If the format of the image isn't recognized or its header can't be read.
+fn image_dimensions(reader: &mut dyn ReadSeek) -> io::Result<(u32, u32)> {
+    ImageReader::new(BufReader::new(reader))
+        .with_guessed_format()?
+        .into_dimensions()
+        .map_err(io::Error::other)
+}
+
+#[async_trait]
+impl FileCheck for ImageDimensions {
+    fn name(&self) -> &str {
+        "image_dimensions"
+    }
+
+    /// Checks the dimensions of files starting with a PNG or JPEG header, an image whose
+    /// dimensions can't be read being rejected.
+    async fn check(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError> {
+        if !ctx.peek().starts_with(b"\x89PNG") && !ctx.peek().starts_with(&[0xFF, 0xD8, 0xFF]) {
+            return Ok(());
+        }
+
+        let Ok((width, height)) = read_content(ctx.content, image_dimensions).await else {
+            return Err(ctx.reject("Image dimensions can't be read from its header"));
+        };
+
+        let too_wide = self.max_width > 0 && width > self.max_width;
+        let too_high = self.max_height > 0 && height > self.max_height;
+        if too_wide || too_high {
+            return Err(ctx.reject(format!(
+                "Image is {}x{} pixels, exceeding the maximum of {}x{}",
+                width, height, limit(self.max_width), limit(self.max_height)
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats a dimension limit, `0` meaning unlimited.
+fn limit(value: u32) -> String {
+    if value == 0 { "any".to_string() } else { value.to_string() }
+}
+
+/// Registers the checks enabled in the configuration with a validator.
+///
+/// # Parameters
+/// - `validator`: The validator to register the checks with.
+/// - `config`: The application configuration.
+pub fn register_configured_checks(validator: &FileValidator, config: &AppConfig) {
+    if !config.blocked_archive_entry_extensions.is_empty() {
+        validator.register_check(ArchiveEntryBlocklist::new(&config.blocked_archive_entry_extensions));
+    }
+
+    if config.inspect_uploaded_archives {
+        validator.register_check(ArchiveSafety::new(
+            config.max_entry_extracted_bytes,
+            config.max_archive_entries,
+            config.inspect_uploaded_tar_gz,
+        ));
+    }
+
+    if config.max_image_width > 0 || config.max_image_height > 0 {
+        validator.register_check(ImageDimensions::new(config.max_image_width, config.max_image_height));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::test_archives::{set_first_zip_entry_mode, tar_gz_archive, zip_archive};
+
+    /// Encodes a grayscale image in the given format.
+    fn image(width: u32, height: u32, format: image::ImageFormat) -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        image::GrayImage::new(width, height).write_to(&mut data, format).unwrap();
+        data.into_inner()
+    }
+
+    fn png_image(width: u32, height: u32) -> Vec<u8> {
+        image(width, height, image::ImageFormat::Png)
+    }
+
+    fn jpeg_image(width: u32, height: u32) -> Vec<u8> {
+        image(width, height, image::ImageFormat::Jpeg)
+    }
+
+    fn dimensions(data: &[u8]) -> io::Result<(u32, u32)> {
+        image_dimensions(&mut Cursor::new(data))
+    }
+
+    /// Runs a check on a file held in memory, resolved to the given type.
+    async fn run_check(check: &dyn FileCheck, filename: &str, file_type: &str, data: Vec<u8>) -> Result<(), FileValidationError> {
+        let validator = FileValidator::new(&AppConfig::for_tests());
+        let content = FileContent::Memory(data.clone());
+        let ctx = FileCheckContext {
+            filename,
+            content_type: "",
+            file_type: validator.get_file_type(file_type).unwrap(),
+            head: &data,
+            content: &content,
+        };
+        check.check(&ctx).await
+    }
+
+    async fn check_image(check: &ImageDimensions, filename: &str, data: Vec<u8>) -> Result<(), FileValidationError> {
+        let file_type = if data.starts_with(b"\x89PNG") { "PNG" } else { "JPEG" };
+        run_check(check, filename, file_type, data).await
+    }
+
+    fn archive_safety() -> ArchiveSafety {
+        ArchiveSafety::new(1024, 10, true)
+    }
+
+    async fn archive_rejection(check: &dyn FileCheck, filename: &str, data: Vec<u8>) -> String {
+        let file_type = if filename.ends_with(".zip") { "ZIP" } else { "TAR_GZ" };
+        let error = run_check(check, filename, file_type, data).await.expect_err("the archive was accepted");
+        assert_eq!(error.reason, RejectionReason::ContentRejected);
+        error.message
+    }
+
+    #[test]
+    fn image_dimensions_are_read_from_the_header() {
+        assert_eq!(dimensions(&png_image(640, 480)).unwrap(), (640, 480));
+        assert_eq!(dimensions(&jpeg_image(1920, 1080)).unwrap(), (1920, 1080));
+    }
+
+    #[test]
+    fn jpeg_dimensions_are_read_past_the_preceding_segments() {
+        let mut data = jpeg_image(800, 600);
+        // An APP1 segment, as left by EXIF metadata, between the start of image and the frame
+        data.splice(2..2, [0xFF, 0xE1, 0x00, 0x08, b'E', b'x', b'i', b'f', 0x00, 0x00]);
+
+        assert_eq!(dimensions(&data).unwrap(), (800, 600));
+    }
+
+    #[test]
+    fn truncated_image_header_fails_to_read() {
+        let png = png_image(640, 480);
+        assert!(dimensions(&png[..20]).is_err());
+        let jpeg = jpeg_image(640, 480);
+        let sof = jpeg.windows(2).position(|marker| marker == [0xFF, 0xC0]).unwrap();
+        assert!(dimensions(&jpeg[..sof + 4]).is_err());
+    }
+
+    #[test]
+    fn unrecognized_image_fails_to_read() {
+        assert!(dimensions(b"\x89PNG not really").is_err());
+        assert!(dimensions(b"plain text").is_err());
+    }
+
+    #[tokio::test]
+    async fn image_within_the_limits_is_accepted() {
+        let check = ImageDimensions::new(1000, 1000);
+
+        assert!(check_image(&check, "image.png", png_image(1000, 1000)).await.is_ok());
+        assert!(check_image(&check, "photo.jpg", jpeg_image(1000, 500)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn image_above_a_limit_is_rejected() {
+        let check = ImageDimensions::new(1000, 0);
+
+        let error = check_image(&check, "image.png", png_image(1001, 10)).await.unwrap_err();
+        assert_eq!(error.reason, RejectionReason::ContentRejected);
+        assert_eq!(error.message, "Image is 1001x10 pixels, exceeding the maximum of 1000xany");
+        assert!(check_image(&check, "photo.jpg", jpeg_image(4000, 10)).await.is_err());
+        assert!(check_image(&check, "tall.png", png_image(10, 20_000)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn image_with_a_truncated_header_is_rejected() {
+        let check = ImageDimensions::new(1000, 1000);
+
+        let error = check_image(&check, "image.png", png_image(10, 10)[..20].to_vec()).await.unwrap_err();
+        assert_eq!(error.message, "Image dimensions can't be read from its header");
+        assert!(check_image(&check, "photo.jpg", jpeg_image(10, 10)[..24].to_vec()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn safe_archives_are_accepted() {
+        let check = archive_safety();
+
+        let zip = zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", b"# Readme")]);
+        assert!(run_check(&check, "safe.zip", "ZIP", zip).await.is_ok());
+        let tar_gz = tar_gz_archive(&[("src/main.rs", EntryType::Regular, b"fn main() {}")]);
+        assert!(run_check(&check, "safe.tar.gz", "TAR_GZ", tar_gz).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn zip_entries_escaping_the_archive_are_rejected() {
+        let check = archive_safety();
+
+        let message = archive_rejection(&check, "evil.zip", zip_archive(&[("../../evil", b"x")])).await;
+        assert_eq!(message, "Archive holds unsafe entries: ../../evil (path escaping the archive)");
+        let message = archive_rejection(&check, "evil.zip", zip_archive(&[("/etc/passwd", b"x")])).await;
+        assert_eq!(message, "Archive holds unsafe entries: /etc/passwd (absolute path)");
+        let message = archive_rejection(&check, "evil.zip", zip_archive(&[("\\windows\\evil", b"x")])).await;
+        assert!(message.contains("(absolute path)"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn zip_symbolic_links_are_rejected() {
+        use zip::write::SimpleFileOptions;
+
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("link", "/etc/passwd", SimpleFileOptions::default()).unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+
+        let message = archive_rejection(&archive_safety(), "link.zip", archive).await;
+        assert_eq!(message, "Archive holds unsafe entries: link (symbolic link)");
+    }
+
+    #[tokio::test]
+    async fn zip_setuid_files_are_rejected() {
+        let mut archive = zip_archive(&[("run.sh", b"#!/bin/sh")]);
+        set_first_zip_entry_mode(&mut archive, 0o104755);
+
+        let message = archive_rejection(&archive_safety(), "setuid.zip", archive).await;
+        assert_eq!(message, "Archive holds unsafe entries: run.sh (setuid or setgid file)");
+    }
+
+    #[tokio::test]
+    async fn zip_entries_above_the_size_limit_are_rejected() {
+        let check = ArchiveSafety::new(16, 10, false);
+
+        let message = archive_rejection(&check, "large.zip", zip_archive(&[("small", &[0; 16]), ("large", &[0; 17])])).await;
+        assert_eq!(message, "Archive holds unsafe entries: large (17 bytes)");
+    }
+
+    #[tokio::test]
+    async fn zip_with_too_many_entries_is_rejected() {
+        let check = ArchiveSafety::new(1024, 2, false);
+
+        let message = archive_rejection(&check, "many.zip", zip_archive(&[("a", b"a"), ("b", b"b"), ("c", b"c")])).await;
+        assert_eq!(message, "Archive holds unsafe entries: 3 entries, above the maximum of 2");
+    }
+
+    #[tokio::test]
+    async fn unreadable_zip_is_rejected() {
+        let message = archive_rejection(&archive_safety(), "broken.zip", b"PK\x03\x04 not an archive".to_vec()).await;
+        assert!(message.starts_with("Archive can't be read"), "{}", message);
+    }
+
+    #[tokio::test]
+    async fn tar_gz_entries_escaping_the_archive_are_rejected() {
+        let archive = tar_gz_archive(&[("ok.txt", EntryType::Regular, b"ok"), ("../../evil", EntryType::Regular, b"x")]);
+
+        let message = archive_rejection(&archive_safety(), "evil.tar.gz", archive).await;
+        assert_eq!(message, "Archive holds unsafe entries: ../../evil (path escaping the archive)");
+    }
+
+    #[tokio::test]
+    async fn tar_gz_links_are_rejected() {
+        let archive = tar_gz_archive(&[("soft", EntryType::Symlink, b""), ("hard", EntryType::Link, b"")]);
+
+        let message = archive_rejection(&archive_safety(), "links.tgz", archive).await;
+        assert_eq!(message, "Archive holds unsafe entries: soft (link), hard (link)");
+    }
+
+    #[tokio::test]
+    async fn tar_gz_is_only_inspected_when_enabled() {
+        let check = ArchiveSafety::new(1024, 10, false);
+        let archive = tar_gz_archive(&[("../../evil", EntryType::Regular, b"x")]);
+
+        assert!(run_check(&check, "evil.tar.gz", "TAR_GZ", archive).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn tar_gz_with_too_many_entries_is_rejected() {
+        let check = ArchiveSafety::new(1024, 1, true);
+        let archive = tar_gz_archive(&[("a", EntryType::Regular, b"a"), ("b", EntryType::Regular, b"b")]);
+
+        let message = archive_rejection(&check, "many.tar.gz", archive).await;
+        assert_eq!(message, "Archive holds unsafe entries: more than 1 entries");
+    }
+
+    #[tokio::test]
+    async fn archive_entries_with_a_blocked_extension_are_rejected() {
+        let check = ArchiveEntryBlocklist::new(&[".exe".to_string(), "DLL".to_string()]);
+        let archive = zip_archive(&[("setup.EXE", b"MZ"), ("lib/core.dll", b"MZ"), ("README.md", b"# Readme")]);
+
+        let message = archive_rejection(&check, "tools.zip", archive).await;
+        assert_eq!(message, "Archive holds blocked entries: setup.EXE, lib/core.dll");
+    }
+
+    #[tokio::test]
+    async fn archive_entries_without_a_blocked_extension_are_accepted() {
+        let check = ArchiveEntryBlocklist::new(&["exe".to_string()]);
+        let archive = zip_archive(&[("exe", b"no extension"), ("notes.exe.txt", b"text")]);
+
+        assert!(run_check(&check, "tools.zip", "ZIP", archive).await.is_ok());
+    }
+}
@@ -1,5 +1,20 @@
+use axum::extract::multipart::MultipartError;
+use axum::body::Bytes;
 use axum::http::StatusCode;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use indexmap::IndexMap;
+use log::{info, warn};
+use sha2::{Digest, Sha256};
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use uuid::Uuid;
+use crate::config::AppConfig;
+use crate::error::AppError;
+use crate::models::file_type::{FileTypeSummary, FileTypesResponse, MagicNumberSummary, MagicSegmentSummary};
+use crate::utils::file_checks::{FileCheck, FileCheckContext, FILE_CHECK_PEEK_BYTES};
 
 /// A struct to represent a file type.
 /// This struct contains information about the file type, such as the name,
@@ -17,10 +32,22 @@ pub struct FileType {
     pub name: String,
     pub extensions: Vec<String>,
     pub content_types: Vec<String>,
-    pub magic_numbers: Vec<Vec<u8>>,
+    pub magic_numbers: Vec<MagicNumber>,
     pub max_size: usize,
 }
 
+/// A struct to represent a magic number identifying a file format.
+/// A magic number is made of one or more byte sequences, each expected at a fixed offset
+/// from the start of the file, and matches only when all of them are present.
+///
+/// # Fields
+/// - `segments`: The byte sequences and the offsets they are expected at.
+///
+#[derive(Debug, Clone)]
+pub struct MagicNumber {
+    pub segments: Vec<(usize, Vec<u8>)>,
+}
+
 /// A struct to represent a file validation error.
 /// This struct contains an HTTP status code and an error message.
 /// The status code is used to set the HTTP status code in the response,
@@ -29,10 +56,272 @@ pub struct FileType {
 /// # Fields
 /// - `code`: The HTTP status code.
 /// - `message`: The error message.
+/// - `reason`: Why the file was rejected.
+/// - `file_type`: The name of the file type the file was resolved to, if it was.
 ///
 pub struct FileValidationError {
     pub code: StatusCode,
     pub message: String,
+    pub reason: RejectionReason,
+    pub file_type: Option<String>,
+}
+
+/// Why a file was rejected by the `FileValidator`, declared in the order of `RejectionReason::ALL`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RejectionReason {
+    MissingFileName,
+    UnsupportedType,
+    ContentMismatch,
+    ContentTypeMismatch,
+    TooLarge,
+    ReadFailed,
+    ContentRejected,
+}
+
+impl RejectionReason {
+    /// Every rejection reason, in the order they are reported.
+    pub const ALL: [RejectionReason; 7] = [
+        RejectionReason::MissingFileName,
+        RejectionReason::UnsupportedType,
+        RejectionReason::ContentMismatch,
+        RejectionReason::ContentTypeMismatch,
+        RejectionReason::TooLarge,
+        RejectionReason::ReadFailed,
+        RejectionReason::ContentRejected,
+    ];
+
+    /// Returns the name the reason is reported under.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RejectionReason::MissingFileName => "missing_file_name",
+            RejectionReason::UnsupportedType => "unsupported_type",
+            RejectionReason::ContentMismatch => "content_mismatch",
+            RejectionReason::ContentTypeMismatch => "content_type_mismatch",
+            RejectionReason::TooLarge => "too_large",
+            RejectionReason::ReadFailed => "read_failed",
+            RejectionReason::ContentRejected => "content_rejected",
+        }
+    }
+
+    /// Returns the HTTP status a file rejected for this reason is answered with.
+    pub fn status(self) -> StatusCode {
+        match self {
+            RejectionReason::MissingFileName => StatusCode::BAD_REQUEST,
+            RejectionReason::UnsupportedType
+            | RejectionReason::ContentMismatch
+            | RejectionReason::ContentTypeMismatch => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            RejectionReason::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            RejectionReason::ReadFailed => StatusCode::INTERNAL_SERVER_ERROR,
+            RejectionReason::ContentRejected => StatusCode::UNPROCESSABLE_ENTITY,
+        }
+    }
+}
+
+impl FileValidationError {
+    /// Creates a validation error answered with the status of its reason.
+    ///
+    /// # Parameters
+    /// - `reason`: Why the file was rejected.
+    /// - `message`: The error message.
+    ///
+    pub fn new(reason: RejectionReason, message: impl Into<String>) -> Self {
+        Self {
+            code: reason.status(),
+            message: message.into(),
+            reason,
+            file_type: None,
+        }
+    }
+
+    /// Sets the file type the rejected file was resolved to.
+    pub fn for_type(mut self, file_type: &FileType) -> Self {
+        self.file_type = Some(file_type.name.clone());
+        self
+    }
+}
+
+/// The number of uploads of a file type which passed and failed validation.
+#[derive(Debug, Default)]
+struct TypeCounters {
+    accepted: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// The number of leading bytes inspected when sniffing whether a file is binary.
+pub const BINARY_SNIFF_BYTES: u64 = 8 * 1024;
+
+/// Extensions of files that are always treated as binary.
+const BINARY_EXTENSIONS: &[&str] = &[
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "webp", "tiff", "pdf", "zip", "gz", "tgz", "tar",
+    "7z", "rar", "jar", "class", "exe", "dll", "so", "dylib", "o", "a", "lib", "bin", "wasm",
+    "woff", "woff2", "ttf", "otf", "mp3", "mp4", "mov", "avi", "wav", "ogg",
+];
+
+/// Content types sent by clients which don't know the type of a file, e.g. `curl -F` or
+/// drag-and-drop uploads in some browsers.
+const GENERIC_CONTENT_TYPES: &[&str] = &["application/octet-stream", "binary/octet-stream"];
+
+/// Returns whether a declared content type is missing or generic, and so says nothing about the file.
+pub fn is_generic_content_type(content_type: &str) -> bool {
+    let content_type = content_type.trim();
+    content_type.is_empty() || GENERIC_CONTENT_TYPES.iter().any(|generic| generic.eq_ignore_ascii_case(content_type))
+}
+
+/// Returns whether the file extension indicates binary content.
+pub fn has_binary_extension(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .is_some_and(|ext| BINARY_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Returns whether the leading bytes of a file indicate binary content.
+/// Unlike `is_text`, this only looks for NUL bytes, since a prefix may end mid UTF-8 sequence.
+pub fn looks_binary(prefix: &[u8]) -> bool {
+    prefix.contains(&0)
+}
+
+/// Returns whether the provided data looks like text.
+/// Data is considered text if it is valid UTF-8 and contains no NUL bytes.
+pub fn is_text(data: &[u8]) -> bool {
+    !data.contains(&0) && std::str::from_utf8(data).is_ok()
+}
+
+/// Converts the CRLF line endings of text data to LF. Lone CR bytes are kept.
+pub fn normalize_line_endings(data: &[u8]) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(data.len());
+    for (index, &byte) in data.iter().enumerate() {
+        if byte == b'\r' && data.get(index + 1) == Some(&b'\n') {
+            continue;
+        }
+        normalized.push(byte);
+    }
+    normalized
+}
+
+/// Guesses the content type of a text file from its extension.
+/// Unknown extensions are served as `text/plain`.
+///
+/// # Parameters
+/// - `path`: The path of the file.
+///
+/// # Returns
+/// The content type, including the UTF-8 charset.
+pub fn guess_text_content_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "xml" => "application/xml; charset=utf-8",
+        "rs" => "text/x-rust; charset=utf-8",
+        "py" => "text/x-python; charset=utf-8",
+        "c" | "h" => "text/x-c; charset=utf-8",
+        "cpp" | "hpp" | "cc" => "text/x-c++; charset=utf-8",
+        "java" => "text/x-java; charset=utf-8",
+        "go" => "text/x-go; charset=utf-8",
+        "sol" => "text/x-solidity; charset=utf-8",
+        "toml" => "application/toml; charset=utf-8",
+        "yaml" | "yml" => "application/yaml; charset=utf-8",
+        "sh" => "text/x-shellscript; charset=utf-8",
+        _ => "text/plain; charset=utf-8",
+    }
+}
+
+/// Returns a file name safe to put in a `Content-Disposition` header.
+/// Only the last segment of the key is kept, and characters other than ASCII letters,
+/// digits, `.`, `-` and `_` are replaced with `_`.
+///
+/// # Parameters
+/// - `key`: The S3 key of the file.
+///
+/// # Returns
+/// The sanitized file name, or `download` if nothing is left of it.
+pub fn attachment_filename(key: &str) -> String {
+    let name: String = key
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') { c } else { '_' })
+        .collect();
+
+    if name.trim_matches('.').is_empty() {
+        "download".to_string()
+    } else {
+        name
+    }
+}
+
+/// Computes the hex-encoded SHA-256 digest of the provided data.
+pub fn compute_sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// A struct to represent a file that passed validation.
+///
+/// # Fields
+/// - `content`: The file content, in memory or spooled to disk.
+/// - `size`: The size of the file content in bytes.
+/// - `sha256`: The hex-encoded SHA-256 digest of the file content.
+/// - `file_type`: The name of the resolved file type.
+/// - `sniffed_mime_type`: The MIME type sniffed from the content with `INFER_FILE_TYPES`, if recognized.
+///
+pub struct ValidatedFile {
+    pub content: FileContent,
+    pub size: usize,
+    pub sha256: String,
+    pub file_type: String,
+    pub sniffed_mime_type: Option<String>,
+}
+
+/// The content of a validated file.
+///
+/// - `Memory`: The content, for files up to `UPLOAD_SPOOL_THRESHOLD_BYTES`.
+/// - `Spooled`: A temporary file holding the content of a larger file.
+pub enum FileContent {
+    Memory(Vec<u8>),
+    Spooled(SpooledFile),
+}
+
+/// A temporary file holding an upload or a downloaded archive, removed when dropped so it
+/// is cleaned up whether the upload or extraction succeeds or fails.
+///
+/// # Fields
+/// - `path`: The path of the temporary file.
+///
+pub struct SpooledFile {
+    path: PathBuf,
+}
+
+impl SpooledFile {
+    /// Creates a new empty temporary file with a unique name in the system temporary directory.
+    ///
+    /// # Parameters
+    /// - `prefix`: The prefix of the file name, telling what the file holds.
+    pub async fn create(prefix: &str) -> std::io::Result<(Self, tokio::fs::File)> {
+        let path = std::env::temp_dir().join(format!("{}-{}", prefix, Uuid::new_v4()));
+        let file = tokio::fs::File::create(&path).await?;
+        Ok((Self { path }, file))
+    }
+
+    /// Returns the path of the temporary file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for SpooledFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Failed to remove temporary file {:?}: {}", self.path, e);
+        }
+    }
 }
 
 impl FileType {
@@ -49,7 +338,7 @@ impl FileType {
         name: &str,
         extensions: Vec<&str>,
         content_types: Vec<&str>,
-        magic_numbers: Vec<Vec<u8>>,
+        magic_numbers: Vec<MagicNumber>,
         max_size: usize,
     ) -> Self {
         Self {
@@ -85,6 +374,7 @@ impl FileType {
     }
 
     /// Validates whether the provided content type matches one of the allowed types.
+    /// A file type without content types accepts any content type.
     ///
     /// # Parameters
     /// - `content_type`: The content type string to check (e.g., `application/zip`).
@@ -95,12 +385,17 @@ impl FileType {
     ///
     /// # Example
     /// ```
+    /// # use rustler::utils::file_utils::FileType;
     /// let file_type = FileType::new("ZIP", vec![], vec!["application/zip"], vec![], 100 * 1024 * 1024);
     /// assert!(file_type.validate_content_type("application/zip"));
     /// assert!(!file_type.validate_content_type("image/png"));
     /// ```
     ///
     pub fn validate_content_type(&self, content_type: &str) -> bool {
+        if self.content_types.is_empty() {
+            return true;
+        }
+
         self.content_types
             .iter()
             .any(|ct| ct.eq_ignore_ascii_case(content_type))
@@ -121,28 +416,348 @@ impl FileType {
             return true;
         }
 
-        self.magic_numbers.iter().any(|magic| {
-            data.len() >= magic.len() && data.starts_with(magic)
+        self.magic_numbers.iter().any(|magic| magic.matches(data))
+    }
+}
+
+impl MagicNumber {
+    /// Creates a new `MagicNumber` expecting the provided bytes at the start of the file.
+    ///
+    /// # Parameters
+    /// - `bytes`: The byte sequence expected at offset 0.
+    ///
+    pub fn new(bytes: &[u8]) -> Self {
+        Self::at(0, bytes)
+    }
+
+    /// Creates a new `MagicNumber` expecting the provided bytes at the given offset.
+    ///
+    /// # Parameters
+    /// - `offset`: The offset of the byte sequence from the start of the file.
+    /// - `bytes`: The expected byte sequence.
+    ///
+    pub fn at(offset: usize, bytes: &[u8]) -> Self {
+        Self {
+            segments: vec![(offset, bytes.to_vec())],
+        }
+    }
+
+    /// Adds another byte sequence that must be present at the given offset.
+    ///
+    /// # Parameters
+    /// - `offset`: The offset of the byte sequence from the start of the file.
+    /// - `bytes`: The expected byte sequence.
+    ///
+    /// # Example
+    /// ```
+    /// # use rustler::utils::file_utils::MagicNumber;
+    /// // WebP: "RIFF", a four byte chunk size, then "WEBP"
+    /// let webp = MagicNumber::new(b"RIFF").and_at(8, b"WEBP");
+    /// assert!(webp.matches(b"RIFF\x24\x00\x00\x00WEBPVP8 "));
+    /// assert!(!webp.matches(b"RIFF\x24\x00\x00\x00WAVEfmt "));
+    /// ```
+    ///
+    pub fn and_at(mut self, offset: usize, bytes: &[u8]) -> Self {
+        self.segments.push((offset, bytes.to_vec()));
+        self
+    }
+
+    /// Checks whether every byte sequence is present at its offset in the provided data.
+    ///
+    /// # Parameters
+    /// - `data`: The byte array to check, starting at the beginning of the file.
+    ///
+    /// # Returns
+    /// - `true` if all byte sequences match.
+    /// - `false` otherwise, including when the data is too short.
+    ///
+    pub fn matches(&self, data: &[u8]) -> bool {
+        self.segments.iter().all(|(offset, bytes)| {
+            data.get(*offset..*offset + bytes.len()) == Some(bytes.as_slice())
         })
     }
+
+    /// Returns the number of leading bytes needed to check the magic number, i.e. the end
+    /// of its furthest byte sequence.
+    pub fn required_len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|(offset, bytes)| offset + bytes.len())
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// The file type definitions of a config file loaded by `FileValidator::from_config`.
+#[derive(Debug, Deserialize)]
+struct FileTypeDefinitions {
+    #[serde(default)]
+    file_types: Vec<FileTypeDefinition>,
+}
+
+/// A file type defined in a config file. Unset fields keep the values of the built-in
+/// type with the same name, if any.
+#[derive(Debug, Deserialize)]
+struct FileTypeDefinition {
+    name: String,
+    extensions: Option<Vec<String>>,
+    content_types: Option<Vec<String>>,
+    magic_numbers: Option<Vec<MagicNumberDefinition>>,
+    max_size: Option<usize>,
+}
+
+/// A magic number defined in a config file, either a hex string expected at the start of
+/// the file or a list of hex segments expected at given offsets.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MagicNumberDefinition {
+    Hex(String),
+    Segments(Vec<MagicSegmentDefinition>),
+}
+
+/// A hex segment of a magic number expected at an offset.
+#[derive(Debug, Deserialize)]
+struct MagicSegmentDefinition {
+    #[serde(default)]
+    offset: usize,
+    hex: String,
+}
+
+impl FileTypeDefinition {
+    /// Converts the definition into a `FileType`, validating its fields.
+    ///
+    /// # Parameters
+    /// - `builtin`: The registered file type with the same name, whose fields are kept when unset.
+    ///
+    /// # Returns
+    /// - `Ok(FileType)`: The defined file type.
+    /// - `Err(AppError::ValidationError)`: If a field is missing or invalid.
+    ///
+    fn into_file_type(self, builtin: Option<FileType>) -> Result<FileType, AppError> {
+        if self.name.trim().is_empty() {
+            return Err(AppError::ValidationError("File type definitions must have a name".to_string()));
+        }
+
+        let magic_numbers = self.magic_numbers
+            .map(|magic_numbers| {
+                magic_numbers
+                    .into_iter()
+                    .map(|magic| magic.into_magic_number(&self.name))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .transpose()?;
+
+        let max_size = self.max_size
+            .or(builtin.as_ref().map(|file_type| file_type.max_size))
+            .ok_or_else(|| AppError::ValidationError(format!("File type '{}' must set max_size", self.name)))?;
+        if max_size == 0 {
+            return Err(AppError::ValidationError(format!("File type '{}' has a zero max_size", self.name)));
+        }
+
+        let builtin = builtin.unwrap_or_else(|| FileType::new(&self.name, vec![], vec![], vec![], max_size));
+        Ok(FileType {
+            name: self.name,
+            extensions: self.extensions.unwrap_or(builtin.extensions),
+            content_types: self.content_types.unwrap_or(builtin.content_types),
+            magic_numbers: magic_numbers.unwrap_or(builtin.magic_numbers),
+            max_size,
+        })
+    }
+}
+
+impl From<&MagicNumber> for MagicNumberSummary {
+    /// Hex-encodes a magic number, as a single string when it is expected at the start of the file.
+    fn from(magic: &MagicNumber) -> Self {
+        match magic.segments.as_slice() {
+            [(0, bytes)] => MagicNumberSummary::Hex(encode_hex(bytes)),
+            segments => MagicNumberSummary::Segments(
+                segments
+                    .iter()
+                    .map(|(offset, bytes)| MagicSegmentSummary { offset: *offset, hex: encode_hex(bytes) })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl MagicNumberDefinition {
+    /// Converts the definition into a `MagicNumber`, decoding its hex segments.
+    ///
+    /// # Parameters
+    /// - `file_type`: The name of the file type, used in error messages.
+    ///
+    fn into_magic_number(self, file_type: &str) -> Result<MagicNumber, AppError> {
+        let segments = match self {
+            MagicNumberDefinition::Hex(hex) => vec![MagicSegmentDefinition { offset: 0, hex }],
+            MagicNumberDefinition::Segments(segments) => segments,
+        };
+
+        let segments = segments
+            .into_iter()
+            .map(|segment| {
+                decode_hex(&segment.hex)
+                    .filter(|bytes| !bytes.is_empty())
+                    .map(|bytes| (segment.offset, bytes))
+                    .ok_or_else(|| AppError::ValidationError(format!(
+                        "File type '{}' has an invalid hex magic number '{}'", file_type, segment.hex
+                    )))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if segments.is_empty() {
+            return Err(AppError::ValidationError(format!("File type '{}' has an empty magic number", file_type)));
+        }
+
+        Ok(MagicNumber { segments })
+    }
+}
+
+/// Decodes a hex string such as `504B0304`, ignoring whitespace.
+/// Returns `None` if the string holds a non-hex character or an odd number of digits.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = hex.bytes().filter(|b| !b.is_ascii_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+
+    digits
+        .chunks(2)
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
+
+/// Encodes bytes as a lowercase hex string.
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
 
 /// A struct to validate files based on their type.
+///
+/// With `INFER_FILE_TYPES`, the content of uploads is also sniffed with the `infer` crate,
+/// which recognizes far more formats than the registered magic numbers.
 pub struct FileValidator {
     file_types: HashMap<String, FileType>,
+    default_file_type: Option<String>,
+    max_upload_size: usize,
+    spool_threshold: usize,
+    sniff_with_infer: bool,
+    strict_content_type: bool,
+    type_counters: HashMap<String, TypeCounters>,
+    rejections: [AtomicU64; RejectionReason::ALL.len()],
+    checks: RwLock<Vec<Arc<dyn FileCheck>>>,
 }
 
 impl FileValidator {
     /// Creates a new `FileValidator` instance with the default file types.
-    pub fn new() -> Self {
+    /// The maximum size of each file type can be overridden through `MAX_SIZE_<NAME>`.
+    ///
+    /// # Parameters
+    /// - `config`: The application configuration holding the configurable size limits.
+    ///
+    pub fn new(config: &AppConfig) -> Self {
         let mut validator = Self {
             file_types: HashMap::new(),
+            default_file_type: config.default_file_type.clone(),
+            max_upload_size: config.max_upload_size_bytes,
+            spool_threshold: config.upload_spool_threshold_bytes,
+            sniff_with_infer: config.infer_file_types,
+            strict_content_type: config.strict_content_type,
+            type_counters: HashMap::new(),
+            rejections: Default::default(),
+            checks: RwLock::new(Vec::new()),
         };
         validator.register_default_types();
+        validator.apply_size_overrides(config);
+
         validator
     }
 
-    /// Registers the default file types (ZIP).
+    /// Creates a new `FileValidator` instance with the default file types merged with the
+    /// file types defined in a TOML or JSON config file, the format being picked from the
+    /// file extension.
+    ///
+    /// A definition whose name matches an already registered type overrides only the fields
+    /// it sets, e.g. the size limit of the built-in ZIP type. New types must set `max_size`.
+    /// Magic numbers are hex strings matched at the start of the file, or lists of
+    /// `{ offset, hex }` segments which must all match:
+    ///
+    /// ```toml
+    /// [[file_types]]
+    /// name = "WASM"
+    /// extensions = ["wasm"]
+    /// content_types = ["application/wasm"]
+    /// magic_numbers = ["0061736d"]
+    /// max_size = 10485760
+    ///
+    /// [[file_types]]
+    /// name = "WAV"
+    /// extensions = ["wav"]
+    /// magic_numbers = [[{ offset = 0, hex = "52494646" }, { offset = 8, hex = "57415645" }]]
+    /// max_size = 52428800
+    ///
+    /// [[file_types]]
+    /// name = "ZIP"
+    /// max_size = 209715200
+    /// ```
+    ///
+    /// The `MAX_SIZE_<NAME>` overrides still take precedence over the config file.
+    ///
+    /// # Parameters
+    /// - `config`: The application configuration holding the configurable size limits.
+    /// - `path`: The path of the file type definitions.
+    ///
+    /// # Returns
+    /// - `Ok(FileValidator)`: The validator with the merged file types.
+    /// - `Err(AppError::ValidationError)`: If the file can't be read or holds an invalid definition.
+    ///
+    pub fn from_config(config: &AppConfig, path: &str) -> Result<Self, AppError> {
+        let definitions: FileTypeDefinitions = config::Config::builder()
+            .add_source(config::File::with_name(path))
+            .build()
+            .and_then(|file| file.try_deserialize())
+            .map_err(|e| AppError::ValidationError(format!("Failed to load file types from '{}': {}", path, e)))?;
+
+        let mut validator = Self {
+            file_types: HashMap::new(),
+            default_file_type: config.default_file_type.clone(),
+            max_upload_size: config.max_upload_size_bytes,
+            spool_threshold: config.upload_spool_threshold_bytes,
+            sniff_with_infer: config.infer_file_types,
+            strict_content_type: config.strict_content_type,
+            type_counters: HashMap::new(),
+            rejections: Default::default(),
+            checks: RwLock::new(Vec::new()),
+        };
+        validator.register_default_types();
+
+        let mut seen = HashSet::new();
+        for definition in definitions.file_types {
+            if !seen.insert(definition.name.clone()) {
+                return Err(AppError::ValidationError(format!(
+                    "Duplicate file type '{}' in '{}'", definition.name, path
+                )));
+            }
+
+            let builtin = validator.file_types.remove(&definition.name);
+            let file_type = definition.into_file_type(builtin)?;
+            validator.register_file_type(file_type);
+        }
+
+        validator.apply_size_overrides(config);
+
+        Ok(validator)
+    }
+
+    /// Applies the `MAX_SIZE_<NAME>` overrides to the registered file types.
+    fn apply_size_overrides(&mut self, config: &AppConfig) {
+        for (name, max_size) in &config.max_file_sizes {
+            if let Some(file_type) = self.file_types.get_mut(name) {
+                file_type.max_size = *max_size;
+            }
+        }
+    }
+
+    /// Registers the default file types (ZIP, TAR GZ, PDF, GIF, WEBP, SVG, BINARY).
     /// You can add more file types using the `register_file_type` method.
     /// This method is called by `new` to initialize the validator with the default file types.
     fn register_default_types(&mut self) {
@@ -151,7 +766,7 @@ impl FileValidator {
             "ZIP",
             vec!["zip"],
             vec!["application/zip"],
-            vec![vec![0x50, 0x4B, 0x03, 0x04]], // ZIP magic number
+            vec![MagicNumber::new(&[0x50, 0x4B, 0x03, 0x04])], // ZIP magic number
             100 * 1024 * 1024, // 100MB
         ));
 
@@ -160,88 +775,488 @@ impl FileValidator {
             "TAR_GZ",
             vec!["tar.gz"],
             vec!["application/gzip", "application/x-gzip"],
-            vec![vec![0x1F, 0x8B]], // GZIP magic number
+            vec![MagicNumber::new(&[0x1F, 0x8B])], // GZIP magic number
             100 * 1024 * 1024, // 100MB
         ));
+
+        // PDF File Type
+        self.register_file_type(FileType::new(
+            "PDF",
+            vec!["pdf"],
+            vec!["application/pdf"],
+            vec![MagicNumber::new(b"%PDF")], // %PDF magic number
+            20 * 1024 * 1024, // 20MB
+        ));
+
+        // GIF File Type
+        self.register_file_type(FileType::new(
+            "GIF",
+            vec!["gif"],
+            vec!["image/gif"],
+            vec![MagicNumber::new(b"GIF87a"), MagicNumber::new(b"GIF89a")], // GIF magic numbers
+            10 * 1024 * 1024, // 10MB
+        ));
+
+        // PNG File Type
+        self.register_file_type(FileType::new(
+            "PNG",
+            vec!["png"],
+            vec!["image/png"],
+            vec![MagicNumber::new(b"\x89PNG\r\n\x1a\n")], // PNG signature
+            10 * 1024 * 1024, // 10MB
+        ));
+
+        // JPEG File Type
+        self.register_file_type(FileType::new(
+            "JPEG",
+            vec!["jpg", "jpeg"],
+            vec!["image/jpeg"],
+            vec![MagicNumber::new(&[0xFF, 0xD8, 0xFF])], // SOI marker followed by a segment marker
+            10 * 1024 * 1024, // 10MB
+        ));
+
+        // WebP File Type
+        self.register_file_type(FileType::new(
+            "WEBP",
+            vec!["webp"],
+            vec!["image/webp"],
+            vec![MagicNumber::new(b"RIFF").and_at(8, b"WEBP")], // RIFF container with a WEBP fourcc
+            10 * 1024 * 1024, // 10MB
+        ));
+
+        // SVG File Type, identified by extension and content type only since it has no reliable magic number
+        self.register_file_type(FileType::new(
+            "SVG",
+            vec!["svg"],
+            vec!["image/svg+xml"],
+            vec![],
+            5 * 1024 * 1024, // 5MB
+        ));
+
+        // Generic BINARY File Type, only used as a `DEFAULT_FILE_TYPE` fallback
+        self.register_file_type(FileType::new(
+            "BINARY",
+            vec![],
+            vec![],
+            vec![],
+            1024 * 1024 * 1024, // 1GB
+        ));
     }
 
     /// Registers a new file type with the validator.
     pub fn register_file_type(&mut self, file_type: FileType) {
+        self.type_counters.entry(file_type.name.clone()).or_default();
         self.file_types.insert(file_type.name.clone(), file_type);
     }
 
-    /// Validates a file based on its type.
-    /// This method reads the file content, validates the extension, content type,
-    /// magic number, and size of the file.
+    /// Registers a check run on every upload validated by `validate_file`, after the
+    /// built-in checks and the checks registered before it.
+    ///
+    /// # Parameters
+    /// - `check`: The check to run.
+    ///
+    pub fn register_check(&self, check: impl FileCheck + 'static) {
+        info!("Registered file check '{}'", check.name());
+        self.checks.write().unwrap_or_else(PoisonError::into_inner).push(Arc::new(check));
+    }
+
+    /// Runs the registered checks on a file, in order, until one of them rejects it.
+    ///
+    /// # Parameters
+    /// - `ctx`: The file and what is known about it.
+    ///
+    async fn run_checks(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError> {
+        let checks = self.checks.read().unwrap_or_else(PoisonError::into_inner).clone();
+        for check in checks {
+            if let Err(validation_error) = check.check(ctx).await {
+                warn!(
+                    "File check '{}' rejected '{}' ({}, declared as '{}'): {}",
+                    check.name(), ctx.filename, ctx.file_type.name, ctx.content_type, validation_error.message
+                );
+                return Err(validation_error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs the checks registered with `register_check` on a file whose head was already
+    /// validated by `validate_head`, e.g. a chunked upload once its parts are assembled.
+    ///
+    /// # Parameters
+    /// - `filename`: The name of the file.
+    /// - `content_type`: The declared content type of the file.
+    /// - `file_type`: The file type resolved by `validate_head`.
+    /// - `content`: The whole content of the file.
+    ///
+    /// # Returns
+    /// - `Ok(())`: If every check accepts the file.
+    /// - `Err(FileValidationError)`: The rejection of the first check refusing it, or of a failure to read it.
+    ///
+    pub async fn check_content(
+        &self,
+        filename: &str,
+        content_type: &str,
+        file_type: &FileType,
+        content: &FileContent,
+    ) -> Result<(), FileValidationError> {
+        let peek = match content {
+            FileContent::Memory(data) => data[..data.len().min(FILE_CHECK_PEEK_BYTES)].to_vec(),
+            FileContent::Spooled(spooled) => {
+                let mut peek = Vec::with_capacity(FILE_CHECK_PEEK_BYTES);
+                let read = async {
+                    let file = tokio::fs::File::open(spooled.path()).await?;
+                    file.take(FILE_CHECK_PEEK_BYTES as u64).read_to_end(&mut peek).await
+                };
+                read.await.map_err(|e| {
+                    FileValidationError::new(RejectionReason::ReadFailed, format!("Failed to read upload: {}", e))
+                        .for_type(file_type)
+                })?;
+                peek
+            }
+        };
+
+        self.run_checks(&FileCheckContext { filename, content_type, file_type, head: &peek, content }).await
+    }
+
+    /// Counts an upload of a file type which passed validation.
+    ///
+    /// # Parameters
+    /// - `file_type`: The name of the resolved file type.
+    ///
+    pub fn record_accepted(&self, file_type: &str) {
+        if let Some(counters) = self.type_counters.get(file_type) {
+            counters.accepted.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts an upload which failed validation, under its reason and, when it was resolved,
+    /// its file type.
+    ///
+    /// # Parameters
+    /// - `error`: The validation error the upload was rejected with.
+    ///
+    pub fn record_rejected(&self, error: &FileValidationError) {
+        self.rejections[error.reason as usize].fetch_add(1, Ordering::Relaxed);
+        if let Some(counters) = error.file_type.as_deref().and_then(|name| self.type_counters.get(name)) {
+            counters.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Describes the registered file types and the validation counters since startup.
+    ///
+    /// # Returns
+    /// The file types sorted by name, with their magic numbers hex-encoded.
+    pub fn summary(&self) -> FileTypesResponse {
+        let mut file_types: Vec<FileTypeSummary> = self.file_types
+            .values()
+            .map(|file_type| {
+                let counters = self.type_counters.get(&file_type.name);
+                FileTypeSummary {
+                    name: file_type.name.clone(),
+                    extensions: file_type.extensions.clone(),
+                    content_types: file_type.content_types.clone(),
+                    magic_numbers: file_type.magic_numbers.iter().map(MagicNumberSummary::from).collect(),
+                    max_size: file_type.max_size,
+                    accepted: counters.map_or(0, |counters| counters.accepted.load(Ordering::Relaxed)),
+                    rejected: counters.map_or(0, |counters| counters.rejected.load(Ordering::Relaxed)),
+                }
+            })
+            .collect();
+        file_types.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let rejections: IndexMap<&'static str, u64> = RejectionReason::ALL
+            .iter()
+            .zip(&self.rejections)
+            .map(|(reason, count)| (reason.as_str(), count.load(Ordering::Relaxed)))
+            .collect();
+
+        FileTypesResponse {
+            accepted: file_types.iter().map(|file_type| file_type.accepted).sum(),
+            rejected: rejections.values().sum(),
+            file_types,
+            default_file_type: self.default_file_type.clone(),
+            rejections,
+        }
+    }
+
+    /// Validates a file and resolves its type.
+    /// This method reads the file content, resolves the file type from the extension and
+    /// the magic number, then validates the content type and size of the file.
+    ///
+    /// Files are buffered in memory up to `UPLOAD_SPOOL_THRESHOLD_BYTES`, and streamed to a
+    /// temporary file past it, so large uploads don't grow the memory usage.
+    ///
+    /// The file type is taken from the extension when it is recognized, and sniffed from the
+    /// magic number otherwise, falling back to the configured default file type. The declared
+    /// content type and the sniffed magic number are cross-checked against the resolved type,
+    /// so a file whose content type or bytes belong to a different registered type is rejected
+    /// with a message naming both types. The checks registered with `register_check` then
+    /// run on the whole content, rejecting the file with a 422.
     ///
     /// # Parameters
-    /// - `file_type_name`: The name of the file type to validate.
     /// - `field`: The `axum::extract::multipart::Field` containing the file data.
     ///
     /// # Returns
-    /// - `Ok(Vec<u8>)`: The file content as a byte array if the file is valid.
+    /// - `Ok(ValidatedFile)`: The file content, its SHA-256 digest and resolved type if the file is valid.
     /// - `Err(FileValidationError)`: An error if the file is invalid.
     ///
     pub async fn validate_file(
         &self,
-        file_type_name: &str,
         field: &mut axum::extract::multipart::Field<'_>,
-    ) -> Result<Vec<u8>, FileValidationError> {
-        let file_type = self.file_types.get(file_type_name).ok_or_else(|| FileValidationError {
-            code: StatusCode::BAD_REQUEST,
-            message: format!("Unsupported file type: {}", file_type_name),
-        })?;
+    ) -> Result<ValidatedFile, FileValidationError> {
+        let result = self.read_file(field).await;
+        match &result {
+            Ok(file) => self.record_accepted(&file.file_type),
+            Err(validation_error) => self.record_rejected(validation_error),
+        }
+        result
+    }
 
-        let filename = field.file_name().ok_or_else(|| FileValidationError {
-            code: StatusCode::BAD_REQUEST,
-            message: "No filename provided".to_string(),
-        })?;
+    /// Reads and validates a file for `validate_file`, which records the outcome.
+    async fn read_file(
+        &self,
+        field: &mut axum::extract::multipart::Field<'_>,
+    ) -> Result<ValidatedFile, FileValidationError> {
+        let filename = field.file_name()
+            .ok_or_else(|| FileValidationError::new(RejectionReason::MissingFileName, "No filename provided"))?
+            .to_string();
+        let content_type = field.content_type().unwrap_or("").to_string();
 
-        if !file_type.validate_extension(filename) {
-            return Err(FileValidationError {
-                code: StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                message: format!("Invalid file extension. Allowed extensions: {:?}", file_type.extensions),
-            });
+        // Chunks are gathered until the longest magic number fits, or the stream ends, since
+        // a client may deliver the first bytes in chunks too small to sniff the file type from
+        let head_len = self.head_len();
+        let mut head = Vec::new();
+        while head.len() < head_len {
+            match field.chunk().await.map_err(|e| self.chunk_error(e))? {
+                Some(chunk) => head.extend_from_slice(&chunk),
+                None => break,
+            }
         }
 
-        let content_type = field.content_type().unwrap_or("");
-        if !file_type.validate_content_type(content_type) {
-            return Err(FileValidationError {
-                code: StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                message: format!("Invalid content type. Allowed types: {:?}", file_type.content_types),
-            });
-        }
+        let file_type = self.validate_head(&filename, &content_type, &head)?;
+        let sniffed_mime_type = self.sniff_mime_type(&head).map(str::to_string);
 
-        // Read and validate file content
+        // Read and validate file content, hashing it as the chunks arrive
         let mut buffer = Vec::new();
-        let mut total_bytes = 0;
+        let mut spooled: Option<(SpooledFile, tokio::fs::File)> = None;
+        let mut size = 0;
+        let mut hasher = Sha256::new();
+        let mut peek = Vec::new();
+        let mut next_chunk = Some(Bytes::from(head));
+
+        while let Some(chunk) = next_chunk {
+            if size + chunk.len() > file_type.max_size {
+                return Err(FileValidationError::new(
+                    RejectionReason::TooLarge,
+                    format!("File exceeds maximum allowed size of {} bytes", file_type.max_size),
+                ).for_type(file_type));
+            }
+
+            hasher.update(&chunk);
+            size += chunk.len();
+            if peek.len() < FILE_CHECK_PEEK_BYTES {
+                let missing = FILE_CHECK_PEEK_BYTES - peek.len();
+                peek.extend_from_slice(&chunk[..chunk.len().min(missing)]);
+            }
+
+            if spooled.is_none() && size > self.spool_threshold {
+                let (spooled_file, mut file) = SpooledFile::create("rustler-upload").await.map_err(|e| self.spool_error(e))?;
+                file.write_all(&buffer).await.map_err(|e| self.spool_error(e))?;
+                buffer = Vec::new();
+                spooled = Some((spooled_file, file));
+            }
+
+            match &mut spooled {
+                Some((_, file)) => file.write_all(&chunk).await.map_err(|e| self.spool_error(e))?,
+                None => buffer.extend_from_slice(&chunk),
+            }
+
+            next_chunk = field.chunk().await.map_err(|e| self.chunk_error(e))?;
+        }
+
+        let content = match spooled {
+            Some((spooled_file, mut file)) => {
+                file.flush().await.map_err(|e| self.spool_error(e))?;
+                FileContent::Spooled(spooled_file)
+            }
+            None => FileContent::Memory(buffer),
+        };
+
+        self.run_checks(&FileCheckContext {
+            filename: &filename,
+            content_type: &content_type,
+            file_type,
+            head: &peek,
+            content: &content,
+        }).await?;
+
+        Ok(ValidatedFile {
+            content,
+            size,
+            sha256: format!("{:x}", hasher.finalize()),
+            file_type: file_type.name.clone(),
+            sniffed_mime_type,
+        })
+    }
+
+    /// Builds the error returned when an upload can't be written to its temporary file.
+    fn spool_error(&self, error: std::io::Error) -> FileValidationError {
+        FileValidationError::new(RejectionReason::ReadFailed, format!("Failed to store upload: {}", error))
+    }
 
-        while let Some(chunk) = field.chunk().await.transpose() {
-            let chunk = chunk.map_err(|e| FileValidationError {
-                code: StatusCode::INTERNAL_SERVER_ERROR,
-                message: format!("Failed to read chunk: {}", e),
-            })?;
+    /// Resolves the file type of an upload from its filename and the start of its content,
+    /// and checks the declared content type against it. With `INFER_FILE_TYPES`, a format
+    /// sniffed by `infer` must also match the extensions or content types of the resolved type.
+    /// A missing or `application/octet-stream` content type is only checked with `STRICT_CONTENT_TYPE`.
+    ///
+    /// The resolved type is cross-checked against the types the content and the content type
+    /// imply, even when it accepts any of them: a file resolved to a type without magic
+    /// numbers is rejected when its content matches another registered type, e.g. a `.svg`
+    /// holding a ZIP archive, and a file resolved to a type accepting any content type is
+    /// rejected when its content type belongs to other registered types.
+    ///
+    /// # Parameters
+    /// - `filename`: The name of the uploaded file.
+    /// - `content_type`: The declared content type of the file.
+    /// - `data`: The first bytes of the file content.
+    ///
+    /// # Returns
+    /// - `Ok(&FileType)`: The resolved file type.
+    /// - `Err(FileValidationError)`: A 415 error if the type is unknown, or the content or
+    ///   content type disagrees with it.
+    ///
+    pub fn validate_head(
+        &self,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+    ) -> Result<&FileType, FileValidationError> {
+        let file_type = self.resolve_upload_type(filename, data)?;
 
-            total_bytes += chunk.len();
-            if total_bytes > file_type.max_size {
-                return Err(FileValidationError {
-                    code: StatusCode::PAYLOAD_TOO_LARGE,
-                    message: format!("File exceeds maximum allowed size of {} bytes", file_type.max_size),
-                });
+        if file_type.magic_numbers.is_empty() {
+            if let Some(sniffed) = self.find_file_type_by_magic(data) {
+                return Err(FileValidationError::new(
+                    RejectionReason::ContentMismatch,
+                    format!("File content indicates {} but the file is {}", sniffed.name, file_type.name),
+                ).for_type(file_type));
             }
+        }
+
+        // The registered magic numbers already vouched for the type when `infer` doesn't know it
+        if let Some(sniffed) = self.sniff(data) {
+            let agrees = file_type.validate_content_type(sniffed.mime_type())
+                || file_type.validate_extension(sniffed.extension());
+            if !agrees {
+                return Err(FileValidationError::new(
+                    RejectionReason::ContentMismatch,
+                    format!("File content indicates {} but the file is {}", sniffed.mime_type(), file_type.name),
+                ).for_type(file_type));
+            }
+        }
+
+        if !self.strict_content_type && is_generic_content_type(content_type) {
+            warn!(
+                "'{}' was uploaded with the generic content type '{}', validated as {} from its extension and content",
+                filename, content_type, file_type.name
+            );
+        } else if !file_type.validate_content_type(content_type) {
+            let message = match self.find_file_type_by_content_type(content_type) {
+                Some(declared) => format!(
+                    "Content type '{}' indicates {} but the file is {}",
+                    content_type, declared.name, file_type.name
+                ),
+                None => format!("Invalid content type. Allowed types: {:?}", file_type.content_types),
+            };
+            return Err(FileValidationError::new(RejectionReason::ContentTypeMismatch, message).for_type(file_type));
+        } else if file_type.content_types.is_empty() {
+            if let Some(declared) = self.find_file_type_by_content_type(content_type) {
+                let message = format!(
+                    "Content type '{}' indicates {} but the file is {}",
+                    content_type, declared.name, file_type.name
+                );
+                return Err(FileValidationError::new(RejectionReason::ContentTypeMismatch, message).for_type(file_type));
+            }
+        }
 
-            buffer.extend_from_slice(&chunk);
+        Ok(file_type)
+    }
+
+    /// Sniffs the MIME type of a file from the start of its content with the `infer` crate.
+    ///
+    /// # Parameters
+    /// - `data`: The first bytes of the file content.
+    ///
+    /// # Returns
+    /// The sniffed MIME type, or `None` when `INFER_FILE_TYPES` is disabled or the format is unknown.
+    pub fn sniff_mime_type(&self, data: &[u8]) -> Option<&'static str> {
+        self.sniff(data).map(|kind| kind.mime_type())
+    }
+
+    /// Sniffs the format of a file with the `infer` crate, when `INFER_FILE_TYPES` is enabled.
+    fn sniff(&self, data: &[u8]) -> Option<infer::Type> {
+        if !self.sniff_with_infer {
+            return None;
+        }
+
+        infer::get(data)
+    }
+
+    /// Resolves the file type of an upload from its filename and the start of its content.
+    ///
+    /// When the extension is recognized, the sniffed magic number must agree with it. Formats
+    /// sharing a magic number (e.g. ZIP-based formats) are resolved by preferring the extension
+    /// whenever its type is among the sniffed candidates. When the extension is not recognized,
+    /// the first sniffed candidate is used, then the configured default file type.
+    ///
+    /// # Parameters
+    /// - `filename`: The name of the uploaded file.
+    /// - `data`: The first bytes of the file content.
+    ///
+    /// # Returns
+    /// - `Ok(&FileType)`: The resolved file type.
+    /// - `Err(FileValidationError)`: A 415 error if the type is unknown or the content disagrees
+    ///   with the extension.
+    ///
+    fn resolve_upload_type(&self, filename: &str, data: &[u8]) -> Result<&FileType, FileValidationError> {
+        let candidates = self.find_file_types_by_magic(data);
 
-            // Validate magic number on first chunk
-            if buffer.len() == chunk.len() && !file_type.validate_magic_number(&buffer) {
-                return Err(FileValidationError {
-                    code: StatusCode::UNSUPPORTED_MEDIA_TYPE,
-                    message: format!("Invalid file format for {}", file_type.name),
-                });
+        match self.find_file_type_by_extension(filename) {
+            Some(by_extension) if by_extension.magic_numbers.is_empty()
+                || candidates.iter().any(|candidate| candidate.name == by_extension.name) => Ok(by_extension),
+            Some(by_extension) => {
+                let message = match candidates.first() {
+                    Some(sniffed) => format!(
+                        "File content indicates {} but the file extension indicates {}",
+                        sniffed.name, by_extension.name
+                    ),
+                    None => format!("Invalid file format for {}", by_extension.name),
+                };
+                Err(FileValidationError::new(RejectionReason::ContentMismatch, message).for_type(by_extension))
             }
+            None => self
+                .find_file_type_by_magic(data)
+                .or_else(|| self.default_file_type())
+                .ok_or_else(|| FileValidationError::new(RejectionReason::UnsupportedType, "Unsupported file extension")),
         }
+    }
 
-        Ok(buffer)
+    /// Converts an error raised while reading a multipart field into a validation error.
+    /// A request body exceeding the upload limit yields a 413 mentioning the limit.
+    ///
+    /// # Parameters
+    /// - `error`: The multipart error.
+    ///
+    /// # Returns
+    /// The corresponding `FileValidationError`.
+    pub fn chunk_error(&self, error: MultipartError) -> FileValidationError {
+        if error.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return FileValidationError::new(
+                RejectionReason::TooLarge,
+                format!("Request body exceeds maximum upload size of {} bytes", self.max_upload_size),
+            );
+        }
+
+        FileValidationError::new(RejectionReason::ReadFailed, format!("Failed to read chunk: {}", error))
     }
 
     /// Finds a file type by its extension.
@@ -250,4 +1265,551 @@ impl FileValidator {
             .values()
             .find(|file_type| file_type.validate_extension(extension))
     }
-}
\ No newline at end of file
+
+    /// Returns the configured default file type, if any.
+    fn default_file_type(&self) -> Option<&FileType> {
+        self.default_file_type
+            .as_deref()
+            .and_then(|name| self.file_types.get(name))
+    }
+
+    /// Returns the number of leading bytes needed to check the magic number of every
+    /// registered file type.
+    pub fn head_len(&self) -> usize {
+        self.file_types
+            .values()
+            .flat_map(|file_type| &file_type.magic_numbers)
+            .map(MagicNumber::required_len)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Finds a registered file type by its name.
+    pub fn get_file_type(&self, name: &str) -> Option<&FileType> {
+        self.file_types.get(name)
+    }
+
+    /// Finds a file type by one of its allowed content types.
+    /// File types without content types are never matched.
+    pub fn find_file_type_by_content_type(&self, content_type: &str) -> Option<&FileType> {
+        self.file_types
+            .values()
+            .find(|file_type| !file_type.content_types.is_empty() && file_type.validate_content_type(content_type))
+    }
+
+    /// Finds a file type whose magic number matches the start of the provided data.
+    /// File types without magic numbers are never matched.
+    pub fn find_file_type_by_magic(&self, data: &[u8]) -> Option<&FileType> {
+        self.find_file_types_by_magic(data).into_iter().next()
+    }
+
+    /// Finds every file type whose magic number matches the start of the provided data,
+    /// sorted by name so formats sharing a magic number are reported consistently.
+    /// File types without magic numbers are never matched.
+    pub fn find_file_types_by_magic(&self, data: &[u8]) -> Vec<&FileType> {
+        let mut candidates: Vec<&FileType> = self.file_types
+            .values()
+            .filter(|file_type| !file_type.magic_numbers.is_empty() && file_type.validate_magic_number(data))
+            .collect();
+        candidates.sort_by(|a, b| a.name.cmp(&b.name));
+        candidates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZIP: &[u8] = b"PK\x03\x04\x14\x00\x00\x00\x08\x00";
+
+    fn validator() -> FileValidator {
+        FileValidator::new(&AppConfig::for_tests())
+    }
+
+    fn validator_with(configure: impl FnOnce(&mut AppConfig)) -> FileValidator {
+        let mut config = AppConfig::for_tests();
+        configure(&mut config);
+        FileValidator::new(&config)
+    }
+
+    /// Validates an upload through a multipart request whose body arrives `chunk_size` bytes at a time.
+    async fn validate_upload(
+        validator: &FileValidator,
+        filename: &str,
+        content_type: &str,
+        data: &[u8],
+        chunk_size: usize,
+    ) -> Result<ValidatedFile, FileValidationError> {
+        use axum::body::Body;
+        use axum::extract::{FromRequest, Multipart};
+        use axum::http::{header, Request};
+        use tokio_util::io::ReaderStream;
+
+        let mut body = format!(
+            "--boundary\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+            filename, content_type
+        ).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n--boundary--\r\n");
+
+        // The body is written through a pipe holding a single chunk, so the multipart parser
+        // receives each chunk on its own rather than the whole body at once
+        let (mut writer, reader) = tokio::io::duplex(chunk_size);
+        tokio::spawn(async move {
+            for chunk in body.chunks(chunk_size) {
+                writer.write_all(chunk).await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+        let stream = ReaderStream::with_capacity(reader, chunk_size);
+        let request = Request::post("/upload")
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+            .body(Body::from_stream(stream))
+            .unwrap();
+        let mut multipart = Multipart::from_request(request, &()).await.unwrap();
+        let mut field = multipart.next_field().await.unwrap().unwrap();
+        validator.validate_file(&mut field).await
+    }
+
+    /// Returns the name of the type a head resolves to, or why it is rejected.
+    fn check_head(validator: &FileValidator, filename: &str, content_type: &str, data: &[u8]) -> Result<String, (RejectionReason, String)> {
+        validator
+            .validate_head(filename, content_type, data)
+            .map(|file_type| file_type.name.clone())
+            .map_err(|e| (e.reason, e.message))
+    }
+
+    #[test]
+    fn pdf_magic_number_accepts_a_minimal_header() {
+        let validator = validator();
+        let pdf = validator.get_file_type("PDF").unwrap();
+
+        assert!(pdf.validate_magic_number(b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n"));
+    }
+
+    #[test]
+    fn pdf_magic_number_rejects_other_content() {
+        let validator = validator();
+        let pdf = validator.get_file_type("PDF").unwrap();
+
+        assert!(!pdf.validate_magic_number(b"PK\x03\x04"));
+        assert!(!pdf.validate_magic_number(b"%PD"));
+        assert!(!pdf.validate_magic_number(b""));
+    }
+
+    #[test]
+    fn head_matching_its_extension_and_content_type_is_accepted() {
+        let validator = validator();
+
+        assert_eq!(check_head(&validator, "archive.zip", "application/zip", ZIP), Ok("ZIP".to_string()));
+        assert_eq!(check_head(&validator, "archive.zip", "application/octet-stream", ZIP), Ok("ZIP".to_string()));
+        assert_eq!(check_head(&validator, "archive.zip", "", ZIP), Ok("ZIP".to_string()));
+    }
+
+    #[test]
+    fn content_type_of_another_type_is_rejected() {
+        let validator = validator();
+
+        let (reason, message) = check_head(&validator, "archive.zip", "application/pdf", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentTypeMismatch);
+        assert_eq!(reason.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(message, "Content type 'application/pdf' indicates PDF but the file is ZIP");
+    }
+
+    #[test]
+    fn unknown_content_type_is_rejected() {
+        let validator = validator();
+
+        let (reason, message) = check_head(&validator, "archive.zip", "text/plain", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentTypeMismatch);
+        assert!(message.starts_with("Invalid content type"), "{}", message);
+    }
+
+    #[test]
+    fn content_of_another_type_than_the_extension_is_rejected() {
+        let validator = validator();
+
+        let (reason, message) = check_head(&validator, "report.pdf", "application/pdf", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentMismatch);
+        assert_eq!(reason.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(message, "File content indicates ZIP but the file extension indicates PDF");
+    }
+
+    #[test]
+    fn content_matching_no_type_is_rejected() {
+        let validator = validator();
+
+        let (reason, message) = check_head(&validator, "report.pdf", "application/pdf", b"plain text").unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentMismatch);
+        assert_eq!(message, "Invalid file format for PDF");
+    }
+
+    #[test]
+    fn extension_without_magic_number_holding_another_type_is_rejected() {
+        let validator = validator();
+
+        assert_eq!(check_head(&validator, "logo.svg", "image/svg+xml", b"<svg></svg>"), Ok("SVG".to_string()));
+        let (reason, message) = check_head(&validator, "logo.svg", "image/svg+xml", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentMismatch);
+        assert_eq!(message, "File content indicates ZIP but the file is SVG");
+    }
+
+    #[test]
+    fn extensionless_file_is_resolved_from_its_content() {
+        let validator = validator();
+
+        assert_eq!(check_head(&validator, "archive", "application/zip", ZIP), Ok("ZIP".to_string()));
+        let (reason, _) = check_head(&validator, "archive", "application/pdf", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentTypeMismatch);
+    }
+
+    #[test]
+    fn default_type_rejects_the_content_type_of_another_type() {
+        let validator = validator_with(|config| config.default_file_type = Some("BINARY".to_string()));
+
+        assert_eq!(check_head(&validator, "data.bin", "application/x-custom", b"\x00\x01\x02\x03"), Ok("BINARY".to_string()));
+        let (reason, message) = check_head(&validator, "data.bin", "application/pdf", b"\x00\x01\x02\x03").unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentTypeMismatch);
+        assert_eq!(message, "Content type 'application/pdf' indicates PDF but the file is BINARY");
+    }
+
+    #[test]
+    fn unknown_extension_is_unsupported_without_a_default_type() {
+        let validator = validator();
+
+        let (reason, _) = check_head(&validator, "data.bin", "application/x-custom", b"\x00\x01\x02\x03").unwrap_err();
+        assert_eq!(reason, RejectionReason::UnsupportedType);
+        assert_eq!(reason.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn strict_content_type_rejects_a_generic_content_type() {
+        let validator = validator_with(|config| config.strict_content_type = true);
+
+        let (reason, _) = check_head(&validator, "archive.zip", "application/octet-stream", ZIP).unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentTypeMismatch);
+    }
+
+    #[test]
+    fn default_types_accept_their_format() {
+        let validator = validator();
+        let uploads: &[(&str, &str, &[u8], &str)] = &[
+            ("archive.zip", "application/zip", ZIP, "ZIP"),
+            ("archive.tar.gz", "application/gzip", b"\x1F\x8B\x08\x00", "TAR_GZ"),
+            ("report.pdf", "application/pdf", b"%PDF-1.4\n", "PDF"),
+            ("old.gif", "image/gif", b"GIF87a\x01\x00", "GIF"),
+            ("new.gif", "image/gif", b"GIF89a\x01\x00", "GIF"),
+            ("image.png", "image/png", b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0DIHDR", "PNG"),
+            ("photo.jpg", "image/jpeg", b"\xFF\xD8\xFF\xE0", "JPEG"),
+            ("photo.jpeg", "image/jpeg", b"\xFF\xD8\xFF\xDB", "JPEG"),
+            ("image.webp", "image/webp", b"RIFF\x24\x00\x00\x00WEBPVP8 ", "WEBP"),
+            ("logo.svg", "image/svg+xml", b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>", "SVG"),
+        ];
+
+        for (filename, content_type, data, expected) in uploads {
+            assert_eq!(check_head(&validator, filename, content_type, data), Ok(expected.to_string()), "{}", filename);
+        }
+    }
+
+    #[test]
+    fn webp_with_another_riff_fourcc_is_rejected() {
+        let validator = validator();
+
+        let (reason, _) = check_head(&validator, "sound.webp", "image/webp", b"RIFF\x24\x00\x00\x00WAVEfmt ").unwrap_err();
+        assert_eq!(reason, RejectionReason::ContentMismatch);
+    }
+
+    #[test]
+    fn default_types_have_their_own_size_limit() {
+        let validator = validator();
+        let limits = [
+            ("ZIP", 100 * 1024 * 1024),
+            ("TAR_GZ", 100 * 1024 * 1024),
+            ("PDF", 20 * 1024 * 1024),
+            ("GIF", 10 * 1024 * 1024),
+            ("PNG", 10 * 1024 * 1024),
+            ("JPEG", 10 * 1024 * 1024),
+            ("WEBP", 10 * 1024 * 1024),
+            ("SVG", 5 * 1024 * 1024),
+        ];
+
+        for (name, max_size) in limits {
+            assert_eq!(validator.get_file_type(name).unwrap().max_size, max_size, "{}", name);
+        }
+    }
+
+    #[test]
+    fn size_limit_can_be_overridden() {
+        let validator = validator_with(|config| {
+            config.max_file_sizes = HashMap::from([("PDF".to_string(), 1024)]);
+        });
+
+        assert_eq!(validator.get_file_type("PDF").unwrap().max_size, 1024);
+        assert_eq!(validator.get_file_type("ZIP").unwrap().max_size, 100 * 1024 * 1024);
+    }
+
+    /// Loads a validator from file type definitions written to a file with the given extension.
+    fn load_definitions(extension: &str, definitions: &str) -> Result<FileValidator, AppError> {
+        let file = tempfile::Builder::new().suffix(&format!(".{}", extension)).tempfile().unwrap();
+        std::fs::write(file.path(), definitions).unwrap();
+        FileValidator::from_config(&AppConfig::for_tests(), file.path().to_str().unwrap())
+    }
+
+    /// Returns the error message of a rejected config file.
+    fn definitions_error(definitions: &str) -> String {
+        match load_definitions("toml", definitions) {
+            Err(AppError::ValidationError(message)) => message,
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("the definitions were accepted"),
+        }
+    }
+
+    #[test]
+    fn config_file_adds_a_type_with_an_offset_magic_number_and_overrides_zip() {
+        let loaded = load_definitions("toml", r#"
+            [[file_types]]
+            name = "WAV"
+            extensions = ["wav"]
+            content_types = ["audio/wav"]
+            magic_numbers = [[{ offset = 0, hex = "52494646" }, { offset = 8, hex = "57415645" }]]
+            max_size = 1048576
+
+            [[file_types]]
+            name = "ZIP"
+            max_size = 209715200
+        "#).unwrap();
+
+        let wav = loaded.get_file_type("WAV").unwrap();
+        assert_eq!(wav.max_size, 1048576);
+        assert_eq!(wav.magic_numbers.len(), 1);
+        assert_eq!(wav.magic_numbers[0].segments, [(0, b"RIFF".to_vec()), (8, b"WAVE".to_vec())]);
+        assert_eq!(check_head(&loaded, "sound.wav", "audio/wav", b"RIFF\x24\x00\x00\x00WAVEfmt ").unwrap(), "WAV");
+        assert_eq!(check_head(&loaded, "sound.wav", "audio/wav", b"RIFF\x24\x00\x00\x00AVI LIST").unwrap_err().0, RejectionReason::ContentMismatch);
+
+        // The override only replaces the size limit of the built-in type
+        let zip = loaded.get_file_type("ZIP").unwrap();
+        let builtin = validator().get_file_type("ZIP").unwrap().clone();
+        assert_eq!(zip.max_size, 209715200);
+        assert_eq!(zip.extensions, builtin.extensions);
+        let magic = |file_type: &FileType| file_type.magic_numbers.iter().map(MagicNumberSummary::from).collect::<Vec<_>>();
+        assert_eq!(magic(zip), magic(&builtin));
+
+        let summary = loaded.summary();
+        let wav = summary.file_types.iter().find(|file_type| file_type.name == "WAV").unwrap();
+        assert_eq!(wav.magic_numbers, vec![MagicNumberSummary::Segments(vec![
+            MagicSegmentSummary { offset: 0, hex: "52494646".to_string() },
+            MagicSegmentSummary { offset: 8, hex: "57415645".to_string() },
+        ])]);
+    }
+
+    #[test]
+    fn config_file_can_be_json() {
+        let validator = load_definitions("json", r#"{
+            "file_types": [{ "name": "WASM", "extensions": ["wasm"], "magic_numbers": ["0061736d"], "max_size": 1024 }]
+        }"#).unwrap();
+
+        assert_eq!(check_head(&validator, "module.wasm", "application/wasm", b"\x00asm\x01\x00\x00\x00").unwrap(), "WASM");
+    }
+
+    #[test]
+    fn invalid_config_files_are_rejected() {
+        let invalid_hex = definitions_error(r#"
+            [[file_types]]
+            name = "WASM"
+            magic_numbers = ["0061736"]
+            max_size = 1024
+        "#);
+        assert_eq!(invalid_hex, "File type 'WASM' has an invalid hex magic number '0061736'");
+
+        let duplicate = definitions_error(r#"
+            [[file_types]]
+            name = "ZIP"
+            max_size = 1024
+
+            [[file_types]]
+            name = "ZIP"
+            max_size = 2048
+        "#);
+        assert!(duplicate.starts_with("Duplicate file type 'ZIP' in '"), "{}", duplicate);
+
+        let zero_size = definitions_error(r#"
+            [[file_types]]
+            name = "ZIP"
+            max_size = 0
+        "#);
+        assert_eq!(zero_size, "File type 'ZIP' has a zero max_size");
+
+        let missing_size = definitions_error(r#"
+            [[file_types]]
+            name = "WASM"
+            extensions = ["wasm"]
+        "#);
+        assert_eq!(missing_size, "File type 'WASM' must set max_size");
+    }
+
+    #[tokio::test]
+    async fn magic_number_is_checked_across_one_byte_chunks() {
+        let validator = validator();
+        let data = b"RIFF\x24\x00\x00\x00WEBPVP8 \x00\x00";
+
+        let file = validate_upload(&validator, "image.webp", "image/webp", data, 1).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(file.file_type, "WEBP");
+        assert_eq!(file.size, data.len());
+        assert_eq!(file.sha256, compute_sha256(data));
+    }
+
+    #[tokio::test]
+    async fn mismatching_content_is_rejected_across_one_byte_chunks() {
+        let validator = validator();
+
+        let Err(error) = validate_upload(&validator, "report.pdf", "application/pdf", ZIP, 1).await else {
+            panic!("a ZIP uploaded as a PDF was accepted");
+        };
+        assert_eq!(error.reason, RejectionReason::ContentMismatch);
+    }
+
+    #[tokio::test]
+    async fn file_shorter_than_the_longest_magic_number_is_validated() {
+        let validator = validator();
+
+        let file = validate_upload(&validator, "report.pdf", "application/pdf", b"%PDF", 1).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(file.file_type, "PDF");
+        assert_eq!(file.size, 4);
+    }
+
+    /// A check rejecting files holding a marker anywhere in their content.
+    struct RejectMarker(&'static [u8]);
+
+    #[async_trait::async_trait]
+    impl FileCheck for RejectMarker {
+        fn name(&self) -> &str {
+            "reject_marker"
+        }
+
+        async fn check(&self, ctx: &FileCheckContext<'_>) -> Result<(), FileValidationError> {
+            let content = match ctx.content {
+                FileContent::Memory(data) => data.clone(),
+                FileContent::Spooled(spooled) => tokio::fs::read(spooled.path()).await.unwrap(),
+            };
+            if content.windows(self.0.len()).any(|window| window == self.0) {
+                return Err(ctx.reject("Payload is not allowed"));
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn registered_check_rejects_its_payload() {
+        let validator = validator();
+        validator.register_check(RejectMarker(b"FORBIDDEN"));
+
+        let file = validate_upload(&validator, "report.pdf", "application/pdf", b"%PDF-1.4 allowed", 64).await;
+        assert!(file.is_ok());
+        let Err(error) = validate_upload(&validator, "report.pdf", "application/pdf", b"%PDF-1.4 FORBIDDEN", 64).await else {
+            panic!("the payload was accepted");
+        };
+        assert_eq!(error.reason, RejectionReason::ContentRejected);
+        assert_eq!(error.code, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.message, "Payload is not allowed");
+        assert_eq!(validator.summary().rejections["content_rejected"], 1);
+    }
+
+    #[tokio::test]
+    async fn registered_check_sees_the_content_of_spooled_files() {
+        let validator = validator_with(|config| config.upload_spool_threshold_bytes = 16);
+        validator.register_check(RejectMarker(b"FORBIDDEN"));
+        let mut data = b"%PDF-1.4 ".to_vec();
+        data.extend_from_slice(&[b' '; 1024]);
+        data.extend_from_slice(b"FORBIDDEN");
+
+        let Err(error) = validate_upload(&validator, "report.pdf", "application/pdf", &data, 64).await else {
+            panic!("the payload was accepted");
+        };
+        assert_eq!(error.reason, RejectionReason::ContentRejected);
+    }
+
+    #[tokio::test]
+    async fn registered_check_runs_on_assembled_content() {
+        let validator = validator();
+        validator.register_check(RejectMarker(b"FORBIDDEN"));
+        let file_type = validator.get_file_type("PDF").unwrap();
+        let (spooled, _) = SpooledFile::create("rustler-test").await.unwrap();
+        tokio::fs::write(spooled.path(), b"%PDF-1.4 FORBIDDEN").await.unwrap();
+
+        let content = FileContent::Spooled(spooled);
+        let Err(error) = validator.check_content("report.pdf", "application/pdf", file_type, &content).await else {
+            panic!("the payload was accepted");
+        };
+        assert_eq!(error.reason, RejectionReason::ContentRejected);
+
+        let content = FileContent::Memory(b"%PDF-1.4 allowed".to_vec());
+        assert!(validator.check_content("report.pdf", "application/pdf", file_type, &content).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn file_above_the_spool_threshold_is_streamed_to_disk() {
+        let validator = validator_with(|config| config.upload_spool_threshold_bytes = 64 * 1024);
+        let mut data = b"%PDF-1.4\n".to_vec();
+        data.resize(1536 * 1024, b' ');
+
+        let file = validate_upload(&validator, "large.pdf", "application/pdf", &data, 64 * 1024).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(file.size, data.len());
+        assert_eq!(file.sha256, compute_sha256(&data));
+        let FileContent::Spooled(spooled) = file.content else {
+            panic!("a file above the spool threshold was held in memory");
+        };
+        assert_eq!(std::fs::read(spooled.path()).unwrap(), data);
+
+        let path = spooled.path().to_path_buf();
+        drop(spooled);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn file_below_the_spool_threshold_is_kept_in_memory() {
+        let validator = validator_with(|config| config.upload_spool_threshold_bytes = 64 * 1024);
+        let data = b"%PDF-1.4\n%%EOF\n";
+
+        let file = validate_upload(&validator, "small.pdf", "application/pdf", data, 64).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert!(matches!(file.content, FileContent::Memory(content) if content == data));
+    }
+
+    #[tokio::test]
+    async fn checks_run_in_registration_order() {
+        let validator = validator();
+        validator.register_check(RejectMarker(b"%PDF"));
+        validator.register_check(RejectMarker(b"never"));
+
+        let Err(error) = validate_upload(&validator, "report.pdf", "application/pdf", b"%PDF-1.4", 64).await else {
+            panic!("the payload was accepted");
+        };
+        assert_eq!(error.message, "Payload is not allowed");
+    }
+
+    #[tokio::test]
+    async fn checks_only_run_on_files_passing_the_built_in_checks() {
+        let validator = validator();
+        validator.register_check(RejectMarker(b"PK"));
+
+        let Err(error) = validate_upload(&validator, "report.pdf", "application/pdf", ZIP, 64).await else {
+            panic!("a ZIP uploaded as a PDF was accepted");
+        };
+        assert_eq!(error.reason, RejectionReason::ContentMismatch);
+    }
+
+    #[tokio::test]
+    async fn upload_of_an_unknown_extension_depends_on_the_default_type() {
+        let data = b"\x00\x01\x02\x03";
+
+        let Err(error) = validate_upload(&validator(), "data.bin", "application/octet-stream", data, 64).await else {
+            panic!("an unknown extension was accepted without a default type");
+        };
+        assert_eq!(error.reason, RejectionReason::UnsupportedType);
+        assert_eq!(error.code, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let lenient = validator_with(|config| config.default_file_type = Some("BINARY".to_string()));
+        let file = validate_upload(&lenient, "data.bin", "application/octet-stream", data, 64).await.unwrap_or_else(|e| panic!("{}", e.message));
+        assert_eq!(file.file_type, "BINARY");
+        assert_eq!(file.size, data.len());
+    }
+}
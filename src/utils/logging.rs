@@ -0,0 +1,30 @@
+use std::env;
+use tracing_subscriber::EnvFilter;
+
+/// The filter applied when `RUST_LOG` is unset.
+const DEFAULT_FILTER: &str = "info";
+
+/// Installs the global tracing subscriber.
+///
+/// Logs are written as one JSON object per line, or in a human-readable format when
+/// `LOG_FORMAT` is `pretty`. The levels are filtered with `RUST_LOG`, defaulting to `info`.
+/// Lines emitted through the `log` macros are bridged into tracing, so they carry the
+/// fields of the span they are logged in, such as the request id.
+///
+/// `LOG_FORMAT` is read directly rather than from `AppConfig`, so errors while loading the
+/// configuration are logged too.
+pub fn init_logging() {
+    dotenv::dotenv().ok();
+
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+    let format = env::var("LOG_FORMAT").unwrap_or_default();
+
+    match format.as_str() {
+        "pretty" => tracing_subscriber::fmt().pretty().with_env_filter(filter).init(),
+        _ => tracing_subscriber::fmt().json().with_current_span(true).with_env_filter(filter).init(),
+    }
+
+    if !format.is_empty() && format != "pretty" && format != "json" {
+        tracing::warn!("Unknown LOG_FORMAT '{}', logging as JSON", format);
+    }
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+use metrics_exporter_prometheus::{BuildError, Matcher, PrometheusBuilder, PrometheusHandle};
+
+/// The counter of handled HTTP requests, labelled by method, route, and status.
+pub const HTTP_REQUESTS_TOTAL: &str = "http_requests_total";
+
+/// The histogram of HTTP request latencies in seconds, labelled by method and route.
+pub const HTTP_REQUEST_DURATION_SECONDS: &str = "http_request_duration_seconds";
+
+/// The histogram of the sizes of stored uploads in bytes.
+pub const UPLOAD_SIZE_BYTES: &str = "upload_size_bytes";
+
+/// The counter of failed S3 requests, labelled by operation, counted once retries are exhausted.
+pub const S3_ERRORS_TOTAL: &str = "s3_errors_total";
+
+/// The counter of retried S3 requests, labelled by operation, counted once per retry.
+pub const S3_RETRIES_TOTAL: &str = "s3_retries_total";
+
+/// The counter of failed PostgreSQL queries and connections.
+pub const POSTGRES_ERRORS_TOTAL: &str = "postgres_errors_total";
+
+/// The counter of failed Redis commands and connections.
+pub const REDIS_ERRORS_TOTAL: &str = "redis_errors_total";
+
+/// How often the recorder drains the samples of its histograms.
+const UPKEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The latency buckets of `http_request_duration_seconds`.
+const DURATION_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// The size buckets of `upload_size_bytes`, from 1 KiB to 1 GiB.
+const SIZE_BUCKETS: &[f64] = &[
+    1024.0,
+    16_384.0,
+    262_144.0,
+    1_048_576.0,
+    16_777_216.0,
+    67_108_864.0,
+    268_435_456.0,
+    1_073_741_824.0,
+];
+
+/// Installs the global Prometheus recorder, and spawns the task keeping its histograms drained.
+///
+/// Until a recorder is installed, recorded metrics are discarded.
+///
+/// # Returns
+/// - `Ok(PrometheusHandle)`: The handle rendering the metrics in the Prometheus text format.
+/// - `Err(BuildError)`: If the recorder can't be built or a recorder is already installed.
+pub fn install_recorder() -> Result<PrometheusHandle, BuildError> {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(HTTP_REQUEST_DURATION_SECONDS.to_string()), DURATION_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(UPLOAD_SIZE_BYTES.to_string()), SIZE_BUCKETS)?
+        .install_recorder()?;
+
+    let upkeep_handle = handle.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(UPKEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            upkeep_handle.run_upkeep();
+        }
+    });
+
+    Ok(handle)
+}
+
+/// Records the size of a stored upload.
+pub fn record_upload_size(size: u64) {
+    metrics::histogram!(UPLOAD_SIZE_BYTES).record(size as f64);
+}
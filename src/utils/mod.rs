@@ -1 +1,8 @@
-pub mod file_utils;
\ No newline at end of file
+pub mod file_checks;
+pub mod file_utils;
+pub mod logging;
+pub mod metrics;
+pub mod openapi;
+pub mod response_format;
+#[cfg(test)]
+pub mod test_archives;
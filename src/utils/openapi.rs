@@ -0,0 +1,887 @@
+use serde_json::{json, Value};
+
+/// Reference to the shared error schema, returned by every failing endpoint.
+const ERROR_SCHEMA: &str = "#/components/schemas/ErrorResponse";
+
+/// Builds the OpenAPI 3.0 document describing the HTTP API.
+///
+/// The document is maintained by hand next to the routers, so a route added to one of
+/// them must be described here as well.
+///
+/// # Returns
+/// The OpenAPI document, served as JSON by `/api-docs/openapi.json`.
+pub fn openapi_document() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "Rustler",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Stores uploaded files and archives, and serves the extracted codebases.",
+        },
+        "paths": paths(),
+        "components": {
+            "schemas": schemas(),
+            "securitySchemes": {
+                "apiKey": { "type": "apiKey", "in": "header", "name": "X-Api-Key" },
+                "adminToken": { "type": "http", "scheme": "bearer" },
+            },
+        },
+    })
+}
+
+/// Returns the operations of every route, keyed by path.
+fn paths() -> Value {
+    let key = path_param("key", "The storage key of the file, with any `/` percent-encoded.");
+    let name = path_param("name", "The name of the competition.");
+    let upload_id = path_param("id", "The id of the upload.");
+
+    let mut paths = json!({
+        "/upload": {
+            "post": {
+                "tags": ["uploads"],
+                "summary": "Upload one or more files",
+                "description": "Each multipart part carrying a file is validated and stored. \
+                    Rate limited per client IP, and bounded by `MAX_UPLOAD_SIZE_BYTES`.",
+                "security": [{ "apiKey": [] }],
+                "parameters": [
+                    query_param("atomic", "boolean", "Reject the whole batch when one of its files fails validation, and delete what it stored when one fails to store."),
+                    query_param("storage_class", "string", "The S3 storage class of the files, one of `ALLOWED_STORAGE_CLASSES`."),
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "multipart/form-data": {
+                            "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "file": {
+                                        "type": "array",
+                                        "items": { "type": "string", "format": "binary" },
+                                        "description": "The files to upload, each sent as a part with a file name.",
+                                    },
+                                },
+                                "required": ["file"],
+                            },
+                        },
+                    },
+                },
+                "responses": {
+                    "200": json_response("The result of each file of the batch.", json!({
+                        "type": "array",
+                        "items": schema_ref("UploadResult"),
+                    })),
+                    "400": error_response("The request isn't a valid multipart upload, or an atomic batch was rejected."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "413": error_response("The upload exceeds the configured size limits."),
+                    "429": error_response("Too many uploads from this client."),
+                },
+            },
+        },
+        "/upload-extract": {
+            "post": {
+                "tags": ["uploads"],
+                "summary": "Upload a competition archive and extract it right away",
+                "description": "The archive is validated and stored like those sent to `/upload`, then extracted \
+                    from its local copy. It must be named after its competition, with a `.zip` or `.tar.gz` extension.",
+                "security": [{ "apiKey": [] }],
+                "parameters": [
+                    query_param("text_only", "boolean", "Skip the binary entries when extracting the archive."),
+                    query_param("storage_class", "string", "The S3 storage class of the archive, one of `ALLOWED_STORAGE_CLASSES`."),
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "multipart/form-data": {
+                            "schema": {
+                                "type": "object",
+                                "properties": {
+                                    "file": {
+                                        "type": "string",
+                                        "format": "binary",
+                                        "description": "The archive, sent as a part with a file name.",
+                                    },
+                                },
+                                "required": ["file"],
+                            },
+                        },
+                    },
+                },
+                "responses": {
+                    "200": json_response("The archive was stored and extracted.", schema_ref("UploadExtractResponse")),
+                    "202": { "description": "The competition is being extracted by another request." },
+                    "400": error_response("The request doesn't hold a single .zip or .tar.gz archive named after a valid competition."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "413": error_response("The upload exceeds the configured size limits."),
+                    "415": error_response("The archive failed validation."),
+                    "422": error_response("The archive was rejected by a configured file check."),
+                    "429": error_response("Too many uploads from this client."),
+                },
+            },
+        },
+        "/upload/init": {
+            "post": {
+                "tags": ["uploads"],
+                "summary": "Start a chunked upload",
+                "security": [{ "apiKey": [] }],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema_ref("InitChunkedUploadRequest") } },
+                },
+                "responses": {
+                    "201": json_response("The upload was started.", schema_ref("ChunkedUploadStarted")),
+                    "400": error_response("The file name or content type is not accepted."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "429": error_response("Too many uploads from this client."),
+                },
+            },
+        },
+        "/upload/{id}/part/{part_number}": {
+            "put": {
+                "tags": ["uploads"],
+                "summary": "Upload a part of a chunked upload",
+                "security": [{ "apiKey": [] }],
+                "parameters": [
+                    upload_id,
+                    path_param("part_number", "The number of the part, starting at 1."),
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": {
+                        "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                    },
+                },
+                "responses": {
+                    "200": json_response("The part was stored.", schema_ref("ChunkedUploadPart")),
+                    "400": error_response("The part number is invalid."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No upload in progress has this id."),
+                    "413": error_response("The part exceeds `CHUNKED_UPLOAD_MAX_PART_BYTES`."),
+                },
+            },
+        },
+        "/upload/{id}/complete": {
+            "post": {
+                "tags": ["uploads"],
+                "summary": "Complete a chunked upload",
+                "security": [{ "apiKey": [] }],
+                "parameters": [upload_id],
+                "responses": {
+                    "200": json_response("The uploaded file.", schema_ref("UploadResponse")),
+                    "400": error_response("Parts are missing or too small."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No upload in progress has this id."),
+                },
+            },
+        },
+        "/uploads/{id}": {
+            "get": {
+                "tags": ["uploads"],
+                "summary": "Fetch the stored metadata of an upload",
+                "description": "Served as MessagePack when the client sends `Accept: application/msgpack`.",
+                "parameters": [
+                    upload_id,
+                    query_param("include_deleted", "boolean", "Also return the upload if it is soft-deleted."),
+                ],
+                "responses": {
+                    "200": json_response("The upload record.", schema_ref("UploadRecord")),
+                    "404": error_response("No upload has this id."),
+                },
+            },
+        },
+        "/files/{key}": {
+            "get": download_operation(&key, "Download a stored file"),
+            "delete": {
+                "tags": ["files"],
+                "summary": "Soft-delete a stored file, which can be restored until it is purged",
+                "description": "The file is moved under the `deleted/` prefix, and purged by the cleanup task \
+                    once `DELETED_FILE_RETENTION_SECS` elapsed.",
+                "security": [{ "apiKey": [] }],
+                "parameters": [key],
+                "responses": {
+                    "200": json_response("The file was deleted.", schema_ref("FileDeleted")),
+                    "400": error_response("The key is already under the `deleted/` prefix."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No file has this key."),
+                },
+            },
+        },
+        "/download/{key}": {
+            "get": download_operation(&key, "Download a stored file, alias of `/files/{key}`"),
+        },
+        "/files/{key}/restore": {
+            "post": {
+                "tags": ["files"],
+                "summary": "Restore a soft-deleted file",
+                "security": [{ "apiKey": [] }],
+                "parameters": [key],
+                "responses": {
+                    "200": json_response("The file was restored.", json!({
+                        "type": "object",
+                        "properties": { "key": { "type": "string" } },
+                    })),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No deleted file had this key."),
+                    "409": error_response("A file was stored under the key since its deletion."),
+                },
+            },
+        },
+        "/files/{key}/checksum": {
+            "get": {
+                "tags": ["files"],
+                "summary": "Fetch the checksum stored with a file",
+                "parameters": [key],
+                "responses": {
+                    "200": json_response("The SHA-256 checksum of the file.", schema_ref("FileChecksum")),
+                    "404": error_response("No checksum is stored for this key."),
+                },
+            },
+        },
+        "/files/{key}/metadata": {
+            "get": {
+                "tags": ["files"],
+                "summary": "Fetch the metadata of a file without downloading it",
+                "parameters": [key],
+                "responses": {
+                    "200": json_response("The metadata of the file.", schema_ref("FileMetadata")),
+                    "404": error_response("No file has this key."),
+                },
+            },
+        },
+        "/files/{key}/rename": {
+            "post": {
+                "tags": ["files"],
+                "summary": "Rename a stored file, moving its extraction and cached data along",
+                "security": [{ "apiKey": [] }],
+                "parameters": [
+                    key,
+                    query_param("overwrite", "boolean", "Replace a file already stored under the new key."),
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema_ref("RenameFileRequest") } },
+                },
+                "responses": {
+                    "200": json_response("The file was renamed.", schema_ref("RenameFileResponse")),
+                    "400": error_response("The new key is empty or unchanged, or either key is under the `deleted/` prefix."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No file has this key."),
+                    "409": error_response("A file is already stored under the new key."),
+                },
+            },
+        },
+        "/files/copy": {
+            "post": {
+                "tags": ["files"],
+                "summary": "Copy a stored file to another key, without downloading it",
+                "security": [{ "apiKey": [] }],
+                "parameters": [
+                    query_param("overwrite", "boolean", "Replace a file already stored under the destination key."),
+                ],
+                "requestBody": {
+                    "required": true,
+                    "content": { "application/json": { "schema": schema_ref("CopyFileRequest") } },
+                },
+                "responses": {
+                    "200": json_response("The file was copied.", schema_ref("CopyFileRequest")),
+                    "400": error_response("The destination key is invalid."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("No file has the source key."),
+                    "409": error_response("A file is already stored under the destination key."),
+                },
+            },
+        },
+        "/archives/{key}/inspect": {
+            "get": {
+                "tags": ["files"],
+                "summary": "Inspect a stored ZIP archive without extracting it",
+                "parameters": [key],
+                "responses": {
+                    "200": json_response("The entries of the archive.", schema_ref("ArchiveInspection")),
+                    "400": error_response("The file isn't a ZIP archive."),
+                    "404": error_response("No file has this key."),
+                },
+            },
+        },
+        "/view-codebase/{name}": {
+            "get": {
+                "tags": ["codebases"],
+                "summary": "List the files of a codebase, extracting its archive if needed",
+                "parameters": [
+                    name,
+                    query_param("text_only", "boolean", "Skip the binary entries when extracting the archive."),
+                ],
+                "responses": {
+                    "200": json_response("The extracted files.", schema_ref("CodebaseFilesResponse")),
+                    "202": { "description": "The codebase is still being extracted by another request." },
+                    "400": error_response("The name could escape the competitions directory."),
+                    "404": error_response("No archive is stored for this codebase."),
+                },
+            },
+        },
+        "/view-codebase/{name}/file/{path}": {
+            "get": {
+                "tags": ["codebases"],
+                "summary": "Read a single extracted file",
+                "parameters": [
+                    name,
+                    path_param("path", "The path of the file within the codebase."),
+                    query_param("raw", "boolean", "Return the file content as the body instead of wrapping it in JSON."),
+                    query_param("normalize_eol", "boolean", "Convert the CRLF line endings of text files to LF."),
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The file content, raw or wrapped in JSON.",
+                        "content": {
+                            "application/json": { "schema": schema_ref("FileContent") },
+                            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                        },
+                    },
+                    "403": error_response("The path escapes the codebase."),
+                    "404": error_response("The file does not exist."),
+                    "415": error_response("The file is binary and `raw` wasn't set."),
+                },
+            },
+        },
+        "/competitions/{name}/file": {
+            "get": {
+                "tags": ["codebases"],
+                "summary": "Fetch a single file of an extracted competition",
+                "parameters": [
+                    name,
+                    required_query_param("path", "The path of the file relative to the competition directory."),
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The file content. Binary files are base64-encoded in JSON unless \
+                            the client accepts `application/octet-stream`.",
+                        "content": {
+                            "application/json": { "schema": schema_ref("FileContent") },
+                            "application/octet-stream": { "schema": { "type": "string", "format": "binary" } },
+                        },
+                    },
+                    "403": error_response("The path escapes the competition."),
+                    "404": error_response("The file does not exist."),
+                },
+            },
+        },
+        "/competitions/{name}": {
+            "delete": {
+                "tags": ["codebases"],
+                "summary": "Delete an extracted competition, keeping its stored archive",
+                "security": [{ "apiKey": [] }],
+                "parameters": [name],
+                "responses": {
+                    "200": json_response("The competition was deleted.", schema_ref("CompetitionDeleted")),
+                    "400": error_response("The name could escape the competitions directory."),
+                    "401": error_response("The API key is missing or invalid."),
+                    "404": error_response("The competition isn't extracted."),
+                    "409": error_response("The competition is being extracted."),
+                },
+            },
+        },
+        "/competitions/{name}/uploads": {
+            "get": {
+                "tags": ["codebases"],
+                "summary": "List the uploaded versions of the archive of a competition, the latest first",
+                "parameters": [
+                    name,
+                    query_param("latest", "boolean", "Return only the latest version, instead of a page."),
+                    query_param("limit", "integer", "The maximum number of versions to return, 20 by default and up to 100."),
+                    query_param("offset", "integer", "The number of most recent versions to skip."),
+                ],
+                "responses": {
+                    "200": {
+                        "description": "The page of versions, or the latest version alone with `latest`.",
+                        "content": {
+                            "application/json": {
+                                "schema": { "oneOf": [schema_ref("UploadHistoryResponse"), schema_ref("UploadVersion")] },
+                            },
+                        },
+                    },
+                    "400": error_response("The page is invalid."),
+                    "404": error_response("No upload of the competition was recorded."),
+                },
+            },
+        },
+        "/generate-codebase-json/{name}": {
+            "get": {
+                "tags": ["codebases"],
+                "summary": "Generate the directory tree of an extracted codebase",
+                "description": "Served as MessagePack when the client sends `Accept: application/msgpack`.",
+                "parameters": [
+                    name,
+                    query_param("detail", "string", "The level of detail of each node, `minimal` or `full`."),
+                    query_param("refresh", "boolean", "Regenerate the tree instead of serving it from the cache."),
+                    query_param("max_depth", "integer", "The maximum depth of folders to descend into."),
+                    query_param("ignore", "string", "Comma-separated glob patterns of entries to leave out."),
+                ],
+                "responses": {
+                    "200": json_response("The directory tree.", schema_ref("CodebaseTreeResponse")),
+                    "400": error_response("An ignore pattern is invalid, or the name could escape the competitions directory."),
+                    "403": error_response("The codebase can't be read."),
+                    "404": error_response("The codebase isn't extracted."),
+                },
+            },
+        },
+        "/health": health_operation("Check every service"),
+        "/health/s3": health_operation("Check the storage backend"),
+        "/health/postgres": health_operation("Check PostgreSQL"),
+        "/health/redis": health_operation("Check Redis"),
+        "/health/live": {
+            "get": {
+                "tags": ["health"],
+                "summary": "Liveness probe, without external calls",
+                "responses": { "200": { "description": "The process is running." } },
+            },
+        },
+        "/health/ready": readiness_operation("Readiness probe, checking the required services"),
+        "/readyz": readiness_operation("Readiness probe, alias of `/health/ready`"),
+        "/metrics": {
+            "get": {
+                "tags": ["metrics"],
+                "summary": "Render the metrics in the Prometheus text format, when `METRICS_ENABLED` is set",
+                "responses": {
+                    "200": {
+                        "description": "The application metrics.",
+                        "content": { "text/plain": { "schema": { "type": "string" } } },
+                    },
+                },
+            },
+        },
+    });
+
+    if let (Some(paths), Value::Object(admin)) = (paths.as_object_mut(), admin_paths()) {
+        paths.extend(admin);
+    }
+    paths
+}
+
+/// Returns the operations of the admin routes, keyed by path.
+fn admin_paths() -> Value {
+    json!({
+        "/admin/cache/flush": {
+            "post": {
+                "tags": ["admin"],
+                "summary": "Delete every Redis key owned by the application",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": { "description": "The number of deleted keys." },
+                    "401": error_response("The admin token is missing or invalid."),
+                    "404": error_response("No admin token is configured."),
+                },
+            },
+        },
+        "/admin/cleanup": {
+            "post": {
+                "tags": ["admin"],
+                "summary": "Remove the expired competitions",
+                "security": [{ "adminToken": [] }],
+                "parameters": [
+                    query_param("dry_run", "boolean", "List the expired competitions without removing them."),
+                ],
+                "responses": {
+                    "200": { "description": "The removed, or expired, competitions." },
+                    "401": error_response("The admin token is missing or invalid."),
+                    "404": error_response("No admin token is configured."),
+                },
+            },
+        },
+        "/admin/file-types": {
+            "get": {
+                "tags": ["admin"],
+                "summary": "List the accepted file types and the validation counters since startup",
+                "security": [{ "adminToken": [] }],
+                "responses": {
+                    "200": json_response("The registered file types.", schema_ref("FileTypes")),
+                    "401": error_response("The admin token is missing or invalid."),
+                    "404": error_response("No admin token is configured."),
+                },
+            },
+        },
+    })
+}
+
+/// Returns the schemas of the request and response bodies.
+fn schemas() -> Value {
+    json!({
+        "ErrorResponse": {
+            "type": "object",
+            "properties": {
+                "error": { "type": "string", "description": "A message describing the error." },
+                "code": { "type": "integer", "description": "The HTTP status code of the response." },
+                "request_id": { "type": "string", "description": "The id of the request, to find it in the logs." },
+            },
+            "required": ["error", "code"],
+        },
+        "UploadResponse": {
+            "type": "object",
+            "properties": {
+                "message": { "type": "string" },
+                "id": { "type": "string", "format": "uuid" },
+                "file_name": { "type": "string" },
+                "key": { "type": "string", "description": "The storage key, `sha256/<digest>` with `CONTENT_ADDRESSED_STORAGE`." },
+                "size": { "type": "integer", "format": "int64" },
+                "sha256": { "type": "string" },
+                "sniffed_mime_type": { "type": "string" },
+                "deduplicated": { "type": "boolean" },
+                "existing_file_name": { "type": "string" },
+            },
+            "required": ["message", "id", "file_name", "key", "size", "sha256", "deduplicated"],
+        },
+        "UploadFailure": {
+            "type": "object",
+            "properties": {
+                "file_name": { "type": "string" },
+                "error": { "type": "string" },
+                "code": { "type": "integer" },
+            },
+            "required": ["file_name", "error", "code"],
+        },
+        "UploadResult": {
+            "oneOf": [schema_ref("UploadResponse"), schema_ref("UploadFailure")],
+        },
+        "FileTypes": {
+            "type": "object",
+            "properties": {
+                "file_types": { "type": "array", "items": schema_ref("FileTypeSummary") },
+                "default_file_type": { "type": "string", "nullable": true },
+                "accepted": { "type": "integer", "format": "int64" },
+                "rejected": { "type": "integer", "format": "int64" },
+                "rejections": {
+                    "type": "object",
+                    "description": "The rejected uploads by reason: `missing_file_name`, `unsupported_type`, \
+                        `content_mismatch`, `content_type_mismatch`, `too_large`, `read_failed` or `content_rejected`.",
+                    "additionalProperties": { "type": "integer", "format": "int64" },
+                },
+            },
+            "required": ["file_types", "accepted", "rejected", "rejections"],
+        },
+        "FileTypeSummary": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "extensions": { "type": "array", "items": { "type": "string" } },
+                "content_types": { "type": "array", "items": { "type": "string" } },
+                "magic_numbers": {
+                    "type": "array",
+                    "description": "Hex strings expected at the start of the file, or lists of `{ offset, hex }` segments.",
+                    "items": {
+                        "oneOf": [
+                            { "type": "string" },
+                            {
+                                "type": "array",
+                                "items": {
+                                    "type": "object",
+                                    "properties": {
+                                        "offset": { "type": "integer" },
+                                        "hex": { "type": "string" },
+                                    },
+                                },
+                            },
+                        ],
+                    },
+                },
+                "max_size": { "type": "integer", "format": "int64" },
+                "accepted": { "type": "integer", "format": "int64" },
+                "rejected": { "type": "integer", "format": "int64" },
+            },
+            "required": ["name", "extensions", "content_types", "magic_numbers", "max_size", "accepted", "rejected"],
+        },
+        "UploadExtractResponse": {
+            "type": "object",
+            "properties": {
+                "upload": schema_ref("UploadResponse"),
+                "codebase": schema_ref("CodebaseFilesResponse"),
+            },
+            "required": ["upload", "codebase"],
+        },
+        "UploadRecord": {
+            "type": "object",
+            "properties": {
+                "id": { "type": "string", "format": "uuid" },
+                "file_name": { "type": "string" },
+                "s3_key": { "type": "string" },
+                "size_bytes": { "type": "integer", "format": "int64" },
+                "content_type": { "type": "string" },
+                "sha256": { "type": "string" },
+                "uploaded_by": { "type": "string", "nullable": true },
+                "created_at": { "type": "string", "format": "date-time" },
+                "deleted_at": { "type": "string", "format": "date-time", "nullable": true },
+            },
+            "required": ["id", "file_name", "s3_key", "size_bytes", "content_type", "sha256", "created_at"],
+        },
+        "InitChunkedUploadRequest": {
+            "type": "object",
+            "properties": {
+                "file_name": { "type": "string" },
+                "content_type": { "type": "string" },
+            },
+            "required": ["file_name", "content_type"],
+        },
+        "ChunkedUploadStarted": {
+            "type": "object",
+            "properties": {
+                "upload_id": { "type": "string", "format": "uuid" },
+                "file_name": { "type": "string" },
+                "expires_in": { "type": "integer", "description": "Seconds before the upload expires." },
+            },
+        },
+        "ChunkedUploadPart": {
+            "type": "object",
+            "properties": {
+                "upload_id": { "type": "string", "format": "uuid" },
+                "part_number": { "type": "integer" },
+                "size": { "type": "integer", "format": "int64" },
+                "etag": { "type": "string" },
+            },
+        },
+        "FileChecksum": {
+            "type": "object",
+            "properties": {
+                "key": { "type": "string" },
+                "sha256": { "type": "string" },
+            },
+        },
+        "FileMetadata": {
+            "type": "object",
+            "properties": {
+                "key": { "type": "string" },
+                "size": { "type": "integer", "format": "int64" },
+                "etag": { "type": "string", "nullable": true },
+                "last_modified": { "type": "string", "format": "date-time", "nullable": true },
+                "content_type": { "type": "string", "nullable": true },
+            },
+        },
+        "RenameFileRequest": {
+            "type": "object",
+            "properties": {
+                "to": { "type": "string", "description": "The key to rename the file to." },
+            },
+            "required": ["to"],
+        },
+        "CopyFileRequest": {
+            "type": "object",
+            "properties": {
+                "src": { "type": "string", "description": "The key of the file to copy." },
+                "dst": { "type": "string", "description": "The key of the copy." },
+            },
+            "required": ["src", "dst"],
+        },
+        "RenameFileResponse": {
+            "type": "object",
+            "properties": {
+                "previous_key": { "type": "string" },
+                "key": { "type": "string" },
+            },
+        },
+        "ArchiveEntry": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "compressed_size": { "type": "integer", "format": "int64" },
+                "uncompressed_size": { "type": "integer", "format": "int64" },
+                "ratio": { "type": "number" },
+                "flagged": { "type": "boolean" },
+            },
+        },
+        "ArchiveInspection": {
+            "type": "object",
+            "properties": {
+                "key": { "type": "string" },
+                "entries": { "type": "array", "items": schema_ref("ArchiveEntry") },
+                "compressed_size": { "type": "integer", "format": "int64" },
+                "uncompressed_size": { "type": "integer", "format": "int64" },
+                "ratio": { "type": "number" },
+                "ratio_threshold": { "type": "number" },
+                "flagged_entries": { "type": "integer" },
+            },
+        },
+        "CodebaseFilesResponse": {
+            "type": "object",
+            "properties": {
+                "files": { "type": "array", "items": { "type": "string" } },
+                "root_dir": { "type": "string", "nullable": true },
+                "skipped": { "type": "array", "items": { "type": "string" } },
+            },
+            "required": ["files", "root_dir"],
+        },
+        "CodebaseTreeResponse": {
+            "type": "object",
+            "properties": {
+                "status": { "type": "string" },
+                "message": { "type": "string" },
+                "data": { "type": "array", "items": { "type": "object" } },
+                "truncated": { "type": "boolean" },
+                "hint": { "type": "string" },
+            },
+            "required": ["status", "message", "data", "truncated"],
+        },
+        "FileContent": {
+            "type": "object",
+            "properties": {
+                "status": { "type": "string" },
+                "path": { "type": "string" },
+                "content_type": { "type": "string" },
+                "encoding": { "type": "string", "description": "`base64` for binary files." },
+                "content": { "type": "string" },
+            },
+        },
+        "UploadVersion": {
+            "type": "object",
+            "properties": {
+                "competition": { "type": "string" },
+                "s3_key": { "type": "string" },
+                "version": { "type": "integer" },
+                "size_bytes": { "type": "integer", "format": "int64" },
+                "sha256": { "type": "string" },
+                "uploaded_by": { "type": "string", "nullable": true },
+                "created_at": { "type": "string", "format": "date-time" },
+                "exists": {
+                    "type": "boolean",
+                    "nullable": true,
+                    "description": "Whether an object is still stored under the key, `null` when storage couldn't tell.",
+                },
+            },
+            "required": ["competition", "s3_key", "version", "size_bytes", "sha256", "created_at", "exists"],
+        },
+        "UploadHistoryResponse": {
+            "type": "object",
+            "properties": {
+                "competition": { "type": "string" },
+                "versions": { "type": "array", "items": schema_ref("UploadVersion") },
+                "total": { "type": "integer" },
+                "limit": { "type": "integer" },
+                "offset": { "type": "integer" },
+            },
+            "required": ["competition", "versions", "total", "limit", "offset"],
+        },
+        "FileDeleted": {
+            "type": "object",
+            "properties": {
+                "key": { "type": "string" },
+                "deleted_key": { "type": "string" },
+                "retention_secs": { "type": "integer", "description": "Seconds before the file is purged." },
+            },
+        },
+        "CompetitionDeleted": {
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "files_removed": { "type": "integer" },
+            },
+        },
+        "ServiceHealth": {
+            "type": "object",
+            "properties": {
+                "status": { "type": "string", "enum": ["up", "down"] },
+                "latency_ms": { "type": "integer" },
+                "error": { "type": "string" },
+            },
+            "required": ["status", "latency_ms"],
+        },
+        "HealthResponse": {
+            "type": "object",
+            "properties": {
+                "status": { "type": "string" },
+                "message": { "type": "string" },
+                "checks": { "type": "object", "additionalProperties": schema_ref("ServiceHealth") },
+                "stale": { "type": "boolean" },
+            },
+            "required": ["status", "message", "checks"],
+        },
+    })
+}
+
+/// Returns the `GET` operation of a download route.
+fn download_operation(key: &Value, summary: &str) -> Value {
+    json!({
+        "tags": ["files"],
+        "summary": summary,
+        "description": "Single byte ranges are supported through the `Range` header.",
+        "parameters": [key],
+        "responses": {
+            "200": {
+                "description": "The file, as an attachment.",
+                "content": { "application/octet-stream": { "schema": { "type": "string", "format": "binary" } } },
+            },
+            "206": { "description": "The requested range of the file." },
+            "404": error_response("No file has this key."),
+            "416": error_response("The requested range can't be satisfied."),
+        },
+    })
+}
+
+/// Returns the operation of a health check route.
+fn health_operation(summary: &str) -> Value {
+    json!({
+        "get": {
+            "tags": ["health"],
+            "summary": summary,
+            "responses": {
+                "200": json_response("The checked services are up.", schema_ref("HealthResponse")),
+                "503": json_response("A checked service is down.", schema_ref("HealthResponse")),
+            },
+        },
+    })
+}
+
+/// Returns the operation of a readiness probe route.
+fn readiness_operation(summary: &str) -> Value {
+    json!({
+        "get": {
+            "tags": ["health"],
+            "summary": summary,
+            "responses": {
+                "200": json_response("The application can serve traffic.", schema_ref("HealthResponse")),
+                "503": json_response("The application is starting or a required service is down.", schema_ref("HealthResponse")),
+            },
+        },
+    })
+}
+
+/// Returns a reference to a schema of the document.
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+/// Returns a JSON response with the given schema.
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": { "application/json": { "schema": schema } },
+    })
+}
+
+/// Returns an error response, referencing the shared error schema.
+fn error_response(description: &str) -> Value {
+    json_response(description, json!({ "$ref": ERROR_SCHEMA }))
+}
+
+/// Returns a required string path parameter.
+fn path_param(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+/// Returns an optional query parameter of the given type.
+fn query_param(name: &str, kind: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": { "type": kind },
+    })
+}
+
+/// Returns a required string query parameter.
+fn required_query_param(name: &str, description: &str) -> Value {
+    let mut param = query_param(name, "string", description);
+    param["required"] = json!(true);
+    param
+}
@@ -0,0 +1,115 @@
+use std::convert::Infallible;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use log::error;
+use serde::Serialize;
+
+/// The content type of MessagePack responses.
+pub const MSGPACK_CONTENT_TYPE: &str = "application/msgpack";
+
+/// The serialization format of a response, negotiated from the `Accept` header.
+///
+/// Responses are serialized as MessagePack when the client accepts `application/msgpack`
+/// (or `application/x-msgpack`), and as JSON otherwise.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    MessagePack,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ResponseFormat {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let accepts_msgpack = parts
+            .headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|media_type| media_type.split(';').next().unwrap_or("").trim())
+            .any(|media_type| {
+                media_type.eq_ignore_ascii_case(MSGPACK_CONTENT_TYPE)
+                    || media_type.eq_ignore_ascii_case("application/x-msgpack")
+            });
+
+        Ok(if accepts_msgpack { ResponseFormat::MessagePack } else { ResponseFormat::Json })
+    }
+}
+
+impl ResponseFormat {
+    /// Serializes a response body in the negotiated format.
+    ///
+    /// The response carries `Vary: Accept`, since its format depends on that header.
+    ///
+    /// # Parameters
+    /// - `status`: The status code of the response.
+    /// - `body`: The body to serialize.
+    ///
+    /// # Returns
+    /// The serialized response, or a 500 if the body can't be serialized.
+    pub fn respond<T: Serialize>(self, status: StatusCode, body: &T) -> Response {
+        let mut response = match self {
+            ResponseFormat::Json => (status, Json(body)).into_response(),
+            ResponseFormat::MessagePack => match rmp_serde::to_vec_named(body) {
+                Ok(bytes) => (
+                    status,
+                    [(header::CONTENT_TYPE, HeaderValue::from_static(MSGPACK_CONTENT_TYPE))],
+                    bytes,
+                ).into_response(),
+                Err(e) => {
+                    error!("Failed to serialize MessagePack response: {}", e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Failed to serialize response").into_response()
+                }
+            },
+        };
+
+        response.headers_mut().insert(header::VARY, HeaderValue::from_static("accept"));
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Request;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+
+    async fn negotiate(accept: Option<&str>) -> ResponseFormat {
+        let mut request = Request::get("/");
+        if let Some(accept) = accept {
+            request = request.header(header::ACCEPT, accept);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        ResponseFormat::from_request_parts(&mut parts, &()).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn msgpack_is_served_only_when_accepted() {
+        assert_eq!(negotiate(None).await, ResponseFormat::Json);
+        assert_eq!(negotiate(Some("application/json")).await, ResponseFormat::Json);
+        assert_eq!(negotiate(Some("*/*")).await, ResponseFormat::Json);
+        assert_eq!(negotiate(Some("application/msgpack")).await, ResponseFormat::MessagePack);
+        assert_eq!(negotiate(Some("text/html, Application/X-MsgPack;q=0.9")).await, ResponseFormat::MessagePack);
+    }
+
+    #[tokio::test]
+    async fn msgpack_response_round_trips_to_the_json_structure() {
+        let body = json!({
+            "data": [{ "name": "src", "type": "folder", "size": 12, "children": [{ "name": "main.rs", "size": 12 }] }],
+            "truncated": false,
+            "root_dir": null,
+        });
+
+        let response = ResponseFormat::MessagePack.respond(StatusCode::OK, &body);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], MSGPACK_CONTENT_TYPE);
+        assert_eq!(response.headers()[header::VARY], "accept");
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let decoded: Value = rmp_serde::from_slice(&bytes).unwrap();
+        assert_eq!(decoded, body);
+    }
+}
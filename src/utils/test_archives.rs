@@ -0,0 +1,65 @@
+//! Builders of the archives used by the unit tests, including malformed and malicious ones.
+
+use std::io::{Cursor, Write};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use tar::{Builder, EntryType, Header};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Builds a ZIP archive holding the given files, deflated.
+pub fn zip_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        writer.start_file(*name, SimpleFileOptions::default().unix_permissions(0o644)).unwrap();
+        writer.write_all(content).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// Sets the Unix mode of the first entry of a ZIP archive in its central directory,
+/// since `ZipWriter` only keeps the permission bits.
+pub fn set_first_zip_entry_mode(archive: &mut [u8], mode: u32) {
+    let header = first_central_header(archive);
+    archive[header + 38..header + 42].copy_from_slice(&(mode << 16).to_le_bytes());
+}
+
+/// Sets the uncompressed size the central directory declares for the first entry of a ZIP
+/// archive, so it understates or overstates the content.
+pub fn set_first_zip_entry_size(archive: &mut [u8], size: u32) {
+    let header = first_central_header(archive);
+    archive[header + 24..header + 28].copy_from_slice(&size.to_le_bytes());
+}
+
+/// Returns the offset of the first central directory header of a ZIP archive.
+fn first_central_header(archive: &[u8]) -> usize {
+    archive.windows(4).position(|signature| signature == b"PK\x01\x02").unwrap()
+}
+
+/// Builds a tar.gz archive holding the given entries, their names written as is so they
+/// can be absolute or escape the archive.
+pub fn tar_gz_archive(entries: &[(&str, EntryType, &[u8])]) -> Vec<u8> {
+    let mut builder = Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    for (name, entry_type, content) in entries {
+        let mut header = Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_entry_type(*entry_type);
+        header.set_mode(0o644);
+        header.set_size(content.len() as u64);
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            header.set_link_name("target").unwrap();
+        }
+        header.set_cksum();
+        builder.append(&header, *content).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+/// Builds a tar.gz archive holding the given regular files.
+pub fn tar_gz_files(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let entries: Vec<(&str, EntryType, &[u8])> = files
+        .iter()
+        .map(|(name, content)| (*name, EntryType::Regular, *content))
+        .collect();
+    tar_gz_archive(&entries)
+}
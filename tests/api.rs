@@ -0,0 +1,1275 @@
+//! End-to-end tests of the upload, codebase and health routes, against real PostgreSQL and
+//! Redis instances. See `common` for the services they need.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use rustler::clients::postgres_client::PostgresClient;
+use rustler::config::PostgresProbe;
+use rustler::models::codebase::CodebaseTreeResponse;
+use sha2::Digest;
+use common::{spawn_app, spawn_app_requiring_redis, spawn_app_requiring_redis_with, spawn_app_with, spawn_app_with_redis, unique_name, zip_archive};
+
+/// A minimal PDF document.
+const PDF: &[u8] = b"%PDF-1.4\n1 0 obj\n<<>>\nendobj\ntrailer\n<<>>\n%%EOF\n";
+
+/// Percent-encodes a storage key for use as a path segment.
+fn encode_key(key: &str) -> String {
+    url::form_urlencoded::byte_serialize(key.as_bytes()).collect()
+}
+
+#[tokio::test]
+async fn upload_stores_a_file_served_back_by_download() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let results = response.json();
+    assert_eq!(results[0]["file_name"], file_name.as_str());
+    assert_eq!(results[0]["size"], PDF.len());
+    let key = results[0]["key"].as_str().expect("the upload has no key").to_string();
+
+    let response = app.get(&format!("/files/{}", encode_key(&key))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, PDF);
+}
+
+#[tokio::test]
+async fn upload_rejects_content_not_matching_the_extension() {
+    let Some(app) = spawn_app().await else { return };
+    let archive = zip_archive(&[("main.rs", b"fn main() {}")]);
+
+    let response = app.upload("/upload", "report.pdf", "application/pdf", &archive).await;
+    assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(response.json()[0]["code"], 415);
+}
+
+#[tokio::test]
+async fn upload_rejects_a_storage_class_not_allowed() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = app.upload("/upload?storage_class=DEEP_ARCHIVE", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert!(response.json().to_string().contains("Storage class 'DEEP_ARCHIVE' is not allowed"));
+
+    let response = app.upload("/upload?storage_class=STANDARD_IA", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn upload_without_a_specific_content_type_is_validated_from_its_content() {
+    let Some(app) = spawn_app().await else { return };
+
+    for content_type in ["", "application/octet-stream"] {
+        let file_name = format!("{}.pdf", unique_name("report"));
+        let response = app.upload("/upload", &file_name, content_type, PDF).await;
+        assert_eq!(response.status, StatusCode::OK, "{:?}: {}", content_type, String::from_utf8_lossy(&response.body));
+        assert_eq!(response.json()[0]["file_name"], file_name.as_str());
+
+        // The extension and content must still agree
+        let file_name = format!("{}.png", unique_name("image"));
+        let response = app.upload("/upload", &file_name, content_type, PDF).await;
+        assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{:?}", content_type);
+    }
+}
+
+#[tokio::test]
+async fn strict_content_type_rejects_an_upload_without_a_specific_content_type() {
+    let Some(app) = spawn_app_with(|config| config.strict_content_type = true).await else { return };
+
+    for content_type in ["", "application/octet-stream"] {
+        let file_name = format!("{}.pdf", unique_name("report"));
+        let response = app.upload("/upload", &file_name, content_type, PDF).await;
+        assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{:?}", content_type);
+    }
+}
+
+#[tokio::test]
+async fn upload_without_an_api_key_is_rejected() {
+    let Some(app) = spawn_app().await else { return };
+    let (boundary, body) = common::multipart_body("report.pdf", "application/pdf", PDF);
+
+    let request = Request::post("/upload")
+        .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["code"], 401);
+}
+
+#[tokio::test]
+async fn health_reports_an_unreachable_redis() {
+    let Some(app) = spawn_app_with_redis("redis://127.0.0.1:1").await else { return };
+
+    let response = app.get("/health").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json();
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+    assert_eq!(report["checks"]["redis"]["status"], "down");
+}
+
+#[tokio::test]
+async fn health_reports_every_failing_service() {
+    let Some(app) = spawn_app_with_redis("redis://127.0.0.1:1").await else { return };
+    break_storage(&app);
+
+    let response = app.get("/health").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json();
+    assert_eq!(report["status"], "degraded");
+    assert_eq!(report["checks"]["s3"]["status"], "down");
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+    assert_eq!(report["checks"]["redis"]["status"], "down");
+    let message = report["message"].as_str().unwrap();
+    assert!(message.contains("S3 Health Check Failed") && message.contains("Redis Health Check"), "{}", message);
+}
+
+#[tokio::test]
+async fn health_identifies_the_single_failing_service() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    break_storage(&app);
+
+    let response = app.get("/health").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json();
+    assert_eq!(report["status"], "degraded");
+    assert_eq!(report["checks"]["s3"]["status"], "down");
+    for service in ["postgres", "redis"] {
+        assert_eq!(report["checks"][service]["status"], "up");
+        assert!(report["checks"][service]["latency_ms"].is_u64());
+    }
+    let message = report["message"].as_str().unwrap();
+    assert!(message.contains("S3 Health Check Failed") && !message.contains("Redis"), "{}", message);
+}
+
+#[tokio::test]
+async fn single_service_health_only_reports_that_service() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+
+    let response = app.get("/health/postgres").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let report = response.json();
+    assert_eq!(report["status"], "healthy");
+    assert_eq!(report["message"], "PostgreSQL is healthy");
+    let checks = report["checks"].as_object().unwrap();
+    assert_eq!(checks.keys().collect::<Vec<_>>(), ["postgres"]);
+    assert!(checks["postgres"]["latency_ms"].is_u64());
+}
+
+#[tokio::test]
+async fn each_health_endpoint_is_cached_independently() {
+    let prefix = format!("{}:", unique_name("rustler"));
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.redis_key_prefix = prefix.clone()).await else { return };
+
+    let response = app.get("/health").await;
+    assert_eq!(response.json()["message"], "All services are healthy");
+
+    // The cached report of every service is never served to a single-service check
+    for _ in 0..2 {
+        let report = app.get("/health/s3").await.json();
+        assert_eq!(report["message"], "S3 is healthy");
+        assert_eq!(report["checks"].as_object().unwrap().len(), 1);
+    }
+
+    let response = app.get("/health").await;
+    assert_eq!(response.json()["message"], "All services are healthy");
+}
+
+#[tokio::test]
+async fn health_reports_every_service_up() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+
+    let response = app.get("/health").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let report = response.json();
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+    assert_eq!(report["checks"]["redis"]["status"], "up");
+}
+
+#[tokio::test]
+async fn redis_connection_is_shared_across_requests() {
+    let Ok(redis_url) = std::env::var("TEST_REDIS_URL") else { return };
+    let upstream = redis_url.trim_start_matches("redis://").trim_end_matches('/').to_string();
+
+    // Forwards the connections to Redis, counting them
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy_url = format!("redis://{}", listener.local_addr().unwrap());
+    let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let accepted = connections.clone();
+    tokio::spawn(async move {
+        while let Ok((mut client, _)) = listener.accept().await {
+            accepted.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let upstream = upstream.clone();
+            tokio::spawn(async move {
+                let mut server = tokio::net::TcpStream::connect(upstream).await.unwrap();
+                let _ = tokio::io::copy_bidirectional(&mut client, &mut server).await;
+            });
+        }
+    });
+
+    let prefix = format!("{}:", unique_name("rustler"));
+    let Some(app) = spawn_app_with(|config| {
+        config.redis_url = proxy_url;
+        config.redis_key_prefix = prefix;
+    }).await else { return };
+
+    for _ in 0..2 {
+        let response = app.get("/health/redis").await;
+        assert_eq!(response.status, StatusCode::OK);
+    }
+    assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn liveness_does_not_depend_on_the_services() {
+    let Some(app) = spawn_app_with_redis("redis://127.0.0.1:1").await else { return };
+    break_storage(&app);
+
+    let response = app.get("/health/live").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["status"], "alive");
+}
+
+#[tokio::test]
+async fn readiness_times_out_a_service_that_never_answers() {
+    // Connections are accepted by the kernel, but nothing is ever answered
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let redis_url = format!("redis://{}", listener.local_addr().unwrap());
+    let Some(app) = spawn_app_with(|config| {
+        config.redis_url = redis_url;
+        config.health_check_timeout_ms = 200;
+    }).await else { return };
+
+    let response = tokio::time::timeout(std::time::Duration::from_secs(5), app.get("/health/ready"))
+        .await
+        .expect("the readiness check was not bounded by its timeout");
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json();
+    assert_eq!(report["status"], "unready");
+    assert_eq!(report["checks"]["redis"]["status"], "down");
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+    assert_eq!(report["message"], "Redis Health Check timed out after 200ms");
+}
+
+#[tokio::test]
+async fn health_reports_every_service_after_one_times_out() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let redis_url = format!("redis://{}", listener.local_addr().unwrap());
+    let Some(app) = spawn_app_with(|config| {
+        config.redis_url = redis_url;
+        config.health_check_timeout_ms = 200;
+    }).await else { return };
+    break_storage(&app);
+
+    let response = tokio::time::timeout(std::time::Duration::from_secs(2), app.get("/health"))
+        .await
+        .expect("the health check was not bounded by its timeout");
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    let report = response.json();
+    assert_eq!(report["checks"]["redis"]["error"], "Redis Health Check timed out after 200ms");
+    assert_eq!(report["checks"]["s3"]["status"], "down");
+    assert_eq!(report["checks"]["postgres"]["status"], "up");
+}
+
+#[tokio::test]
+async fn readiness_reports_an_optional_service_down_as_degraded() {
+    let Some(app) = spawn_app_with(|config| {
+        config.redis_url = "redis://127.0.0.1:1".to_string();
+        config.health_optional_checks = vec!["redis".to_string()];
+    }).await else { return };
+
+    let response = app.get("/health/ready").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let report = response.json();
+    assert_eq!(report["status"], "degraded");
+    assert_eq!(report["checks"]["redis"]["status"], "down");
+}
+
+#[tokio::test]
+async fn uploaded_archive_is_served_by_view_codebase() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let name = unique_name("competition");
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", b"# Test")]);
+
+    let response = app.upload("/upload-extract", &format!("{}.zip", name), "application/zip", &archive).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+
+    let response = app.get(&format!("/view-codebase/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let mut files: Vec<String> = serde_json::from_value(response.json()["files"].clone()).unwrap();
+    files.sort();
+    assert_eq!(files, ["README.md", "src/main.rs"]);
+}
+
+#[tokio::test]
+async fn concurrent_view_codebase_requests_extract_the_archive_once() {
+    let Some((app, storage)) = common::spawn_app_with_slow_storage(std::time::Duration::from_millis(300)).await else { return };
+    let name = unique_name("competition");
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}")]);
+    app.upload("/upload", &format!("{}.zip", name), "application/zip", &archive).await;
+
+    let uri = format!("/view-codebase/{}", name);
+    let (first, second) = tokio::join!(app.get(&uri), app.get(&uri));
+    for response in [first, second] {
+        assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+        assert_eq!(response.json()["files"], serde_json::json!(["src/main.rs"]));
+    }
+    assert_eq!(storage.downloads(), 1);
+}
+
+#[tokio::test]
+async fn codebase_caches_round_trip_through_redis() {
+    let prefix = format!("{}:", unique_name("rustler"));
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.redis_key_prefix = prefix).await else { return };
+    let file_service = app.state.get_file_service();
+
+    assert_eq!(file_service.get_cached_files("demo").await.unwrap(), None);
+    let files = vec!["src/main.rs".to_string(), "README.md".to_string()];
+    file_service.cache_files("demo", &files).await.unwrap();
+    assert_eq!(file_service.get_cached_files("demo").await.unwrap(), Some(files));
+
+    let tree = CodebaseTreeResponse {
+        status: "success".to_string(),
+        message: "Codebase JSON generated".to_string(),
+        data: vec![serde_json::json!({ "name": "src", "type": "folder", "children": [] })],
+        truncated: false,
+        hint: None,
+    };
+    file_service.cache_codebase_json("demo", "full", 42, &tree).await.unwrap();
+    assert_eq!(file_service.get_cached_codebase_json("demo", "full", 42).await.unwrap(), Some(tree));
+    // A tree is only served for the directory state and options it was generated with
+    assert_eq!(file_service.get_cached_codebase_json("demo", "full", 43).await.unwrap(), None);
+    assert_eq!(file_service.get_cached_codebase_json("demo", "minimal", 42).await.unwrap(), None);
+
+    file_service.invalidate_codebase_json("demo").await.unwrap();
+    assert_eq!(file_service.get_cached_codebase_json("demo", "full", 42).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn same_archive_uploaded_under_two_names_is_served_under_both() {
+    for (content_addressed, deduplicate) in [(true, false), (false, true)] {
+        let Some(app) = spawn_app_requiring_redis_with(|config| {
+            config.content_addressed_storage = content_addressed;
+            config.deduplicate_uploads = deduplicate;
+        }).await else { return };
+        let (first, second) = (unique_name("competition"), unique_name("competition"));
+        // Unique content, so the upload isn't deduplicated against another test's
+        let readme = format!("# {}", first);
+        let archive = zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", readme.as_bytes())]);
+
+        for name in [&first, &second] {
+            let response = app.upload("/upload", &format!("{}.zip", name), "application/zip", &archive).await;
+            assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+        }
+
+        for name in [&first, &second] {
+            let response = app.get(&format!("/view-codebase/{}", name)).await;
+            assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+            let mut files: Vec<String> = serde_json::from_value(response.json()["files"].clone()).unwrap();
+            files.sort();
+            assert_eq!(files, ["README.md", "src/main.rs"]);
+        }
+    }
+}
+
+#[tokio::test]
+async fn view_codebase_of_an_unknown_competition_is_not_found() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+
+    let response = app.get(&format!("/view-codebase/{}", unique_name("missing"))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn upload_with_a_wrong_api_key_is_rejected() {
+    let Some(app) = spawn_app().await else { return };
+    let (boundary, body) = common::multipart_body("report.pdf", "application/pdf", PDF);
+
+    let request = Request::post("/upload")
+        .header(header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary))
+        .header("x-api-key", "wrong-key")
+        .body(Body::from(body))
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::UNAUTHORIZED);
+    assert_eq!(response.json()["code"], 401);
+}
+
+#[tokio::test]
+async fn upload_with_a_valid_api_key_records_its_identity() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let id = response.json()[0]["id"].as_str().expect("the upload has no id").to_string();
+
+    let response = app.get(&format!("/uploads/{}", id)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["uploaded_by"], "tests");
+}
+
+#[tokio::test]
+async fn deleted_file_is_served_again_once_restored() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    let key = response.json()[0]["key"].as_str().expect("the upload has no key").to_string();
+    let path = format!("/files/{}", encode_key(&key));
+
+    let request = Request::delete(&path).header("x-api-key", common::API_KEY).body(Body::empty()).unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(app.get(&path).await.status, StatusCode::NOT_FOUND);
+
+    let request = Request::post(format!("{}/restore", path)).header("x-api-key", common::API_KEY).body(Body::empty()).unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+
+    let response = app.get(&path).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, PDF);
+}
+
+#[tokio::test]
+async fn view_codebase_file_rejects_paths_escaping_the_competition() {
+    let Some(app) = spawn_app().await else { return };
+    let name = unique_name("competition");
+    let competition_dir = app.competitions_dir.path().join(&name);
+    std::fs::create_dir_all(competition_dir.join("src")).unwrap();
+    std::fs::write(competition_dir.join("src/main.rs"), b"fn main() {}").unwrap();
+    std::fs::write(app.competitions_dir.path().join("secret.txt"), b"secret").unwrap();
+
+    let response = app.get(&format!("/view-codebase/{}/file/src/main.rs", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["content"], "fn main() {}");
+
+    for path in ["../../etc/passwd", "src/../../secret.txt", "%2e%2e/secret.txt", "..%2F..%2Fetc%2Fpasswd"] {
+        let response = app.get(&format!("/view-codebase/{}/file/{}", name, path)).await;
+        assert_eq!(response.status, StatusCode::FORBIDDEN, "{}", path);
+    }
+
+    let response = app.get(&format!("/view-codebase/{}/file/src/missing.rs", name)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn view_codebase_file_only_normalizes_the_line_endings_of_text_files() {
+    let Some(app) = spawn_app().await else { return };
+    let name = unique_name("competition");
+    let competition_dir = app.competitions_dir.path().join(&name);
+    std::fs::create_dir_all(&competition_dir).unwrap();
+    std::fs::write(competition_dir.join("notes.txt"), b"first\r\nsecond\r\n").unwrap();
+    let binary = b"\x00\x01\r\n\x02";
+    std::fs::write(competition_dir.join("data.bin"), binary).unwrap();
+    let file = |path: &str, query: &str| format!("/view-codebase/{}/file/{}?{}", name, path, query);
+
+    let response = app.get(&file("notes.txt", "raw=true&normalize_eol=true")).await;
+    assert_eq!(response.body, b"first\nsecond\n");
+    let response = app.get(&file("notes.txt", "normalize_eol=true")).await;
+    assert_eq!(response.json()["content"], "first\nsecond\n");
+
+    let response = app.get(&file("notes.txt", "raw=true")).await;
+    assert_eq!(response.body, b"first\r\nsecond\r\n");
+
+    let response = app.get(&file("data.bin", "raw=true&normalize_eol=true")).await;
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/octet-stream");
+    assert_eq!(response.body, binary);
+}
+
+/// Makes the storage unwritable, by replacing its temporary directory with a file.
+fn break_storage(app: &common::TestApp) {
+    let tmp_dir = app.storage_dir.path().join("tmp");
+    if tmp_dir.is_dir() {
+        std::fs::remove_dir_all(&tmp_dir).unwrap();
+    }
+    std::fs::write(tmp_dir, b"").unwrap();
+}
+
+#[tokio::test]
+async fn health_serves_the_last_good_status_when_a_check_fails() {
+    let prefix = format!("{}:", unique_name("rustler"));
+    let Some(app) = spawn_app_requiring_redis_with(|config| {
+        config.health_stale_grace_secs = 60;
+        config.redis_key_prefix = prefix.clone();
+    }).await else { return };
+
+    let response = app.get("/health/s3").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(response.json().get("stale").is_none());
+
+    // Expire the fresh report, then make the storage directory unwritable
+    let redis_client = app.state.get_clients().get_redis_client();
+    let mut con = redis_client.get_connection().await.unwrap();
+    let _: () = redis::AsyncCommands::del(&mut con, format!("{}health_check_status:s3", prefix)).await.unwrap();
+    break_storage(&app);
+
+    let response = app.get("/health/s3").await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers["x-health-stale"], "true");
+    let report = response.json();
+    assert_eq!(report["stale"], true);
+    assert_eq!(report["status"], "healthy");
+}
+
+#[tokio::test]
+async fn health_reports_a_failing_check_without_a_grace_window() {
+    let prefix = format!("{}:", unique_name("rustler"));
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.redis_key_prefix = prefix.clone()).await else { return };
+    break_storage(&app);
+
+    let response = app.get("/health/s3").await;
+    assert_eq!(response.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert!(response.headers.get("x-health-stale").is_none());
+}
+
+#[tokio::test]
+async fn extraction_reports_the_root_directory_of_a_wrapped_archive() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let wrapped = zip_archive(&[("project/src/main.rs", b"fn main() {}"), ("project/README.md", b"# Test")]);
+    let unwrapped = zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", b"# Test")]);
+
+    let name = unique_name("wrapped");
+    let response = app.upload("/upload-extract", &format!("{}.zip", name), "application/zip", &wrapped).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()["codebase"]["root_dir"], "project");
+
+    let name = unique_name("unwrapped");
+    let response = app.upload("/upload-extract", &format!("{}.zip", name), "application/zip", &unwrapped).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert!(response.json()["codebase"]["root_dir"].is_null());
+}
+
+#[tokio::test]
+async fn postgres_probe_only_checks_the_schema_when_full() {
+    let Some(app) = spawn_app().await else { return };
+    let mut config = app.state.get_config().clone();
+
+    // A search path without the tables of the application, as on a fresh database
+    let separator = if config.database_url.contains('?') { '&' } else { '?' };
+    config.database_url = format!("{}{}options=-c%20search_path%3Dempty", config.database_url, separator);
+
+    let client = PostgresClient::new(&config).await.unwrap();
+    assert!(client.test_connection().await.is_ok());
+
+    config.postgres_probe = PostgresProbe::Full;
+    let client = PostgresClient::new(&config).await.unwrap();
+    assert!(client.test_connection().await.is_err());
+}
+
+/// Returns the names of the files of a codebase tree, depth first.
+fn tree_files(nodes: &serde_json::Value) -> Vec<String> {
+    let mut files = Vec::new();
+    for node in nodes.as_array().expect("the tree is not an array") {
+        match node["type"].as_str() {
+            Some("folder") => files.extend(tree_files(&node["children"])),
+            _ => files.push(node["name"].as_str().unwrap().to_string()),
+        }
+    }
+    files
+}
+
+#[tokio::test]
+async fn codebase_json_is_served_from_the_cache_until_refreshed() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let name = unique_name("competition");
+    let competition_dir = app.competitions_dir.path().join(&name);
+    std::fs::create_dir_all(competition_dir.join("src")).unwrap();
+    std::fs::write(competition_dir.join("src/main.rs"), b"fn main() {}").unwrap();
+
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(tree_files(&response.json()["data"]), ["main.rs"]);
+
+    // A file added below the top level leaves the modification time of the repository as is,
+    // so only a traversal of the filesystem would see it
+    std::fs::write(competition_dir.join("src/lib.rs"), b"pub fn run() {}").unwrap();
+
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert_eq!(tree_files(&response.json()["data"]), ["main.rs"]);
+
+    let response = app.get(&format!("/generate-codebase-json/{}?refresh=true", name)).await;
+    assert_eq!(tree_files(&response.json()["data"]), ["lib.rs", "main.rs"]);
+
+    // A top-level change invalidates the cached tree
+    std::thread::sleep(std::time::Duration::from_millis(10));
+    std::fs::write(competition_dir.join("README.md"), b"# Test").unwrap();
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert_eq!(tree_files(&response.json()["data"]), ["README.md", "lib.rs", "main.rs"]);
+}
+
+#[tokio::test]
+async fn renamed_archive_moves_its_extraction_and_caches() {
+    let Some((app, storage)) = common::spawn_app_with_slow_storage(std::time::Duration::ZERO).await else { return };
+    let name = unique_name("competition");
+    let new_name = unique_name("competition");
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}")]);
+    app.upload("/upload", &format!("{}.zip", name), "application/zip", &archive).await;
+
+    assert_eq!(app.get(&format!("/view-codebase/{}", name)).await.status, StatusCode::OK);
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert_eq!(tree_files(&response.json()["data"]), ["main.rs"]);
+    assert_eq!(storage.downloads(), 1);
+
+    let body = serde_json::json!({ "to": format!("{}.zip", new_name) }).to_string();
+    let response = app.send(authenticated("POST", &format!("/files/{}.zip/rename", name), body)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()["key"], format!("{}.zip", new_name));
+
+    assert!(!app.competitions_dir.path().join(&name).exists());
+    let new_dir = app.competitions_dir.path().join(&new_name);
+    assert!(new_dir.join("src/main.rs").exists());
+
+    let file_service = app.state.get_file_service();
+    assert_eq!(file_service.get_cached_files(&name).await.unwrap(), None);
+    assert_eq!(file_service.get_cached_files(&new_name).await.unwrap(), Some(vec!["src/main.rs".to_string()]));
+
+    // The moved extraction is served as up to date, without downloading the archive again
+    let response = app.get(&format!("/view-codebase/{}", new_name)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(storage.downloads(), 1);
+    assert_eq!(app.get(&format!("/view-codebase/{}", name)).await.status, StatusCode::NOT_FOUND);
+
+    // A file added below the top level is only missing from the tree cached before the rename
+    std::fs::write(new_dir.join("src/lib.rs"), b"pub fn run() {}").unwrap();
+    let response = app.get(&format!("/generate-codebase-json/{}", new_name)).await;
+    assert_eq!(tree_files(&response.json()["data"]), ["main.rs"]);
+}
+
+/// Returns the path and method of every `.route(...)` declared in the source of a router.
+fn declared_routes(source: &str) -> Vec<(String, String)> {
+    source
+        .split(".route(\"")
+        .skip(1)
+        .map(|declaration| {
+            let (path, rest) = declaration.split_once('"').expect("the route has no path");
+            let method = rest.trim_start_matches([',', ' ', '\n']).split('(').next().unwrap();
+            (path.replace("{*", "{"), method.to_string())
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn openapi_document_lists_every_route() {
+    let Some(app) = spawn_app().await else { return };
+    let response = app.get("/api-docs/openapi.json").await;
+    assert_eq!(response.status, StatusCode::OK);
+    let paths = &response.json()["paths"];
+
+    // The documentation routes serve the document itself, and aren't part of it
+    let sources = [
+        include_str!("../src/routes/file_routes.rs"),
+        include_str!("../src/routes/health_routes.rs"),
+        include_str!("../src/routes/admin_routes.rs"),
+        include_str!("../src/routes/metrics_routes.rs"),
+    ];
+    let routes: Vec<_> = sources.into_iter().flat_map(declared_routes).collect();
+    assert!(routes.len() > 30, "only found {} routes", routes.len());
+    for (path, method) in routes {
+        assert!(paths[&path][&method].is_object(), "{} {} is missing from the OpenAPI document", method, path);
+    }
+}
+
+#[tokio::test]
+async fn upload_history_numbers_the_versions_of_a_competition() {
+    let Some(app) = spawn_app().await else { return };
+    let name = unique_name("competition");
+    let mut digests = Vec::new();
+    for version in 1..=3 {
+        let content = format!("fn main() {{ println!(\"{}\"); }}", version);
+        let archive = zip_archive(&[("src/main.rs", content.as_bytes())]);
+        let response = app.upload("/upload", &format!("{}.zip", name), "application/zip", &archive).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+        digests.push(format!("{:x}", sha2::Sha256::digest(&archive)));
+    }
+
+    let response = app.get(&format!("/competitions/{}/uploads", name)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let history = response.json();
+    assert_eq!(history["total"], 3);
+    let versions = history["versions"].as_array().unwrap();
+    assert_eq!(versions.iter().map(|version| version["version"].as_i64().unwrap()).collect::<Vec<_>>(), [3, 2, 1]);
+    for (version, digest) in versions.iter().zip(digests.iter().rev()) {
+        assert_eq!(version["sha256"], digest.as_str());
+        assert_eq!(version["s3_key"], format!("{}.zip", name));
+        assert_eq!(version["exists"], true);
+    }
+
+    let response = app.get(&format!("/competitions/{}/uploads?limit=1&offset=1", name)).await;
+    let history = response.json();
+    assert_eq!(history["total"], 3);
+    assert_eq!(history["versions"][0]["version"], 2);
+    assert_eq!(history["versions"].as_array().unwrap().len(), 1);
+
+    let response = app.get(&format!("/competitions/{}/uploads?latest=true", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["version"], 3);
+    assert_eq!(response.json()["sha256"], digests[2].as_str());
+
+    let response = app.get(&format!("/competitions/{}/uploads", unique_name("competition"))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn codebase_json_is_compressed_but_downloads_are_not() {
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.response_compression = true).await else { return };
+    let name = unique_name("competition");
+    let competition_dir = app.competitions_dir.path().join(&name);
+    std::fs::create_dir_all(competition_dir.join("src")).unwrap();
+    for module in 0..64 {
+        std::fs::write(competition_dir.join(format!("src/module_{}.rs", module)), b"pub fn run() {}").unwrap();
+    }
+    let mut pdf = PDF.to_vec();
+    pdf.resize(16 * 1024, b' ');
+    let response = app.upload("/upload", &format!("{}.pdf", unique_name("report")), "application/pdf", &pdf).await;
+    let key = response.json()[0]["key"].as_str().expect("the upload has no key").to_string();
+
+    let get_gzip = |uri: String| Request::get(uri).header(header::ACCEPT_ENCODING, "gzip").body(Body::empty()).unwrap();
+
+    let response = app.send(get_gzip(format!("/generate-codebase-json/{}", name))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_ENCODING], "gzip");
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert!(!response.headers.contains_key(header::CONTENT_ENCODING));
+
+    let response = app.send(get_gzip(format!("/files/{}", encode_key(&key)))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert!(!response.headers.contains_key(header::CONTENT_ENCODING));
+    assert_eq!(response.body, pdf);
+}
+
+#[tokio::test]
+async fn upload_body_is_limited_to_the_configured_size() {
+    const LIMIT: usize = 4096;
+    let Some(app) = spawn_app_with(|config| config.max_upload_size_bytes = LIMIT).await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    // The limit applies to the whole body, multipart framing included
+    let framing = common::multipart_body(&file_name, "application/pdf", b"").1.len();
+    let pdf = |size: usize| {
+        let mut pdf = PDF.to_vec();
+        pdf.resize(size - framing, b' ');
+        pdf
+    };
+
+    let response = app.upload("/upload", &file_name, "application/pdf", &pdf(LIMIT)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", &pdf(LIMIT + 1)).await;
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(response.json()["error"], "Request body exceeds maximum upload size of 4096 bytes");
+}
+
+#[tokio::test]
+async fn file_types_endpoint_counts_the_validation_outcomes() {
+    let Some(app) = spawn_app_with(|config| config.admin_token = Some("admin-token".to_string())).await else { return };
+
+    let response = app.upload("/upload", &format!("{}.pdf", unique_name("report")), "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let response = app.upload("/upload", &format!("{}.png", unique_name("image")), "image/png", PDF).await;
+    assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    assert_eq!(app.get("/admin/file-types").await.status, StatusCode::UNAUTHORIZED);
+    let request = Request::get("/admin/file-types")
+        .header(header::AUTHORIZATION, "Bearer admin-token")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+
+    let summary = response.json();
+    assert_eq!(summary["accepted"], 1);
+    assert_eq!(summary["rejected"], 1);
+    assert_eq!(summary["rejections"]["content_mismatch"], 1);
+    assert_eq!(summary["rejections"]["unsupported_type"], 0);
+    let counters = |name: &str| {
+        let file_types = summary["file_types"].as_array().unwrap();
+        let file_type = file_types.iter().find(|file_type| file_type["name"] == name).expect("the file type isn't listed");
+        (file_type["accepted"].as_u64().unwrap(), file_type["rejected"].as_u64().unwrap())
+    };
+    assert_eq!(counters("PDF"), (1, 0));
+    assert_eq!(counters("PNG"), (0, 1));
+}
+
+#[tokio::test]
+async fn cache_flush_only_deletes_the_prefixed_keys() {
+    // The prefix holds glob metacharacters, which must match literally
+    let prefix = format!("{}[*]:", unique_name("rustler"));
+    let Some(app) = spawn_app_requiring_redis_with(|config| {
+        config.redis_key_prefix = prefix.clone();
+        config.admin_token = Some("admin-token".to_string());
+    }).await else { return };
+
+    let redis_client = app.state.get_clients().get_redis_client();
+    let mut con = redis_client.get_connection().await.unwrap();
+    for key in ["codebase:a", "codebase:b", "health_check_status:s3"] {
+        let _: () = redis::AsyncCommands::set(&mut con, format!("{}{}", prefix, key), "cached").await.unwrap();
+    }
+    let co_tenant = format!("{}x:codebase:a", prefix.trim_end_matches("[*]:"));
+    let _: () = redis::AsyncCommands::set(&mut con, &co_tenant, "kept").await.unwrap();
+
+    let flush = || Request::post("/admin/cache/flush")
+        .header(header::AUTHORIZATION, "Bearer admin-token")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.send(flush()).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["deleted"], 3);
+
+    let kept: Option<String> = redis::AsyncCommands::get(&mut con, &co_tenant).await.unwrap();
+    assert_eq!(kept.as_deref(), Some("kept"));
+    let _: () = redis::AsyncCommands::del(&mut con, &co_tenant).await.unwrap();
+
+    let response = app.send(flush()).await;
+    assert_eq!(response.json()["deleted"], 0);
+}
+
+/// Returns the number of objects in the storage directory of an application.
+fn stored_objects(app: &common::TestApp) -> usize {
+    fn count(dir: &std::path::Path) -> usize {
+        std::fs::read_dir(dir).map_or(0, |entries| {
+            entries
+                .map(|entry| entry.unwrap().path())
+                .map(|path| if path.is_dir() { count(&path) } else { 1 })
+                .sum()
+        })
+    }
+    count(&app.storage_dir.path().join("objects"))
+}
+
+#[tokio::test]
+async fn same_content_uploaded_under_two_names_is_stored_once() {
+    let Some(app) = spawn_app_with(|config| config.deduplicate_uploads = true).await else { return };
+    // The content is unique to the test, as the tests share the database
+    let pdf = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let first_name = format!("{}.pdf", unique_name("report"));
+    let second_name = format!("{}.pdf", unique_name("report-copy"));
+
+    let first = app.upload("/upload", &first_name, "application/pdf", &pdf).await.json();
+    assert_eq!(first[0]["deduplicated"], false);
+
+    let response = app.upload("/upload", &second_name, "application/pdf", &pdf).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let second = response.json();
+    assert_eq!(second[0]["deduplicated"], true);
+    assert_eq!(second[0]["file_name"], second_name.as_str());
+    assert_eq!(second[0]["existing_file_name"], first_name.as_str());
+    assert_eq!(second[0]["key"], first[0]["key"]);
+    assert_eq!(stored_objects(&app), 1);
+
+    // Each upload keeps its own record, the second one pointing at the object of the first
+    assert_ne!(second[0]["id"], first[0]["id"]);
+    let record = app.get(&format!("/uploads/{}", first[0]["id"].as_str().unwrap())).await.json();
+    assert_eq!((record["file_name"].as_str(), record["s3_key"].as_str()), (Some(first_name.as_str()), first[0]["key"].as_str()));
+    let record = app.get(&format!("/uploads/{}", second[0]["id"].as_str().unwrap())).await.json();
+    assert_eq!((record["file_name"].as_str(), record["s3_key"].as_str()), (Some(second_name.as_str()), first[0]["key"].as_str()));
+}
+
+#[tokio::test]
+async fn same_content_uploaded_under_two_names_keeps_both_records() {
+    let Some(app) = spawn_app().await else { return };
+    let pdf = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let first_name = format!("{}.pdf", unique_name("report"));
+    let second_name = format!("{}.pdf", unique_name("report-copy"));
+
+    let first = app.upload("/upload", &first_name, "application/pdf", &pdf).await.json();
+    let second = app.upload("/upload", &second_name, "application/pdf", &pdf).await.json();
+    assert_eq!(second[0]["deduplicated"], false);
+    assert_eq!(stored_objects(&app), 2);
+
+    let first_id = first[0]["id"].as_str().unwrap().to_string();
+    let record = app.get(&format!("/uploads/{}", first_id)).await.json();
+    assert_eq!(record["file_name"], first_name.as_str());
+    assert_eq!(record["s3_key"], first_name.as_str());
+
+    // The first record is still found by its key, to be soft-deleted and restored
+    let path = format!("/files/{}", encode_key(&first_name));
+    let request = Request::delete(&path).header("x-api-key", common::API_KEY).body(Body::empty()).unwrap();
+    assert_eq!(app.send(request).await.status, StatusCode::OK);
+    assert_eq!(app.get(&format!("/uploads/{}", first_id)).await.status, StatusCode::NOT_FOUND);
+    let request = Request::post(format!("{}/restore", path)).header("x-api-key", common::API_KEY).body(Body::empty()).unwrap();
+    assert_eq!(app.send(request).await.status, StatusCode::OK);
+    assert_eq!(app.get(&format!("/uploads/{}", first_id)).await.json()["file_name"], first_name.as_str());
+    assert_eq!(app.get(&format!("/uploads/{}", second[0]["id"].as_str().unwrap())).await.json()["file_name"], second_name.as_str());
+}
+
+#[tokio::test]
+async fn upload_is_not_deduplicated_against_a_replaced_object() {
+    let Some(app) = spawn_app_with(|config| config.deduplicate_uploads = true).await else { return };
+    let original = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let replacement = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let first_name = format!("{}.pdf", unique_name("report"));
+    let second_name = format!("{}.pdf", unique_name("report-copy"));
+
+    app.upload("/upload", &first_name, "application/pdf", &original).await;
+    app.upload("/upload", &first_name, "application/pdf", &replacement).await;
+
+    // The object of the first upload now holds the replacement
+    let response = app.upload("/upload", &second_name, "application/pdf", &original).await.json();
+    assert_eq!(response[0]["deduplicated"], false);
+    assert_eq!(response[0]["key"], second_name.as_str());
+    assert_eq!(app.get(&format!("/files/{}", encode_key(&second_name))).await.body, original);
+}
+
+#[tokio::test]
+async fn up_to_date_extraction_is_served_without_downloading_the_archive() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let name = unique_name("competition");
+    let key = format!("{}.zip", name);
+
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}")]);
+    let response = app.upload("/upload", &key, "application/zip", &archive).await;
+    assert_eq!(response.json()[0]["key"], key.as_str());
+    let response = app.get(&format!("/view-codebase/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // A download would now fail its integrity check, while the ETag is unchanged
+    std::fs::write(app.storage_dir.path().join("objects").join(&key), b"corrupted").unwrap();
+    let response = app.get(&format!("/view-codebase/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()["files"], serde_json::json!(["src/main.rs"]));
+
+    // A new archive under the same key changes the ETag, and is extracted again
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}"), ("src/lib.rs", b"pub fn run() {}")]);
+    app.upload("/upload", &key, "application/zip", &archive).await;
+    let response = app.get(&format!("/view-codebase/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    let mut files: Vec<String> = serde_json::from_value(response.json()["files"].clone()).unwrap();
+    files.sort();
+    assert_eq!(files, ["src/lib.rs", "src/main.rs"]);
+}
+
+#[tokio::test]
+async fn codebase_json_of_a_missing_repository_is_not_found() {
+    let Some(app) = spawn_app().await else { return };
+    let name = unique_name("missing");
+
+    let response = app.get(&format!("/generate-codebase-json/{}", name)).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    let error = response.json()["error"].as_str().unwrap().to_string();
+    assert!(!error.contains(&*app.competitions_dir.path().to_string_lossy()), "{}", error);
+}
+
+#[tokio::test]
+async fn batch_upload_stores_the_valid_files_and_reports_the_others() {
+    let Some(app) = spawn_app().await else { return };
+    let first = format!("{}.pdf", unique_name("report"));
+    let unsupported = format!("{}.xyz", unique_name("notes"));
+    let second = format!("{}.pdf", unique_name("report"));
+    let batch: [(&str, &str, &[u8]); 3] = [
+        (&first, "application/pdf", PDF),
+        (&unsupported, "application/octet-stream", b"\x00\x01\x02\x03"),
+        (&second, "application/pdf", PDF),
+    ];
+
+    let response = app.upload_files("/upload", &batch).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    let results = response.json();
+    assert_eq!(results[0]["file_name"], first.as_str());
+    assert_eq!(results[0]["key"], first.as_str());
+    assert_eq!(results[1]["file_name"], unsupported.as_str());
+    assert_eq!(results[1]["code"], 415);
+    assert!(results[1].get("key").is_none());
+    assert_eq!(results[2]["key"], second.as_str());
+    assert_eq!(stored_objects(&app), 2);
+
+    // In atomic mode, the invalid file fails the whole batch
+    let response = app.upload_files("/upload?atomic=true", &batch).await;
+    assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let results = response.json();
+    assert!(results.as_array().unwrap().iter().all(|result| result.get("key").is_none()));
+}
+
+#[tokio::test]
+async fn batch_upload_is_limited_in_file_count() {
+    let Some(app) = spawn_app_with(|config| config.max_upload_files = 2).await else { return };
+    let names: Vec<String> = (0..3).map(|_| format!("{}.pdf", unique_name("report"))).collect();
+    let batch: Vec<(&str, &str, &[u8])> = names.iter().map(|name| (name.as_str(), "application/pdf", PDF)).collect();
+
+    let response = app.upload_files("/upload", &batch).await;
+    assert_eq!(response.status, StatusCode::MULTI_STATUS);
+    let results = response.json();
+    assert!(results[1]["key"].is_string());
+    assert_eq!(results[2]["error"], "Too many files in the batch, the maximum is 2");
+}
+
+#[tokio::test]
+async fn codebase_json_served_as_msgpack_round_trips_to_the_same_structure() {
+    let Some(app) = spawn_app().await else { return };
+    let name = unique_name("competition");
+    let competition_dir = app.competitions_dir.path().join(&name);
+    std::fs::create_dir_all(competition_dir.join("src")).unwrap();
+    std::fs::write(competition_dir.join("src/main.rs"), b"fn main() {}").unwrap();
+    std::fs::write(competition_dir.join("README.md"), b"# Test").unwrap();
+    let uri = format!("/generate-codebase-json/{}", name);
+
+    let json = app.get(&uri).await;
+    assert_eq!(json.status, StatusCode::OK);
+
+    let request = Request::get(&uri).header(header::ACCEPT, "application/msgpack").body(Body::empty()).unwrap();
+    let msgpack = app.send(request).await;
+    assert_eq!(msgpack.status, StatusCode::OK);
+    assert_eq!(msgpack.headers[header::CONTENT_TYPE], "application/msgpack");
+    assert!(msgpack.body.len() < json.body.len());
+    let decoded: serde_json::Value = rmp_serde::from_slice(&msgpack.body).unwrap();
+    assert_eq!(decoded, json.json());
+}
+
+/// Sends a request with a body, authenticated with the test API key.
+fn authenticated(method: &str, uri: &str, body: impl Into<Body>) -> Request<Body> {
+    Request::builder()
+        .method(method)
+        .uri(uri)
+        .header("x-api-key", common::API_KEY)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn chunked_upload_accepts_parts_out_of_order_and_resumes_after_a_dropped_part() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let init = serde_json::json!({ "file_name": file_name, "content_type": "application/pdf" });
+    let response = app.send(authenticated("POST", "/upload/init", init.to_string())).await;
+    assert_eq!(response.status, StatusCode::CREATED);
+    let id = response.json()["upload_id"].as_str().unwrap().to_string();
+
+    // Every part but the last must hold at least 5 MiB
+    let mut first_part = PDF.to_vec();
+    first_part.resize(5 * 1024 * 1024, b' ');
+    let last_part = b"\n%%EOF\n".to_vec();
+
+    let response = app.send(authenticated("PUT", &format!("/upload/{}/part/2", id), last_part.clone())).await;
+    assert_eq!(response.status, StatusCode::OK);
+
+    // The first part was dropped, so the upload can't be completed yet
+    let response = app.send(authenticated("POST", &format!("/upload/{}/complete", id), Body::empty())).await;
+    assert_eq!(response.status, StatusCode::BAD_REQUEST);
+    assert_eq!(response.json()["missing_parts"], serde_json::json!([1]));
+
+    let response = app.send(authenticated("PUT", &format!("/upload/{}/part/1", id), first_part.clone())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let response = app.send(authenticated("POST", &format!("/upload/{}/complete", id), Body::empty())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()["size"], first_part.len() + last_part.len());
+
+    let response = app.get(&format!("/files/{}", encode_key(&file_name))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.body, [first_part, last_part].concat());
+
+    // The session ends once completed
+    let response = app.send(authenticated("POST", &format!("/upload/{}/complete", id), Body::empty())).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+/// Uploads a file as a chunked upload of a single part, returning the response completing it.
+async fn chunked_upload(app: &common::TestApp, file_name: &str, content_type: &str, content: &[u8]) -> common::TestResponse {
+    let init = serde_json::json!({ "file_name": file_name, "content_type": content_type });
+    let response = app.send(authenticated("POST", "/upload/init", init.to_string())).await;
+    assert_eq!(response.status, StatusCode::CREATED, "{}", String::from_utf8_lossy(&response.body));
+    let id = response.json()["upload_id"].as_str().unwrap().to_string();
+
+    let response = app.send(authenticated("PUT", &format!("/upload/{}/part/1", id), content.to_vec())).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    app.send(authenticated("POST", &format!("/upload/{}/complete", id), Body::empty())).await
+}
+
+#[tokio::test]
+async fn chunked_upload_is_content_addressed_like_a_regular_upload() {
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.content_addressed_storage = true).await else { return };
+    let pdf = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = chunked_upload(&app, &file_name, "application/pdf", &pdf).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let sha256 = format!("{:x}", sha2::Sha256::digest(&pdf));
+    let key = format!("sha256/{}", sha256);
+    assert_eq!(response.json()["key"], key.as_str());
+    assert_eq!(response.json()["sha256"], sha256.as_str());
+
+    // Only the content-addressed object is left, the staged one is deleted
+    assert_eq!(stored_objects(&app), 1);
+    assert_eq!(app.get(&format!("/files/{}", encode_key(&key))).await.body, pdf);
+}
+
+#[tokio::test]
+async fn chunked_upload_is_deduplicated_like_a_regular_upload() {
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.deduplicate_uploads = true).await else { return };
+    let pdf = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let first_name = format!("{}.pdf", unique_name("report"));
+    let second_name = format!("{}.pdf", unique_name("report-copy"));
+    app.upload("/upload", &first_name, "application/pdf", &pdf).await;
+
+    let response = chunked_upload(&app, &second_name, "application/pdf", &pdf).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let result = response.json();
+    assert_eq!(result["deduplicated"], true);
+    assert_eq!(result["file_name"], second_name.as_str());
+    assert_eq!(result["key"], first_name.as_str());
+    assert_eq!(result["existing_file_name"], first_name.as_str());
+    assert_eq!(stored_objects(&app), 1);
+
+    // Without a match, the upload is stored under its own name
+    let other = [PDF, unique_name("%").as_bytes(), b"\n"].concat();
+    let response = chunked_upload(&app, &second_name, "application/pdf", &other).await;
+    assert_eq!(response.json()["key"], second_name.as_str());
+    assert_eq!(stored_objects(&app), 2);
+    assert_eq!(app.get(&format!("/files/{}", encode_key(&second_name))).await.body, other);
+}
+
+#[tokio::test]
+async fn chunked_upload_of_an_unsafe_archive_is_rejected_once_completed() {
+    let Some(app) = spawn_app_requiring_redis_with(|config| config.inspect_uploaded_archives = true).await else { return };
+    let name = unique_name("competition");
+    let file_name = format!("{}.zip", name);
+
+    // Incompressible padding, so the archive spans two parts, the unsafe entry being in the last one
+    let padding: Vec<u8> = (0..6 * 1024 * 1024 / 32).flat_map(|i: u32| sha2::Sha256::digest(i.to_le_bytes())).collect();
+    let archive = zip_archive(&[("padding.bin", &padding), ("../../evil", b"x")]);
+    let (first_part, last_part) = archive.split_at(5 * 1024 * 1024);
+
+    let init = serde_json::json!({ "file_name": file_name, "content_type": "application/zip" });
+    let id = app.send(authenticated("POST", "/upload/init", init.to_string())).await.json()["upload_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    for (number, part) in [(1, first_part), (2, last_part)] {
+        let response = app.send(authenticated("PUT", &format!("/upload/{}/part/{}", id, number), part.to_vec())).await;
+        assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    }
+
+    let response = app.send(authenticated("POST", &format!("/upload/{}/complete", id), Body::empty())).await;
+    assert_eq!(response.status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(response.json()["error"], "Archive holds unsafe entries: ../../evil (path escaping the archive)");
+
+    // The assembled object is deleted, and no upload is recorded
+    assert_eq!(app.get(&format!("/files/{}", encode_key(&file_name))).await.status, StatusCode::NOT_FOUND);
+    assert_eq!(stored_objects(&app), 0);
+    assert_eq!(app.get(&format!("/competitions/{}/uploads", name)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn chunked_upload_validates_its_first_part() {
+    let Some(app) = spawn_app_requiring_redis().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let init = serde_json::json!({ "file_name": file_name, "content_type": "application/pdf" });
+    let id = app.send(authenticated("POST", "/upload/init", init.to_string())).await.json()["upload_id"]
+        .as_str()
+        .unwrap()
+        .to_string();
+
+    let response = app.send(authenticated("PUT", &format!("/upload/{}/part/1", id), zip_archive(&[("a.txt", b"a")]))).await;
+    assert_eq!(response.status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn tar_gz_download_is_served_with_a_gzip_content_type() {
+    let Some(app) = spawn_app().await else { return };
+    let content = [b"\x1f\x8b\x08\x00".as_slice(), unique_name("").as_bytes()].concat();
+
+    // Without a declared content type, the type is resolved from the whole `.tar.gz` extension
+    let file_name = format!("{}.tar.gz", unique_name("archive"));
+    let response = app.upload("/upload", &file_name, "", &content).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let response = app.get(&format!("/download/{}", encode_key(&file_name))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/gzip");
+    assert_eq!(response.body, content);
+
+    // The declared content type is stored with the object and served back
+    let file_name = format!("{}.tar.gz", unique_name("archive"));
+    app.upload("/upload", &file_name, "application/x-gzip", &content).await;
+    let response = app.get(&format!("/download/{}", encode_key(&file_name))).await;
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/x-gzip");
+}
+
+#[tokio::test]
+async fn download_streams_the_object_with_its_headers() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+    app.upload("/upload", &file_name, "application/pdf", PDF).await;
+
+    let response = app.get(&format!("/download/{}", encode_key(&file_name))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(response.headers[header::CONTENT_LENGTH], PDF.len().to_string().as_str());
+    assert_eq!(response.headers[header::CONTENT_DISPOSITION], format!("attachment; filename=\"{}\"", file_name).as_str());
+    assert_eq!(response.body, PDF);
+
+    let response = app.get(&format!("/download/{}", encode_key(&unique_name("missing")))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+    assert_eq!(response.json()["code"], 404);
+}
+
+#[tokio::test]
+async fn ranged_download_returns_the_requested_bytes() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+    app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    let uri = format!("/files/{}", encode_key(&file_name));
+    let ranged = |range: &str| Request::get(&uri).header(header::RANGE, range).body(Body::empty()).unwrap();
+
+    let response = app.send(ranged("bytes=4-11")).await;
+    assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.headers[header::CONTENT_RANGE], format!("bytes 4-11/{}", PDF.len()).as_str());
+    assert_eq!(response.headers[header::CONTENT_LENGTH], "8");
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(response.body, &PDF[4..12]);
+
+    let response = app.send(ranged("bytes=-6")).await;
+    assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.body, &PDF[PDF.len() - 6..]);
+
+    let response = app.send(ranged(&format!("bytes={}-", PDF.len()))).await;
+    assert_eq!(response.status, StatusCode::RANGE_NOT_SATISFIABLE);
+
+    let response = app.get(&uri).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::ACCEPT_RANGES], "bytes");
+    assert!(response.headers[header::CONTENT_DISPOSITION].to_str().unwrap().starts_with("attachment; filename="));
+
+    let response = app.get(&format!("/files/{}", encode_key(&unique_name("missing")))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn large_upload_spooled_to_disk_is_stored_intact() {
+    let Some(app) = spawn_app_with(|config| config.upload_spool_threshold_bytes = 64 * 1024).await else { return };
+    let file_name = format!("{}.pdf", unique_name("large"));
+    let mut pdf = PDF.to_vec();
+    pdf.extend((0..8 * 1024 * 1024).map(|i| b'a' + (i % 26) as u8));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", &pdf).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()[0]["size"], pdf.len());
+
+    let response = app.get(&format!("/files/{}", encode_key(&file_name))).await;
+    assert_eq!(response.body.len(), pdf.len());
+    assert!(response.body == pdf);
+}
+
+#[tokio::test]
+async fn checksum_of_an_upload_is_served_back() {
+    let Some(app) = spawn_app().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+    let expected = format!("{:x}", sha2::Sha256::digest(PDF));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.json()[0]["sha256"], expected.as_str());
+
+    let response = app.get(&format!("/files/{}/checksum", encode_key(&file_name))).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["sha256"], expected.as_str());
+    assert_eq!(response.json()["key"], file_name.as_str());
+
+    let response = app.get(&format!("/files/{}/checksum", encode_key(&unique_name("missing")))).await;
+    assert_eq!(response.status, StatusCode::NOT_FOUND);
+}
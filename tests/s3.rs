@@ -0,0 +1,73 @@
+//! Tests of the S3 storage backend against an S3-compatible endpoint, skipped unless
+//! `TEST_S3_ENDPOINT_URL` is set, and failing when it is not on CI. See the `common` module for how to run them.
+
+mod common;
+
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use common::{spawn_app_on_s3, unique_name, zip_archive};
+
+const PDF: &[u8] = b"%PDF-1.4\n1 0 obj\n<<>>\nendobj\ntrailer\n<<>>\n%%EOF\n";
+
+#[tokio::test]
+async fn storage_health_check_reaches_the_bucket() {
+    let Some(app) = spawn_app_on_s3().await else { return };
+
+    app.state.get_clients().get_storage().test_connection().await.unwrap();
+    let response = app.get("/health/s3").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    assert_eq!(response.json()["status"], "healthy");
+}
+
+#[tokio::test]
+async fn uploaded_file_round_trips_through_the_bucket() {
+    let Some(app) = spawn_app_on_s3().await else { return };
+    let file_name = format!("{}.pdf", unique_name("report"));
+
+    let response = app.upload("/upload", &file_name, "application/pdf", PDF).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let key = response.json()[0]["key"].as_str().expect("the upload has no key").to_string();
+    let storage = app.state.get_clients().get_storage();
+    assert!(storage.exists(&key).await.unwrap());
+
+    let response = app.get(&format!("/files/{}", key)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.headers[header::CONTENT_TYPE], "application/pdf");
+    assert_eq!(response.body, PDF);
+
+    let response = app.get(&format!("/files/{}/metadata", key)).await;
+    assert_eq!(response.status, StatusCode::OK);
+    assert_eq!(response.json()["size"], PDF.len());
+
+    let request = Request::get(format!("/files/{}", key)).header(header::RANGE, "bytes=5-7").body(Body::empty()).unwrap();
+    let response = app.send(request).await;
+    assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(response.body, &PDF[5..=7]);
+
+    let missing = format!("{}.pdf", unique_name("missing"));
+    assert!(!storage.exists(&missing).await.unwrap());
+    assert_eq!(app.get(&format!("/files/{}", missing)).await.status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn archive_stored_in_the_bucket_is_viewed_and_reported_healthy() {
+    let Some(app) = spawn_app_on_s3().await else { return };
+    let name = unique_name("competition");
+    let archive = zip_archive(&[("src/main.rs", b"fn main() {}"), ("README.md", b"# Test")]);
+
+    let response = app.upload("/upload", &format!("{}.zip", name), "application/zip", &archive).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+
+    let response = app.get(&format!("/view-codebase/{}", name)).await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let mut files: Vec<String> = serde_json::from_value(response.json()["files"].clone()).unwrap();
+    files.sort();
+    assert_eq!(files, ["README.md", "src/main.rs"]);
+
+    let response = app.get("/health").await;
+    assert_eq!(response.status, StatusCode::OK, "{}", String::from_utf8_lossy(&response.body));
+    let report = response.json();
+    for service in ["s3", "postgres", "redis"] {
+        assert_eq!(report["checks"][service]["status"], "up", "{}", report);
+    }
+}